@@ -59,9 +59,22 @@ extern crate criterion;
 extern crate unwrap;
 
 #[cfg(feature = "testing")]
-use criterion::Criterion;
+use criterion::{Bencher, Benchmark, Criterion, Throughput};
+#[cfg(all(feature = "testing", feature = "compression"))]
+use parsec::Request;
 #[cfg(feature = "testing")]
-use parsec::dev_utils::Record;
+use parsec::{
+    dev_utils::{
+        meta_vote_inputs::{Contention, MetaVoteInputs},
+        new_common_rng, new_rng, Record, RngChoice,
+    },
+    mock::{self, PeerId, Transaction},
+    ConsensusMode, GossipPolicy, HaveSummary, Observation, Parsec,
+};
+#[cfg(feature = "testing")]
+use rand::Rng;
+#[cfg(feature = "testing")]
+use std::collections::BTreeSet;
 
 #[cfg(feature = "testing")]
 fn bench(c: &mut Criterion) {
@@ -155,11 +168,72 @@ fn bench(c: &mut Criterion) {
             name,
         );
     }
+
+    for &num_peers in &[4, 7, 16, 31] {
+        bench_meta_vote_next(c, num_peers);
+    }
+
+    for &num_peers in &[4, 10, 30] {
+        bench_pull_sync(c, num_peers);
+    }
+
+    for &num_peers in &[4, 10] {
+        bench_gossip_policy(c, num_peers);
+    }
+
+    #[cfg(feature = "malice-detection")]
+    for name in &["a_node16_opaque_evt16", "a_node32_opaque_evt16"] {
+        bench_dot_file_with(
+            c,
+            format!("{} - bench_section_size_evt16 - malice detection off", name),
+            "bench_section_size_evt16",
+            name,
+            |record| record.play_with(|parsec| parsec.set_malice_detection(false)),
+        );
+    }
+
+    #[cfg(feature = "malice-detection")]
+    for &num_peers in &[4, 10, 30] {
+        bench_malice_detection(c, num_peers);
+    }
+
+    #[cfg(feature = "compression")]
+    for &num_peers in &[4, 10, 30] {
+        bench_compression(c, num_peers);
+    }
+
+    // Compare with the same graphs played normally above to measure the cost of verifying the
+    // signatures of the received events.
+    for name in &["a_node16_opaque_evt16", "a_node32_opaque_evt16"] {
+        bench_dot_file_with(
+            c,
+            format!("{} - bench_section_size_evt16 - trusted", name),
+            "bench_section_size_evt16",
+            name,
+            Record::play_trusted,
+        );
+    }
 }
 
 #[cfg(feature = "testing")]
 fn bench_dot_file(c: &mut Criterion, group_name: &'static str, name: &'static str) {
-    let test_name = format!("{} - {}", name, group_name);
+    bench_dot_file_with(
+        c,
+        format!("{} - {}", name, group_name),
+        group_name,
+        name,
+        Record::play,
+    )
+}
+
+#[cfg(feature = "testing")]
+fn bench_dot_file_with(
+    c: &mut Criterion,
+    test_name: String,
+    group_name: &'static str,
+    name: &'static str,
+    play: fn(Record) -> Parsec<Transaction, PeerId>,
+) {
     let _ = c.bench_function(&test_name, move |b| {
         let record = unwrap!(Record::parse(format!(
             "input_graphs/{}/{}.dot",
@@ -170,7 +244,7 @@ fn bench_dot_file(c: &mut Criterion, group_name: &'static str, name: &'static st
             |record| {
                 let expected_history = record.consensus_history();
 
-                let parsec = record.play();
+                let parsec = play(record);
                 let actual_history = parsec.meta_election_consensus_history_hash();
 
                 // Verify parsec reached the same consensus as in source dot file.
@@ -181,6 +255,346 @@ fn bench_dot_file(c: &mut Criterion, group_name: &'static str, name: &'static st
     });
 }
 
+// Measures computing the next meta-votes of a voter among `num_peers`, with all voters agreeing or
+// evenly split.
+#[cfg(feature = "testing")]
+fn bench_meta_vote_next(c: &mut Criterion, num_peers: usize) {
+    for &contention in &[Contention::Uncontended, Contention::Contended] {
+        let inputs = MetaVoteInputs::new(num_peers, contention);
+        let _ = c.bench_function(
+            &format!("meta-vote next - {} peers - {:?}", num_peers, contention),
+            move |b| b.iter(|| inputs.next()),
+        );
+    }
+}
+
+// Compares the throughput of ingesting the events of a generated gossip session with and without
+// malice detection.
+#[cfg(all(feature = "testing", feature = "malice-detection"))]
+fn bench_malice_detection(c: &mut Criterion, num_peers: usize) {
+    let mut rng = new_common_rng(RngChoice::Seeded([1, 2, 3, 4]));
+    let record = Record::generate(num_peers, 64, &mut rng);
+    let num_events = record.num_received_events() as u32;
+
+    let play = |malice_detection: bool| {
+        let record = record.clone();
+        move |b: &mut Bencher| {
+            b.iter_with_setup(
+                || record.clone(),
+                |record| record.play_with(|parsec| parsec.set_malice_detection(malice_detection)),
+            )
+        }
+    };
+    let _ = c.bench(
+        &format!("a_node{}_opaque - generated - ingest", num_peers),
+        Benchmark::new("malice detection on", play(true))
+            .with_function("malice detection off", play(false))
+            .throughput(Throughput::Elements(num_events)),
+    );
+}
+
+// Measures creating and handling the `HaveSummary` with which a peer catches up after falling 1000
+// events behind the others, and prints the bytes it takes on the wire compared to push gossip, both
+// straight away and once the peer has already caught up through gossip with another one.
+#[cfg(feature = "testing")]
+fn bench_pull_sync(c: &mut Criterion, num_peers: usize) {
+    let num_behind = 1000;
+    let (mut peers, ids) = peers_with_last_behind(num_peers, num_behind);
+    let mut behind = unwrap!(peers.pop());
+    let behind_id = unwrap!(ids.last()).clone();
+    let ahead_id = ids[0].clone();
+
+    let summary = unwrap!(behind.create_have_summary(&ahead_id));
+    print_pull_vs_push_len(num_peers, "behind", &mut peers[0], &behind_id, &summary);
+
+    let request = unwrap!(peers[1].create_gossip(&behind_id));
+    let response = unwrap!(behind.handle_request(&ids[1], request));
+    unwrap!(peers[1].handle_response(&behind_id, response));
+    print_pull_vs_push_len(
+        num_peers,
+        "behind, then pushed to by another peer",
+        &mut peers[0],
+        &behind_id,
+        &unwrap!(behind.create_have_summary(&ahead_id)),
+    );
+
+    let ahead = peers.swap_remove(0);
+    let _ = c.bench(
+        &format!("a_node{}_opaque - pull sync", num_peers),
+        Benchmark::new("create summary", move |b| {
+            b.iter(|| unwrap!(behind.create_have_summary(&ahead_id)))
+        })
+        .with_function("handle summary", move |b| {
+            b.iter(|| unwrap!(ahead.handle_have_summary(&behind_id, &summary)))
+        })
+        .throughput(Throughput::Elements(num_behind as u32)),
+    );
+}
+
+// Prints the size of the request `ahead` would push to `behind_id`, and of the `summary` it is sent
+// by `behind_id` along with its response.
+#[cfg(feature = "testing")]
+fn print_pull_vs_push_len(
+    num_peers: usize,
+    case: &str,
+    ahead: &mut Parsec<Transaction, PeerId>,
+    behind_id: &PeerId,
+    summary: &HaveSummary<PeerId>,
+) {
+    let response = unwrap!(ahead.handle_have_summary(behind_id, summary));
+    let pull_len =
+        unwrap!(bincode::serialized_size(summary)) + unwrap!(bincode::serialized_size(&response));
+    let request = unwrap!(ahead.create_gossip(behind_id));
+    let push_len = unwrap!(bincode::serialized_size(&request));
+    println!(
+        "a_node{}_opaque - 1000 events {} - push: {} bytes, pull: {} bytes ({:.2}x)",
+        num_peers,
+        case,
+        push_len,
+        pull_len,
+        push_len as f64 / pull_len as f64
+    );
+}
+
+// Measures a genesis group of `num_peers` peers with nothing left to agree on gossiping for 100
+// rounds under each gossip policy, and prints the bytes they send.
+#[cfg(feature = "testing")]
+fn bench_gossip_policy(c: &mut Criterion, num_peers: usize) {
+    let num_rounds = 100;
+    let policies = [
+        GossipPolicy::Always,
+        GossipPolicy::OnChange,
+        GossipPolicy::Adaptive,
+    ];
+    for &policy in &policies {
+        let mut rng = new_common_rng(RngChoice::Seeded([1, 2, 3, 4]));
+        let (mut peers, ids) = idle_peers(num_peers, policy, &mut rng);
+        let len: u64 = (0..num_rounds)
+            .map(|_| gossip_if_allowed(&mut peers, &ids, &mut rng))
+            .sum();
+        println!(
+            "a_node{}_opaque - {} idle rounds - {:?}: {} bytes",
+            num_peers, num_rounds, policy, len
+        );
+    }
+
+    let run = |policy| {
+        move |b: &mut Bencher| {
+            b.iter_with_setup(
+                || {
+                    let mut rng = new_common_rng(RngChoice::Seeded([1, 2, 3, 4]));
+                    let (peers, ids) = idle_peers(num_peers, policy, &mut rng);
+                    (peers, ids, rng)
+                },
+                |(mut peers, ids, mut rng)| {
+                    for _ in 0..num_rounds {
+                        let _ = gossip_if_allowed(&mut peers, &ids, &mut rng);
+                    }
+                },
+            )
+        }
+    };
+    let _ = c.bench(
+        &format!("a_node{}_opaque - idle gossip", num_peers),
+        Benchmark::new("Always", run(policies[0]))
+            .with_function("OnChange", run(policies[1]))
+            .with_function("Adaptive", run(policies[2])),
+    );
+}
+
+// Returns a genesis group of `num_peers` peers which have all consensused and polled a payload
+// they all voted for, then switched to `policy`, along with their IDs.
+#[cfg(feature = "testing")]
+fn idle_peers<R: Rng>(
+    num_peers: usize,
+    policy: GossipPolicy,
+    rng: &mut R,
+) -> (Vec<Parsec<Transaction, PeerId>>, Vec<PeerId>) {
+    let (mut peers, ids) = genesis_peers(num_peers, rng);
+    for peer in &mut peers {
+        let payload = Transaction::new("idle");
+        unwrap!(peer.vote_for(Observation::OpaquePayload(payload)));
+    }
+    while peers.iter().any(Parsec::has_unpolled_observations) {
+        gossip(&mut peers, &ids, rng);
+        for peer in &mut peers {
+            let _ = peer.poll_iter().count();
+        }
+    }
+    for peer in &mut peers {
+        peer.set_gossip_policy(policy);
+    }
+    (peers, ids)
+}
+
+// Has each of `peers` pick another one at random, and gossip to it if its gossip policy allows.
+// Returns the bytes sent in requests and responses.
+#[cfg(feature = "testing")]
+fn gossip_if_allowed<R: Rng>(
+    peers: &mut [Parsec<Transaction, PeerId>],
+    ids: &[PeerId],
+    rng: &mut R,
+) -> u64 {
+    let num_peers = peers.len();
+    let mut len = 0;
+    for src in 0..num_peers {
+        let dst = (src + rng.gen_range(1, num_peers)) % num_peers;
+        if !peers[src].should_gossip_to(&ids[dst]) {
+            continue;
+        }
+        let request = unwrap!(peers[src].create_gossip(&ids[dst]));
+        len += unwrap!(bincode::serialized_size(&request));
+        let response = unwrap!(peers[dst].handle_request(&ids[src], request));
+        len += unwrap!(bincode::serialized_size(&response));
+        unwrap!(peers[src].handle_response(&ids[dst], response));
+    }
+    len
+}
+
+// Has a genesis group of `num_peers` peers gossip until they have all consensused a first payload,
+// then all but the last one gossip until the last one is missing at least `num_behind` events.
+// Returns the peers along with their IDs.
+#[cfg(feature = "testing")]
+fn peers_with_last_behind(
+    num_peers: usize,
+    num_behind: usize,
+) -> (Vec<Parsec<Transaction, PeerId>>, Vec<PeerId>) {
+    let mut rng = new_common_rng(RngChoice::Seeded([1, 2, 3, 4]));
+    let (mut peers, ids) = peers_with_first_block(num_peers, &mut rng);
+    let num_active = num_peers - 1;
+    while peers[0].state_sizes().graph_events
+        < peers[num_active].state_sizes().graph_events + num_behind
+    {
+        gossip(&mut peers[..num_active], &ids, &mut rng);
+    }
+    (peers, ids)
+}
+
+// Has a genesis group of `num_peers` peers, each voting for its own payload, gossip until they have
+// all consensused a first payload.  Returns the peers along with their IDs.
+#[cfg(feature = "testing")]
+fn peers_with_first_block<R: Rng>(
+    num_peers: usize,
+    rng: &mut R,
+) -> (Vec<Parsec<Transaction, PeerId>>, Vec<PeerId>) {
+    let (mut peers, ids) = genesis_peers(num_peers, rng);
+    for peer in &mut peers {
+        let payload = Transaction::new(format!("{:?}", peer.our_pub_id()));
+        unwrap!(peer.vote_for(Observation::OpaquePayload(payload)));
+    }
+
+    let mut polled = vec![false; num_peers];
+    while polled.contains(&false) {
+        gossip(&mut peers, &ids, rng);
+        for (peer, polled) in peers.iter_mut().zip(&mut polled) {
+            *polled |= peer.poll_iter().count() > 0;
+        }
+    }
+    (peers, ids)
+}
+
+// Returns a fresh genesis group of `num_peers` peers along with their IDs.
+#[cfg(feature = "testing")]
+fn genesis_peers<R: Rng>(
+    num_peers: usize,
+    rng: &mut R,
+) -> (Vec<Parsec<Transaction, PeerId>>, Vec<PeerId>) {
+    let ids = mock::create_ids(num_peers);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let peers = ids
+        .iter()
+        .map(|id| {
+            Parsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                vec![],
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(rng)),
+            )
+        })
+        .collect();
+    (peers, ids)
+}
+
+// Has each of `peers` gossip to another one picked at random.
+#[cfg(feature = "testing")]
+fn gossip<R: Rng>(peers: &mut [Parsec<Transaction, PeerId>], ids: &[PeerId], rng: &mut R) {
+    let num_peers = peers.len();
+    for src in 0..num_peers {
+        let dst = (src + rng.gen_range(1, num_peers)) % num_peers;
+        let request = unwrap!(peers[src].create_gossip(&ids[dst]));
+        let response = unwrap!(peers[dst].handle_request(&ids[src], request));
+        unwrap!(peers[src].handle_response(&ids[dst], response));
+    }
+}
+
+// Measures compressing and decompressing the request with which a peer catches up another one
+// which has been cut off from the section since genesis, and prints the compression ratio.
+#[cfg(all(feature = "testing", feature = "compression"))]
+fn bench_compression(c: &mut Criterion, num_peers: usize) {
+    let request = catch_up_request(num_peers, 64);
+    let uncompressed_len = unwrap!(bincode::serialized_size(&request));
+    let compressed = request.to_compressed_bytes();
+    println!(
+        "a_node{}_opaque - catch-up request compressed from {} to {} bytes ({:.2}x)",
+        num_peers,
+        uncompressed_len,
+        compressed.len(),
+        uncompressed_len as f64 / compressed.len() as f64
+    );
+
+    let _ = c.bench(
+        &format!("a_node{}_opaque - catch-up request", num_peers),
+        Benchmark::new("compress", move |b| {
+            b.iter(|| request.to_compressed_bytes())
+        })
+        .with_function("decompress", move |b| {
+            b.iter(|| {
+                unwrap!(Request::<Transaction, PeerId>::from_compressed_bytes(
+                    &compressed
+                ))
+            })
+        })
+        .throughput(Throughput::Bytes(uncompressed_len as u32)),
+    );
+}
+
+// Has all but the last of a genesis group of `num_peers` peers gossip until they have consensused
+// `num_votes` opaque payloads, then returns the request the first peer sends to the last one,
+// which contains the whole gossip graph.
+#[cfg(all(feature = "testing", feature = "compression"))]
+fn catch_up_request(num_peers: usize, num_votes: usize) -> Request<Transaction, PeerId> {
+    let mut rng = new_common_rng(RngChoice::Seeded([1, 2, 3, 4]));
+    let ids = mock::create_ids(num_peers);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let mut peers = ids
+        .iter()
+        .map(|id| {
+            Parsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                vec![],
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut rng)),
+            )
+        })
+        .collect::<Vec<_>>();
+    let num_active = num_peers - 1;
+    for peer in &mut peers[..num_active] {
+        for index in 0..num_votes {
+            let payload = Transaction::new(format!("{}", index));
+            unwrap!(peer.vote_for(Observation::OpaquePayload(payload)));
+        }
+    }
+
+    let mut num_polled = 0;
+    while num_polled <= num_votes {
+        gossip(&mut peers[..num_active], &ids, &mut rng);
+        num_polled += peers[0].poll_iter().count();
+    }
+    unwrap!(peers[0].create_gossip(&ids[num_active]))
+}
+
 #[cfg(feature = "testing")]
 criterion_group! {
     name = benches;
@@ -10,7 +10,7 @@ use crate::{
     error::Error,
     id::{Proof, PublicId},
     network_event::NetworkEvent,
-    observation::Observation,
+    observation::{supermajority, ConsensusMode, Observation, ObservationHash},
     vote::Vote,
     DkgResult, DkgResultWrapper,
 };
@@ -67,11 +67,48 @@ impl<T: NetworkEvent, P: PublicId> Block<T, P> {
         &self.payload
     }
 
+    /// Returns the hash of the payload of this block. This is the same hash used internally to
+    /// identify the observation, so can be used to deduplicate blocks by payload.
+    pub fn payload_hash(&self) -> ObservationHash {
+        ObservationHash::from(&self.payload)
+    }
+
+    /// Returns the application data carried by this block if its payload is
+    /// `Observation::OpaquePayload`, or `None` if it is any of the observations made by PARSEC
+    /// itself.  This lets a consumer of `poll()` apply the application data directly, and only
+    /// match on [payload](#method.payload) for the other blocks, e.g. to connect to added peers.
+    pub fn opaque_payload(&self) -> Option<&T> {
+        if let Observation::OpaquePayload(ref payload) = self.payload {
+            Some(payload)
+        } else {
+            None
+        }
+    }
+
     /// Returns the proofs of this block.
     pub fn proofs(&self) -> &BTreeSet<Proof<P>> {
         &self.proofs
     }
 
+    /// Returns the IDs of the peers which signed this block.
+    pub fn signatories(&self) -> BTreeSet<&P> {
+        self.proofs.iter().map(Proof::public_id).collect()
+    }
+
+    /// Is this block signed by enough peers to be valid among `voter_count` voters, with `mode`
+    /// being the consensus mode which applies to its payload?  That is, by at least one peer for
+    /// `ConsensusMode::Single`, or by more than two thirds of the voters for
+    /// `ConsensusMode::Supermajority`.
+    ///
+    /// This only counts the distinct signatories; it doesn't check the proofs are valid nor that
+    /// the signatories are voters.  Note that `DkgResult` blocks aren't signed at all.
+    pub fn reached_quorum(&self, voter_count: usize, mode: ConsensusMode) -> bool {
+        match mode {
+            ConsensusMode::Single => !self.proofs.is_empty(),
+            ConsensusMode::Supermajority => self.signatories().len() >= supermajority(voter_count),
+        }
+    }
+
     /// Is this block signed by the given peer?
     pub fn is_signed_by(&self, peer_id: &P) -> bool {
         self.proofs.iter().any(|proof| proof.public_id() == peer_id)
@@ -130,3 +167,116 @@ impl<T: NetworkEvent, P: PublicId> DerefMut for BlockGroup<T, P> {
         &mut self.0
     }
 }
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::{PeerId, Transaction};
+    use itertools::Itertools;
+    use std::iter;
+
+    #[test]
+    fn payload_hash() {
+        let create_block = |peer_names: &[&str], payload: &str| {
+            let votes: BTreeMap<_, _> = peer_names
+                .iter()
+                .map(|name| {
+                    let peer_id = PeerId::new(name);
+                    let vote = Vote::new(
+                        &peer_id,
+                        Observation::OpaquePayload(Transaction::new(payload)),
+                    );
+                    (peer_id, vote)
+                })
+                .collect();
+            unwrap!(Block::new(&votes))
+        };
+
+        let block_0 = create_block(&["Alice", "Bob"], "ABCD");
+        let block_1 = create_block(&["Carol"], "ABCD");
+        let block_2 = create_block(&["Alice", "Bob"], "EFGH");
+
+        assert_eq!(
+            block_0.payload_hash(),
+            ObservationHash::from(block_0.payload())
+        );
+        assert_eq!(block_0.payload_hash(), block_1.payload_hash());
+        assert_ne!(block_0.payload_hash(), block_2.payload_hash());
+    }
+
+    #[test]
+    fn opaque_payload() {
+        let peer_id = PeerId::new("Alice");
+        let create_block = |payload: Observation<Transaction, PeerId>| {
+            let votes: BTreeMap<_, _> =
+                iter::once((peer_id.clone(), Vote::new(&peer_id, payload))).collect();
+            unwrap!(Block::new(&votes))
+        };
+
+        let transaction = Transaction::new("ABCD");
+        let block = create_block(Observation::OpaquePayload(transaction.clone()));
+        assert_eq!(block.opaque_payload(), Some(&transaction));
+
+        let block = create_block(Observation::Remove {
+            peer_id: PeerId::new("Bob"),
+            related_info: vec![],
+        });
+        assert_eq!(block.opaque_payload(), None);
+    }
+
+    #[test]
+    fn signatories_and_quorum() {
+        let peer_ids = ["Alice", "Bob", "Carol"]
+            .iter()
+            .map(|name| PeerId::new(name))
+            .collect_vec();
+        let votes: BTreeMap<_, _> = peer_ids[..2]
+            .iter()
+            .map(|peer_id| {
+                let vote = Vote::new(
+                    peer_id,
+                    Observation::OpaquePayload(Transaction::new("ABCD")),
+                );
+                (peer_id.clone(), vote)
+            })
+            .collect();
+        let mut block = unwrap!(Block::new(&votes));
+        assert_eq!(block.signatories(), peer_ids[..2].iter().collect());
+
+        // Two out of three isn't a supermajority.
+        assert!(block.reached_quorum(3, ConsensusMode::Single));
+        assert!(!block.reached_quorum(3, ConsensusMode::Supermajority));
+        assert!(block.reached_quorum(2, ConsensusMode::Supermajority));
+
+        let carol_vote = Vote::new(
+            &peer_ids[2],
+            Observation::OpaquePayload(Transaction::new("ABCD")),
+        );
+        assert!(unwrap!(block.add_vote(&peer_ids[2], &carol_vote)));
+        assert_eq!(block.signatories(), peer_ids.iter().collect());
+        assert!(block.reached_quorum(3, ConsensusMode::Supermajority));
+    }
+
+    #[test]
+    fn duplicate_signatory_counted_once() {
+        let alice = PeerId::new("Alice");
+        let vote = |payload| {
+            Vote::new(
+                &alice,
+                Observation::OpaquePayload(Transaction::new(payload)),
+            )
+        };
+        let votes: BTreeMap<_, _> = iter::once((alice.clone(), vote("ABCD"))).collect();
+        let mut block = unwrap!(Block::new(&votes));
+
+        // A second proof by Alice, e.g. a relayed block carrying another of her signatures.
+        let other_proof = unwrap!(vote("EFGH").create_proof(&alice));
+        assert!(block.proofs.insert(other_proof));
+        assert_eq!(block.proofs().len(), 2);
+        assert_eq!(block.signatories().len(), 1);
+
+        // One signatory out of two voters isn't a supermajority.
+        assert!(block.reached_quorum(2, ConsensusMode::Single));
+        assert!(!block.reached_quorum(2, ConsensusMode::Supermajority));
+    }
+}
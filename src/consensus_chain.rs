@@ -0,0 +1,148 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{hash::Hash, observation::ObservationHash, serialise};
+use std::fmt::{self, Debug, Formatter};
+
+/// Hash identifying a link of a [ConsensusChain](struct.ConsensusChain.html), and through it all
+/// the payloads consensused up to that link.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ChainHash(Hash);
+
+impl ChainHash {
+    /// Computes the hash of the link for the payload whose hash is `payload_hash` (see
+    /// [Block::payload_hash](struct.Block.html#method.payload_hash)), consensused right after the
+    /// link whose hash is `previous`, or first if `previous` is `None`.
+    ///
+    /// This is the SHA3-256 hash of the bincode serialisation of the `(previous, payload_hash)`
+    /// tuple.
+    pub fn new(previous: Option<&ChainHash>, payload_hash: &ObservationHash) -> Self {
+        ChainHash(Hash::from(serialise(&(previous, payload_hash)).as_slice()))
+    }
+}
+
+impl Debug for ChainHash {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "{:?}", self.0)
+    }
+}
+
+/// A link of a [ConsensusChain](struct.ConsensusChain.html).
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ConsensusLink {
+    /// Hash of the consensused payload.
+    pub payload_hash: ObservationHash,
+    /// Hash of this link, computed from `payload_hash` and the hash of the previous link.
+    pub chain_hash: ChainHash,
+}
+
+/// Tamper-evident record of the order in which payloads got consensused, as returned by
+/// [Parsec::consensus_chain](struct.Parsec.html#method.consensus_chain).
+///
+/// Each link holds the hash of a consensused payload along with a hash chaining it to all the
+/// previous ones, so the hash of the last link commits to the whole sequence of decisions: peers
+/// which consensused the same payloads in the same order have the same last `chain_hash`.  An
+/// auditor can check the chain with `verify`, and match its payload hashes against those of the
+/// blocks returned by `poll`.  Internal payloads (see `Observation::is_internal`) are linked too,
+/// but never returned by `poll`, so the polled blocks must be matched as a subsequence, skipping
+/// the links which don't correspond to any of them.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ConsensusChain {
+    base: Option<ChainHash>,
+    links: Vec<ConsensusLink>,
+}
+
+impl ConsensusChain {
+    pub(crate) fn new<'a, I>(base: Option<ChainHash>, payload_hashes: I) -> Self
+    where
+        I: IntoIterator<Item = &'a ObservationHash>,
+    {
+        let mut previous = base;
+        let links = payload_hashes
+            .into_iter()
+            .map(|payload_hash| {
+                let chain_hash = ChainHash::new(previous.as_ref(), payload_hash);
+                previous = Some(chain_hash);
+                ConsensusLink {
+                    payload_hash: *payload_hash,
+                    chain_hash,
+                }
+            })
+            .collect();
+        Self { base, links }
+    }
+
+    /// Returns the hash of the link preceding the first one of `links`, or `None` if the chain
+    /// starts with the first consensused payload.  Links may have been dropped from the front of
+    /// the chain to limit memory usage (see
+    /// [Parsec::set_consensus_history_retention](struct.Parsec.html#method.set_consensus_history_retention)).
+    pub fn base(&self) -> Option<&ChainHash> {
+        self.base.as_ref()
+    }
+
+    /// Returns the links of the chain, in consensus order.
+    pub fn links(&self) -> &[ConsensusLink] {
+        &self.links
+    }
+
+    /// Returns the hash of the last link, which commits to the whole chain.
+    pub fn last_hash(&self) -> Option<&ChainHash> {
+        self.links
+            .last()
+            .map(|link| &link.chain_hash)
+            .or_else(|| self.base())
+    }
+
+    /// Recomputes the hash of every link from its payload hash and the hash of the previous link,
+    /// starting from `base`, and checks it matches the recorded one.
+    pub fn verify(&self) -> bool {
+        let mut previous = self.base.as_ref();
+        for link in &self.links {
+            if link.chain_hash != ChainHash::new(previous, &link.payload_hash) {
+                return false;
+            }
+            previous = Some(&link.chain_hash);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify() {
+        let payload_hashes = (0..4u8)
+            .map(|index| ObservationHash(Hash::from(&[index][..])))
+            .collect::<Vec<_>>();
+        let chain = ConsensusChain::new(None, &payload_hashes);
+        assert!(chain.verify());
+        assert_eq!(chain.links().len(), 4);
+
+        // The chain can be split, the second part being based on the end of the first one.
+        let head = ConsensusChain::new(None, &payload_hashes[..2]);
+        let tail = ConsensusChain::new(head.last_hash().cloned(), &payload_hashes[2..]);
+        assert!(tail.verify());
+        assert_eq!(tail.links(), &chain.links()[2..]);
+        assert_eq!(tail.last_hash(), chain.last_hash());
+
+        // Reordering or altering any payload is detected.
+        let mut reordered = chain.clone();
+        reordered.links.swap(1, 2);
+        assert!(!reordered.verify());
+        let mut altered = chain.clone();
+        altered.links[3].payload_hash = payload_hashes[0];
+        assert!(!altered.verify());
+        let rebased = ConsensusChain {
+            base: Some(chain.links[0].chain_hash),
+            links: chain.links.clone(),
+        };
+        assert!(!rebased.verify());
+    }
+}
@@ -0,0 +1,77 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::growth_rate::Clock;
+use rand::Rng;
+use std::{
+    cell::Cell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+/// The true time of a simulated network: the number of steps executed so far, each lasting
+/// `STEP_DURATION`.
+#[derive(Clone)]
+pub(super) struct NetworkTime {
+    origin: Instant,
+    step: Rc<Cell<usize>>,
+}
+
+impl NetworkTime {
+    pub const STEP_DURATION: Duration = Duration::from_millis(100);
+
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+            step: Rc::new(Cell::new(0)),
+        }
+    }
+
+    pub fn set_step(&self, step: usize) {
+        self.step.set(step);
+    }
+
+    /// Returns a clock for a new peer, ahead of the true time by up to `max_offset` and running
+    /// at a rate which deviates from the true one by up to `max_drift`, e.g. 0.1 for 10%.
+    ///
+    /// `rng` should only be used for clocks, so that runs with and without skew can be compared.
+    pub fn skewed_clock<R: Rng>(
+        &self,
+        rng: &mut R,
+        max_offset: Duration,
+        max_drift: f64,
+    ) -> SkewedClock {
+        assert!(
+            (0.0..1.0).contains(&max_drift),
+            "clock drift must be in [0, 1): {}",
+            max_drift
+        );
+        let offset = max_offset.mul_f64(rng.gen());
+        let rate = 1.0 + max_drift * (2.0 * rng.gen::<f64>() - 1.0);
+        SkewedClock {
+            time: self.clone(),
+            offset,
+            rate,
+        }
+    }
+}
+
+/// `Clock` of a simulated peer: the network time, shifted by a fixed offset and running at a
+/// slightly wrong rate.
+pub struct SkewedClock {
+    time: NetworkTime,
+    offset: Duration,
+    rate: f64,
+}
+
+impl Clock for SkewedClock {
+    fn now(&self) -> Instant {
+        let elapsed = NetworkTime::STEP_DURATION.mul_f64(self.time.step.get() as f64 * self.rate);
+        self.time.origin + self.offset + elapsed
+    }
+}
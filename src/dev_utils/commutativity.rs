@@ -0,0 +1,168 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{dot_parser::parse_dot_file, new_rng, record::extract_genesis_group};
+use crate::{
+    block::Block,
+    gossip::PackedEvent,
+    mock::{PeerId, Transaction},
+    parsec::TestParsec,
+};
+use itertools::Itertools;
+use rand::Rng;
+use std::path::Path;
+
+/// Checks that the blocks a peer consensuses don't depend on the order in which it ingests the
+/// events of a gossip graph, as long as every event is ingested after its parents.
+///
+/// The graph is read from a dot file dumped by a member of the genesis group.  Starting from that
+/// member's genesis state, its remaining events are fed to separate copies of it: once in the order
+/// they appear in the file, then in `num_orders` random topological orders drawn from `rng`.  Each
+/// copy must yield the same sequence of blocks.  The graph must not contain membership changes, as
+/// events created by peers we don't know yet can't be ingested.
+pub(crate) struct CommutativityCheck {
+    base: TestParsec<Transaction, PeerId>,
+    packed_events: Vec<PackedEvent<Transaction, PeerId>>,
+    // Positions in `packed_events` of the parents of each event which are not part of `base`.
+    parents: Vec<Vec<usize>>,
+}
+
+impl CommutativityCheck {
+    // Number of events the genesis state of the graph's owner consists of: its initial event and
+    // the one carrying `Observation::Genesis`.
+    const NUM_GENESIS_EVENTS: usize = 2;
+
+    pub fn new<P: AsRef<Path>, R: Rng>(path: P, rng: &mut R) -> Self {
+        let contents = unwrap!(parse_dot_file(path.as_ref()));
+        let genesis_group = unwrap!(
+            contents
+                .graph
+                .iter()
+                .find_map(|event| extract_genesis_group(event.inner(), &contents.observations))
+                .cloned(),
+            "No event carrying Observation::Genesis found"
+        );
+
+        let base = TestParsec::from_genesis(
+            contents.our_id.clone(),
+            &genesis_group,
+            contents.consensus_modes.global(),
+            Box::new(new_rng(rng)),
+        );
+        for (ours, theirs) in base.graph().iter().zip(&contents.graph) {
+            assert_eq!(ours.hash(), theirs.hash(), "Genesis state mismatch");
+        }
+
+        let position =
+            |topological_index: usize| topological_index.checked_sub(Self::NUM_GENESIS_EVENTS);
+        let (packed_events, parents) = contents
+            .graph
+            .iter()
+            .skip(Self::NUM_GENESIS_EVENTS)
+            .map(|event| {
+                let packed_event = unwrap!(event.pack(contents.event_context()));
+                let parents = event
+                    .self_parent()
+                    .into_iter()
+                    .chain(event.other_parent())
+                    .filter_map(|parent| position(parent.topological_index()))
+                    .collect();
+                (packed_event, parents)
+            })
+            .unzip();
+
+        Self {
+            base,
+            packed_events,
+            parents,
+        }
+    }
+
+    /// Panics if any of the orders yields different blocks than the order of the file.
+    pub fn run<R: Rng>(&self, num_orders: usize, rng: &mut R) {
+        let expected = self.play(0..self.packed_events.len(), rng);
+        assert!(
+            !expected.is_empty(),
+            "The graph doesn't lead to any block, so there is nothing to compare"
+        );
+
+        for _ in 0..num_orders {
+            let order = self.random_topological_order(rng);
+            let actual = self.play(order.iter().cloned(), rng);
+            assert_eq!(
+                expected, actual,
+                "Blocks differ when ingesting in order {:?}",
+                order
+            );
+        }
+    }
+
+    fn play<I, R>(&self, order: I, rng: &mut R) -> Vec<Block<Transaction, PeerId>>
+    where
+        I: IntoIterator<Item = usize>,
+        R: Rng,
+    {
+        let mut parsec = TestParsec::from(self.base.clone_with_rng(Box::new(new_rng(rng))));
+        for position in order {
+            let _ = unwrap!(parsec.unpack_and_add_event(self.packed_events[position].clone()));
+        }
+        parsec.poll_iter().collect_vec()
+    }
+
+    // Picks each next event uniformly among those whose parents have all been picked already.
+    fn random_topological_order<R: Rng>(&self, rng: &mut R) -> Vec<usize> {
+        let mut num_missing_parents = self.parents.iter().map(Vec::len).collect_vec();
+        let mut children = vec![Vec::new(); self.parents.len()];
+        for (child, parents) in self.parents.iter().enumerate() {
+            for &parent in parents {
+                children[parent].push(child);
+            }
+        }
+
+        let mut ready = num_missing_parents
+            .iter()
+            .positions(|&num| num == 0)
+            .collect_vec();
+        let mut order = Vec::with_capacity(self.parents.len());
+        while !ready.is_empty() {
+            let next = ready.swap_remove(rng.gen_range(0, ready.len()));
+            order.push(next);
+            for &child in &children[next] {
+                num_missing_parents[child] -= 1;
+                if num_missing_parents[child] == 0 {
+                    ready.push(child);
+                }
+            }
+        }
+        assert_eq!(order.len(), self.parents.len());
+        order
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dev_utils::{new_common_rng, RngChoice};
+
+    const NUM_ORDERS: usize = 5;
+
+    fn check(path: &str) {
+        let mut rng = new_common_rng(RngChoice::Seeded([1, 2, 3, 4]));
+        CommutativityCheck::new(path, &mut rng).run(NUM_ORDERS, &mut rng);
+    }
+
+    #[test]
+    fn minimal() {
+        check("input_graphs/benches/minimal.dot")
+    }
+
+    #[test]
+    fn static_membership() {
+        check("input_graphs/benches/static.dot")
+    }
+}
@@ -10,6 +10,8 @@
 use crate::error::Error;
 #[cfg(any(all(test, feature = "mock"), feature = "testing"))]
 use crate::gossip::EventContextRef;
+#[cfg(feature = "dump-graphs")]
+use crate::{gossip::GraphSnapshot, meta_voting::MetaElectionSnapshot};
 use crate::{
     gossip::{CauseInput, Event, EventIndex, Graph, IndexedEventRef},
     hash::{Hash, HASH_LEN},
@@ -18,8 +20,8 @@ use crate::{
     },
     mock::{PeerId, Transaction},
     observation::{
-        ConsensusMode, Malice, MaliceInput, Observation, ObservationHash, ObservationInfo,
-        ObservationKey, ObservationStore,
+        ConsensusMode, ConsensusModes, Malice, MaliceInput, Observation, ObservationHash,
+        ObservationInfo, ObservationKey, ObservationStore,
     },
     peer_list::{PeerIndex, PeerIndexMap, PeerIndexSet, PeerList, PeerState},
 };
@@ -541,6 +543,7 @@ fn parse_observation() -> Parser<u8, Observation<Transaction, PeerId>> {
         | parse_opaque()
         | parse_start_dkg()
         | parse_dkg_msg()
+        | parse_heartbeat()
 }
 
 fn parse_accusation() -> Parser<u8, (PeerId, MaliceInput)> {
@@ -638,6 +641,11 @@ fn parse_dkg_msg() -> Parser<u8, Observation<Transaction, PeerId>> {
     .map(Observation::DkgMessage)
 }
 
+fn parse_heartbeat() -> Parser<u8, Observation<Transaction, PeerId>> {
+    (seq(b"Heartbeat(") * parse_usize() - seq(b")"))
+        .map(|index| Observation::Heartbeat(index as u64))
+}
+
 fn parse_transaction() -> Parser<u8, String> {
     is_a(alphanum).repeat(1..).convert(String::from_utf8)
 }
@@ -761,6 +769,10 @@ fn parse_end() -> Parser<u8, ()> {
     one_of(b" \r\n").repeat(0..) * end()
 }
 
+/// The contents of a `.core` file, as written when `DUMP_CORE` is set.
+#[cfg(feature = "dump-graphs")]
+pub(crate) type Snapshot = (GraphSnapshot, MetaElectionSnapshot<PeerId>);
+
 /// The event graph and associated info that were parsed from the dumped dot file.
 pub(crate) struct ParsedContents {
     pub our_id: PeerId,
@@ -768,7 +780,7 @@ pub(crate) struct ParsedContents {
     pub meta_election: MetaElection,
     pub peer_list: PeerList<PeerId>,
     pub observations: ObservationStore<Transaction, PeerId>,
-    pub consensus_mode: ConsensusMode,
+    pub consensus_modes: ConsensusModes,
     pub secure_rng_values: Vec<u32>,
     pub serialized_key_gens_and_next_id: Option<Vec<u8>>,
 }
@@ -785,7 +797,7 @@ impl ParsedContents {
             meta_election,
             peer_list,
             observations: ObservationStore::new(),
-            consensus_mode: ConsensusMode::Supermajority,
+            consensus_modes: ConsensusModes::new(ConsensusMode::Supermajority),
             secure_rng_values: Vec::new(),
             serialized_key_gens_and_next_id: None,
         }
@@ -823,7 +835,7 @@ impl ParsedContents {
             graph: &self.graph,
             peer_list: &self.peer_list,
             observations: &self.observations,
-            consensus_mode: self.consensus_mode,
+            consensus_modes: &self.consensus_modes,
         }
     }
 
@@ -896,9 +908,10 @@ fn derive_test_name_from_current_thread_name() -> String {
 }
 
 /// For use by functional/unit tests which provide a dot file for the test setup.  This reads and
-/// parses the dot file as per `parse_dot_file()` above, with test name being part of the path.
+/// parses the dot file as per `parse_dot_file()` above, with test name being part of the path.  Tests
+/// sharing the setup of another test pass that test's name to reuse its dot file.
 #[cfg(all(test, feature = "mock"))]
-fn parse_dot_file_with_test_name(filename: &str, test_name: &str) -> ParsedContents {
+pub(crate) fn parse_dot_file_with_test_name(filename: &str, test_name: &str) -> ParsedContents {
     use std::path::PathBuf;
 
     let mut dot_path = PathBuf::from("input_graphs");
@@ -971,7 +984,7 @@ fn convert_into_parsed_contents(result: ParsedFile) -> ParsedContents {
 
     parsed_contents.peer_list = peer_list;
     parsed_contents.meta_election = meta_election;
-    parsed_contents.consensus_mode = consensus_mode;
+    parsed_contents.consensus_modes = ConsensusModes::new(consensus_mode);
     parsed_contents.secure_rng_values = secure_rng_values;
     parsed_contents.serialized_key_gens_and_next_id = serialized_key_gens_and_next_id;
     parsed_contents
@@ -1058,8 +1071,12 @@ fn convert_to_meta_election(
         interesting_events,
         unconsensused_events,
         consensus_history: meta_election.consensus_history,
+        num_archived_consensus: 0,
+        archived_chain_hash: None,
+        consensus_history_retention: None,
         continue_consensus_start_index: 0,
         new_consensus_start_index: 0,
+        forced_coin_tosses: Vec::new(),
     }
 }
 
@@ -1255,22 +1272,23 @@ fn next_topological_event(
 #[cfg(all(test, feature = "dump-graphs"))]
 mod tests {
     use super::*;
+    #[cfg(feature = "testing")]
+    use crate::{
+        dev_utils::{new_common_rng, new_rng},
+        parsec::Parsec,
+    };
     use crate::{
         dev_utils::{Environment, RngChoice, Schedule, ScheduleOptions},
-        dump_graph::DIR,
-        gossip::GraphSnapshot,
-        meta_voting::MetaElectionSnapshot,
-        mock::PeerId,
+        dump_graph::{DIR, DUMP_CORE},
     };
     use std::fs;
 
-    type Snapshot = (GraphSnapshot, MetaElectionSnapshot<PeerId>);
-
     // Alter the seed here to reproduce failures
     static SEED: RngChoice = RngChoice::Random;
 
     #[test]
     fn dot_parser() {
+        DUMP_CORE.with(|dump_core| dump_core.set(true));
         let mut env = Environment::new(SEED);
         let options = ScheduleOptions {
             genesis_size: 4,
@@ -1305,8 +1323,57 @@ mod tests {
                 MetaElectionSnapshot::new(&parsed.meta_election, &parsed.graph, &parsed.peer_list),
             );
 
+            let diff = actual_snapshot.0.diff(&expected_snapshot.0);
+            assert!(diff.is_empty(), "{:?}:\n{}", entry.path(), diff);
             assert_eq!(actual_snapshot, expected_snapshot);
         }
         assert_ne!(num_of_files, 0u8);
     }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn from_core_snapshot() {
+        DUMP_CORE.with(|dump_core| dump_core.set(true));
+        let mut env = Environment::new(SEED);
+        let options = ScheduleOptions {
+            genesis_size: 4,
+            opaque_to_add: 2,
+            ..Default::default()
+        };
+        let schedule = Schedule::new(&mut env, &options);
+        unwrap!(env.execute_schedule(schedule));
+
+        let core_paths = DIR.with(|dir| {
+            unwrap!(fs::read_dir(dir))
+                .map(|entry| unwrap!(entry).path())
+                .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("core"))
+                .sorted()
+                .collect_vec()
+        });
+        assert!(core_paths.len() > 1);
+
+        let mut rng = new_common_rng(SEED);
+        for path in &core_paths {
+            let mut parsec = unwrap!(Parsec::from_core_snapshot(
+                path,
+                Box::new(new_rng(&mut rng))
+            ));
+            // The restored peer is live: it can vote straight away.
+            let events_len = parsec.events_len();
+            unwrap!(parsec.vote_for(Observation::OpaquePayload(Transaction::new("restored"))));
+            assert_eq!(parsec.events_len(), events_len + 1);
+        }
+
+        // A `.core` file is checked against the dot file next to it.
+        let mismatched = core_paths[0].with_file_name("mismatched.core");
+        unwrap!(fs::copy(&core_paths[1], &mismatched));
+        unwrap!(fs::copy(
+            core_paths[0].with_extension("dot"),
+            mismatched.with_extension("dot")
+        ));
+        let error = Parsec::from_core_snapshot(&mismatched, Box::new(new_rng(&mut rng)))
+            .err()
+            .map(|error| error.kind());
+        assert_eq!(error, Some(io::ErrorKind::InvalidData));
+    }
 }
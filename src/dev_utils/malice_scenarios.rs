@@ -0,0 +1,82 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Simulated network setups which are guaranteed to make the well-behaved peers accuse the
+//! malicious ones of one specific kind of malice.
+//!
+//! Each function returns an `Environment` along with a `Schedule` which, once executed, yields an
+//! accusation block for every malicious peer.  The consensus checks run by
+//! `Environment::execute_schedule` already fail on any accusation the scenario didn't expect, so a
+//! successful execution also shows that nothing but the intended malice was detected.
+//!
+//! Malicious peers of the simulated network can currently only commit forks, duplicate votes and
+//! invalid accusations (see `MaliciousBehaviour`), so only these kinds of malice have a scenario.
+//! The remaining kinds need malformed events (`UnexpectedGenesis`, `MissingGenesis`,
+//! `IncorrectGenesis`, `OtherParentBySameCreator`, `SelfParentByDifferentCreator`,
+//! `InvalidRequest`, `InvalidResponse`), a cooperating malicious peer (`Accomplice`) or a dispute
+//! only consensus can settle (`Unprovable`).  They are exercised from hand-crafted graphs by the
+//! `handle_malice` functional tests instead.
+
+use crate::{
+    dev_utils::{Environment, MaliciousBehaviour, RngChoice, Schedule, ScheduleOptions},
+    parsec::ForkPolicy,
+};
+
+/// A genesis group of five peers, one of which gossips a `Requesting` event forking from its
+/// previous one.  The good peers accuse it with `Malice::Fork`.
+pub fn fork(seed: RngChoice) -> (Environment, Schedule) {
+    fork_with_policy(seed, ForkPolicy::AccuseAndProposeRemoval)
+}
+
+/// As `fork`, with all peers using `fork_policy`.
+pub fn fork_with_policy(seed: RngChoice, fork_policy: ForkPolicy) -> (Environment, Schedule) {
+    let mut env = Environment::new(seed);
+    let options = ScheduleOptions {
+        genesis_size: 5,
+        malicious_genesis_count: 1,
+        opaque_to_add: 2,
+        fork_policy,
+        ..Default::default()
+    };
+    let schedule = Schedule::new(&mut env, &options);
+    (env, schedule)
+}
+
+/// A genesis group of five peers, one of which creates a second event voting for an opaque payload
+/// it already voted for.  The good peers accuse it with `Malice::DuplicateVote`.
+///
+/// The good peers are also made to vote twice for every opaque payload, which they must neither
+/// gossip nor be accused for.
+pub fn duplicate_vote(seed: RngChoice) -> (Environment, Schedule) {
+    let mut env = Environment::new(seed);
+    let options = ScheduleOptions {
+        genesis_size: 5,
+        malicious_genesis_count: 1,
+        malicious_behaviour: MaliciousBehaviour::DuplicateVote,
+        opaque_to_add: 2,
+        prob_vote_duplication: 1.0,
+        ..Default::default()
+    };
+    let schedule = Schedule::new(&mut env, &options);
+    (env, schedule)
+}
+
+/// A genesis group of five peers, one of which accuses a well-behaved peer of a fork it hasn't
+/// committed.  The good peers accuse it with `Malice::InvalidAccusation`.
+pub fn invalid_accusation(seed: RngChoice) -> (Environment, Schedule) {
+    let mut env = Environment::new(seed);
+    let options = ScheduleOptions {
+        genesis_size: 5,
+        malicious_genesis_count: 1,
+        malicious_behaviour: MaliciousBehaviour::InvalidAccusation,
+        opaque_to_add: 2,
+        ..Default::default()
+    };
+    let schedule = Schedule::new(&mut env, &options);
+    (env, schedule)
+}
@@ -0,0 +1,87 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Synthetic meta-votes for measuring the binary agreement step of the meta-election in isolation,
+//! i.e. without a network nor a gossip graph.
+
+use crate::meta_voting::{BoolSet, MetaVote, Step, VoteWeights};
+use itertools::Itertools;
+use std::{collections::BTreeMap, num::NonZeroUsize};
+
+/// Number of rounds the voters have gone through in `Contention::Contended`.
+pub const CONTENDED_ROUNDS: usize = 4;
+
+/// How the meta-votes of the voters are split.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Contention {
+    /// All the voters agree from the start: the meta-votes are on their way to being decided in
+    /// the first round.
+    Uncontended,
+    /// The voters are evenly split: they went through `CONTENDED_ROUNDS` rounds without deciding,
+    /// and still can't agree.
+    Contended,
+}
+
+/// The meta-votes of every voter of a meta-election for a single binary value, as seen by one of
+/// them about to compute its next meta-votes.
+pub struct MetaVoteInputs {
+    ours: Vec<MetaVote>,
+    others: Vec<Vec<MetaVote>>,
+    coin_tosses: BTreeMap<usize, bool>,
+    weights: VoteWeights,
+}
+
+impl MetaVoteInputs {
+    /// Creates the meta-votes of `num_peers` equally weighted voters.
+    pub fn new(num_peers: usize, contention: Contention) -> Self {
+        let total = unwrap!(NonZeroUsize::new(num_peers));
+        let history = |voter: usize| match contention {
+            Contention::Uncontended => vec![MetaVote::new(
+                0,
+                Step::ForcedTrue,
+                BoolSet::Single(true),
+                BoolSet::Single(true),
+                Some(true),
+                None,
+            )],
+            Contention::Contended => (0..CONTENDED_ROUNDS)
+                .cartesian_product(&[Step::ForcedTrue, Step::ForcedFalse, Step::GenuineFlip])
+                .map(|(round, &step)| {
+                    let value = voter % 2 == 1;
+                    MetaVote::new(
+                        round,
+                        step,
+                        BoolSet::Single(value),
+                        BoolSet::Both,
+                        Some(value),
+                        None,
+                    )
+                })
+                .collect(),
+        };
+
+        Self {
+            ours: history(0),
+            others: (1..num_peers).map(history).collect(),
+            coin_tosses: (0..CONTENDED_ROUNDS).map(|round| (round, true)).collect(),
+            weights: VoteWeights { own: 1, total },
+        }
+    }
+
+    /// Computes our next meta-votes from the others', as done for each of our sync events, i.e.
+    /// `MetaVote::next_temp` followed by `MetaVote::next_final`.  Returns how many there are.
+    pub fn next(&self) -> usize {
+        let others = self
+            .others
+            .iter()
+            .map(|votes| (&votes[..], 1))
+            .collect_vec();
+        let temp = MetaVote::next_temp(&self.ours, &others, self.weights);
+        MetaVote::next_final(&temp, &self.coin_tosses, self.weights).len()
+    }
+}
@@ -9,10 +9,17 @@
 #[macro_use]
 mod macros;
 
+mod clock;
+#[cfg(all(test, feature = "mock"))]
+mod commutativity;
 /// This is used to read a dumped dot file and rebuild the event graph and associated info.
 #[cfg(any(all(test, feature = "mock"), feature = "testing"))]
 mod dot_parser;
 mod environment;
+#[cfg(all(feature = "testing", feature = "malice-detection"))]
+pub mod malice_scenarios;
+#[cfg(feature = "testing")]
+pub mod meta_vote_inputs;
 mod misc;
 mod network;
 mod peer;
@@ -24,16 +31,22 @@ mod pseudo_random;
 mod record;
 mod schedule;
 
+#[cfg(all(feature = "testing", feature = "dump-graphs"))]
+pub(crate) use self::dot_parser::Snapshot;
+#[cfg(any(
+    all(test, feature = "mock"),
+    all(feature = "testing", feature = "dump-graphs")
+))]
+pub(crate) use self::dot_parser::{parse_dot_file, ParsedContents};
 #[cfg(all(test, feature = "mock"))]
-pub(crate) use self::dot_parser::parse_test_dot_file;
-#[cfg(all(test, feature = "mock"))]
-pub(crate) use self::dot_parser::ParsedContents;
+pub(crate) use self::dot_parser::{parse_dot_file_with_test_name, parse_test_dot_file};
 #[cfg(any(all(test, feature = "mock"), feature = "testing"))]
 pub use self::record::Record;
 pub use self::{
+    clock::SkewedClock,
     environment::Environment,
     misc::TestIterator,
-    network::{ConsensusError, Network},
+    network::{ConsensusError, Message, Network, RunSummary},
     peer::{NetworkView, Peer, PeerStatus},
     peer_statuses::PeerStatuses,
     pseudo_random::{new_common_rng, new_rng, thread_rng, ReplayRng, RngChoice, RngDebug},
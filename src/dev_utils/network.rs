@@ -7,15 +7,16 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{
+    clock::NetworkTime,
     new_rng,
     peer::{NetworkView, Peer, PeerStatus},
-    schedule::{AddPeerType, Schedule, ScheduleEvent, ScheduleOptions},
-    Observation,
+    schedule::{AddPeerType, GossipScheduler, Schedule, ScheduleEvent, ScheduleOptions},
+    thread_rng, Observation,
 };
 use crate::{
     block::Block,
     error::Error,
-    gossip::{Request, Response},
+    gossip::{EventHash, Request, Response},
     mock::{PeerId, Transaction},
     observation::{
         is_more_than_two_thirds, ConsensusMode, Malice, Observation as ParsecObservation,
@@ -23,13 +24,17 @@ use crate::{
 };
 use itertools::Itertools;
 use rand::{seq::SliceRandom, Rng};
+use rand_xorshift::XorShiftRng;
 use std::{
     collections::{BTreeMap, BTreeSet, VecDeque},
     fmt,
 };
 
-enum Message {
+/// A message in flight between two peers of the simulated network.
+pub enum Message {
+    /// A gossip request, along with the delay after which its response is to be delivered.
     Request(Request<Transaction, PeerId>, usize),
+    /// A gossip response.
     Response(Response<Transaction, PeerId>),
 }
 
@@ -44,6 +49,86 @@ pub struct Network {
     genesis: BTreeSet<PeerId>,
     msg_queue: BTreeMap<PeerId, Vec<QueueEntry>>,
     consensus_mode: ConsensusMode,
+    fair_gossip: FairGossip,
+    time: NetworkTime,
+    // Only used to skew the peers' clocks, so that skew leaves the random streams driving the rest
+    // of a seeded run untouched.
+    clock_rng: XorShiftRng,
+    num_steps: usize,
+    num_requests_sent: usize,
+    num_responses_sent: usize,
+}
+
+// State of `GossipScheduler::Fair`: the recipients each peer has yet to gossip to in its current
+// round.
+#[derive(Default)]
+struct FairGossip {
+    rounds: BTreeMap<PeerId, Vec<PeerId>>,
+}
+
+impl FairGossip {
+    // Returns the next recipient of `sender`, starting a new round in a random order once the
+    // current one is over.  Recipients which stopped running since the round started are skipped.
+    fn next_recipient<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        sender: &PeerId,
+        present_peers: &[PeerId],
+    ) -> Option<PeerId> {
+        let round = self.rounds.entry(sender.clone()).or_default();
+        loop {
+            if round.is_empty() {
+                round.extend(present_peers.iter().filter(|id| *id != sender).cloned());
+                round.shuffle(rng);
+            }
+            let recipient = round.pop()?;
+            if present_peers.contains(&recipient) {
+                return Some(recipient);
+            }
+        }
+    }
+}
+
+/// Overview of what happened in a `Network`, across all the schedules it executed so far.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunSummary {
+    /// Number of local steps executed.
+    pub num_steps: usize,
+    /// Number of gossip requests sent to running peers.
+    pub num_requests_sent: usize,
+    /// Number of gossip responses sent to running peers.
+    pub num_responses_sent: usize,
+    /// Number of blocks decided, as polled by the most up-to-date well-behaved peer.
+    pub num_blocks: usize,
+    /// Number of these blocks which are accusations.
+    pub num_accusations: usize,
+    /// Final status of every peer.
+    pub peers: BTreeMap<PeerId, PeerStatus>,
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let rows = [
+            ("steps", self.num_steps),
+            ("requests sent", self.num_requests_sent),
+            ("responses sent", self.num_responses_sent),
+            ("blocks decided", self.num_blocks),
+            ("accusations", self.num_accusations),
+        ];
+        for (name, value) in &rows {
+            writeln!(formatter, "{:<16}{:>8}", name, value)?;
+        }
+        write!(formatter, "peers")?;
+        for (peer_id, status) in &self.peers {
+            write!(
+                formatter,
+                "\n  {:<14}{:>8}",
+                format!("{:?}", peer_id),
+                format!("{:?}", status)
+            )?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -106,6 +191,12 @@ pub enum ConsensusError {
         accused: PeerId,
         malice: Malice<Transaction, PeerId>,
     },
+    DivergentMetaVotes {
+        event: EventHash,
+        voter: PeerId,
+        peers: (PeerId, PeerId),
+        meta_votes: (String, String),
+    },
 }
 
 impl Network {
@@ -116,6 +207,12 @@ impl Network {
             genesis: BTreeSet::new(),
             msg_queue: BTreeMap::new(),
             consensus_mode,
+            fair_gossip: FairGossip::default(),
+            time: NetworkTime::new(),
+            clock_rng: thread_rng(),
+            num_steps: 0,
+            num_requests_sent: 0,
+            num_responses_sent: 0,
         }
     }
 
@@ -198,6 +295,10 @@ impl Network {
         if !self.peer(dst).is_running() {
             return;
         }
+        match message {
+            Message::Request(..) => self.num_requests_sent += 1,
+            Message::Response(..) => self.num_responses_sent += 1,
+        }
         self.msg_queue
             .entry(dst.clone())
             .or_insert_with(Vec::new)
@@ -208,6 +309,20 @@ impl Network {
             });
     }
 
+    /// Queues the given message to be delivered from `src` to `dst` at step `deliver_after` of the
+    /// next executed schedule. This allows crafted requests and responses to be handled at a
+    /// precise point, rather than relying on the random scheduler to produce them.
+    #[cfg(feature = "testing")]
+    pub fn inject_message(
+        &mut self,
+        src: PeerId,
+        dst: &PeerId,
+        message: Message,
+        deliver_after: usize,
+    ) {
+        self.send_message(src, dst, message, deliver_after)
+    }
+
     /// Handles incoming requests and responses.
     fn handle_messages(&mut self, peer: &PeerId, step: usize) {
         if let Some(msgs) = self.msg_queue.remove(peer) {
@@ -244,31 +359,41 @@ impl Network {
         }
     }
 
-    fn send_gossip<R: Rng>(
-        &mut self,
+    fn choose_random_recipient<'a, R: Rng>(
         rng: &mut R,
-        options: &ScheduleOptions,
         sender: &PeerId,
-        present_peers: &[PeerId],
-        step: usize,
-    ) {
+        present_peers: &'a [PeerId],
+    ) -> Option<&'a PeerId> {
         if present_peers.len() == 1 && present_peers.contains(sender) {
-            return;
+            return None;
         }
 
-        let recipient = loop {
+        loop {
             let recipient = unwrap!(present_peers.choose(rng));
             if recipient != sender {
-                break recipient;
+                return Some(recipient);
             }
-        };
+        }
+    }
+
+    fn send_gossip<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        options: &ScheduleOptions,
+        sender: &PeerId,
+        recipient: &PeerId,
+        step: usize,
+    ) {
         let valid = self
             .peer(sender)
             .gossip_recipients()
             .any(|valid_recipient| valid_recipient == recipient);
+        if valid && !self.peer_mut(sender).should_gossip_to(recipient) {
+            return;
+        }
         let result = if self.peer(sender).is_malicious() && !self.peer(sender).has_misbehaved() {
             self.peer_mut(sender)
-                .create_gossip_with_fork(recipient, rng)
+                .create_gossip_with_malice(recipient, rng)
         } else {
             self.peer_mut(sender).create_gossip(recipient)
         };
@@ -404,12 +529,12 @@ impl Network {
         block: &Block<Transaction, PeerId>,
         section: &BTreeSet<PeerId>,
     ) -> Result<(), ConsensusError> {
-        let signatories: BTreeSet<_> = block
-            .proofs()
+        let signatories = block.signatories();
+        if let Some(pub_id) = signatories
             .iter()
-            .map(|proof| proof.public_id().clone())
-            .collect();
-        if let Some(pub_id) = signatories.difference(section).next() {
+            .cloned()
+            .find(|pub_id| !section.contains(*pub_id))
+        {
             return Err(ConsensusError::InvalidSignatory {
                 observation: block.payload().clone(),
                 signatory: pub_id.clone(),
@@ -421,16 +546,10 @@ impl Network {
         } else {
             ConsensusMode::Supermajority
         };
-        let correct_signatories = match consensus_mode {
-            ConsensusMode::Single => !signatories.is_empty(),
-            ConsensusMode::Supermajority => {
-                is_more_than_two_thirds(signatories.len(), section.len())
-            }
-        };
-        if !correct_signatories {
+        if !block.reached_quorum(section.len(), consensus_mode) {
             return Err(ConsensusError::TooFewSignatures {
                 observation: block.payload().clone(),
-                signatures: signatories,
+                signatures: signatories.into_iter().cloned().collect(),
             });
         }
         Ok(())
@@ -471,13 +590,48 @@ impl Network {
         Ok(())
     }
 
-    /// Check that no well-behaved peer has been accused of malice.
+    /// Checks that peers in the same meta-election agree on the meta-votes of the events they have
+    /// in common.  Any disagreement means the peers will eventually decide differently.
+    fn check_meta_votes_consistent(&self) -> Result<(), ConsensusError> {
+        let snapshots = self
+            .running_non_malicious_peers()
+            .filter(|peer| !peer.ignore_process_events())
+            .map(|peer| (peer.id(), peer.meta_election_snapshot()))
+            .collect_vec();
+
+        for ((peer_1, (elections_1, snapshot_1)), (peer_2, (elections_2, snapshot_2))) in
+            snapshots.iter().tuple_combinations()
+        {
+            if elections_1 != elections_2 {
+                continue;
+            }
+
+            if let Some((event, voter, meta_votes_1, meta_votes_2)) =
+                snapshot_1.find_divergent_meta_votes(snapshot_2)
+            {
+                return Err(ConsensusError::DivergentMetaVotes {
+                    event: *event,
+                    voter: voter.clone(),
+                    peers: ((*peer_1).clone(), (*peer_2).clone()),
+                    meta_votes: (format!("{:?}", meta_votes_1), format!("{:?}", meta_votes_2)),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that no well-behaved peer has been accused of malice, other than by the false
+    /// accusation of a malicious peer.
     fn check_unexpected_accusations(&self, peer_id: &PeerId) -> Result<(), ConsensusError> {
-        let accusation = self
-            .peer(peer_id)
+        let accuser = self.peer(peer_id);
+        let accusation = accuser
             .unpolled_accusations()
+            .filter(|(offender, malice)| !accuser.has_falsely_accused(offender, malice))
             .find(|(offender, malice)| match malice {
-                Malice::Fork(..) => !self.peer(offender).has_misbehaved(),
+                Malice::Fork(..) | Malice::DuplicateVote(..) | Malice::InvalidAccusation(..) => {
+                    !self.peer(offender).has_committed(malice)
+                }
                 _ => true,
             });
 
@@ -492,6 +646,34 @@ impl Network {
         }
     }
 
+    /// Returns an overview of the schedules executed so far, e.g. to compare the outcome of a
+    /// scenario under different configurations.
+    pub fn summary(&self) -> RunSummary {
+        let blocks = self
+            .running_non_malicious_peers()
+            .max_by_key(|peer| peer.blocks().count())
+            .map(|peer| peer.blocks_payloads())
+            .unwrap_or_else(Vec::new);
+        RunSummary {
+            num_steps: self.num_steps,
+            num_requests_sent: self.num_requests_sent,
+            num_responses_sent: self.num_responses_sent,
+            num_blocks: blocks.len(),
+            num_accusations: blocks
+                .iter()
+                .filter_map(|payload| match payload {
+                    ParsecObservation::Accusation { .. } => Some(()),
+                    _ => None,
+                })
+                .count(),
+            peers: self
+                .peers
+                .values()
+                .map(|peer| (peer.id().clone(), peer.status()))
+                .collect(),
+        }
+    }
+
     /// Simulates the network according to the given schedule.
     pub fn execute_schedule<R: Rng>(
         &mut self,
@@ -505,7 +687,7 @@ impl Network {
             max_observations,
             events,
             additional_steps,
-            options,
+            mut options,
         } = schedule;
         let mut queue: VecDeque<_> = events.into_iter().collect();
         let mut retry = Vec::new();
@@ -513,7 +695,7 @@ impl Network {
         let mut additional_step = || additional_steps.next().map(ScheduleEvent::LocalStep);
 
         while let Some(event) = queue.pop_front().or_else(&mut additional_step) {
-            if self.execute_event(rng, rng2, &options, event.clone())? {
+            if self.execute_event(rng, rng2, &mut options, event.clone())? {
                 for event in retry.drain(..).rev() {
                     queue.push_front(event)
                 }
@@ -522,6 +704,10 @@ impl Network {
                     self.check_consensus_broken()?;
                 }
 
+                if options.meta_vote_consistency_checks {
+                    self.check_meta_votes_consistent()?;
+                }
+
                 if self.consensus_complete(&peers, max_observations) {
                     break;
                 }
@@ -544,7 +730,7 @@ impl Network {
         &mut self,
         rng: &mut R,
         rng2: &mut R,
-        options: &ScheduleOptions,
+        options: &mut ScheduleOptions,
         event: ScheduleEvent,
     ) -> Result<bool, ConsensusError> {
         match event {
@@ -574,6 +760,7 @@ impl Network {
                             &genesis_ids,
                             self.consensus_mode,
                             Box::new(new_rng(rng2)),
+                            options.malicious_behaviour,
                         )
                     })
                     .collect_vec();
@@ -581,7 +768,17 @@ impl Network {
                 self.peers = good_peers
                     .into_iter()
                     .chain(malicious_peers.into_iter())
-                    .map(|peer| (peer.id().clone(), peer))
+                    .map(|mut peer| {
+                        peer.set_event_creation_policy(options.event_creation_policy);
+                        peer.set_fork_policy(options.fork_policy);
+                        peer.set_gossip_policy(options.gossip_policy);
+                        peer.set_clock(Box::new(self.time.skewed_clock(
+                            &mut self.clock_rng,
+                            options.max_clock_offset,
+                            options.max_clock_drift,
+                        )));
+                        (peer.id().clone(), peer)
+                    })
                     .collect();
 
                 if let Some(keep_consensus) = &options.genesis_restrict_consensus_to {
@@ -604,16 +801,22 @@ impl Network {
                     return Ok(false);
                 }
                 let current_peers = self.active_peers().map(|peer| peer.id().clone()).collect();
-                let _ = self.peers.insert(
+                let mut peer = Peer::from_existing(
                     peer_id.clone(),
-                    Peer::from_existing(
-                        peer_id,
-                        &self.genesis,
-                        &current_peers,
-                        self.consensus_mode,
-                        Box::new(new_rng(rng2)),
-                    ),
+                    &self.genesis,
+                    &current_peers,
+                    self.consensus_mode,
+                    Box::new(new_rng(rng2)),
                 );
+                peer.set_event_creation_policy(options.event_creation_policy);
+                peer.set_fork_policy(options.fork_policy);
+                peer.set_gossip_policy(options.gossip_policy);
+                peer.set_clock(Box::new(self.time.skewed_clock(
+                    &mut self.clock_rng,
+                    options.max_clock_offset,
+                    options.max_clock_drift,
+                )));
+                let _ = self.peers.insert(peer_id, peer);
             }
             ScheduleEvent::RemovePeer(peer_id) => {
                 if self.allow_removal_of_peer(&peer_id) {
@@ -629,7 +832,12 @@ impl Network {
                     return Ok(false);
                 }
             }
+            ScheduleEvent::NetworkDelayChange(delay_distr) => {
+                options.delay_distr = delay_distr;
+            }
             ScheduleEvent::LocalStep(step) => {
+                self.num_steps += 1;
+                self.time.set_step(step);
                 for peer_id in self.running_peers_ids() {
                     self.peer_mut(&peer_id).make_votes();
                     self.handle_messages(&peer_id, step);
@@ -641,8 +849,20 @@ impl Network {
                 Peer::update_network_views(&mut self.peers);
                 let running_peers_ids = self.running_peers_ids();
                 for peer_id in &running_peers_ids {
-                    if rng.gen::<f64>() < options.prob_gossip {
-                        self.send_gossip(rng, options, peer_id, &running_peers_ids, step);
+                    let recipient = match options.gossip_scheduler {
+                        GossipScheduler::Random => {
+                            if rng.gen::<f64>() >= options.prob_gossip {
+                                continue;
+                            }
+                            Self::choose_random_recipient(rng, peer_id, &running_peers_ids).cloned()
+                        }
+                        GossipScheduler::Fair => {
+                            self.fair_gossip
+                                .next_recipient(rng, peer_id, &running_peers_ids)
+                        }
+                    };
+                    if let Some(recipient) = recipient {
+                        self.send_gossip(rng, options, peer_id, &recipient, step);
                     }
                 }
             }
@@ -709,3 +929,93 @@ impl Network {
             || is_more_than_two_thirds(joined_count, joined_count + joining_count + 1)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dev_utils::{new_common_rng, new_rng, DelayDistribution, RngChoice},
+        mock,
+    };
+
+    #[test]
+    fn fair_gossip_bounds_gaps_between_pairs() {
+        const NUM_STEPS: usize = 100;
+
+        let mut rng = new_common_rng(RngChoice::Seeded([1, 2, 3, 4]));
+        let mut ids = mock::create_ids(5);
+        let max_gap = 2 * (ids.len() - 1);
+        let mut fair_gossip = FairGossip::default();
+        let mut last_sent = BTreeMap::new();
+
+        for step in 0..NUM_STEPS {
+            for sender in &ids {
+                let recipient = unwrap!(fair_gossip.next_recipient(&mut rng, sender, &ids));
+                assert_ne!(recipient, *sender);
+                if let Some(prev_step) = last_sent.insert((sender.clone(), recipient), step) {
+                    assert!(step - prev_step < max_gap);
+                }
+            }
+        }
+        assert_eq!(last_sent.len(), ids.len() * (ids.len() - 1));
+        assert!(last_sent
+            .values()
+            .all(|&last_step| NUM_STEPS - last_step <= max_gap));
+
+        // Peers which stopped running are skipped, even in the middle of a round.
+        let stopped = unwrap!(ids.pop());
+        for _ in 0..max_gap {
+            for sender in &ids {
+                let recipient = unwrap!(fair_gossip.next_recipient(&mut rng, sender, &ids));
+                assert_ne!(recipient, stopped);
+            }
+        }
+
+        let lone_peer = &ids[..1];
+        assert!(fair_gossip
+            .next_recipient(&mut rng, &lone_peer[0], lone_peer)
+            .is_none());
+    }
+
+    #[test]
+    fn delay_change_only_affects_later_messages() {
+        let mut rng = new_common_rng(RngChoice::Seeded([1, 2, 3, 4]));
+        let mut rng2 = new_common_rng(RngChoice::Seeded([5, 6, 7, 8]));
+        let ids = mock::create_ids(2);
+        let genesis_ids: BTreeSet<_> = ids.iter().cloned().collect();
+        let mut network = Network::new(ConsensusMode::Supermajority);
+        for id in &ids {
+            let peer = Peer::from_genesis(
+                id.clone(),
+                &genesis_ids,
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut rng)),
+            );
+            let _ = network.peers.insert(id.clone(), peer);
+        }
+        let mut options = ScheduleOptions {
+            delay_distr: DelayDistribution::Constant(1),
+            ..Default::default()
+        };
+
+        network.send_gossip(&mut rng, &options, &ids[0], &ids[1], 0);
+        let event = ScheduleEvent::NetworkDelayChange(DelayDistribution::Constant(10));
+        assert!(unwrap!(network.execute_event(
+            &mut rng,
+            &mut rng2,
+            &mut options,
+            event
+        )));
+        network.send_gossip(&mut rng, &options, &ids[0], &ids[1], 1);
+
+        // The request in flight keeps the old delays, for itself and for its response.
+        let delays = network.msg_queue[&ids[1]]
+            .iter()
+            .map(|entry| match entry.message {
+                Message::Request(_, resp_delay) => (entry.deliver_after, resp_delay),
+                Message::Response(_) => panic!("Unexpected response"),
+            })
+            .collect_vec();
+        assert_eq!(delays, vec![(1, 1), (11, 10)]);
+    }
+}
@@ -6,16 +6,18 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::Observation;
+use super::{MaliciousBehaviour, Observation};
 use crate::{
-    block::{Block, BlockGroup},
+    block::Block,
     error::Result,
     gossip::{Cause, Event, EventIndex, Request, Response},
+    growth_rate::Clock,
+    meta_voting::MetaElectionSnapshot,
     mock::{PeerId, Transaction},
     observation::{
         is_more_than_two_thirds, ConsensusMode, Malice, Observation as ParsecObservation,
     },
-    parsec::{Parsec, TestParsec},
+    parsec::{EventCreationPolicy, ForkPolicy, GossipPolicy, Parsec, TestParsec},
     peer_list::PeerIndex,
 };
 use itertools::Itertools;
@@ -97,12 +99,94 @@ struct ForkedEvent {
 
 struct MaliciousComponents {
     test_parsec: TestParsec<Transaction, PeerId>,
+    behaviour: MaliciousBehaviour,
     // A forked event, mapped to the EventIndex of the first event we created with the same
     // self-parent.  This forked event is not added to our graph when we create it.
     forked_event: Option<ForkedEvent>,
+    // Whether we have voted twice for the same observation.
+    duplicated_vote: bool,
+    // The offender and malice of the accusation we made against a well-behaved peer, if any.
+    false_accusation: Option<(PeerId, Malice<Transaction, PeerId>)>,
 }
 
 impl MaliciousComponents {
+    fn new(test_parsec: TestParsec<Transaction, PeerId>, behaviour: MaliciousBehaviour) -> Self {
+        Self {
+            test_parsec,
+            behaviour,
+            forked_event: None,
+            duplicated_vote: false,
+            false_accusation: None,
+        }
+    }
+
+    fn has_misbehaved(&self) -> bool {
+        self.forked_event.is_some() || self.duplicated_vote || self.false_accusation.is_some()
+    }
+
+    fn create_gossip_with_malice<R: Rng>(
+        &mut self,
+        recipient_id: &PeerId,
+        rng: &mut R,
+    ) -> Result<Request<Transaction, PeerId>> {
+        match self.behaviour {
+            MaliciousBehaviour::Fork => self.create_gossip_with_fork(recipient_id, rng),
+            MaliciousBehaviour::DuplicateVote => {
+                self.create_gossip_with_duplicate_vote(recipient_id)
+            }
+            MaliciousBehaviour::InvalidAccusation => {
+                self.create_gossip_with_invalid_accusation(recipient_id)
+            }
+        }
+    }
+
+    // Votes again for one of the opaque payloads we already voted for, if any, then gossips as
+    // usual.  Until we've voted for any, this is the same as a normal `create_gossip`.
+    fn create_gossip_with_duplicate_vote(
+        &mut self,
+        recipient_id: &PeerId,
+    ) -> Result<Request<Transaction, PeerId>> {
+        assert!(!self.duplicated_vote);
+        let observation = self
+            .test_parsec
+            .our_unpolled_observations()
+            .find(|observation| observation.is_opaque())
+            .cloned();
+        if let Some(observation) = observation {
+            unwrap!(self.test_parsec.vote_for_again(observation));
+            self.duplicated_vote = true;
+        }
+        self.test_parsec.create_gossip(recipient_id)
+    }
+
+    // Accuses the recipient of having forked at its last event we know of, then gossips as usual.
+    // Until we know of any event by the recipient, this is the same as a normal `create_gossip`.
+    fn create_gossip_with_invalid_accusation(
+        &mut self,
+        recipient_id: &PeerId,
+    ) -> Result<Request<Transaction, PeerId>> {
+        assert!(self.false_accusation.is_none());
+        let recipient_index = self.test_parsec.get_peer_index(recipient_id)?;
+        let last_hash = self
+            .test_parsec
+            .peer_list()
+            .last_event(recipient_index)
+            .and_then(|event_index| self.test_parsec.graph().get(event_index))
+            .map(|event| *event.hash());
+        if let Some(last_hash) = last_hash {
+            let malice = Malice::Fork(last_hash);
+            // `vote_for` rejects accusations, so bypass it.
+            unwrap!(self
+                .test_parsec
+                .vote_for_again(ParsecObservation::Accusation {
+                    offender: recipient_id.clone(),
+                    malice: malice.clone(),
+                }));
+            self.false_accusation = Some((recipient_id.clone(), malice));
+        }
+        self.test_parsec.create_gossip(recipient_id)
+    }
+
     fn create_gossip_with_fork<R: Rng>(
         &mut self,
         recipient_id: &PeerId,
@@ -229,7 +313,7 @@ impl DerefMut for WrappedParsec {
 pub struct Peer {
     parsec: WrappedParsec,
     /// The blocks returned by `parsec.poll()`, held in the order in which they were returned.
-    grouped_blocks: Vec<BlockGroup<Transaction, PeerId>>,
+    grouped_blocks: Vec<Vec<Block<Transaction, PeerId>>>,
     status: PeerStatus,
     network_view: NetworkView,
     votes_to_make: Vec<Observation>,
@@ -260,11 +344,12 @@ impl Peer {
         genesis_group: &BTreeSet<PeerId>,
         consensus_mode: ConsensusMode,
         secure_rng: Box<dyn RngCore>,
+        behaviour: MaliciousBehaviour,
     ) -> Self {
-        Self::new(WrappedParsec::Malicious(MaliciousComponents {
-            test_parsec: TestParsec::from_genesis(id, genesis_group, consensus_mode, secure_rng),
-            forked_event: None,
-        }))
+        Self::new(WrappedParsec::Malicious(MaliciousComponents::new(
+            TestParsec::from_genesis(id, genesis_group, consensus_mode, secure_rng),
+            behaviour,
+        )))
     }
 
     pub fn from_existing(
@@ -289,17 +374,12 @@ impl Peer {
         current_group: &BTreeSet<PeerId>,
         consensus_mode: ConsensusMode,
         secure_rng: Box<dyn RngCore>,
+        behaviour: MaliciousBehaviour,
     ) -> Self {
-        Self::new(WrappedParsec::Malicious(MaliciousComponents {
-            test_parsec: TestParsec::from_existing(
-                id,
-                genesis_group,
-                current_group,
-                consensus_mode,
-                secure_rng,
-            ),
-            forked_event: None,
-        }))
+        Self::new(WrappedParsec::Malicious(MaliciousComponents::new(
+            TestParsec::from_existing(id, genesis_group, current_group, consensus_mode, secure_rng),
+            behaviour,
+        )))
     }
 
     fn new(parsec: WrappedParsec) -> Self {
@@ -382,13 +462,16 @@ impl Peer {
 
             self.grouped_blocks.push(block_group);
         }
+
+        // Everything stable must have been returned, and nothing else.
+        assert_eq!(self.blocks().count(), self.parsec.stable_up_to());
     }
 
     pub fn id(&self) -> &PeerId {
         self.parsec.our_pub_id()
     }
 
-    pub(crate) fn grouped_blocks(&self) -> &[BlockGroup<Transaction, PeerId>] {
+    pub(crate) fn grouped_blocks(&self) -> &[Vec<Block<Transaction, PeerId>>] {
         &self.grouped_blocks
     }
 
@@ -404,6 +487,40 @@ impl Peer {
         self.parsec.ignore_process_events()
     }
 
+    pub(crate) fn meta_election_snapshot(&self) -> (usize, MetaElectionSnapshot<PeerId>) {
+        self.parsec.meta_election_snapshot()
+    }
+
+    pub fn set_event_creation_policy(&mut self, policy: EventCreationPolicy) {
+        self.parsec.set_event_creation_policy(policy);
+    }
+
+    pub fn set_fork_policy(&mut self, policy: ForkPolicy) {
+        self.parsec.set_fork_policy(policy);
+    }
+
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.parsec.set_clock(clock);
+    }
+
+    pub fn set_gossip_policy(&mut self, policy: GossipPolicy) {
+        self.parsec.set_gossip_policy(policy);
+    }
+
+    pub fn should_gossip_to(&mut self, dst: &PeerId) -> bool {
+        self.parsec.should_gossip_to(dst)
+    }
+
+    /// Returns the IDs of the section members as seen by this peer, including itself.
+    pub fn section_members(&self) -> impl Iterator<Item = &PeerId> {
+        self.parsec.peer_ids()
+    }
+
+    /// Returns the number of events in this peer's gossip graph created by each peer.
+    pub fn event_count_by_creator(&self) -> BTreeMap<PeerId, usize> {
+        self.parsec.event_count_by_creator()
+    }
+
     pub fn set_ignore_process_events(&mut self) {
         self.parsec.set_ignore_process_events();
     }
@@ -522,24 +639,64 @@ impl Peer {
         match self.parsec {
             WrappedParsec::Good(..) => false,
             WrappedParsec::Malicious(ref malicious_components) => {
-                malicious_components.forked_event.is_some()
+                malicious_components.has_misbehaved()
             }
         }
     }
 
-    /// This will create a `Request` where the final `Requesting` sync event is a fork.
+    /// Whether this peer has committed the given kind of malice, so that accusing it of `malice`
+    /// is legitimate.
+    pub fn has_committed(&self, malice: &Malice<Transaction, PeerId>) -> bool {
+        let malicious_components = match self.parsec {
+            WrappedParsec::Good(..) => return false,
+            WrappedParsec::Malicious(ref malicious_components) => malicious_components,
+        };
+        match *malice {
+            Malice::Fork(..) => malicious_components.forked_event.is_some(),
+            Malice::DuplicateVote(..) => malicious_components.duplicated_vote,
+            Malice::InvalidAccusation(..) => malicious_components.false_accusation.is_some(),
+            _ => false,
+        }
+    }
+
+    /// Whether this peer has accused `offender` of `malice` as the malice it was created with, so
+    /// that the accusation is expected even though `offender` is well-behaved.
+    pub fn has_falsely_accused(
+        &self,
+        offender: &PeerId,
+        malice: &Malice<Transaction, PeerId>,
+    ) -> bool {
+        match self.parsec {
+            WrappedParsec::Good(..) => false,
+            WrappedParsec::Malicious(ref malicious_components) => {
+                malicious_components.false_accusation.as_ref()
+                    == Some(&(offender.clone(), malice.clone()))
+            }
+        }
+    }
+
+    /// This will create a `Request` after committing the malice this peer was created with.
     ///
-    /// First we create a `Requesting` event to a random peer other than `recipient_id`, then we
+    /// For `MaliciousBehaviour::Fork`, the final `Requesting` sync event of the request is a fork:
+    /// first we create a `Requesting` event to a random peer other than `recipient_id`, then we
     /// create a `Requesting` fork to `recipient_id`.
     ///
+    /// For `MaliciousBehaviour::DuplicateVote`, we first vote again for an opaque payload we
+    /// already voted for.  If there is no such payload yet, the request is a normal one and the
+    /// malice is left for a later call.
+    ///
+    /// For `MaliciousBehaviour::InvalidAccusation`, we first accuse `recipient_id` of having forked
+    /// at its last event we know of.  If we know of no such event yet, the request is a normal one
+    /// and the malice is left for a later call.
+    ///
     /// Panics if this peer is not malicious.
-    pub fn create_gossip_with_fork<R: Rng>(
+    pub fn create_gossip_with_malice<R: Rng>(
         &mut self,
         recipient_id: &PeerId,
         rng: &mut R,
     ) -> Result<Request<Transaction, PeerId>> {
         self.malicious_components_mut()
-            .create_gossip_with_fork(recipient_id, rng)
+            .create_gossip_with_malice(recipient_id, rng)
     }
 
     fn malicious_components_mut(&mut self) -> &mut MaliciousComponents {
@@ -8,16 +8,18 @@
 
 use super::{
     dot_parser::{parse_dot_file, ParsedContents},
-    ReplayRng,
+    new_rng, ReplayRng,
 };
 use crate::{
     gossip::{Cause, Event, IndexedEventRef, PackedEvent, Request, Response},
     hash::Hash,
-    mock::{PeerId, Transaction},
-    observation::{ConsensusMode, Observation, ObservationKey, ObservationStore},
+    mock::{self, PeerId, Transaction},
+    observation::{ConsensusMode, Observation, ObservationHash, ObservationKey, ObservationStore},
     parsec::Parsec,
     peer_list::PeerIndex,
 };
+use itertools::Itertools;
+use rand::{seq::SliceRandom, Rng};
 use std::{collections::BTreeSet, io, path::Path};
 
 /// Record of a Parsec session which consist of sequence of operations (`vote_for`, `handle_request`
@@ -44,7 +46,124 @@ impl Record {
         Ok(Self::from(contents))
     }
 
+    /// Simulates a gossip session among a genesis group of `num_peers` peers, all voting for the
+    /// same `num_votes` opaque payloads, and records it from the point of view of the first peer.
+    ///
+    /// Every round, each peer in turn, in an order drawn from `rng`, sends a request to another
+    /// peer picked at random.  The session ends once every peer has polled all the payloads.
+    /// Unlike the dot files, this allows benchmarking sections of any size.
+    pub fn generate<R: Rng>(num_peers: usize, num_votes: usize, rng: &mut R) -> Self {
+        const MAX_ROUNDS: usize = 100;
+
+        let ids = mock::create_ids(num_peers);
+        let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+        let consensus_mode = ConsensusMode::Supermajority;
+        let mut peers = ids
+            .iter()
+            .map(|id| {
+                Parsec::from_genesis(
+                    id.clone(),
+                    &genesis_group,
+                    vec![],
+                    consensus_mode,
+                    Box::new(new_rng(rng)),
+                )
+            })
+            .collect_vec();
+
+        // Every peer votes for the same payloads, but in its own order.
+        let mut observations = (0..num_votes)
+            .map(|index| Observation::OpaquePayload(Transaction::new(format!("{}", index))))
+            .collect_vec();
+        let mut actions = Vec::new();
+        for (index, peer) in peers.iter_mut().enumerate() {
+            observations.shuffle(rng);
+            for observation in &observations {
+                if index == 0 {
+                    actions.push(Action::Vote(observation.clone()));
+                }
+                unwrap!(peer.vote_for(observation.clone()));
+            }
+        }
+
+        let done = |payloads: &[Observation<Transaction, PeerId>]| {
+            payloads
+                .iter()
+                .filter(|payload| payload.is_opaque())
+                .count()
+                == num_votes
+        };
+        let mut senders = (0..num_peers).collect_vec();
+        let mut polled = vec![Vec::new(); num_peers];
+        for _ in 0..MAX_ROUNDS {
+            senders.shuffle(rng);
+            for &src in &senders {
+                let dst = (src + rng.gen_range(1, num_peers)) % num_peers;
+                let request = unwrap!(peers[src].create_gossip(&ids[dst]));
+                if src == 0 {
+                    actions.push(Action::Requesting(ids[dst].clone()));
+                } else if dst == 0 {
+                    actions.push(Action::Request(ids[src].clone(), request.clone()));
+                }
+
+                let response = unwrap!(peers[dst].handle_request(&ids[src], request));
+                if src == 0 {
+                    actions.push(Action::Response(ids[dst].clone(), response.clone()));
+                }
+                unwrap!(peers[src].handle_response(&ids[dst], response));
+            }
+
+            for (peer, polled) in peers.iter_mut().zip(&mut polled) {
+                polled.extend(peer.poll_iter().map(|block| block.payload().clone()));
+            }
+            if polled.iter().all(|payloads| done(payloads)) {
+                break;
+            }
+        }
+        assert!(
+            polled.iter().all(|payloads| done(payloads)),
+            "Generated session didn't lead to consensus"
+        );
+
+        let consensus_history = polled[0]
+            .iter()
+            .map(|payload| ObservationKey::Supermajority(ObservationHash::from(payload)))
+            .collect();
+
+        Record {
+            our_id: ids[0].clone(),
+            genesis_group,
+            secure_rng_values: vec![],
+            actions,
+            consensus_history,
+            consensus_mode,
+            added_final_requesting_event: false,
+        }
+    }
+
     pub fn play(self) -> Parsec<Transaction, PeerId> {
+        self.play_with(|_| ())
+    }
+
+    /// Plays the record on a `Parsec` instance which is passed to `setup` right after construction,
+    /// e.g. to change its settings.
+    pub fn play_with<F>(self, setup: F) -> Parsec<Transaction, PeerId>
+    where
+        F: FnOnce(&mut Parsec<Transaction, PeerId>),
+    {
+        self.play_impl(setup, false)
+    }
+
+    /// Plays the record handling the gossip messages with `handle_request_trusted` and
+    /// `handle_response_trusted`, i.e. without verifying the signatures of the received events.
+    pub fn play_trusted(self) -> Parsec<Transaction, PeerId> {
+        self.play_impl(|parsec| parsec.set_trusted_ingest(true), true)
+    }
+
+    fn play_impl<F>(self, setup: F, trusted: bool) -> Parsec<Transaction, PeerId>
+    where
+        F: FnOnce(&mut Parsec<Transaction, PeerId>),
+    {
         let mut parsec = Parsec::from_genesis(
             self.our_id,
             &self.genesis_group,
@@ -52,14 +171,27 @@ impl Record {
             self.consensus_mode,
             Box::new(ReplayRng::new(self.secure_rng_values.clone())),
         );
+        setup(&mut parsec);
 
         for action in self.actions {
-            action.run(&mut parsec)
+            action.run(&mut parsec, trusted)
         }
 
         parsec
     }
 
+    /// Returns the number of events received in the recorded requests and responses.
+    pub fn num_received_events(&self) -> usize {
+        self.actions
+            .iter()
+            .map(|action| match *action {
+                Action::Request(_, ref request) => request.packed_events.len(),
+                Action::Response(_, ref response) => response.packed_events.len(),
+                Action::Vote(_) | Action::Requesting(_) => 0,
+            })
+            .sum()
+    }
+
     pub fn consensus_history(&self) -> Vec<Hash> {
         self.consensus_history
             .iter()
@@ -105,11 +237,7 @@ impl From<ParsedContents> for Record {
             }
 
             if event.creator() == PeerIndex::OUR {
-                if let Some(observation) = event
-                    .payload_key()
-                    .and_then(|key| contents.observations.get(key))
-                    .map(|info| &info.observation)
-                {
+                if let Some(observation) = event.payload(&contents.observations) {
                     known[event.topological_index()] = true;
 
                     match *observation {
@@ -121,8 +249,8 @@ impl From<ParsedContents> for Record {
                                 panic!("Unexpected accusation {:?}", *event);
                             }
                         }
-                        Observation::DkgMessage(_) => {
-                            // Skip DkgMessage that we generate.
+                        Observation::DkgMessage(_) | Observation::Heartbeat(_) => {
+                            // Skip DkgMessage and Heartbeat that we generate.
                             continue;
                         }
                         _ => (),
@@ -192,7 +320,7 @@ impl From<ParsedContents> for Record {
             secure_rng_values: contents.secure_rng_values,
             actions,
             consensus_history: contents.meta_election.consensus_history,
-            consensus_mode: contents.consensus_mode,
+            consensus_mode: contents.consensus_modes.global(),
             added_final_requesting_event,
         }
     }
@@ -207,35 +335,41 @@ enum Action {
 }
 
 impl Action {
-    fn run(self, parsec: &mut Parsec<Transaction, PeerId>) {
+    fn run(self, parsec: &mut Parsec<Transaction, PeerId>, trusted: bool) {
         match self {
             Action::Vote(observation) => unwrap!(parsec.vote_for(observation)),
             Action::Requesting(recipient) => {
                 let _ = unwrap!(parsec.create_gossip(&recipient));
             }
             Action::Request(src, request) => {
-                let _ = unwrap!(parsec.handle_request(&src, request));
+                let _ = if trusted {
+                    unwrap!(parsec.handle_request_trusted(&src, request))
+                } else {
+                    unwrap!(parsec.handle_request(&src, request))
+                };
+            }
+            Action::Response(src, response) => {
+                if trusted {
+                    unwrap!(parsec.handle_response_trusted(&src, response))
+                } else {
+                    unwrap!(parsec.handle_response(&src, response))
+                }
             }
-            Action::Response(src, response) => unwrap!(parsec.handle_response(&src, response)),
         }
     }
 }
 
-fn extract_genesis_group<'a>(
+pub(super) fn extract_genesis_group<'a>(
     event: &Event<PeerId>,
     observations: &'a ObservationStore<Transaction, PeerId>,
 ) -> Option<&'a BTreeSet<PeerId>> {
-    event
-        .payload_key()
-        .and_then(|key| observations.get(key))
-        .map(|info| &info.observation)
-        .and_then(|observation| {
-            if let Observation::Genesis { ref group, .. } = *observation {
-                Some(group)
-            } else {
-                None
-            }
-        })
+    event.payload(observations).and_then(|observation| {
+        if let Observation::Genesis { ref group, .. } = *observation {
+            Some(group)
+        } else {
+            None
+        }
+    })
 }
 
 fn collect_events_to_gossip(
@@ -440,4 +574,16 @@ mod tests {
             missing_one_consensus,
         )
     }
+
+    #[test]
+    fn generate() {
+        let mut rng = new_common_rng(SEED);
+        let record = Record::generate(4, 3, &mut rng);
+        let expected = record.consensus_history();
+        assert_eq!(expected.len(), 4);
+        assert!(record.num_received_events() > 0);
+
+        let parsec = record.play();
+        assert_eq!(parsec.meta_election_consensus_history_hash(), expected);
+    }
 }
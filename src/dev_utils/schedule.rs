@@ -12,12 +12,15 @@ use crate::dump_graph::DIR;
 use crate::{
     mock::{PeerId, Transaction, NAMES},
     observation::{ConsensusMode, Observation as ParsecObservation},
+    parsec::{EventCreationPolicy, ForkPolicy, GossipPolicy},
 };
 use itertools::Itertools;
 use rand::{seq::SliceRandom, Rng};
 use std::{
     collections::{BTreeMap, BTreeSet},
     fmt, iter, mem,
+    ops::Range,
+    time::Duration,
 };
 #[cfg(feature = "dump-graphs")]
 use std::{fs::File, io::Write};
@@ -128,6 +131,9 @@ pub enum ScheduleEvent {
     /// It is similar to Fail in that the peer will stop responding; however, this will also
     /// cause the other peers to vote for removal
     RemovePeer(PeerId),
+    /// Replaces the distribution of message delays for all messages sent from now on. Messages
+    /// already in flight keep the delays they were sent with.
+    NetworkDelayChange(DelayDistribution),
 }
 
 impl ScheduleEvent {
@@ -147,6 +153,9 @@ impl ScheduleEvent {
             ScheduleEvent::AddPeer(ref peer, _) => peer,
             ScheduleEvent::RemovePeer(ref peer) => peer,
             ScheduleEvent::Genesis(_) => panic!("ScheduleEvent::get_peer called on Genesis!"),
+            ScheduleEvent::NetworkDelayChange(_) => {
+                panic!("ScheduleEvent::get_peer called on NetworkDelayChange!")
+            }
         }
     }
 }
@@ -232,6 +241,29 @@ pub enum DelayDistribution {
     Constant(usize),
 }
 
+/// How peers pick when and to whom they gossip during their local steps
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GossipScheduler {
+    /// Each peer gossips with probability `prob_gossip` per step, to a uniformly random peer
+    Random,
+    /// Each peer gossips on every step, going round the other running peers in an order shuffled
+    /// anew for every round.  Once it has been running for a whole round, a peer hence gossips to
+    /// every other running peer at least once in any `2 * (n - 1)` consecutive steps, where `n` is
+    /// the number of running peers.  `prob_gossip` is ignored.
+    Fair,
+}
+
+/// The malice committed by the malicious peers of a simulated network, once each
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MaliciousBehaviour {
+    /// Gossip a `Requesting` event forking from the previous one
+    Fork,
+    /// Create a second event voting for an opaque payload already voted for
+    DuplicateVote,
+    /// Accuse the recipient of gossip of a fork it hasn't committed
+    InvalidAccusation,
+}
+
 /// A struct aggregating the options controlling schedule generation
 #[derive(Clone, Debug)]
 pub struct ScheduleOptions {
@@ -239,6 +271,8 @@ pub struct ScheduleOptions {
     pub genesis_size: usize,
     /// Number of malicious peers included in the genesis group
     pub malicious_genesis_count: usize,
+    /// The malice the malicious peers commit
+    pub malicious_behaviour: MaliciousBehaviour,
     /// Probability per step that a random node will fail
     pub prob_failure: f64,
     /// Probability that a vote will get repeated
@@ -249,6 +283,8 @@ pub struct ScheduleOptions {
     pub delay_distr: DelayDistribution,
     /// The probability that a node will gossip during its local step
     pub prob_gossip: f64,
+    /// How peers pick when and to whom they gossip
+    pub gossip_scheduler: GossipScheduler,
     /// When true, nodes will first insert all votes into the graph, then start gossiping
     pub votes_before_gossip: bool,
     /// Number of opaque observations to make
@@ -279,10 +315,23 @@ pub struct ScheduleOptions {
     pub transparent_voters: Sampling,
     /// Intermediate consistency checks (Raise error closer to the source)
     pub intermediate_consistency_checks: bool,
+    /// Compare the meta-votes of peers in the same meta-election after every event (Raise error
+    /// as soon as consensus starts diverging, at the cost of a much slower simulation)
+    pub meta_vote_consistency_checks: bool,
     /// The only genesis members that will compute consensus if provided. All if none.
     pub genesis_restrict_consensus_to: Option<BTreeSet<PeerId>>,
     /// Allows for voting for the same OpaquePayload. This applies only when `ConsensusMode::Single`
     pub vote_for_same: bool,
+    /// The policy all peers use to decide when to create gossip events
+    pub event_creation_policy: EventCreationPolicy,
+    /// The policy all peers use to decide what happens to peers accused of forking
+    pub fork_policy: ForkPolicy,
+    /// The policy all peers use to decide whether to take a gossip opportunity
+    pub gossip_policy: GossipPolicy,
+    /// Maximum amount by which each peer's clock is ahead of the true time
+    pub max_clock_offset: Duration,
+    /// Maximum relative deviation of the rate of each peer's clock from the true rate
+    pub max_clock_drift: f64,
 }
 
 impl ScheduleOptions {
@@ -302,6 +351,8 @@ impl Default for ScheduleOptions {
             genesis_size: 4,
             // no malicious genesis peers
             malicious_genesis_count: 0,
+            // ...which would fork if there were any
+            malicious_behaviour: MaliciousBehaviour::Fork,
             // no randomised failures
             prob_failure: 0.0,
             // no vote duplication
@@ -312,6 +363,8 @@ impl Default for ScheduleOptions {
             delay_distr: DelayDistribution::Poisson(4.0),
             // gossip every so often
             prob_gossip: 0.05,
+            // pick gossip recipients at random
+            gossip_scheduler: GossipScheduler::Random,
             // vote while gossiping
             votes_before_gossip: false,
             // add 5 opaque observations
@@ -339,8 +392,15 @@ impl Default for ScheduleOptions {
             opaque_voters: Sampling::Fraction(1.0, 1.0),
             transparent_voters: Sampling::Fraction(1.0, 1.0),
             intermediate_consistency_checks: true,
+            meta_vote_consistency_checks: false,
             genesis_restrict_consensus_to: None,
             vote_for_same: false,
+            event_creation_policy: EventCreationPolicy::Always,
+            fork_policy: ForkPolicy::AccuseAndProposeRemoval,
+            gossip_policy: GossipPolicy::Always,
+            // synchronised clocks
+            max_clock_offset: Duration::from_secs(0),
+            max_clock_drift: 0.0,
         }
     }
 }
@@ -379,6 +439,34 @@ pub struct ObservationSchedule {
 }
 
 impl ObservationSchedule {
+    /// Creates a schedule with no events for the given genesis group.
+    pub fn new(genesis: Genesis) -> Self {
+        Self {
+            genesis,
+            schedule: vec![],
+        }
+    }
+
+    /// Adds `count` opaque transactions with random contents, each at a step drawn uniformly from
+    /// `steps`.  As for any `ObservationEvent::Opaque`, the peers voting for each of them are
+    /// sampled by `Schedule::from_observation_schedule`, according to
+    /// `ScheduleOptions::opaque_voters`.  Everything is drawn from `rng`, so the batch is
+    /// reproducible under a fixed seed.
+    pub fn with_opaque_batch<R: Rng>(
+        mut self,
+        rng: &mut R,
+        count: usize,
+        steps: Range<usize>,
+    ) -> Self {
+        for _ in 0..count {
+            let step = rng.gen_range(steps.start, steps.end);
+            let payload = Transaction::new(format!("{:016x}", rng.gen::<u64>()));
+            self.schedule
+                .push((step, ObservationEvent::Opaque(payload)));
+        }
+        self
+    }
+
     fn gen<R: Rng>(rng: &mut R, options: &ScheduleOptions) -> ObservationSchedule {
         let mut schedule = vec![];
         let mut names_iter = NAMES
@@ -510,7 +598,7 @@ pub struct Schedule {
     pub min_observations: usize,
     pub max_observations: usize,
     pub events: Vec<ScheduleEvent>,
-    pub additional_steps: std::ops::Range<usize>,
+    pub additional_steps: Range<usize>,
     pub options: ScheduleOptions,
 }
 
@@ -756,7 +844,11 @@ impl Schedule {
         // The constant (adjustment_coeff) is for making the number big enough.
         let non_zero_ln = 2;
         let n = std::cmp::max(peers.present_peers().count(), non_zero_ln) as f64;
-        let adjustment_coeff = 250.0 / options.prob_gossip;
+        let prob_gossip = match options.gossip_scheduler {
+            GossipScheduler::Random => options.prob_gossip,
+            GossipScheduler::Fair => 1.0,
+        };
+        let adjustment_coeff = 250.0 / prob_gossip;
         let additional_steps = (adjustment_coeff * n.ln()) as usize;
 
         // Peers scheduled for removal / failure might not get a chance to vote for their scheduled
@@ -59,7 +59,7 @@ pub(crate) fn to_file<T: NetworkEvent, S: SecretId>(info: ToFileInfo<T, S>) {
 pub(crate) fn to_file<T: NetworkEvent, S: SecretId>(_: ToFileInfo<T, S>) {}
 
 #[cfg(feature = "dump-graphs")]
-pub use self::detail::{DumpGraphMode, DIR, DUMP_MODE};
+pub use self::detail::{DumpGraphMode, DIR, DUMP_CORE, DUMP_MODE};
 
 #[cfg(feature = "dump-graphs")]
 mod detail {
@@ -78,7 +78,7 @@ mod detail {
     use itertools::Itertools;
     use rand::{self, distributions::Alphanumeric, Rng};
     use std::{
-        cell::RefCell,
+        cell::{Cell, RefCell},
         cmp,
         collections::{BTreeMap, BTreeSet},
         env,
@@ -121,6 +121,11 @@ mod detail {
                 }
             }).unwrap_or(DumpGraphMode::OnConsensus)
         };
+
+        static ref DUMP_CORE_FILES: bool = {
+            // PARSEC_DUMP_CORE=1 to also write a binary snapshot next to each dot file.
+            env::var("PARSEC_DUMP_CORE").ok().as_deref() == Some("1")
+        };
     }
 
     thread_local!(
@@ -146,6 +151,11 @@ mod detail {
 
         /// Which dumps to output
         pub static DUMP_MODE: RefCell<DumpGraphMode> = RefCell::new(DUMP_GRAPH_MODE.clone());
+
+        /// Whether to write a `.core` file next to each dot file, holding the serialised
+        /// `(GraphSnapshot, MetaElectionSnapshot)` of the dumped graph, e.g. to compare against the
+        /// result of parsing the dot file back.
+        pub static DUMP_CORE: Cell<bool> = Cell::new(*DUMP_CORE_FILES);
     );
 
     thread_local!(static DUMP_COUNTS: RefCell<BTreeMap<String, usize>> =
@@ -167,7 +177,7 @@ mod detail {
         peer_list: &PeerList<S>,
         meta_election: &MetaElection,
     ) {
-        if let Some("dev_utils::dot_parser::tests::dot_parser") = thread::current().name() {
+        if DUMP_CORE.with(Cell::get) {
             let snapshot = (
                 GraphSnapshot::new(gossip_graph),
                 MetaElectionSnapshot::new(meta_election, gossip_graph, peer_list),
@@ -308,14 +318,15 @@ mod detail {
     ) -> Vec<String> {
         let mut lines = vec![];
         if comment {
-            lines.push("  stage est bin aux dec".to_string());
+            lines.push("  stage est bin aux dec why".to_string());
         } else {
             lines.push(
                 "<tr><td></td><td width=\"50\">stage</td>\
                  <td width=\"30\">est</td>\
                  <td width=\"30\">bin</td>\
                  <td width=\"30\">aux</td>\
-                 <td width=\"30\">dec</td></tr>"
+                 <td width=\"30\">dec</td>\
+                 <td width=\"30\">why</td></tr>"
                     .to_string(),
             );
         }
@@ -331,16 +342,20 @@ mod detail {
             let mut prefix: &str = prefix.as_str();
             for mv in meta_votes {
                 let (est, bin, aux, dec) = mv.values.as_chars();
+                let why = mv
+                    .diagnostics
+                    .reason()
+                    .map_or('-', |reason| reason.as_char());
                 let line = if comment {
                     format!(
-                        "{}{}/{:?}   {}   {}   {}   {} ",
-                        prefix, mv.round, mv.step, est, bin, aux, dec
+                        "{}{}/{:?}   {}   {}   {}   {}   {} ",
+                        prefix, mv.round, mv.step, est, bin, aux, dec, why
                     )
                 } else {
                     format!(
                         "<tr><td>{}</td><td>{}/{:?}</td><td>{}</td>\
-                         <td>{}</td><td>{}</td><td>{}</td></tr>",
-                        prefix, mv.round, mv.step, est, bin, aux, dec
+                         <td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                        prefix, mv.round, mv.step, est, bin, aux, dec, why
                     )
                 };
 
@@ -1034,6 +1049,7 @@ mod detail {
                     dkg_result,
                 } => format!("DkgResult({:?}, {:?})", participants, dkg_result),
                 Observation::DkgMessage(msg) => format!("DkgMessage({:?})", msg),
+                Observation::Heartbeat(index) => format!("Heartbeat({})", index),
                 Observation::OpaquePayload(payload) => {
                     let max_length = 16;
                     let mut payload_str = sanitise_string(format!("{:?}", payload));
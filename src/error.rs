@@ -21,10 +21,14 @@ pub enum Error {
     UnknownPayload,
     /// Attempt to create a block with no votes.
     MissingVotes,
+    /// The block isn't signed by enough voters for its payload's consensus mode.
+    InsufficientVotes,
     /// Failed to verify signature.
     SignatureFailure,
     /// Peer is not known to our node.
     UnknownPeer,
+    /// Peer is already known to our node.
+    DuplicatePeer,
     /// Peer is known to us, but has unexpected state.
     InvalidPeerState {
         /// State we require the peer to be in
@@ -45,16 +49,24 @@ pub enum Error {
     UnknownSelfParent,
     /// The event's other-parent is unknown to our node.
     UnknownOtherParent,
-    /// Our node has already voted for this network event.
+    /// Our node has already voted for this network event, or a block carries several proofs by
+    /// the same peer.
     DuplicateVote,
+    /// The observation is output or internal only, or its payload is invalid, and can't be voted
+    /// for.
+    InvalidObservation,
     /// The peer sent a message to us before knowing we could handle it.
     PrematureGossip,
+    /// Our node was asked to gossip to itself.
+    SelfGossip,
     /// The request or response is invalid.
     InvalidMessage,
     /// The request or response has already been handled by us.
     DuplicateMessage,
     /// Faild DKG process
     FailedDkg,
+    /// The configuration passed to `ParsecBuilder` is inconsistent.
+    InvalidConfiguration(&'static str),
     /// Logic error.
     Logic,
 }
@@ -71,11 +83,13 @@ impl Display for Error {
                 "The payload hash doesn't correspond to any payload known to our node."
             ),
             Error::MissingVotes => write!(f, "Block cannot be created with no votes"),
+            Error::InsufficientVotes => write!(f, "The block isn't signed by enough voters."),
             Error::SignatureFailure => write!(
                 f,
                 "The message or signature might be corrupted, or the signer is wrong."
             ),
             Error::UnknownPeer => write!(f, "The peer_id is not known to our node's peer_list."),
+            Error::DuplicatePeer => write!(f, "The peer_id is already in our node's peer_list."),
             Error::InvalidPeerState { required, actual } => write!(
                 f,
                 "The peer is in invalid state (required: {:?}, actual: {:?}).",
@@ -94,13 +108,20 @@ impl Display for Error {
                 write!(f, "The event's other-parent is unknown to this node.")
             }
             Error::DuplicateVote => write!(f, "Our node has already voted for this network event."),
+            Error::InvalidObservation => write!(
+                f,
+                "This observation is output or internal only, or its payload is invalid, and \
+                 can't be voted for."
+            ),
             Error::PrematureGossip => write!(
                 f,
                 "The peer did not know we could handle a message from it."
             ),
+            Error::SelfGossip => write!(f, "Our node can't gossip to itself."),
             Error::InvalidMessage => write!(f, "This non-empty message is invalid."),
             Error::DuplicateMessage => write!(f, "This message has already been handled."),
             Error::FailedDkg => write!(f, "The requested DKG could not proceed."),
+            Error::InvalidConfiguration(reason) => write!(f, "Invalid configuration: {}", reason),
             Error::Logic => write!(
                 f,
                 "This is a logic error and represents a flaw in the code."
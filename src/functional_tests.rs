@@ -8,17 +8,33 @@
 
 use crate::{
     block::Block,
-    dev_utils::{new_common_rng, new_rng, parse_test_dot_file, Record, RngChoice, TestIterator},
+    dev_utils::{
+        new_common_rng, new_rng, parse_dot_file, parse_dot_file_with_test_name,
+        parse_test_dot_file, Environment, Record, RngChoice, Schedule, ScheduleOptions,
+        TestIterator,
+    },
     error::Error,
-    gossip::{Event, Graph, GraphSnapshot},
+    gossip::{Event, Graph, GraphSnapshot, PackedEvent, Request, RequestProgress},
     id::{Proof, PublicId},
-    meta_voting::MetaElectionSnapshot,
+    light_client::LightClient,
+    meta_voting::{BoolSet, MetaElectionSnapshot, MetaVote, Step, VoteWeights},
     mock::{self, PeerId, Transaction},
-    observation::{ConsensusMode, Observation},
-    parsec::TestParsec,
+    observation::{
+        supermajority, ConsensusMode, Malice, MembershipChange, Observation, ObservationHash,
+        UnprovableMalice,
+    },
+    parsec::{EventCreationPolicy, GossipPolicy, Parsec, TestParsec, UnknownPeerPolicy},
     peer_list::{PeerListSnapshot, PeerState},
+    serialise,
+};
+use itertools::Itertools;
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    iter,
+    num::NonZeroUsize,
+    rc::Rc,
 };
-use std::collections::BTreeSet;
 
 // Use Fixed seed for functional tests: No randomization.
 static SEED: RngChoice = RngChoice::Seeded([1, 2, 3, 4]);
@@ -69,9 +85,17 @@ fn from_existing() {
 
     // Existing section + us
     assert_eq!(parsec.peer_list().all_ids().count(), peers.len() + 1);
+    assert_eq!(
+        parsec.peer_ids().cloned().collect::<BTreeSet<_>>(),
+        peers
+            .iter()
+            .chain(iter::once(parsec.our_pub_id()))
+            .cloned()
+            .collect()
+    );
 
     // The gossip graph should be initially empty.
-    assert_eq!(parsec.graph().len(), 0);
+    assert!(parsec.graph().is_empty());
 }
 
 #[test]
@@ -196,6 +220,175 @@ fn from_genesis_requires_the_genesis_group_contains_us() {
     );
 }
 
+#[test]
+#[should_panic(expected = "Genesis group can't be empty")]
+fn from_genesis_requires_non_empty_genesis_group() {
+    let mut common_rng = new_common_rng(SEED);
+    let our_id = PeerId::new("Alice");
+
+    let _ = TestParsec::<Transaction, _>::from_genesis(
+        our_id,
+        &BTreeSet::new(),
+        ConsensusMode::Supermajority,
+        Box::new(new_rng(&mut common_rng)),
+    );
+}
+
+#[test]
+fn from_genesis_with_sole_member() {
+    let mut common_rng = new_common_rng(SEED);
+    let our_id = PeerId::new("Alice");
+    let genesis_group = iter::once(our_id.clone()).collect();
+
+    let mut alice = TestParsec::<Transaction, _>::from_genesis(
+        our_id,
+        &genesis_group,
+        ConsensusMode::Supermajority,
+        Box::new(new_rng(&mut common_rng)),
+    );
+    assert_eq!(alice.gossip_recipients().count(), 0);
+    assert!(unwrap!(alice.create_bootstrap_gossip()).is_none());
+
+    // Every vote is consensused straight away, without any gossip.
+    for payload in &["A", "B"] {
+        let observation = Observation::OpaquePayload(Transaction::new(*payload));
+        unwrap!(alice.vote_for(observation.clone()));
+        let payloads = alice
+            .poll_iter()
+            .map(|block| block.payload().clone())
+            .collect_vec();
+        assert_eq!(payloads.last(), Some(&observation));
+    }
+    assert_eq!(alice.stable_up_to(), 3);
+}
+
+#[test]
+fn create_bootstrap_gossip() {
+    let mut common_rng = new_common_rng(SEED);
+    let mut peers = mock::create_ids(5);
+    let joining_id = unwrap!(peers.pop());
+    let genesis_group: BTreeSet<_> = peers.iter().cloned().collect();
+
+    // A genesis member can start gossiping straight away.
+    let mut alice = TestParsec::<Transaction, _>::from_genesis(
+        peers[0].clone(),
+        &genesis_group,
+        ConsensusMode::Supermajority,
+        Box::new(new_rng(&mut common_rng)),
+    );
+    let (recipient_id, request) = unwrap!(unwrap!(alice.create_bootstrap_gossip()));
+    assert_ne!(recipient_id, *alice.our_pub_id());
+    assert!(genesis_group.contains(&recipient_id));
+
+    let mut recipient = TestParsec::<Transaction, _>::from_genesis(
+        recipient_id,
+        &genesis_group,
+        ConsensusMode::Supermajority,
+        Box::new(new_rng(&mut common_rng)),
+    );
+    let _ = unwrap!(recipient.handle_request(alice.our_pub_id(), request));
+
+    // A joining peer has to wait to be contacted by the section.
+    let mut joining = TestParsec::<Transaction, _>::from_existing(
+        joining_id,
+        &genesis_group,
+        &genesis_group,
+        ConsensusMode::Supermajority,
+        Box::new(new_rng(&mut common_rng)),
+    );
+    match joining.create_bootstrap_gossip() {
+        Err(Error::InvalidSelfState { .. }) => (),
+        x => panic!("Unexpected {:?}", x),
+    }
+
+    // A sole member has nobody to gossip to.
+    let sole_id = peers[0].clone();
+    let mut sole = TestParsec::<Transaction, _>::from_genesis(
+        sole_id.clone(),
+        &iter::once(sole_id).collect(),
+        ConsensusMode::Supermajority,
+        Box::new(new_rng(&mut common_rng)),
+    );
+    assert!(unwrap!(sole.create_bootstrap_gossip()).is_none());
+}
+
+#[test]
+fn create_gossip_to_ourselves() {
+    let mut common_rng = new_common_rng(SEED);
+    let peers = mock::create_ids(4);
+    let genesis_group: BTreeSet<_> = peers.iter().cloned().collect();
+
+    let mut alice = TestParsec::<Transaction, _>::from_genesis(
+        peers[0].clone(),
+        &genesis_group,
+        ConsensusMode::Supermajority,
+        Box::new(new_rng(&mut common_rng)),
+    );
+    let num_events = alice.graph().len();
+    let our_id = alice.our_pub_id().clone();
+    assert_eq!(alice.create_gossip(&our_id), Err(Error::SelfGossip));
+
+    // No requesting event was created for the rejected gossip.
+    assert_eq!(alice.graph().len(), num_events);
+    let _ = unwrap!(alice.create_gossip(&peers[1]));
+}
+
+#[test]
+fn vote_for_rejects_invalid_payload() {
+    let mut common_rng = new_common_rng(SEED);
+    let peers = mock::create_ids(4);
+    let our_id = unwrap!(peers.first()).clone();
+    let peers: BTreeSet<_> = peers.into_iter().collect();
+
+    let mut parsec = TestParsec::<Transaction, _>::from_genesis(
+        our_id,
+        &peers,
+        ConsensusMode::Supermajority,
+        Box::new(new_rng(&mut common_rng)),
+    );
+    let graph_len = parsec.graph().len();
+
+    let invalid = Observation::OpaquePayload(Transaction::new_invalid("ABCD"));
+    assert_eq!(parsec.vote_for(invalid), Err(Error::InvalidObservation));
+    assert_eq!(parsec.graph().len(), graph_len);
+}
+
+#[test]
+fn vote_for_rejects_output_and_internal_only_observations() {
+    let mut common_rng = new_common_rng(SEED);
+    let peers = mock::create_ids(4);
+    let our_id = unwrap!(peers.first()).clone();
+    let peers: BTreeSet<_> = peers.into_iter().collect();
+
+    let mut parsec = TestParsec::<Transaction, _>::from_genesis(
+        our_id,
+        &peers,
+        ConsensusMode::Supermajority,
+        Box::new(new_rng(&mut common_rng)),
+    );
+    let graph_len = parsec.graph().len();
+
+    let accusation = Observation::Accusation {
+        offender: PeerId::new("Bob"),
+        malice: Malice::Unprovable(UnprovableMalice::Spam),
+    };
+    assert_eq!(
+        parsec.can_vote_for(&accusation),
+        Err(Error::InvalidObservation)
+    );
+    assert_eq!(parsec.vote_for(accusation), Err(Error::InvalidObservation));
+
+    // No event should have been created for the rejected vote.
+    assert_eq!(parsec.graph().len(), graph_len);
+
+    // Application and internal-input observations can still be voted for.
+    let opaque = Observation::OpaquePayload(Transaction::new("ABCD"));
+    assert_eq!(parsec.can_vote_for(&opaque), Ok(()));
+    assert_eq!(parsec.can_vote_for(&Observation::StartDkg(peers)), Ok(()));
+    unwrap!(parsec.vote_for(opaque.clone()));
+    assert_eq!(parsec.can_vote_for(&opaque), Err(Error::DuplicateVote));
+}
+
 #[test]
 fn from_parsed_contents() {
     let mut common_rng = new_common_rng(SEED);
@@ -291,6 +484,7 @@ fn remove_peer() {
         .peer_list()
         .all_ids()
         .any(|(_, peer_id)| *peer_id == eric_id));
+    assert!(alice.peer_ids().any(|peer_id| *peer_id == eric_id));
     assert_ne!(
         alice.peer_list().peer_state(eric_index),
         PeerState::inactive()
@@ -302,6 +496,7 @@ fn remove_peer() {
         alice.peer_list().peer_state(eric_index),
         PeerState::inactive()
     );
+    assert!(alice.peer_ids().all(|peer_id| *peer_id != eric_id));
 
     // Try calling `create_gossip()` for Eric shall result in error.
     assert_eq!(
@@ -373,45 +568,1981 @@ fn unpolled_observations() {
     assert!(alice.has_unpolled_observations());
     assert_eq!(alice.our_unpolled_observations().count(), 1);
     assert_eq!(*unwrap!(alice.our_unpolled_observations().next()), add_eric);
+    let num_stable_blocks = alice.stable_up_to();
 
     // Have Alice process D_21 to get consensus on `Add(Eric)`.
+    assert!(!alice
+        .consensused_observations()
+        .any(|(_, observation)| *observation == add_eric));
+
     unwrap!(alice.add_event(d_21));
+    assert_eq!(alice.stable_up_to(), num_stable_blocks + 1);
+    assert!(alice
+        .consensused_observations()
+        .any(|(_, observation)| *observation == add_eric));
 
     // Since we haven't call `poll()` again yet, should still return our vote for `Add(Eric)`.
     assert!(alice.has_unpolled_observations());
     assert_eq!(alice.our_unpolled_observations().count(), 1);
     assert_eq!(*unwrap!(alice.our_unpolled_observations().next()), add_eric);
 
-    // Call `poll()` and retry - should return none.
-    unwrap!(alice.poll());
-    assert!(alice.poll().is_none());
-    assert!(alice.our_unpolled_observations().next().is_none());
-    assert!(!alice.has_unpolled_observations());
+    // Call `poll()` and retry - should return none. Polling doesn't affect the stable count.
+    unwrap!(alice.poll());
+    assert!(alice.poll().is_none());
+    assert_eq!(alice.stable_up_to(), num_stable_blocks + 1);
+    assert!(alice.our_unpolled_observations().next().is_none());
+    assert!(!alice.has_unpolled_observations());
+
+    // Vote for a new observation and check it is returned as unpolled, and that
+    // `has_unpolled_observations()` returns `true` again.
+    let vote = Observation::OpaquePayload(Transaction::new("ABCD"));
+    unwrap!(alice.vote_for(vote.clone()));
+
+    assert!(alice.has_unpolled_observations());
+    assert_eq!(alice.our_unpolled_observations().count(), 1);
+    assert_eq!(*unwrap!(alice.our_unpolled_observations().next()), vote);
+
+    // Reset, re-run, and add another vote by Alice. Both `Add(Eric)` and the new vote should be
+    // returned in `out_unpolled_observations`. `Add(Eric)` should appear first, because it is
+    // consensused while the other vote is not.
+    let mut alice_contents = parse_test_dot_file("alice.dot");
+    let d_21 = unwrap!(alice_contents.remove_last_event());
+    let mut alice =
+        TestParsec::from_parsed_contents(alice_contents, Box::new(new_rng(&mut common_rng)));
+    unwrap!(alice.add_event(d_21));
+
+    unwrap!(alice.vote_for(vote.clone()));
+
+    let mut unpolled_observations = alice.our_unpolled_observations();
+    assert_eq!(*unwrap!(unpolled_observations.next()), add_eric);
+    assert_eq!(*unwrap!(unpolled_observations.next()), vote);
+    assert!(unpolled_observations.next().is_none());
+}
+
+#[test]
+fn observer_latencies() {
+    let mut common_rng = new_common_rng(SEED);
+    // Alice is one event away from consensus on `Add(Eric)`, so the current meta-election has
+    // observers.
+    let mut alice_contents = unwrap!(parse_dot_file(
+        "input_graphs/functional_tests_unpolled_observations/alice.dot"
+    ));
+    let d_21 = unwrap!(alice_contents.remove_last_event());
+    let mut alice =
+        TestParsec::from_parsed_contents(alice_contents, Box::new(new_rng(&mut common_rng)));
+
+    let add_eric = Observation::Add {
+        peer_id: PeerId::new("Eric"),
+        related_info: vec![],
+    };
+    let latencies = alice.observer_latencies();
+    assert_eq!(latencies.len(), 1);
+    let (observation, latency) = latencies[0];
+    assert_eq!(*observation, add_eric);
+    assert!(latency > 0 && latency < alice.graph().len());
+
+    // Once decided, the meta-election's data is gone.
+    unwrap!(alice.add_event(d_21));
+    assert!(alice.observer_latencies().is_empty());
+}
+
+#[test]
+fn trusted_gossip_handling() {
+    let record = unwrap!(Record::parse("input_graphs/benches/static.dot"));
+    let expected = TestParsec::from(record.clone().play());
+    let actual = TestParsec::from(record.play_trusted());
+
+    // Skipping the signature checks makes no difference when all events are genuine.
+    assert_eq!(
+        actual
+            .graph()
+            .iter()
+            .map(|event| *event.hash())
+            .collect_vec(),
+        expected
+            .graph()
+            .iter()
+            .map(|event| *event.hash())
+            .collect_vec()
+    );
+    assert_eq!(
+        actual.meta_election_consensus_history_hash(),
+        expected.meta_election_consensus_history_hash()
+    );
+}
+
+#[test]
+fn corrupted_signature_is_rejected() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(2);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let mut peers = ids
+        .iter()
+        .map(|id| {
+            TestParsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut common_rng)),
+            )
+        })
+        .collect_vec();
+
+    unwrap!(peers[0].vote_for(Observation::OpaquePayload(Transaction::new("A"))));
+    let mut request = unwrap!(peers[0].create_gossip(&ids[1]));
+    let corrupted = unwrap!(request.packed_events.last_mut());
+    corrupted.corrupt_signature();
+    let corrupted_hash = corrupted.compute_hash();
+
+    assert_eq!(
+        peers[1]
+            .handle_request(&ids[0], request.clone())
+            .map(|_| ()),
+        Err(Error::SignatureFailure)
+    );
+    assert!(!peers[1].graph().contains(&corrupted_hash));
+
+    // Trusted handling has to be enabled explicitly, and doesn't look at the signatures then.
+    assert_eq!(
+        peers[1]
+            .handle_request_trusted(&ids[0], request.clone())
+            .map(|_| ()),
+        Err(Error::InvalidConfiguration("Trusted ingest isn't enabled"))
+    );
+    peers[1].set_trusted_ingest(true);
+    let _ = unwrap!(peers[1].handle_request_trusted(&ids[0], request));
+    assert!(peers[1].graph().contains(&corrupted_hash));
+}
+
+#[test]
+fn poll_iter_partial_consumption() {
+    // Reuses the graph of `our_unpolled_observations_with_consensus_mode_single`.
+    let parse_alice_dot_file = || {
+        parse_dot_file_with_test_name(
+            "alice.dot",
+            "functional_tests_our_unpolled_observations_with_consensus_mode_single",
+        )
+    };
+    let mut alice = Record::from(parse_alice_dot_file()).play();
+    let expected = iter::from_fn(|| alice.poll()).collect_vec();
+    assert!(expected.len() > 1);
+
+    // Consuming only part of the iterator leaves the rest to be polled later, in order.
+    let mut alice = Record::from(parse_alice_dot_file()).play();
+    let first = alice.poll_iter().take(1).collect_vec();
+    let second = unwrap!(alice.poll());
+    let rest = alice.poll_iter().collect_vec();
+    assert!(alice.poll().is_none());
+
+    let actual = first
+        .into_iter()
+        .chain(iter::once(second))
+        .chain(rest)
+        .collect_vec();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn clone_with_rng() {
+    let mut common_rng = new_common_rng(SEED);
+    // Reuses the graph of `unpolled_observations`, in which D_21 stabilises a block.
+    let mut alice_contents =
+        parse_dot_file_with_test_name("alice.dot", "functional_tests_unpolled_observations");
+    let d_21 = unwrap!(alice_contents.remove_last_event());
+    let mut alice =
+        TestParsec::from_parsed_contents(alice_contents, Box::new(new_rng(&mut common_rng)));
+
+    let mut copy = TestParsec::from(alice.clone_with_rng(Box::new(new_rng(&mut common_rng))));
+    assert_eq!(copy.graph(), alice.graph());
+
+    // Adding an event to the original leaves the copy untouched.
+    let num_stable_blocks = alice.stable_up_to();
+    unwrap!(alice.add_event(d_21.clone()));
+    assert_eq!(alice.stable_up_to(), num_stable_blocks + 1);
+    assert_eq!(copy.stable_up_to(), num_stable_blocks);
+    assert_ne!(copy.graph(), alice.graph());
+
+    // Given the same input, the copy reaches the same state.
+    unwrap!(copy.add_event(d_21));
+    assert_eq!(copy.graph(), alice.graph());
+    assert_eq!(
+        copy.poll_iter().collect_vec(),
+        alice.poll_iter().collect_vec()
+    );
+}
+
+#[test]
+fn reset_to_genesis() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(4);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+
+    let mut alice = TestParsec::from_genesis(
+        ids[0].clone(),
+        &genesis_group,
+        ConsensusMode::Supermajority,
+        Box::new(new_rng(&mut common_rng)),
+    );
+    let mut bob = TestParsec::<Transaction, _>::from_genesis(
+        ids[1].clone(),
+        &genesis_group,
+        ConsensusMode::Supermajority,
+        Box::new(new_rng(&mut common_rng)),
+    );
+    unwrap!(alice.vote_for(Observation::OpaquePayload(Transaction::new("ABCD"))));
+    let request = unwrap!(bob.create_gossip(alice.our_pub_id()));
+    let _ = unwrap!(alice.handle_request(bob.our_pub_id(), request));
+    assert!(alice.graph().len() > 2);
+
+    // Resetting yields the same state as a freshly constructed instance.
+    let smaller_group: BTreeSet<_> = ids.iter().take(3).cloned().collect();
+    alice.reset_to_genesis(&smaller_group, vec![]);
+    let fresh = TestParsec::<Transaction, _>::from_genesis(
+        ids[0].clone(),
+        &smaller_group,
+        ConsensusMode::Supermajority,
+        Box::new(new_rng(&mut common_rng)),
+    );
+    assert_eq!(alice.our_pub_id(), fresh.our_pub_id());
+    assert_eq!(alice.graph(), fresh.graph());
+    assert_eq!(
+        alice.peer_list().all_ids().collect_vec(),
+        fresh.peer_list().all_ids().collect_vec()
+    );
+    assert_eq!(
+        alice.our_unpolled_observations().collect_vec(),
+        fresh.our_unpolled_observations().collect_vec()
+    );
+    assert!(alice.poll().is_none());
+
+    // The configuration is kept: Alice weighs more than two thirds of the section, so her vote
+    // alone is enough.
+    let weights = iter::once((ids[0].clone(), 5)).collect();
+    unwrap!(alice.set_voter_weights(weights));
+    alice.reset_to_genesis(&smaller_group, vec![]);
+    let observation = Observation::OpaquePayload(Transaction::new("EFGH"));
+    unwrap!(alice.vote_for(observation.clone()));
+    assert!(alice.has_supermajority_votes(&observation));
+}
+
+#[test]
+fn poll_membership() {
+    let mut alice = Record::from(parse_test_dot_file("alice.dot")).play();
+    let expected = alice.poll_iter().enumerate().collect_vec();
+    // The graph holds no accusation.
+    let (expected_membership, expected_others): (Vec<_>, Vec<_>) =
+        expected.into_iter().partition(|(_, block)| {
+            MembershipChange::from_observation(block.payload(), false).is_some()
+        });
+    let expected_changes = expected_membership
+        .iter()
+        .filter_map(|(index, block)| {
+            MembershipChange::from_observation(block.payload(), false)
+                .map(|change| (*index, change))
+        })
+        .collect_vec();
+    assert_eq!(
+        expected_changes
+            .iter()
+            .map(|(_, change)| change.clone())
+            .skip(1)
+            .collect_vec(),
+        vec![
+            MembershipChange::Added(PeerId::new("Dave")),
+            MembershipChange::Added(PeerId::new("Eric")),
+            MembershipChange::Added(PeerId::new("Fred")),
+        ]
+    );
+    match expected_changes[0] {
+        (0, MembershipChange::Genesis(_)) => (),
+        ref change => panic!("Expected genesis, got {:?}", change),
+    }
+    // Some of the additions are consensused in between other blocks.
+    assert!(expected_others
+        .iter()
+        .any(|(index, _)| *index > expected_changes[1].0));
+
+    // Take the membership changes first.
+    let mut alice = Record::from(parse_test_dot_file("alice.dot")).play();
+    assert_eq!(alice.next_block_index(), 0);
+    let changes = iter::from_fn(|| alice.poll_membership()).collect_vec();
+    assert_eq!(changes, expected_changes);
+    assert_eq!(alice.next_block_index(), 1);
+
+    // The remaining blocks are still returned by `poll()`, in order and with their indices.
+    let mut others = vec![];
+    while alice.next_block_index() < alice.stable_up_to() {
+        let index = alice.next_block_index();
+        others.push((index, unwrap!(alice.poll())));
+    }
+    assert!(alice.poll().is_none());
+    assert!(alice.poll_membership().is_none());
+    assert_eq!(others, expected_others);
+}
+
+#[test]
+fn poll_batch() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(4);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let mut peers = ids
+        .iter()
+        .map(|id| {
+            TestParsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut common_rng)),
+            )
+        })
+        .collect_vec();
+
+    let payloads = ["A", "B", "C", "D"]
+        .iter()
+        .map(|payload| Observation::OpaquePayload(Transaction::new(*payload)))
+        .collect_vec();
+    for peer in &mut peers {
+        for payload in &payloads {
+            unwrap!(peer.vote_for(payload.clone()));
+        }
+    }
+    while peers[0].stable_up_to() <= payloads.len() {
+        for (src, dst) in (0..peers.len()).tuple_combinations() {
+            let request = unwrap!(peers[src].create_gossip(&ids[dst]));
+            let response = unwrap!(peers[dst].handle_request(&ids[src], request));
+            unwrap!(peers[src].handle_response(&ids[dst], response));
+        }
+    }
+
+    let alice = &mut peers[0];
+    let mut copy = TestParsec::from(alice.clone_with_rng(Box::new(new_rng(&mut common_rng))));
+    let batches = iter::from_fn(|| alice.poll_batch()).collect_vec();
+    assert!(batches.iter().flatten().map(Block::payload).eq(copy
+        .poll_iter()
+        .collect_vec()
+        .iter()
+        .map(Block::payload)));
+
+    // The genesis block comes on its own, then the opaque payloads decided together.
+    assert!(batches.len() > 1);
+    assert_eq!(batches[0].len(), 1);
+    assert!(batches[1..]
+        .iter()
+        .all(|batch| batch.iter().all(|block| block.opaque_payload().is_some())));
+    assert!(batches.iter().any(|batch| batch.len() > 1));
+    assert_eq!(alice.next_block_index(), alice.stable_up_to());
+}
+
+#[test]
+fn consensus_mode_by_tag() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(4);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let single_tag = 7;
+
+    let mut peers = ids
+        .iter()
+        .map(|id| {
+            TestParsec::from(
+                Parsec::from_genesis(
+                    id.clone(),
+                    &genesis_group,
+                    vec![],
+                    ConsensusMode::Supermajority,
+                    Box::new(new_rng(&mut common_rng)),
+                )
+                .with_consensus_mode_for(single_tag, ConsensusMode::Single),
+            )
+        })
+        .collect_vec();
+
+    // Only Alice votes for either payload, so only the one whose tag requires a single vote can
+    // get consensused.
+    let tagged = Observation::OpaquePayload(Transaction::new_tagged(single_tag, "ABCD"));
+    let untagged = Observation::OpaquePayload(Transaction::new("EFGH"));
+    unwrap!(peers[0].vote_for(tagged.clone()));
+    unwrap!(peers[0].vote_for(untagged.clone()));
+
+    let polled = gossip_until(&mut peers, |polled| polled.contains(&tagged));
+    for payloads in polled {
+        assert!(payloads.contains(&tagged));
+        assert!(!payloads.contains(&untagged));
+    }
+}
+
+#[test]
+fn consensus_history_retention() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(4);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+
+    let mut peers = ids
+        .iter()
+        .map(|id| {
+            TestParsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut common_rng)),
+            )
+        })
+        .collect_vec();
+    peers[0].set_consensus_history_retention(Some(2));
+
+    let payloads = (0..4)
+        .map(|index| Observation::OpaquePayload(Transaction::new(format!("ABCD{}", index))))
+        .collect_vec();
+    for peer in &mut peers {
+        for payload in &payloads {
+            unwrap!(peer.vote_for(payload.clone()));
+        }
+    }
+
+    let polled = gossip_until(&mut peers, |polled| {
+        payloads.iter().all(|payload| polled.contains(payload))
+    });
+
+    // Archiving the history doesn't affect consensus, nor block numbering.
+    assert_eq!(polled[0], polled[1]);
+    let retained = peers[0].meta_election_consensus_history_hash();
+    let full = peers[1].meta_election_consensus_history_hash();
+    assert_eq!(retained.len(), 2);
+    assert!(full.len() > retained.len());
+    assert!(full.ends_with(&retained));
+    assert_eq!(
+        peers[0].meta_election_snapshot().0,
+        peers[1].meta_election_snapshot().0
+    );
+}
+
+#[test]
+fn consensus_chain() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(4);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+
+    let mut peers = ids
+        .iter()
+        .map(|id| {
+            TestParsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut common_rng)),
+            )
+        })
+        .collect_vec();
+    peers[0].set_consensus_history_retention(Some(2));
+
+    let payloads = (0..4)
+        .map(|index| Observation::OpaquePayload(Transaction::new(format!("ABCD{}", index))))
+        .collect_vec();
+    for peer in &mut peers {
+        for payload in &payloads {
+            unwrap!(peer.vote_for(payload.clone()));
+        }
+    }
+
+    let polled = gossip_until(&mut peers, |polled| {
+        payloads.iter().all(|payload| polled.contains(payload))
+    });
+
+    // The chain links the polled payloads in order.
+    let chain = peers[1].consensus_chain();
+    assert!(chain.verify());
+    assert!(chain.base().is_none());
+    let payload_hashes = chain
+        .links()
+        .iter()
+        .map(|link| link.payload_hash)
+        .collect_vec();
+    assert_eq!(
+        payload_hashes,
+        polled[1].iter().map(ObservationHash::from).collect_vec()
+    );
+
+    // All peers agree on it, including the one which only retains the end of it.
+    let retained = peers[0].consensus_chain();
+    assert!(retained.verify());
+    assert_eq!(retained.links(), &chain.links()[chain.links().len() - 2..]);
+    assert!(retained.base().is_some());
+    for peer in &peers {
+        assert_eq!(peer.consensus_chain().last_hash(), chain.last_hash());
+    }
+}
+
+#[test]
+fn state_sizes() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(4);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+
+    let mut peers = ids
+        .iter()
+        .map(|id| {
+            TestParsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut common_rng)),
+            )
+        })
+        .collect_vec();
+    peers[0].set_block_retention(true);
+
+    let payloads = (0..4)
+        .map(|index| Observation::OpaquePayload(Transaction::new(format!("ABCD{}", index))))
+        .collect_vec();
+    for peer in &mut peers {
+        for payload in &payloads {
+            unwrap!(peer.vote_for(payload.clone()));
+        }
+    }
+    // Our votes, plus our vote for the genesis group.
+    let before = peers[0].state_sizes();
+    assert_eq!(before.unconsensused_events, payloads.len() + 1);
+    assert_eq!(before.observations, payloads.len() + 1);
+
+    let polled = gossip_until(&mut peers, |polled| {
+        payloads.iter().all(|payload| polled.contains(payload))
+    });
+
+    let sizes = peers[0].state_sizes();
+    assert_eq!(sizes.graph_events, peers[0].events_len());
+    assert_eq!(sizes.graph_events, peers[0].graph().len());
+    assert_eq!(
+        sizes.meta_events,
+        peers[0].meta_election().meta_events.len()
+    );
+    assert_eq!(sizes.consensus_history, polled[0].len());
+    assert_eq!(sizes.retained_blocks, polled[0].len());
+    assert!(sizes.estimated_bytes > before.estimated_bytes);
+
+    // Pruning the consensus history shows in the sizes.
+    peers[0].set_consensus_history_retention(Some(1));
+    let pruned = peers[0].state_sizes();
+    assert_eq!(pruned.consensus_history, 1);
+    assert!(pruned.estimated_bytes < sizes.estimated_bytes);
+}
+
+// Makes every peer gossip with every other one, polling blocks after each round, until the
+// payloads polled by each of them satisfy `done`.  Returns the payloads polled by each peer.
+fn gossip_until<F>(
+    peers: &mut [TestParsec<Transaction, PeerId>],
+    done: F,
+) -> Vec<Vec<Observation<Transaction, PeerId>>>
+where
+    F: Fn(&[Observation<Transaction, PeerId>]) -> bool,
+{
+    let ids = peers
+        .iter()
+        .map(|peer| peer.our_pub_id().clone())
+        .collect_vec();
+    let mut polled = vec![Vec::new(); peers.len()];
+    for _ in 0..50 {
+        for (src, dst) in (0..peers.len()).tuple_combinations() {
+            let request = unwrap!(peers[src].create_gossip(&ids[dst]));
+            let response = unwrap!(peers[dst].handle_request(&ids[src], request));
+            unwrap!(peers[src].handle_response(&ids[dst], response));
+        }
+        for (peer, polled) in peers.iter_mut().zip(&mut polled) {
+            polled.extend(peer.poll_iter().map(|block| block.payload().clone()));
+        }
+        if polled.iter().all(|payloads| done(payloads)) {
+            break;
+        }
+    }
+    polled
+}
+
+#[test]
+fn light_client_follows_membership_changes() {
+    let mut env = Environment::new(SEED);
+    let options = ScheduleOptions {
+        genesis_size: 4,
+        peers_to_add: 3,
+        peers_to_remove: 1,
+        opaque_to_add: 5,
+        ..Default::default()
+    };
+    let schedule = Schedule::new(&mut env, &options);
+    unwrap!(env.execute_schedule(schedule));
+
+    let peer = unwrap!(env.network.running_non_malicious_peers().next());
+    let genesis_group = match unwrap!(peer.blocks().next()).payload() {
+        Observation::Genesis { group, .. } => group.clone(),
+        payload => panic!("Unexpected first block payload {:?}", payload),
+    };
+    let mut client = LightClient::new(genesis_group.clone(), ConsensusMode::Supermajority);
+    for block_group in peer.grouped_blocks() {
+        unwrap!(client.handle_block_group(block_group));
+    }
+
+    let expected_voters = env
+        .network
+        .running_non_malicious_peers()
+        .map(|peer| peer.id().clone())
+        .collect();
+    assert_ne!(*client.voters(), genesis_group);
+    assert_eq!(*client.voters(), expected_voters);
+    assert_eq!(client.num_blocks(), peer.blocks().count());
+}
+
+#[test]
+fn add_peer_after_isolating_one() {
+    let ids = mock::create_ids(4);
+    let (bob_id, dave_id) = (&ids[1], &ids[3]);
+    let genesis_group: BTreeSet<_> = ids[..3].iter().cloned().collect();
+    let mut alice = TestParsec::from_genesis(
+        ids[0].clone(),
+        &genesis_group,
+        ConsensusMode::Supermajority,
+        Box::new(new_rng(&mut new_common_rng(SEED))),
+    );
+
+    // Isolating Bob stops us from gossiping to him, but not his state.
+    alice.isolate_peer(bob_id);
+    let bob_index = unwrap!(alice.get_peer_index(bob_id));
+    assert!(alice.peer_list().peer_state(bob_index).can_recv());
+    assert!(alice.confirm_allowed_to_gossip_to(bob_index).is_err());
+    assert!(alice.gossip_recipients().all(|id| id != bob_id));
+    assert!(alice.peers_in_state(PeerState::RECV).contains(bob_id));
+
+    // So Dave, added later, can receive gossip straight away like with no peer isolated.
+    alice.handle_add_peer(dave_id);
+    let dave_index = unwrap!(alice.get_peer_index(dave_id));
+    assert!(alice.peer_list().peer_state(dave_index).can_recv());
+    unwrap!(alice.confirm_allowed_to_gossip_to(dave_index));
+    assert!(alice.gossip_recipients().any(|id| id == dave_id));
+}
+
+#[test]
+fn stalled_joins() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(4);
+    let dave_id = ids[3].clone();
+    let genesis_group: BTreeSet<_> = ids[..3].iter().cloned().collect();
+    let mut peers = ids[..3]
+        .iter()
+        .map(|id| {
+            let mut peer = TestParsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut common_rng)),
+            );
+            peer.set_join_stall_threshold(Some(1));
+            peer
+        })
+        .collect_vec();
+
+    // Add Dave, who never gossips to anyone.
+    let add_dave = Observation::Add {
+        peer_id: dave_id.clone(),
+        related_info: vec![],
+    };
+    for peer in &mut peers {
+        unwrap!(peer.vote_for(add_dave.clone()));
+    }
+    let _ = gossip_until(&mut peers, |polled| polled.contains(&add_dave));
+    for peer in &peers {
+        assert!(peer.peers_in_state(PeerState::VOTE).contains(&dave_id));
+        assert!(!peer
+            .peers_in_state(PeerState::VOTE)
+            .contains(&PeerId::new("Eric")));
+        assert_eq!(peer.peers_in_state(PeerState::inactive()).len(), 4);
+        assert!(peer.stalled_joins().is_empty());
+    }
+
+    // Three out of four is still a supermajority, so the others keep reaching consensus.
+    let payloads = ["A", "B"]
+        .iter()
+        .map(|payload| Observation::OpaquePayload(Transaction::new(*payload)))
+        .collect_vec();
+    for peer in &mut peers {
+        for payload in &payloads {
+            unwrap!(peer.vote_for(payload.clone()));
+        }
+    }
+    let _ = gossip_until(&mut peers, |polled| {
+        payloads.iter().all(|payload| polled.contains(payload))
+    });
+    for peer in &mut peers {
+        assert_eq!(peer.stalled_joins(), vec![&dave_id]);
+        peer.set_join_stall_threshold(Some(2));
+        assert!(peer.stalled_joins().is_empty());
+    }
+}
+
+#[test]
+fn event_count_by_creator() {
+    fn check(peer: &TestPeer) {
+        let mut expected = BTreeMap::new();
+        for event in peer.graph() {
+            let creator = unwrap!(peer.peer_list().get(event.creator())).id().clone();
+            *expected.entry(creator).or_insert(0) += 1;
+        }
+        assert_eq!(peer.event_count_by_creator(), expected);
+    }
+
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(3);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let mut peers = ids
+        .iter()
+        .map(|id| {
+            TestParsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut common_rng)),
+            )
+        })
+        .collect_vec();
+    for peer in &peers {
+        assert_eq!(peer.event_count_by_creator().len(), 1);
+        check(peer);
+    }
+
+    unwrap!(peers[0].vote_for(Observation::OpaquePayload(Transaction::new("A"))));
+    let _ = gossip_until(&mut peers, |polled| {
+        polled.iter().any(Observation::is_opaque)
+    });
+    for peer in &peers {
+        assert_eq!(peer.event_count_by_creator().len(), 3);
+        check(peer);
+    }
+
+    // Graphs including events by a peer added later, and a fork.
+    for path in &[
+        "input_graphs/functional_tests_add_peer/alice.dot",
+        "input_graphs/functional_tests_handle_malice_basic_fork/alice.dot",
+    ] {
+        let peer = TestParsec::from_parsed_contents(
+            unwrap!(parse_dot_file(path)),
+            Box::new(new_rng(&mut common_rng)),
+        );
+        check(&peer);
+    }
+}
+
+#[test]
+fn event_descriptors() {
+    let mut common_rng = new_common_rng(SEED);
+
+    // Graphs including events by a peer added later, and a fork.
+    for path in &[
+        "input_graphs/functional_tests_add_peer/alice.dot",
+        "input_graphs/functional_tests_handle_malice_basic_fork/alice.dot",
+    ] {
+        let peer = TestParsec::from_parsed_contents(
+            unwrap!(parse_dot_file(path)),
+            Box::new(new_rng(&mut common_rng)),
+        );
+        let descriptors = peer.event_descriptors().collect_vec();
+        assert_eq!(descriptors.len(), peer.graph().len());
+
+        for (event, descriptor) in peer.graph().iter().zip(&descriptors) {
+            let creator = unwrap!(peer.peer_list().get(event.creator())).id();
+            assert_eq!(descriptor.hash, *event.hash());
+            assert_eq!(descriptor.creator, *creator);
+            assert_eq!(descriptor.index, event.index_by_creator());
+
+            // Only the hash, the creator and a fixed size index go on the wire.
+            assert_eq!(
+                serialise(descriptor).len(),
+                serialise(&descriptor.hash).len() + serialise(creator).len() + 8
+            );
+        }
+    }
+}
+
+#[test]
+fn pull_sync() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(4);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let mut peers = ids
+        .iter()
+        .map(|id| {
+            TestParsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut common_rng)),
+            )
+        })
+        .collect_vec();
+
+    // Dave is cut off while the others consensus a payload.
+    let (active, dave) = peers.split_at_mut(3);
+    let dave = &mut dave[0];
+    for peer in active.iter_mut() {
+        unwrap!(peer.vote_for(Observation::OpaquePayload(Transaction::new("A"))));
+    }
+    let polled = gossip_until(active, |polled| polled.iter().any(Observation::is_opaque));
+    assert!(polled[0].iter().any(Observation::is_opaque));
+
+    let summary = unwrap!(dave.create_have_summary(&ids[0]));
+    assert_eq!(
+        active[0].handle_have_summary(&ids[0], &summary),
+        Err(Error::SelfGossip)
+    );
+    let response = unwrap!(active[0].handle_have_summary(&ids[3], &summary));
+    let num_our_events = dave.peer_list().our_events().count();
+    unwrap!(dave.handle_have_response(&ids[0], response));
+
+    // Dave now has all of Alice's events, without having created any, and reaches the same
+    // consensus.
+    assert!(active[0]
+        .graph()
+        .iter()
+        .all(|event| dave.graph().contains(event.hash())));
+    assert_eq!(dave.peer_list().our_events().count(), num_our_events);
+    assert_eq!(
+        dave.poll_iter()
+            .map(|block| block.payload().clone())
+            .collect_vec(),
+        polled[0]
+    );
+
+    // Once caught up, nothing is pulled anymore.
+    let summary = unwrap!(dave.create_have_summary(&ids[0]));
+    let response = unwrap!(active[0].handle_have_summary(&ids[3], &summary));
+    assert!(response.packed_events.is_empty());
+    unwrap!(dave.handle_have_response(&ids[0], response));
+
+    // Alice pulls the events only Dave has the other way round.
+    let summary = unwrap!(active[0].create_have_summary(&ids[3]));
+    let response = unwrap!(dave.handle_have_summary(&ids[0], &summary));
+    assert_eq!(response.packed_events.len(), num_our_events);
+    unwrap!(active[0].handle_have_response(&ids[3], response));
+    assert_eq!(active[0].graph().len(), dave.graph().len());
+}
+
+#[test]
+fn pull_sync_with_fork() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(4);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let (mut alice, mut bob) = unwrap!(ids
+        .iter()
+        .take(2)
+        .map(|id| {
+            TestParsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut common_rng)),
+            )
+        })
+        .collect_tuple());
+
+    // Alice creates a couple of events on top of her last one, which Bob pulls.
+    let fork_point = alice.our_last_event_index();
+    let mut self_parent = fork_point;
+    for payload in &["one", "two"] {
+        let observation = Observation::OpaquePayload(Transaction::new(*payload));
+        let event = unwrap!(alice.new_event_from_observation(self_parent, observation));
+        self_parent = unwrap!(alice.add_event(event));
+    }
+    let summary = unwrap!(bob.create_have_summary(&ids[0]));
+    let response = unwrap!(alice.handle_have_summary(&ids[1], &summary));
+    unwrap!(bob.handle_have_response(&ids[0], response));
+
+    // Alice then forks at the same point and goes on from there, so her last event has a higher
+    // index by creator than any Bob holds, but its self-parents are unknown to him.
+    let mut self_parent = fork_point;
+    let mut fork = vec![];
+    for payload in &["one-fork", "two-fork", "three-fork"] {
+        let observation = Observation::OpaquePayload(Transaction::new(*payload));
+        let event = unwrap!(alice.new_event_from_observation(self_parent, observation));
+        fork.push(*event.hash());
+        self_parent = unwrap!(alice.add_event(event));
+    }
+    let summary = unwrap!(bob.create_have_summary(&ids[0]));
+    let response = unwrap!(alice.handle_have_summary(&ids[1], &summary));
+    unwrap!(bob.handle_have_response(&ids[0], response));
+    assert!(fork.iter().all(|hash| bob.graph().contains(hash)));
+    assert!(alice
+        .graph()
+        .iter()
+        .all(|event| bob.graph().contains(event.hash())));
+}
+
+#[test]
+fn have_response_from_removed_or_unsolicited_peer() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(4);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let (mut alice, bob, carol) = unwrap!(ids
+        .iter()
+        .take(3)
+        .map(|id| {
+            TestParsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut common_rng)),
+            )
+        })
+        .collect_tuple());
+
+    // Alice only accepts the response to her summary from Bob, and only once.
+    let summary = unwrap!(alice.create_have_summary(&ids[1]));
+    let response = unwrap!(bob.handle_have_summary(&ids[0], &summary));
+    assert_eq!(
+        alice.handle_have_response(&ids[2], response.clone()),
+        Err(Error::InvalidMessage)
+    );
+    unwrap!(alice.handle_have_response(&ids[1], response.clone()));
+    assert_eq!(
+        alice.handle_have_response(&ids[1], response),
+        Err(Error::InvalidMessage)
+    );
+
+    // Nor does she accept Carol's once Carol has been removed.
+    let summary = unwrap!(alice.create_have_summary(&ids[2]));
+    let response = unwrap!(carol.handle_have_summary(&ids[0], &summary));
+    alice.handle_remove_peer(&ids[2]);
+    match alice.handle_have_response(&ids[2], response) {
+        Err(Error::InvalidPeerState { .. }) => (),
+        result => panic!("Unexpected {:?}", result),
+    }
+}
+
+#[test]
+fn redundant_events_received() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(3);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let mut peers = ids
+        .iter()
+        .map(|id| {
+            TestParsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut common_rng)),
+            )
+        })
+        .collect_vec();
+    let exchange = |peers: &mut [TestParsec<Transaction, PeerId>], src: usize, dst: usize| {
+        let request = unwrap!(peers[src].create_gossip(&ids[dst]));
+        let num_known = request
+            .packed_events
+            .iter()
+            .filter(|packed_event| peers[dst].graph().contains(&packed_event.compute_hash()))
+            .count();
+        let response = unwrap!(peers[dst].handle_request(&ids[src], request));
+        unwrap!(peers[src].handle_response(&ids[dst], response));
+        num_known
+    };
+
+    // Bob passes Alice's events on to Carol, so Alice then sends Carol some she already has.
+    assert_eq!(exchange(&mut peers, 0, 1), 0);
+    assert_eq!(exchange(&mut peers, 1, 2), 0);
+    let num_known = exchange(&mut peers, 0, 2);
+    assert!(num_known > 0);
+
+    let expected: BTreeMap<_, _> = iter::once((ids[0].clone(), num_known)).collect();
+    assert_eq!(peers[2].redundant_events_received(), expected);
+    assert_eq!(peers[2].total_redundant_events_received(), num_known);
+    assert_eq!(peers[0].total_redundant_events_received(), 0);
+}
+
+#[test]
+fn blocks_from_and_rev() {
+    fn payloads<'a, I>(blocks: I) -> Vec<Observation<Transaction, PeerId>>
+    where
+        I: IntoIterator<Item = &'a Block<Transaction, PeerId>>,
+    {
+        blocks
+            .into_iter()
+            .map(|block| block.payload().clone())
+            .collect()
+    }
+
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(3);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let mut peers = ids
+        .iter()
+        .map(|id| {
+            TestParsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                ConsensusMode::Single,
+                Box::new(new_rng(&mut common_rng)),
+            )
+        })
+        .collect_vec();
+    peers[0].set_block_retention(true);
+
+    let a = Observation::OpaquePayload(Transaction::new("A"));
+    unwrap!(peers[0].vote_for(a.clone()));
+    let polled = gossip_until(&mut peers, |polled| polled.contains(&a));
+    assert!(polled[0].contains(&a));
+
+    // Polling doesn't affect the retained blocks.
+    assert_eq!(peers[0].first_retained_block_index(), 0);
+    assert_eq!(payloads(peers[0].blocks()), polled[0]);
+    assert_eq!(
+        payloads(peers[0].blocks_rev()),
+        polled[0].iter().rev().cloned().collect_vec()
+    );
+    assert_eq!(payloads(peers[0].blocks_from(1)), polled[0][1..].to_vec());
+    assert_eq!(peers[0].blocks_from(polled[0].len()).count(), 0);
+    assert_eq!(peers[1].blocks().count(), 0);
+
+    // Only the blocks made stable after enabling retention are retained.
+    peers[1].set_block_retention(true);
+    let start = peers[1].stable_up_to();
+    assert_eq!(peers[1].first_retained_block_index(), start);
+
+    let b = Observation::OpaquePayload(Transaction::new("B"));
+    unwrap!(peers[1].vote_for(b.clone()));
+    let _ = gossip_until(&mut peers, |polled| polled.contains(&b));
+    assert_eq!(payloads(peers[1].blocks()), vec![b]);
+    assert_eq!(
+        payloads(peers[1].blocks_from(0)),
+        payloads(peers[1].blocks_from(start))
+    );
+    assert_eq!(
+        payloads(peers[0].blocks_from(start)),
+        payloads(peers[1].blocks())
+    );
+
+    peers[0].set_block_retention(false);
+    assert_eq!(peers[0].blocks().count(), 0);
+    assert_eq!(
+        peers[0].first_retained_block_index(),
+        peers[0].stable_up_to()
+    );
+}
+
+#[test]
+fn has_supermajority_votes() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(4);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let mut peers = ids
+        .iter()
+        .map(|id| {
+            TestParsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut common_rng)),
+            )
+        })
+        .collect_vec();
+
+    // Everybody but Alice votes, then tells her about it one by one.
+    let a = Observation::OpaquePayload(Transaction::new("A"));
+    for peer in &mut peers[1..] {
+        unwrap!(peer.vote_for(a.clone()));
+        assert!(!peer.has_supermajority_votes(&a));
+    }
+    for src in 1..peers.len() {
+        assert!(!peers[0].has_supermajority_votes(&a));
+        let request = unwrap!(peers[src].create_gossip(&ids[0]));
+        let _ = unwrap!(peers[0].handle_request(&ids[src], request));
+    }
+
+    // Three out of four is enough, even though nothing has been decided yet.
+    assert!(peers[0].has_supermajority_votes(&a));
+    assert!(peers[0].poll().is_none());
+    let b = Observation::OpaquePayload(Transaction::new("B"));
+    assert!(!peers[0].has_supermajority_votes(&b));
+
+    let _ = gossip_until(&mut peers, |polled| polled.contains(&a));
+    assert!(!peers[0].has_supermajority_votes(&a));
+}
+
+#[test]
+fn interesting_events() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(4);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let mut peers = ids
+        .iter()
+        .map(|id| {
+            TestParsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut common_rng)),
+            )
+        })
+        .collect_vec();
+
+    let a = Observation::OpaquePayload(Transaction::new("A"));
+    for peer in &mut peers {
+        unwrap!(peer.vote_for(a.clone()));
+    }
+
+    // Gossip without polling until Alice sees interesting events.
+    let mut interesting = BTreeMap::new();
+    for _ in 0..10 {
+        for (src, dst) in (0..peers.len()).tuple_combinations() {
+            let request = unwrap!(peers[src].create_gossip(&ids[dst]));
+            let response = unwrap!(peers[dst].handle_request(&ids[src], request));
+            unwrap!(peers[src].handle_response(&ids[dst], response));
+        }
+        interesting = peers[0].interesting_events();
+        if !interesting.is_empty() {
+            break;
+        }
+    }
+    assert!(!interesting.is_empty());
+    let graph = peers[0].graph();
+    for (peer_id, hashes) in &interesting {
+        let peer_index = unwrap!(peers[0].peer_list().get_index(peer_id));
+        assert!(!hashes.is_empty());
+        for hash in hashes {
+            let event = unwrap!(graph.get_index(hash).and_then(|index| graph.get(index)));
+            assert_eq!(event.creator(), peer_index);
+        }
+    }
+
+    // Once the meta-election is over, there is nothing interesting left.
+    let _ = gossip_until(&mut peers, |polled| polled.contains(&a));
+    assert!(peers[0].interesting_events().is_empty());
+}
+
+#[test]
+fn gossip_policy() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(4);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let mut peers = ids
+        .iter()
+        .map(|id| {
+            TestParsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut common_rng)),
+            )
+        })
+        .collect_vec();
+
+    // The genesis observation is pending, so even `OnChange` gossips.
+    peers[0].set_gossip_policy(GossipPolicy::OnChange);
+    assert!(peers[0].should_gossip_to(&ids[1]));
+
+    let a = Observation::OpaquePayload(Transaction::new("A"));
+    for peer in &mut peers {
+        unwrap!(peer.vote_for(a.clone()));
+    }
+    let _ = gossip_until(&mut peers, |polled| polled.contains(&a));
+
+    // Everything is decided and Bob has seen all of Alice's votes: Alice is idle.
+    assert!(!peers[0].should_gossip_to(&ids[1]));
+    peers[0].set_gossip_policy(GossipPolicy::Always);
+    assert!(peers[0].should_gossip_to(&ids[1]));
+
+    // When idle, `Adaptive` skips 1, 2, 4... opportunities between gossips.
+    peers[0].set_gossip_policy(GossipPolicy::Adaptive);
+    let taken = (0..10)
+        .map(|_| peers[0].should_gossip_to(&ids[1]))
+        .collect_vec();
+    assert_eq!(
+        taken,
+        vec![false, true, false, false, true, false, false, false, false, true]
+    );
+
+    // A new vote is news for Bob, and leaves the meta-election undecided for everyone.
+    let b = Observation::OpaquePayload(Transaction::new("B"));
+    unwrap!(peers[0].vote_for(b));
+    peers[0].set_gossip_policy(GossipPolicy::OnChange);
+    peers[2].set_gossip_policy(GossipPolicy::OnChange);
+    assert!(peers[0].should_gossip_to(&ids[1]));
+    assert!(!peers[2].should_gossip_to(&ids[1]));
+    let request = unwrap!(peers[0].create_gossip(&ids[2]));
+    let _ = unwrap!(peers[2].handle_request(&ids[0], request));
+    assert!(peers[2].should_gossip_to(&ids[1]));
+}
+
+#[test]
+fn quorum_size_follows_membership_changes() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(4);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let mut peers = ids
+        .iter()
+        .map(|id| {
+            TestParsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut common_rng)),
+            )
+        })
+        .collect_vec();
+    assert_eq!(peers[0].quorum_size(), supermajority(4));
+
+    // Eric never joins, but once added he counts towards the quorum.
+    let eric_id = PeerId::new("Eric");
+    let add_eric = Observation::Add {
+        peer_id: eric_id.clone(),
+        related_info: vec![],
+    };
+    for peer in &mut peers {
+        unwrap!(peer.vote_for(add_eric.clone()));
+    }
+    let _ = gossip_until(&mut peers, |polled| polled.contains(&add_eric));
+    for peer in &peers {
+        assert_eq!(peer.quorum_size(), supermajority(5));
+    }
+
+    let remove_eric = Observation::Remove {
+        peer_id: eric_id,
+        related_info: vec![],
+    };
+    for peer in &mut peers {
+        unwrap!(peer.vote_for(remove_eric.clone()));
+    }
+    let _ = gossip_until(&mut peers, |polled| polled.contains(&remove_eric));
+    for peer in &peers {
+        assert_eq!(peer.quorum_size(), supermajority(4));
+    }
+}
+
+#[test]
+fn voter_weights() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(4);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    // Alice weighs 5 out of a total of 8, so she needs just one other voter to back her.
+    let weights: BTreeMap<_, _> = iter::once((ids[0].clone(), 5)).collect();
+    let mut peers = ids
+        .iter()
+        .map(|id| {
+            let mut peer = TestParsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut common_rng)),
+            );
+            unwrap!(peer.set_voter_weights(weights.clone()));
+            peer
+        })
+        .collect_vec();
+
+    // Zero weights are rejected, leaving the previous ones in place.
+    let zero = iter::once((ids[1].clone(), 0)).collect();
+    assert_eq!(
+        peers[0].set_voter_weights(zero),
+        Err(Error::InvalidConfiguration("Voter weights can't be zero"))
+    );
+
+    // A weight of 6 out of 8 is needed, rather than 3 voters out of 4.
+    assert_eq!(peers[0].quorum_size(), 6);
+
+    let a = Observation::OpaquePayload(Transaction::new("A"));
+    unwrap!(peers[1].vote_for(a.clone()));
+    let request = unwrap!(peers[1].create_gossip(&ids[0]));
+    let _ = unwrap!(peers[0].handle_request(&ids[1], request));
+    assert!(!peers[0].has_supermajority_votes(&a));
+    unwrap!(peers[0].vote_for(a.clone()));
+    assert!(peers[0].has_supermajority_votes(&a));
+
+    // Two votes out of four are enough to decide.
+    let polled = gossip_until(&mut peers, |polled| polled.contains(&a));
+    assert!(polled.iter().all(|payloads| payloads.contains(&a)));
+    assert!(polled.iter().all(|payloads| *payloads == polled[0]));
+}
+
+#[test]
+fn pending_observations() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(4);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let mut peers = ids
+        .iter()
+        .map(|id| {
+            TestParsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut common_rng)),
+            )
+        })
+        .collect_vec();
+    let pending = |peer: &TestPeer| peer.pending_observations().cloned().collect_vec();
+
+    // Everybody voted for the genesis group, which isn't consensused until they gossip.
+    let genesis = unwrap!(peers[0].pending_observations().next()).clone();
+    assert_eq!(pending(&peers[0]), vec![genesis.clone()]);
+    let _ = gossip_until(&mut peers, |polled| polled.contains(&genesis));
+    assert!(peers[0].pending_observations().next().is_none());
+
+    let a = Observation::OpaquePayload(Transaction::new("A"));
+    let b = Observation::OpaquePayload(Transaction::new("B"));
+    unwrap!(peers[0].vote_for(b.clone()));
+    assert_eq!(pending(&peers[0]), vec![b.clone()]);
+
+    // Alice learns about the others' votes, each payload being voted for by several of them.
+    for peer in &mut peers[1..] {
+        unwrap!(peer.vote_for(a.clone()));
+    }
+    unwrap!(peers[1].vote_for(b.clone()));
+    unwrap!(peers[3].vote_for(b.clone()));
+    for src in 1..peers.len() {
+        let request = unwrap!(peers[src].create_gossip(&ids[0]));
+        let _ = unwrap!(peers[0].handle_request(&ids[src], request));
+    }
+
+    let expected = vec![a.clone(), b.clone()]
+        .into_iter()
+        .sorted_by(|lhs, rhs| lhs.consistent_cmp(rhs))
+        .collect_vec();
+    assert_eq!(pending(&peers[0]), expected);
+    // Bob and Carol only know about their own votes.
+    assert_eq!(pending(&peers[1]), expected);
+    assert_eq!(pending(&peers[2]), vec![a.clone()]);
+
+    let _ = gossip_until(&mut peers, |polled| {
+        polled.contains(&a) && polled.contains(&b)
+    });
+    assert!(peers[0].pending_observations().next().is_none());
+}
+
+#[test]
+fn block_archive() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(4);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let mut peers = ids
+        .iter()
+        .map(|id| {
+            TestParsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut common_rng)),
+            )
+        })
+        .collect_vec();
+
+    let archived = Rc::new(RefCell::new(Vec::new()));
+    let archive = Rc::clone(&archived);
+    peers[0].set_block_retention(true);
+    peers[0].set_block_archive(
+        2,
+        Box::new(move |block: Block<Transaction, PeerId>| {
+            archive.borrow_mut().push(block.payload().clone())
+        }),
+    );
+
+    let payloads = ["A", "B", "C", "D"]
+        .iter()
+        .map(|payload| Observation::OpaquePayload(Transaction::new(*payload)))
+        .collect_vec();
+    for peer in &mut peers {
+        for payload in &payloads {
+            unwrap!(peer.vote_for(payload.clone()));
+        }
+    }
+    while peers[0].stable_up_to() <= payloads.len() {
+        for (src, dst) in (0..peers.len()).tuple_combinations() {
+            let request = unwrap!(peers[src].create_gossip(&ids[dst]));
+            let response = unwrap!(peers[dst].handle_request(&ids[src], request));
+            unwrap!(peers[src].handle_response(&ids[dst], response));
+        }
+    }
+
+    // Nothing is archived before it's polled, even beyond the cap.
+    let alice = &mut peers[0];
+    let num_blocks = alice.stable_up_to();
+    assert!(archived.borrow().is_empty());
+    assert_eq!(alice.blocks().count(), num_blocks);
+    assert_eq!(alice.first_retained_block_index(), 0);
+
+    let first = unwrap!(alice.poll());
+    assert_eq!(*archived.borrow(), vec![first.payload().clone()]);
+    assert_eq!(alice.first_retained_block_index(), 1);
+    assert_eq!(alice.blocks().count(), num_blocks - 1);
+
+    // Once everything is polled, only the last `cap` blocks are retained, and the archive holds
+    // all the others in consensus order.
+    let polled = iter::once(first)
+        .chain(alice.poll_iter())
+        .map(|block| block.payload().clone())
+        .collect_vec();
+    assert_eq!(alice.blocks().count(), 2);
+    assert_eq!(alice.first_retained_block_index(), num_blocks - 2);
+    assert!(alice.blocks_from(0).eq(alice.blocks()));
+    assert_eq!(
+        archived
+            .borrow()
+            .iter()
+            .chain(alice.blocks().map(Block::payload))
+            .cloned()
+            .collect_vec(),
+        polled
+    );
+}
+
+#[test]
+fn heartbeat() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(4);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let mut peers = ids
+        .iter()
+        .map(|id| {
+            TestParsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut common_rng)),
+            )
+        })
+        .collect_vec();
+    let num_heartbeats = |peer: &TestParsec<Transaction, PeerId>| {
+        peer.consensused_observations()
+            .filter_map(|(_, observation)| match observation {
+                Observation::Heartbeat(index) => Some(index),
+                _ => None,
+            })
+            .count()
+    };
+
+    assert_eq!(
+        peers[0].can_vote_for(&Observation::Heartbeat(0)),
+        Err(Error::InvalidObservation)
+    );
+
+    // Without a heartbeat interval, an idle section creates no heartbeats.
+    let polled = gossip_until(&mut peers, |payloads| !payloads.is_empty());
+    assert!(polled.iter().all(|payloads| !payloads.is_empty()));
+    assert!(peers.iter().all(|peer| num_heartbeats(peer) == 0));
+
+    // With one, an idle section with nothing awaiting consensus still creates no heartbeats.
+    for peer in &mut peers {
+        assert_eq!(
+            peer.set_heartbeat_interval(Some(0)),
+            Err(Error::InvalidConfiguration(
+                "Heartbeat interval can't be zero"
+            ))
+        );
+        unwrap!(peer.set_heartbeat_interval(Some(3)));
+    }
+    let _ = gossip_until(&mut peers, |_| false);
+    assert!(peers.iter().all(|peer| num_heartbeats(peer) == 0));
+
+    // Once something is pending, the peers cast heartbeats until it gets consensused.  These are
+    // consensused too, but never returned by `poll`.
+    let transaction = Observation::OpaquePayload(Transaction::new("tx"));
+    for peer in &mut peers {
+        unwrap!(peer.vote_for(transaction.clone()));
+    }
+    let polled = gossip_until(&mut peers, |payloads| !payloads.is_empty());
+    assert!(polled
+        .iter()
+        .all(|payloads| payloads[..] == [transaction.clone()]));
+    assert!(peers.iter().all(|peer| num_heartbeats(peer) > 0));
+
+    // Once it's decided, the section goes quiet again.
+    let _ = gossip_until(&mut peers, |_| false);
+    let settled = peers.iter().map(&num_heartbeats).collect_vec();
+    let _ = gossip_until(&mut peers, |_| false);
+    assert_eq!(peers.iter().map(&num_heartbeats).collect_vec(), settled);
+}
+
+#[test]
+fn gossip_recipients_with_staleness() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(3);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let mut peers = ids
+        .iter()
+        .map(|id| {
+            TestParsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut common_rng)),
+            )
+        })
+        .collect_vec();
+    let staleness = |peer: &TestPeer| {
+        peer.gossip_recipients_with_staleness()
+            .map(|(id, staleness)| (id.clone(), staleness))
+            .collect::<BTreeMap<_, _>>()
+    };
+
+    // Alice hasn't heard from anyone yet, so everything she has is new to them.
+    let alice_graph_len = peers[0].graph().len();
+    let expected = ids[1..]
+        .iter()
+        .map(|id| (id.clone(), alice_graph_len))
+        .collect::<BTreeMap<_, _>>();
+    assert_eq!(staleness(&peers[0]), expected);
+
+    let request = unwrap!(peers[0].create_gossip(&ids[1]));
+    let response = unwrap!(peers[1].handle_request(&ids[0], request));
+    unwrap!(peers[0].handle_response(&ids[1], response));
+
+    // Bob's response carried everything Alice sent him, so he's only missing the events Alice
+    // created since.  Carol has seen nothing.
+    let staleness = staleness(&peers[0]);
+    assert!(staleness.keys().eq(peers[0].gossip_recipients()));
+    assert_eq!(staleness[&ids[2]], peers[0].graph().len());
+    assert!(staleness[&ids[1]] < staleness[&ids[2]]);
+    assert_eq!(
+        staleness[&ids[1]],
+        unwrap!(peers[0].create_gossip(&ids[1])).packed_events.len() - 1
+    );
+}
+
+#[test]
+fn peer_responsiveness() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(4);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let mut peers = ids
+        .iter()
+        .map(|id| {
+            TestParsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut common_rng)),
+            )
+        })
+        .collect_vec();
+
+    // Alice hasn't heard from anyone yet.
+    let responsiveness = peers[0].peer_responsiveness();
+    assert!(responsiveness.keys().eq(&ids[1..]));
+    assert!(responsiveness.values().all(|responsiveness| {
+        !responsiveness.heard_from && responsiveness.events_behind == peers[0].graph().len()
+    }));
+
+    let _ = gossip_until(&mut peers, |polled| !polled.is_empty());
+    assert!(peers[0]
+        .peer_responsiveness()
+        .values()
+        .all(|responsiveness| responsiveness.heard_from));
+
+    // Dave stops gossiping, so he falls further behind with every round the others make.
+    let mut dave_behind = 0;
+    for _ in 0..3 {
+        let _ = gossip_until(&mut peers[..3], |_| true);
+        let responsiveness = peers[0].peer_responsiveness();
+        assert!(responsiveness[&ids[3]].events_behind > dave_behind);
+        dave_behind = responsiveness[&ids[3]].events_behind;
+        assert!(responsiveness[&ids[1]].events_behind < dave_behind);
+        assert!(responsiveness[&ids[2]].events_behind < dave_behind);
+    }
+}
+
+#[test]
+fn deferred_events_added_once_peer_known() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(4);
+    let eric_id = PeerId::new("Eric");
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let mut peers = ids
+        .iter()
+        .map(|id| {
+            TestParsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut common_rng)),
+            )
+        })
+        .collect_vec();
+
+    // Alice relays Eric's initial event to Dave before anyone has added Eric.
+    let eric_initial = PackedEvent::new_initial(eric_id.clone());
+    let message = Request {
+        packed_events: vec![eric_initial.clone()],
+    };
+    let mut dave_rejecting =
+        TestParsec::from(peers[3].clone_with_rng(Box::new(new_rng(&mut common_rng))));
+    dave_rejecting.set_unknown_peer_policy(UnknownPeerPolicy::Reject);
+    assert_eq!(
+        dave_rejecting.handle_request(&ids[0], message.clone()),
+        Err(Error::UnknownPeer)
+    );
+    assert_eq!(dave_rejecting.deferred_event_count(), 0);
+
+    assert_eq!(
+        peers[3].handle_request(&ids[0], message).map(|_| ()),
+        Err(Error::UnknownPeer)
+    );
+    assert_eq!(peers[3].deferred_event_count(), 1);
+
+    // Once Dave has added Eric, the event is added without being gossiped again.
+    let add_eric = Observation::Add {
+        peer_id: eric_id,
+        related_info: vec![],
+    };
+    for peer in &mut peers {
+        unwrap!(peer.vote_for(add_eric.clone()));
+    }
+    let _ = gossip_until(&mut peers, |polled| polled.contains(&add_eric));
+    assert_eq!(peers[3].deferred_event_count(), 0);
+    assert!(peers[3].graph().contains(&eric_initial.compute_hash()));
+}
+
+#[test]
+fn deferred_events_evicted() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(4);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let mut peers = ids
+        .iter()
+        .map(|id| {
+            TestParsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut common_rng)),
+            )
+        })
+        .collect_vec();
+    for peer in &mut peers {
+        peer.set_unknown_peer_policy(UnknownPeerPolicy::Buffer {
+            capacity: 2,
+            max_blocks: 1,
+        });
+    }
+
+    // Alice relays three events by Eric, who is never added.  Only the last two are kept.
+    let eric_id = PeerId::new("Eric");
+    let e_0 = PackedEvent::new_initial(eric_id.clone());
+    let e_1 = PackedEvent::new_observation(
+        eric_id.clone(),
+        e_0.compute_hash(),
+        Observation::OpaquePayload(Transaction::new("A")),
+    );
+    let e_2 = PackedEvent::new_observation(
+        eric_id,
+        e_1.compute_hash(),
+        Observation::OpaquePayload(Transaction::new("B")),
+    );
+    let message = Request {
+        packed_events: vec![e_0, e_1, e_2],
+    };
+    assert_eq!(
+        peers[3].handle_request(&ids[0], message).map(|_| ()),
+        Err(Error::UnknownPeer)
+    );
+    assert_eq!(peers[3].deferred_event_count(), 2);
+
+    // They are dropped once more than one block has been consensused since.
+    let payloads = ["C", "D"]
+        .iter()
+        .map(|payload| Observation::OpaquePayload(Transaction::new(*payload)))
+        .collect_vec();
+    for peer in &mut peers {
+        for payload in &payloads {
+            unwrap!(peer.vote_for(payload.clone()));
+        }
+    }
+    let _ = gossip_until(&mut peers, |polled| {
+        payloads.iter().all(|payload| polled.contains(payload))
+    });
+    assert_eq!(peers[3].deferred_event_count(), 0);
+}
+
+#[test]
+fn deferred_events_evicted_from_flooding_peer() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(4);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let mut peers = ids
+        .iter()
+        .map(|id| {
+            TestParsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut common_rng)),
+            )
+        })
+        .collect_vec();
+    for peer in &mut peers {
+        peer.set_unknown_peer_policy(UnknownPeerPolicy::Buffer {
+            capacity: 3,
+            max_blocks: 10,
+        });
+    }
+
+    // Bob relays Eric's initial event to Dave before anyone has added Eric.
+    let eric_id = PeerId::new("Eric");
+    let eric_initial = PackedEvent::new_initial(eric_id.clone());
+    let message = Request {
+        packed_events: vec![eric_initial.clone()],
+    };
+    assert_eq!(
+        peers[3].handle_request(&ids[1], message).map(|_| ()),
+        Err(Error::UnknownPeer)
+    );
+
+    // Alice then floods Dave with the initial events of made-up peers.  Only hers are evicted.
+    for index in 0..5 {
+        let message = Request {
+            packed_events: vec![PackedEvent::new_initial(PeerId::new(&format!(
+                "Fake{}",
+                index
+            )))],
+        };
+        assert_eq!(
+            peers[3].handle_request(&ids[0], message).map(|_| ()),
+            Err(Error::UnknownPeer)
+        );
+    }
+    assert_eq!(peers[3].deferred_event_count(), 3);
+
+    // Once Dave has added Eric, the event relayed by Bob is still added.
+    let add_eric = Observation::Add {
+        peer_id: eric_id,
+        related_info: vec![],
+    };
+    for peer in &mut peers {
+        unwrap!(peer.vote_for(add_eric.clone()));
+    }
+    let _ = gossip_until(&mut peers, |polled| polled.contains(&add_eric));
+    assert!(peers[3].graph().contains(&eric_initial.compute_hash()));
+}
+
+#[test]
+fn continue_request_with_deferred_events() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(4);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let mut dave = TestParsec::from_genesis(
+        ids[3].clone(),
+        &genesis_group,
+        ConsensusMode::Supermajority,
+        Box::new(new_rng(&mut common_rng)),
+    );
+
+    // Alice relays her initial event, then Eric's before anyone has added Eric.  Handled one event
+    // at a time, the request ends as it would with `handle_request`: Eric's event is put aside
+    // and, as it's the last one, the request fails.
+    let alice_initial = PackedEvent::new_initial(ids[0].clone());
+    let eric_initial = PackedEvent::new_initial(PeerId::new("Eric"));
+    let message = Request {
+        packed_events: vec![alice_initial.clone(), eric_initial],
+    };
+    let pending = unwrap!(dave.start_request(&ids[0], message));
+    let pending = match unwrap!(dave.continue_request(pending, 1)) {
+        RequestProgress::Pending(pending) => pending,
+        RequestProgress::Done(_) => panic!("Request handled in one step"),
+    };
+    assert_eq!(
+        dave.continue_request(pending, 1).map(|_| ()),
+        Err(Error::UnknownPeer)
+    );
+    assert!(dave.graph().contains(&alice_initial.compute_hash()));
+    assert_eq!(dave.deferred_event_count(), 1);
+}
+
+#[test]
+fn deferred_events_signatures_verified() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(4);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let mut dave = TestParsec::from_genesis(
+        ids[3].clone(),
+        &genesis_group,
+        ConsensusMode::Supermajority,
+        Box::new(new_rng(&mut common_rng)),
+    );
+
+    // A forged event by Eric, who isn't known yet, is rejected rather than put aside.
+    let mut eric_initial = PackedEvent::new_initial(PeerId::new("Eric"));
+    eric_initial.corrupt_signature();
+    let message = Request {
+        packed_events: vec![eric_initial],
+    };
+    assert_eq!(
+        dave.handle_request(&ids[0], message).map(|_| ()),
+        Err(Error::SignatureFailure)
+    );
+    assert_eq!(dave.deferred_event_count(), 0);
+}
+
+#[test]
+fn forced_coin_tosses() {
+    let ids = mock::create_ids(4);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let payloads = ["A", "B", "C"]
+        .iter()
+        .map(|payload| Observation::OpaquePayload(Transaction::new(*payload)))
+        .collect_vec();
+    let temp_votes = (0..4)
+        .map(|round| MetaVote {
+            round,
+            ..Default::default()
+        })
+        .collect_vec();
+
+    for &coin in &[false, true] {
+        let mut common_rng = new_common_rng(SEED);
+        let mut peers = ids
+            .iter()
+            .map(|id| {
+                TestParsec::from_genesis(
+                    id.clone(),
+                    &genesis_group,
+                    ConsensusMode::Supermajority,
+                    Box::new(new_rng(&mut common_rng)),
+                )
+            })
+            .collect_vec();
+
+        // The forced results replace the common coin for the rounds they cover only.
+        let common_coin = peers[0].toss_coins(&temp_votes);
+        for peer in &mut peers {
+            peer.force_coin_tosses(vec![coin, coin]);
+        }
+        let coin_tosses = peers[0].toss_coins(&temp_votes);
+        assert_eq!(coin_tosses[&0], coin);
+        assert_eq!(coin_tosses[&1], coin);
+        assert_eq!(coin_tosses[&2], common_coin[&2]);
+        assert_eq!(coin_tosses[&3], common_coin[&3]);
+
+        // Either way, the peers agree on the blocks.
+        for peer in &mut peers {
+            for payload in &payloads {
+                unwrap!(peer.vote_for(payload.clone()));
+            }
+        }
+        let polled = gossip_until(&mut peers, |polled| {
+            payloads.iter().all(|payload| polled.contains(payload))
+        });
+        assert!(polled.iter().all_equal());
+    }
+}
 
-    // Vote for a new observation and check it is returned as unpolled, and that
-    // `has_unpolled_observations()` returns `true` again.
-    let vote = Observation::OpaquePayload(Transaction::new("ABCD"));
-    unwrap!(alice.vote_for(vote.clone()));
+#[test]
+fn forced_coin_decides_contended_meta_vote() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(4);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let mut alice: TestPeer = TestParsec::from_genesis(
+        ids[0].clone(),
+        &genesis_group,
+        ConsensusMode::Supermajority,
+        Box::new(new_rng(&mut common_rng)),
+    );
+    let weights = VoteWeights::equal(unwrap!(NonZeroUsize::new(ids.len())));
+
+    // All four voters reached the genuine flip of round 0 with their aux values split evenly, so
+    // nothing can be decided in that round: the estimates of the next one come from the coin.
+    let flip = |aux_value| {
+        MetaVote::new(
+            0,
+            Step::GenuineFlip,
+            BoolSet::Both,
+            BoolSet::Both,
+            Some(aux_value),
+            None,
+        )
+    };
+    let split = vec![vec![flip(true)], vec![flip(false)], vec![flip(false)]];
+
+    for &coin in &[false, true] {
+        alice.force_coin_tosses(vec![coin; 4]);
+        let next = |parent: MetaVote, others: &[Vec<MetaVote>]| {
+            let others = others
+                .iter()
+                .map(|votes| (votes.as_slice(), 1))
+                .collect_vec();
+            let temp = MetaVote::next_temp(&[parent], &others, weights);
+            let coin_tosses = alice.toss_coins(&temp);
+            *unwrap!(MetaVote::next_final(&temp, &coin_tosses, weights).last())
+        };
 
-    assert!(alice.has_unpolled_observations());
-    assert_eq!(alice.our_unpolled_observations().count(), 1);
-    assert_eq!(*unwrap!(alice.our_unpolled_observations().next()), vote);
+        // Every voter gets the same forced coin, and takes it as its estimate...
+        let mut vote = next(flip(true), &split);
+        assert_eq!(
+            vote,
+            MetaVote::new(
+                1,
+                Step::ForcedTrue,
+                BoolSet::Single(coin),
+                BoolSet::Empty,
+                None,
+                None
+            )
+        );
 
-    // Reset, re-run, and add another vote by Alice. Both `Add(Eric)` and the new vote should be
-    // returned in `out_unpolled_observations`. `Add(Eric)` should appear first, because it is
-    // consensused while the other vote is not.
-    let mut alice_contents = parse_test_dot_file("alice.dot");
-    let d_21 = unwrap!(alice_contents.remove_last_event());
-    let mut alice =
-        TestParsec::from_parsed_contents(alice_contents, Box::new(new_rng(&mut common_rng)));
-    unwrap!(alice.add_event(d_21));
+        // ...so they go on to decide it within that round, whichever way it fell.
+        while vote.decision().is_none() {
+            assert_eq!(vote.round_and_step().0, 1);
+            vote = next(vote, &vec![vec![vote]; 3]);
+        }
+        assert_eq!(vote.decision(), Some(coin));
+    }
+}
 
-    unwrap!(alice.vote_for(vote.clone()));
+#[test]
+fn continue_request() {
+    let mut common_rng = new_common_rng(SEED);
+    let ids = mock::create_ids(4);
+    let genesis_group: BTreeSet<_> = ids.iter().cloned().collect();
+    let mut peers = ids
+        .iter()
+        .map(|id| {
+            TestParsec::from_genesis(
+                id.clone(),
+                &genesis_group,
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut common_rng)),
+            )
+        })
+        .collect_vec();
+
+    // Let everybody but Alice reach consensus, so that Alice has plenty to catch up on.
+    for (index, peer) in peers.iter_mut().enumerate().skip(1) {
+        unwrap!(
+            peer.vote_for(Observation::OpaquePayload(Transaction::new(format!(
+                "{}",
+                index
+            ))))
+        );
+    }
+    let _ = gossip_until(&mut peers[1..], |polled| {
+        polled.iter().any(Observation::is_opaque)
+    });
+
+    let request = unwrap!(peers[1].create_gossip(&ids[0]));
+    let mut alice = peers.swap_remove(0);
+    let mut copy = TestParsec::from(alice.clone_with_rng(Box::new(new_rng(&mut common_rng))));
+
+    let expected_response = unwrap!(alice.handle_request(&ids[1], request.clone()));
+
+    let max_events = 4;
+    let mut pending = unwrap!(copy.start_request(&ids[1], request));
+    assert!(pending.remaining_events() > 2 * max_events);
+    let response = loop {
+        let remaining_events = pending.remaining_events();
+        match unwrap!(copy.continue_request(pending, max_events)) {
+            RequestProgress::Pending(next) => {
+                assert_eq!(next.remaining_events(), remaining_events - max_events);
+                pending = next;
+            }
+            RequestProgress::Done(response) => break response,
+        }
+    };
 
-    let mut unpolled_observations = alice.our_unpolled_observations();
-    assert_eq!(*unwrap!(unpolled_observations.next()), add_eric);
-    assert_eq!(*unwrap!(unpolled_observations.next()), vote);
-    assert!(unpolled_observations.next().is_none());
+    assert_eq!(response, expected_response);
+    assert_eq!(copy.graph(), alice.graph());
+    assert_eq!(
+        copy.poll_iter().collect_vec(),
+        alice.poll_iter().collect_vec()
+    );
 }
 
 #[test]
@@ -508,6 +2639,123 @@ fn gossip_after_fork() {
     assert!(bob.graph().contains(&a_2_fork_hash));
 }
 
+#[test]
+fn fork_duplicate_votes_counted_once() {
+    let mut common_rng = new_common_rng(SEED);
+    let genesis_ids = mock::create_ids(4).into_iter().collect::<BTreeSet<_>>();
+    let mut new_peer = |id: &PeerId| {
+        TestParsec::<Transaction, _>::from_genesis(
+            id.clone(),
+            &genesis_ids,
+            ConsensusMode::Supermajority,
+            Box::new(new_rng(&mut common_rng)),
+        )
+    };
+    let (alice_id, bob_id, carol_id, dave_id) =
+        unwrap!(genesis_ids.iter().cloned().collect_tuple());
+    // Two instances of Alice sharing her initial event, so that their later events form a fork.
+    let mut alice0 = new_peer(&alice_id);
+    let mut alice1 = new_peer(&alice_id);
+    let mut bob = new_peer(&bob_id);
+    let mut carol = new_peer(&carol_id);
+    let mut dave = new_peer(&dave_id);
+
+    let payload = Observation::OpaquePayload(Transaction::new("x"));
+    let payload_hash = ObservationHash::from(&payload);
+    let is_interesting_to_dave = |dave: &TestParsec<Transaction, PeerId>| {
+        dave.meta_election()
+            .meta_events()
+            .values()
+            .any(|meta_event| {
+                meta_event
+                    .interesting_content
+                    .iter()
+                    .any(|payload_key| *payload_key.hash() == payload_hash)
+            })
+    };
+
+    // Alice votes for the payload on both branches of the fork: on one after voting for another
+    // payload, on the other right after her initial event.  Bob votes for it too.
+    unwrap!(alice0.vote_for(Observation::OpaquePayload(Transaction::new("y"))));
+    unwrap!(alice0.vote_for(payload.clone()));
+    unwrap!(alice1.vote_for(payload.clone()));
+    unwrap!(bob.vote_for(payload.clone()));
+
+    // Dave learns the first branch through Bob, then the second one directly from Alice, so his
+    // latest event sees all three votes.
+    let request = unwrap!(alice0.create_gossip(&bob_id));
+    let response = unwrap!(bob.handle_request(&alice_id, request));
+    unwrap!(alice0.handle_response(&bob_id, response));
+    let request = unwrap!(bob.create_gossip(&dave_id));
+    let response = unwrap!(dave.handle_request(&bob_id, request));
+    unwrap!(bob.handle_response(&dave_id, response));
+    let request = unwrap!(alice1.create_gossip(&dave_id));
+    let _ = unwrap!(dave.handle_request(&alice_id, request));
+
+    // Three votes, but only two distinct voters out of four: not a supermajority.
+    let votes = dave
+        .graph()
+        .iter()
+        .filter(|event| dave.event_payload(event.inner()) == Some(&payload))
+        .collect_vec();
+    assert_eq!(votes.len(), 3);
+    let last_event = unwrap!(dave.graph().get(dave.our_last_event_index()));
+    assert!(votes
+        .iter()
+        .all(|vote| last_event.is_descendant_of(vote.inner())));
+    assert!(!is_interesting_to_dave(&dave));
+
+    // Once Carol's vote is seen as well, the payload becomes interesting.
+    unwrap!(carol.vote_for(payload));
+    let request = unwrap!(carol.create_gossip(&dave_id));
+    let _ = unwrap!(dave.handle_request(&carol_id, request));
+    assert!(is_interesting_to_dave(&dave));
+}
+
+#[test]
+fn redundant_response_creates_no_event() {
+    let mut common_rng = new_common_rng(SEED);
+    let genesis_ids = mock::create_ids(4).into_iter().collect::<BTreeSet<_>>();
+    let (mut alice, mut bob) = unwrap!(genesis_ids
+        .iter()
+        .take(2)
+        .map(|id| {
+            TestParsec::<Transaction, _>::from_genesis(
+                id.clone(),
+                &genesis_ids,
+                ConsensusMode::Supermajority,
+                Box::new(new_rng(&mut common_rng)),
+            )
+        })
+        .collect_tuple());
+    let alice_id = alice.our_pub_id().clone();
+    let bob_id = bob.our_pub_id().clone();
+    alice.set_event_creation_policy(EventCreationPolicy::SkipRedundantResponses);
+
+    // Alice gossips to Bob, but before his response arrives, she learns all of his events through
+    // a request from him.
+    let request = unwrap!(alice.create_gossip(&bob_id));
+    let response = unwrap!(bob.handle_request(&alice_id, request));
+    let request = unwrap!(bob.create_gossip(&alice_id));
+    let _ = unwrap!(alice.handle_request(&bob_id, request));
+
+    // The response doesn't teach Alice anything, so she doesn't create an event for it.
+    let alice_graph_len = alice.graph().len();
+    unwrap!(alice.handle_response(&bob_id, response));
+    assert_eq!(alice.graph().len(), alice_graph_len);
+
+    // With the default policy, a response event is always created.
+    let request = unwrap!(alice.create_gossip(&bob_id));
+    let response = unwrap!(bob.handle_request(&alice_id, request));
+    let request = unwrap!(bob.create_gossip(&alice_id));
+    let _ = unwrap!(alice.handle_request(&bob_id, request));
+    alice.set_event_creation_policy(EventCreationPolicy::Always);
+
+    let alice_graph_len = alice.graph().len();
+    unwrap!(alice.handle_response(&bob_id, response));
+    assert_eq!(alice.graph().len(), alice_graph_len + 1);
+}
+
 #[test]
 fn sees() {
     let mut common_rng = new_common_rng(SEED);
@@ -550,16 +2798,23 @@ fn sees() {
 mod handle_malice {
     use super::*;
     use crate::{
-        dev_utils::{parse_test_dot_file, ParsedContents},
+        dev_utils::{parse_dot_file_with_test_name, parse_test_dot_file, ParsedContents, Record},
         gossip::{Event, EventHash},
         id::SecretId,
         mock::{self, Transaction},
         network_event::NetworkEvent,
         observation::Malice,
+        parsec::AccusationDecision,
         peer_list::{PeerIndex, PeerList, PeerState},
         PackedEvent, Request, Response,
     };
     use itertools::Itertools;
+    use std::time::Instant;
+
+    // Parses one of the dot files of `basic_fork`, whose scenario several tests share.
+    fn parse_basic_fork_dot_file(filename: &str) -> ParsedContents {
+        parse_dot_file_with_test_name(filename, "functional_tests_handle_malice_basic_fork")
+    }
 
     fn take_packed_events<T: NetworkEvent, S: SecretId>(
         peer: &TestParsec<T, S>,
@@ -723,6 +2978,42 @@ mod handle_malice {
         assert_eq!(hash, e_1_hash);
     }
 
+    #[test]
+    fn invalid_opaque_payload() {
+        let (mut alice, bob, mut carol) =
+            unwrap!(initialise_genesis_parsecs(3).into_iter().collect_tuple());
+
+        // Create B_2 by Bob, voting for a payload which fails validation.
+        let b_0 = nth_event(bob.graph(), 0);
+        let b_1 = nth_event(bob.graph(), 1);
+        let b_2_packed = PackedEvent::new_observation(
+            bob.our_pub_id().clone(),
+            *b_1.hash(),
+            Observation::OpaquePayload(Transaction::new_invalid("ABCD")),
+        );
+
+        // Send Bob's message to Alice.  B_2 should be rejected as invalid.
+        let message = Request {
+            packed_events: vec![
+                unwrap!(b_0.pack(bob.event_context())),
+                unwrap!(b_1.pack(bob.event_context())),
+                b_2_packed.clone(),
+            ],
+        };
+        assert_eq!(
+            alice.handle_request(bob.our_pub_id(), message),
+            Err(Error::InvalidEvent)
+        );
+        assert!(alice.graph().contains(b_1.hash()));
+        assert!(!alice.graph().contains(&b_2_packed.compute_hash()));
+
+        // Alice should accuse Bob of spamming.
+        let spam = Malice::Unprovable(UnprovableMalice::Spam);
+        let request = unwrap!(carol.create_gossip(alice.our_pub_id()));
+        let _ = unwrap!(alice.handle_request(carol.our_pub_id(), request));
+        assert_peer_has_accused(&alice, vec![(bob.our_pub_id(), &spam)]);
+    }
+
     fn initialise_genesis_parsecs(count: usize) -> Vec<TestPeer> {
         let mut common_rng = new_common_rng(SEED);
         let genesis_ids = mock::create_ids(count).into_iter().collect::<BTreeSet<_>>();
@@ -1213,6 +3504,7 @@ mod handle_malice {
         accomplice_event_hash: EventHash,
         alice: TestPeer,
         bob: TestPeer,
+        carol: TestPeer,
         dave: TestPeer,
     }
 
@@ -1242,6 +3534,7 @@ mod handle_malice {
                 accomplice_event_hash,
                 alice,
                 bob,
+                carol,
                 dave,
             }
         }
@@ -1254,6 +3547,10 @@ mod handle_malice {
             self.bob.our_pub_id()
         }
 
+        fn carol_id(&self) -> &PeerId {
+            self.carol.our_pub_id()
+        }
+
         fn dave_id(&self) -> &PeerId {
             self.dave.our_pub_id()
         }
@@ -1296,6 +3593,23 @@ mod handle_malice {
         env.assert_dave_accused_alice_only();
     }
 
+    #[test]
+    // Alice has falsely accused Carol of creating a fork.  Carol will detect this when Alice
+    // gossips to her, and will counter-accuse Alice rather than being removed silently.
+    fn invalid_accusation_detected_by_accused_peer() {
+        let mut env = AccompliceEnvironment::new();
+        let alice_id = env.alice_id().clone();
+        let carol_id = env.carol_id().clone();
+
+        // Send gossip from Alice to Carol.
+        let message = unwrap!(env.alice.create_gossip(&carol_id));
+        unwrap!(env.carol.handle_request(&alice_id, message));
+
+        assert!(env.carol.graph().contains(&env.invalid_accusation_hash));
+        let expected_malice = Malice::InvalidAccusation(env.invalid_accusation_hash);
+        assert_peer_has_accused(&env.carol, vec![(&alice_id, &expected_malice)]);
+    }
+
     #[test]
     // Alice has falsely accused Carol of creating a fork.  Bob knows this, but as an accomplice,
     // hasn't accused Alice of `InvalidAccusation`.  Dave will detect this when Bob gossips to him.
@@ -1395,6 +3709,223 @@ mod handle_malice {
         assert_peer_has_accused(&dave, vec![(alice0.our_pub_id(), &expected_malice)]);
     }
 
+    #[test]
+    fn malice_detection_disabled() {
+        let mut common_rng = new_common_rng(SEED);
+        // Same scenario as `basic_fork`, but Dave has malice detection disabled.
+        let mut alice0 = TestParsec::from_parsed_contents(
+            parse_basic_fork_dot_file("alice.dot"),
+            Box::new(new_rng(&mut common_rng)),
+        );
+        let mut bob = TestParsec::from_parsed_contents(
+            parse_basic_fork_dot_file("bob.dot"),
+            Box::new(new_rng(&mut common_rng)),
+        );
+        let message0 = unwrap!(alice0.create_gossip(bob.our_pub_id()));
+        unwrap!(bob.handle_request(alice0.our_pub_id(), message0));
+
+        let mut alice1 = TestParsec::from_parsed_contents(
+            parse_basic_fork_dot_file("alice.dot"),
+            Box::new(new_rng(&mut common_rng)),
+        );
+        let mut dave = TestParsec::from_parsed_contents(
+            parse_basic_fork_dot_file("dave.dot"),
+            Box::new(new_rng(&mut common_rng)),
+        );
+        dave.set_malice_detection(false);
+        let message1 = unwrap!(alice1.create_gossip(dave.our_pub_id()));
+        unwrap!(dave.handle_request(alice1.our_pub_id(), message1));
+
+        // Dave accepts both sides of the fork without accusing Alice.
+        let bob_a_21_hash = *unwrap!(bob.graph().find_by_short_name("A_21")).hash();
+        let message = unwrap!(bob.create_gossip(dave.our_pub_id()));
+        unwrap!(dave.handle_request(bob.our_pub_id(), message));
+        assert!(dave.graph().contains(&bob_a_21_hash));
+        assert!(dave.pending_accusations().is_empty());
+        assert_peer_has_accused(&dave, vec![]);
+
+        // Structurally invalid events are still rejected, though without raising an accusation.
+        let (mut alice, bob, _carol) =
+            unwrap!(initialise_genesis_parsecs(3).into_iter().collect_tuple());
+        alice.set_malice_detection(false);
+        let b_0 = nth_event(bob.graph(), 0);
+        let b_1 = nth_event(bob.graph(), 1);
+        let b_2_packed =
+            PackedEvent::new_request(bob.our_pub_id().clone(), *b_1.hash(), *b_0.hash());
+        let packed_events = vec![
+            unwrap!(b_0.pack(bob.event_context())),
+            unwrap!(b_1.pack(bob.event_context())),
+            b_2_packed.clone(),
+        ];
+        let result = alice.handle_request(bob.our_pub_id(), Request { packed_events });
+        assert_eq!(result.map(|_| ()), Err(Error::InvalidEvent));
+        assert!(!alice.graph().contains(&b_2_packed.compute_hash()));
+        assert!(alice.pending_accusations().is_empty());
+    }
+
+    #[test]
+    fn accusation_hook() {
+        let mut common_rng = new_common_rng(SEED);
+        // Same scenario as `basic_fork`, but Dave's accusation hook vetoes the accusation.
+        let mut alice0 = TestParsec::from_parsed_contents(
+            parse_basic_fork_dot_file("alice.dot"),
+            Box::new(new_rng(&mut common_rng)),
+        );
+        let mut bob = TestParsec::from_parsed_contents(
+            parse_basic_fork_dot_file("bob.dot"),
+            Box::new(new_rng(&mut common_rng)),
+        );
+        let message0 = unwrap!(alice0.create_gossip(bob.our_pub_id()));
+        unwrap!(bob.handle_request(alice0.our_pub_id(), message0));
+
+        let mut alice1 = TestParsec::from_parsed_contents(
+            parse_basic_fork_dot_file("alice.dot"),
+            Box::new(new_rng(&mut common_rng)),
+        );
+        let mut dave = TestParsec::from_parsed_contents(
+            parse_basic_fork_dot_file("dave.dot"),
+            Box::new(new_rng(&mut common_rng)),
+        );
+        let vetoed = Rc::new(RefCell::new(Vec::new()));
+        let hook_vetoed = Rc::clone(&vetoed);
+        dave.set_accusation_hook(Box::new(move |offender, malice| {
+            hook_vetoed
+                .borrow_mut()
+                .push((offender.clone(), malice.clone()));
+            AccusationDecision::Veto
+        }));
+        let message1 = unwrap!(alice1.create_gossip(dave.our_pub_id()));
+        unwrap!(dave.handle_request(alice1.our_pub_id(), message1));
+
+        // Dave detects the fork, but doesn't accuse Alice of it.
+        let message = unwrap!(bob.create_gossip(dave.our_pub_id()));
+        unwrap!(dave.handle_request(bob.our_pub_id(), message));
+        let expected_malice = Malice::Fork(*unwrap!(bob.graph().find_by_short_name("A_20")).hash());
+        assert_eq!(
+            *vetoed.borrow(),
+            vec![(alice0.our_pub_id().clone(), expected_malice.clone())]
+        );
+        assert!(dave.pending_accusations().is_empty());
+        assert_peer_has_accused(&dave, vec![]);
+
+        // Approving lets the accusation through as usual.
+        let mut dave = TestParsec::from_parsed_contents(
+            parse_basic_fork_dot_file("dave.dot"),
+            Box::new(new_rng(&mut common_rng)),
+        );
+        dave.set_accusation_hook(Box::new(|_, _| AccusationDecision::Approve));
+        let message1 = unwrap!(alice1.create_gossip(dave.our_pub_id()));
+        unwrap!(dave.handle_request(alice1.our_pub_id(), message1));
+        let message = unwrap!(bob.create_gossip(dave.our_pub_id()));
+        unwrap!(dave.handle_request(bob.our_pub_id(), message));
+        assert_peer_has_accused(&dave, vec![(alice0.our_pub_id(), &expected_malice)]);
+    }
+
+    #[test]
+    #[ignore]
+    fn malice_detection_overhead() {
+        // Guards against the detection becoming disproportionately expensive as more kinds of
+        // malice get detected, e.g. by scanning the whole graph for every received event.  The
+        // benchmarks report the actual throughputs.
+        //
+        // This compares wall-clock times, which are too noisy on shared CI machines, so it only
+        // runs when asked for with `--ignored`, ideally in release mode.
+        const MAX_OVERHEAD: f64 = 3.0;
+        const NUM_RUNS: usize = 5;
+
+        let mut common_rng = new_common_rng(SEED);
+        let record = Record::generate(10, 64, &mut common_rng);
+        let fastest_play = |malice_detection: bool| {
+            (0..NUM_RUNS)
+                .map(|_| {
+                    let record = record.clone();
+                    let start = Instant::now();
+                    let _ =
+                        record.play_with(|parsec| parsec.set_malice_detection(malice_detection));
+                    start.elapsed()
+                })
+                .min()
+        };
+
+        let with_detection = unwrap!(fastest_play(true)).as_secs_f64();
+        let without_detection = unwrap!(fastest_play(false)).as_secs_f64();
+        assert!(
+            with_detection <= MAX_OVERHEAD * without_detection,
+            "Ingesting {} events took {:.3}s with malice detection, {:.3}s without",
+            record.num_received_events(),
+            with_detection,
+            without_detection
+        );
+    }
+
+    #[test]
+    fn event_by_index_returns_first_seen_fork() {
+        let mut common_rng = new_common_rng(SEED);
+        // Same scenario as `basic_fork`: Dave receives one A_21 from Alice, then the other one
+        // from Bob.
+        let mut alice0 = TestParsec::from_parsed_contents(
+            parse_basic_fork_dot_file("alice.dot"),
+            Box::new(new_rng(&mut common_rng)),
+        );
+        let mut bob = TestParsec::from_parsed_contents(
+            parse_basic_fork_dot_file("bob.dot"),
+            Box::new(new_rng(&mut common_rng)),
+        );
+        let message0 = unwrap!(alice0.create_gossip(bob.our_pub_id()));
+        unwrap!(bob.handle_request(alice0.our_pub_id(), message0));
+
+        let mut alice1 = TestParsec::from_parsed_contents(
+            parse_basic_fork_dot_file("alice.dot"),
+            Box::new(new_rng(&mut common_rng)),
+        );
+        let mut dave = TestParsec::from_parsed_contents(
+            parse_basic_fork_dot_file("dave.dot"),
+            Box::new(new_rng(&mut common_rng)),
+        );
+        let message1 = unwrap!(alice1.create_gossip(dave.our_pub_id()));
+        unwrap!(dave.handle_request(alice1.our_pub_id(), message1));
+
+        let alice_index = unwrap!(dave.peer_list().get_index(alice0.our_pub_id()));
+        let first_seen = unwrap!(dave.graph().find_by_short_name("A_21")).event_index();
+        let last_index_by_creator = unwrap!(dave.graph().get(first_seen)).index_by_creator();
+
+        // Out of bounds.
+        assert_eq!(
+            dave.peer_list()
+                .event_by_index(alice_index, last_index_by_creator + 1),
+            None
+        );
+
+        let message = unwrap!(bob.create_gossip(dave.our_pub_id()));
+        unwrap!(dave.handle_request(bob.our_pub_id(), message));
+
+        // Dave now holds both sides of the fork, but direct lookup still yields the one he
+        // received first.
+        assert_eq!(
+            dave.peer_list()
+                .events_by_index(alice_index, last_index_by_creator)
+                .count(),
+            2
+        );
+        assert_eq!(
+            dave.peer_list()
+                .event_by_index(alice_index, last_index_by_creator),
+            Some(first_seen)
+        );
+
+        // Every non-forked index-by-creator matches the events of that creator in order.
+        for event_index in dave.peer_list().peer_events(alice_index) {
+            let event = unwrap!(dave.graph().get(event_index));
+            if event.index_by_creator() < last_index_by_creator {
+                assert_eq!(
+                    dave.peer_list()
+                        .event_by_index(alice_index, event.index_by_creator()),
+                    Some(event_index)
+                );
+            }
+        }
+    }
+
     #[test]
     //             A_3,0     A_3,1
     //               |         |
@@ -1627,22 +4158,41 @@ mod handle_malice {
         OtherParent,
     }
 
-    fn invalid_parent_creator_test(test_type: InvalidCreatorFor) {
+    // The kind of gossip message carrying the invalid event.
+    #[derive(PartialEq)]
+    enum MessageType {
+        Request,
+        Response,
+    }
+
+    fn invalid_parent_creator_test(test_type: InvalidCreatorFor, message_type: MessageType) {
         let (mut alice, bob, mut carol) =
             unwrap!(initialise_genesis_parsecs(3).into_iter().collect_tuple());
 
-        // Create invalid B_2 by Bob - either a `Request` event with other-parent as his first event
-        // or an `Observation` with self-parent as Carol's second event.
+        // Create invalid B_2 by Bob.  When sent in a request, it's either a `Request` event with
+        // other-parent as his first event or an `Observation` with self-parent as Carol's second
+        // event.  When sent in a response, it's a `Response` event with either other-parent as his
+        // first event or self-parent as Carol's second event.
         let b_0 = nth_event(bob.graph(), 0);
         let b_1 = nth_event(bob.graph(), 1);
-        let b_2_packed = if test_type == InvalidCreatorFor::OtherParent {
-            PackedEvent::new_request(bob.our_pub_id().clone(), *b_1.hash(), *b_0.hash())
-        } else {
-            PackedEvent::new_observation(
+        let c_1 = nth_event(carol.graph(), 1);
+        let b_2_packed = match (&test_type, &message_type) {
+            (InvalidCreatorFor::OtherParent, MessageType::Request) => {
+                PackedEvent::new_request(bob.our_pub_id().clone(), *b_1.hash(), *b_0.hash())
+            }
+            (InvalidCreatorFor::SelfParent, MessageType::Request) => PackedEvent::new_observation(
                 bob.our_pub_id().clone(),
-                *nth_event(carol.graph(), 1).hash(),
+                *c_1.hash(),
                 Observation::OpaquePayload(Transaction::new("ABCD")),
-            )
+            ),
+            (InvalidCreatorFor::OtherParent, MessageType::Response) => {
+                PackedEvent::new_response(bob.our_pub_id().clone(), *b_1.hash(), *b_0.hash())
+            }
+            (InvalidCreatorFor::SelfParent, MessageType::Response) => PackedEvent::new_response(
+                bob.our_pub_id().clone(),
+                *c_1.hash(),
+                *nth_event(alice.graph(), 0).hash(),
+            ),
         };
 
         if test_type == InvalidCreatorFor::SelfParent {
@@ -1653,17 +4203,20 @@ mod handle_malice {
         }
 
         // Send Bob's message to Alice.  B_2 should be rejected as invalid.
-        let message = Request {
-            packed_events: vec![
-                unwrap!(b_0.pack(bob.event_context())),
-                unwrap!(b_1.pack(bob.event_context())),
-                b_2_packed.clone(),
-            ],
+        let packed_events = vec![
+            unwrap!(b_0.pack(bob.event_context())),
+            unwrap!(b_1.pack(bob.event_context())),
+            b_2_packed.clone(),
+        ];
+        let result = match message_type {
+            MessageType::Request => alice
+                .handle_request(bob.our_pub_id(), Request { packed_events })
+                .map(|_| ()),
+            MessageType::Response => {
+                alice.handle_response(bob.our_pub_id(), Response { packed_events })
+            }
         };
-        assert_eq!(
-            alice.handle_request(bob.our_pub_id(), message),
-            Err(Error::InvalidEvent)
-        );
+        assert_eq!(result, Err(Error::InvalidEvent));
 
         // Alice's graph shouldn't contain B_2.
         assert!(alice.graph().contains(b_0.hash()));
@@ -1692,12 +4245,22 @@ mod handle_malice {
 
     #[test]
     fn self_parent_by_different_creator() {
-        invalid_parent_creator_test(InvalidCreatorFor::SelfParent);
+        invalid_parent_creator_test(InvalidCreatorFor::SelfParent, MessageType::Request);
     }
 
     #[test]
     fn other_parent_by_same_creator() {
-        invalid_parent_creator_test(InvalidCreatorFor::OtherParent);
+        invalid_parent_creator_test(InvalidCreatorFor::OtherParent, MessageType::Request);
+    }
+
+    #[test]
+    fn self_parent_by_different_creator_in_response() {
+        invalid_parent_creator_test(InvalidCreatorFor::SelfParent, MessageType::Response);
+    }
+
+    #[test]
+    fn other_parent_by_same_creator_in_response() {
+        invalid_parent_creator_test(InvalidCreatorFor::OtherParent, MessageType::Response);
     }
 
     #[test]
@@ -1747,7 +4310,7 @@ mod handle_malice {
         assert!(alice
             .graph()
             .iter()
-            .all(|ev| !fred.graph().contains(ev.inner().hash())));
+            .all(|ev| !fred.graph().contains(ev.hash())));
 
         // Now Alice will prematurely gossip to Fred
         let request = unwrap!(alice.create_gossip(&fred_id));
@@ -1760,7 +4323,7 @@ mod handle_malice {
         assert!(alice
             .graph()
             .iter()
-            .all(|ev| fred.graph().contains(ev.inner().hash())));
+            .all(|ev| fred.graph().contains(ev.hash())));
     }
 
     #[test]
@@ -24,7 +24,7 @@ use crate::{
 #[cfg(any(all(test, feature = "mock"), feature = "testing"))]
 use crate::{
     mock::{PeerId, Transaction},
-    observation::{ConsensusMode, ObservationStore},
+    observation::{ConsensusMode, ConsensusModes, ObservationStore},
 };
 use serde::{Deserialize, Serialize};
 
@@ -47,6 +47,34 @@ pub(crate) enum Cause<V, E, P> {
     Initial,
 }
 
+impl<V, E, P> Cause<V, E, P> {
+    pub(crate) fn kind(&self) -> CauseKind {
+        match self {
+            Cause::Requesting { .. } => CauseKind::Requesting,
+            Cause::Request { .. } => CauseKind::Request,
+            Cause::Response { .. } => CauseKind::Response,
+            Cause::Observation { .. } => CauseKind::Observation,
+            Cause::Initial => CauseKind::Initial,
+        }
+    }
+}
+
+/// The reason an event was created for, as returned by
+/// [PackedEvent::cause_kind](struct.PackedEvent.html#method.cause_kind).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CauseKind {
+    /// Recording the creator's intent to send a request to another peer.
+    Requesting,
+    /// Recording receipt of a request from another peer.
+    Request,
+    /// Recording receipt of a response from another peer.
+    Response,
+    /// Casting a vote for an observation.
+    Observation,
+    /// The creator's first event.
+    Initial,
+}
+
 impl<P: PublicId> Cause<VoteKey<P>, EventIndex, PeerIndex> {
     pub(crate) fn unpack<T: NetworkEvent, S: SecretId<PublicId = P>>(
         packed_cause: Cause<Vote<T, P>, EventHash, P>,
@@ -87,7 +115,7 @@ impl<P: PublicId> Cause<VoteKey<P>, EventIndex, PeerIndex> {
             Cause::Observation { self_parent, vote } => {
                 let self_parent = self_parent_index(ctx.graph, &self_parent)?;
 
-                let (vote_key, observation) = VoteKey::new(vote, creator, ctx.consensus_mode);
+                let (vote_key, observation) = VoteKey::new(vote, creator, ctx.consensus_modes);
                 let payload_key = *vote_key.payload_key();
 
                 (
@@ -209,7 +237,8 @@ impl Cause<VoteKey<PeerId>, EventIndex, PeerIndex> {
                 other_parent,
             },
             Cause::Observation { vote, .. } => {
-                let (vote_key, observation) = VoteKey::new(vote, creator, consensus_mode);
+                let (vote_key, observation) =
+                    VoteKey::new(vote, creator, &ConsensusModes::new(consensus_mode));
                 let _ = observations
                     .entry(*vote_key.payload_key())
                     .or_insert_with(|| ObservationInfo::new(observation));
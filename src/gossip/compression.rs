@@ -0,0 +1,69 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Wire format of compressed gossip messages: a flag byte telling how the rest is encoded, followed
+//! by the bincode serialisation of the message, either as is or deflated.
+//!
+//! Compression only applies to the bytes sent over the wire.  The messages, and so the events and
+//! their hashes, are exactly the same once decoded.
+
+use crate::{
+    error::{Error, Result},
+    serialise,
+};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fmt::Debug,
+    io::{Read, Write},
+    iter,
+};
+
+const UNCOMPRESSED: u8 = 0;
+const DEFLATE: u8 = 1;
+
+/// Largest size a deflated message may expand to.  This is well above the size of the requests
+/// sent to catch a peer up, but keeps a small, maliciously crafted message from expanding into
+/// gigabytes.
+pub(super) const MAX_DECOMPRESSED_LEN: u64 = 32 * 1024 * 1024;
+
+// Messages are only sent deflated if that makes them smaller, which typically isn't the case for
+// the few events of a regular gossip exchange.
+pub(super) fn encode<M: Serialize + Debug>(message: &M) -> Vec<u8> {
+    let serialised = serialise(message);
+    let mut encoder = DeflateEncoder::new(vec![DEFLATE], Compression::default());
+    match encoder
+        .write_all(&serialised)
+        .and_then(|()| encoder.finish())
+    {
+        Ok(compressed) if compressed.len() <= serialised.len() => compressed,
+        _ => iter::once(UNCOMPRESSED).chain(serialised).collect(),
+    }
+}
+
+pub(super) fn decode<M: DeserializeOwned>(bytes: &[u8]) -> Result<M> {
+    let (flag, payload) = bytes.split_first().ok_or(Error::InvalidMessage)?;
+    let message = match *flag {
+        UNCOMPRESSED => bincode::deserialize(payload),
+        DEFLATE => bincode::deserialize(&inflate(payload)?),
+        _ => return Err(Error::InvalidMessage),
+    };
+    message.map_err(|_| Error::InvalidMessage)
+}
+
+fn inflate(payload: &[u8]) -> Result<Vec<u8>> {
+    let mut inflated = vec![];
+    let _ = DeflateDecoder::new(payload)
+        .take(MAX_DECOMPRESSED_LEN + 1)
+        .read_to_end(&mut inflated)
+        .map_err(|_| Error::InvalidMessage)?;
+    if inflated.len() as u64 > MAX_DECOMPRESSED_LEN {
+        return Err(Error::InvalidMessage);
+    }
+    Ok(inflated)
+}
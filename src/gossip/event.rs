@@ -7,9 +7,10 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{
-    cause::{self, Cause},
+    cause::{self, Cause, CauseKind},
     content::Content,
     event_context::EventContextRef,
+    event_descriptor::EventDescriptor,
     event_hash::EventHash,
     event_utils::{compute_ancestor_info, AncestorInfo, IndexSet},
     graph::{EventIndex, Graph},
@@ -32,8 +33,12 @@ use crate::{
     vote::{Vote, VoteKey},
 };
 use itertools::Itertools;
-use std::fmt::{self, Debug, Display, Formatter};
+use std::{
+    fmt::{self, Debug, Display, Formatter},
+    mem::size_of,
+};
 
+#[derive(Clone)]
 pub(crate) struct Event<P: PublicId> {
     content: Content<VoteKey<P>, EventIndex, PeerIndex>,
     // Creator's signature of `content`.
@@ -198,6 +203,9 @@ impl<P: PublicId> Event<P> {
 
     // Creates an event from a `PackedEvent`.
     //
+    // The packed content carries no index-by-creator; it is always derived from the self-parent
+    // (see `compute_index_by_creator`), so it can't be misreported by the sender.
+    //
     // Returns:
     //   - `Ok(None)` if the event already exists
     //   - `Err(Error::SignatureFailure)` if signature validation fails
@@ -207,11 +215,26 @@ impl<P: PublicId> Event<P> {
         packed_event: PackedEvent<T, P>,
         ctx: EventContextRef<T, S>,
     ) -> Result<Option<UnpackedEvent<T, P>>, Error> {
-        let hash = compute_event_hash_and_verify_signature(
-            &packed_event.content,
-            &packed_event.signature,
-        )?;
+        let hash = packed_event.compute_hash_and_verify_signature()?;
+        Self::unpack_with_hash(packed_event, hash, ctx)
+    }
+
+    // Same as `unpack`, except that the signature isn't verified, so `Error::SignatureFailure` is
+    // never returned.  Anyone able to feed events through this can impersonate any peer: it must
+    // only be used for events received from fully trusted peers.
+    pub fn unpack_trusted<T: NetworkEvent, S: SecretId<PublicId = P>>(
+        packed_event: PackedEvent<T, P>,
+        ctx: EventContextRef<T, S>,
+    ) -> Result<Option<UnpackedEvent<T, P>>, Error> {
+        let hash = packed_event.compute_hash();
+        Self::unpack_with_hash(packed_event, hash, ctx)
+    }
 
+    fn unpack_with_hash<T: NetworkEvent, S: SecretId<PublicId = P>>(
+        packed_event: PackedEvent<T, P>,
+        hash: EventHash,
+        ctx: EventContextRef<T, S>,
+    ) -> Result<Option<UnpackedEvent<T, P>>, Error> {
         if ctx.graph.contains(&hash) {
             return Ok(None);
         }
@@ -311,6 +334,17 @@ impl<P: PublicId> Event<P> {
         }
     }
 
+    // The observation this event votes for, if any, looked up in `observations`.
+    #[cfg(any(test, feature = "testing", feature = "malice-detection"))]
+    pub fn payload<'a, T: NetworkEvent>(
+        &self,
+        observations: &'a ObservationStore<T, P>,
+    ) -> Option<&'a Observation<T, P>> {
+        self.payload_key()
+            .and_then(|key| observations.get(key))
+            .map(|info| &info.observation)
+    }
+
     pub fn vote_and_payload_key<T: NetworkEvent>(
         &self,
         observations: &ObservationStore<T, P>,
@@ -342,7 +376,9 @@ impl<P: PublicId> Event<P> {
         &self.cache.hash
     }
 
-    // Index of this event relative to other events by the same creator.
+    // Index of this event relative to other events by the same creator. The initial event has
+    // index zero and every other event has its self-parent's index plus one. Indices are never
+    // rebased, so they can be compared against the `last_ancestors` of any other event.
     pub fn index_by_creator(&self) -> usize {
         self.cache.index_by_creator
     }
@@ -371,10 +407,20 @@ impl<P: PublicId> Event<P> {
         &self.cache.ancestor_info
     }
 
+    // Rough number of bytes taken by an event when `num_peers` peers are known: its fixed part
+    // plus the ancestor info it caches for each of them.
+    pub fn estimated_size(num_peers: usize) -> usize {
+        size_of::<Self>() + num_peers * size_of::<AncestorInfo>()
+    }
+
+    pub fn cause_kind(&self) -> CauseKind {
+        self.content.cause.kind()
+    }
+
     pub fn is_sync_event(&self) -> bool {
-        match self.content.cause {
-            Cause::Requesting { .. } | Cause::Request { .. } | Cause::Response { .. } => true,
-            Cause::Initial | Cause::Observation { .. } => false,
+        match self.cause_kind() {
+            CauseKind::Requesting | CauseKind::Request | CauseKind::Response => true,
+            CauseKind::Initial | CauseKind::Observation => false,
         }
     }
 
@@ -384,11 +430,7 @@ impl<P: PublicId> Event<P> {
         all(test, feature = "mock")
     ))]
     pub fn is_requesting(&self) -> bool {
-        if let Cause::Requesting { .. } = self.content.cause {
-            true
-        } else {
-            false
-        }
+        self.cause_kind() == CauseKind::Requesting
     }
 
     #[cfg(feature = "malice-detection")]
@@ -400,36 +442,42 @@ impl<P: PublicId> Event<P> {
         }
     }
 
-    #[cfg(any(
-        feature = "testing",
-        feature = "malice-detection",
-        all(test, feature = "mock")
-    ))]
+    #[cfg(any(test, feature = "testing", feature = "malice-detection"))]
     pub fn is_request(&self) -> bool {
-        if let Cause::Request { .. } = self.content.cause {
-            true
-        } else {
-            false
-        }
+        self.cause_kind() == CauseKind::Request
     }
 
     #[cfg(any(test, feature = "testing", feature = "malice-detection"))]
     pub fn is_response(&self) -> bool {
-        if let Cause::Response { .. } = self.content.cause {
-            true
-        } else {
-            false
-        }
+        self.cause_kind() == CauseKind::Response
     }
 
     pub fn is_initial(&self) -> bool {
-        if let Cause::Initial = self.content.cause {
-            true
-        } else {
-            false
+        self.cause_kind() == CauseKind::Initial
+    }
+
+    /// Returns the creator's full ID, followed by a dash and the event's index, e.g. "Alice-14".
+    /// Unlike `short_name`, this doesn't depend on the other peers' IDs, so it names the same event
+    /// on every peer, e.g. for correlating their logs.  Forks share the same name.
+    pub fn qualified_name<S: SecretId<PublicId = P>>(&self, peer_list: &PeerList<S>) -> String {
+        match peer_list.get(self.creator()) {
+            Some(peer) => format!("{:?}-{}", peer.id(), self.index_by_creator()),
+            None => format!("???-{}", self.index_by_creator()),
         }
     }
 
+    /// Returns the descriptor of this event, or `None` if its creator isn't in `peer_list`.
+    pub fn descriptor<S: SecretId<PublicId = P>>(
+        &self,
+        peer_list: &PeerList<S>,
+    ) -> Option<EventDescriptor<P>> {
+        peer_list.get(self.creator()).map(|peer| EventDescriptor {
+            hash: *self.hash(),
+            creator: peer.id().clone(),
+            index: self.index_by_creator(),
+        })
+    }
+
     /// Returns the first char of the creator's ID, followed by an underscore and the event's index.
     #[cfg(any(test, feature = "testing"))]
     pub fn short_name(&self) -> ShortName {
@@ -594,6 +642,7 @@ pub(crate) enum CauseInput {
 }
 
 // Properties of `Event` that can be computed from its `Content`.
+#[derive(Clone)]
 struct Cache {
     // Hash of `Event`s `Content`.
     hash: EventHash,
@@ -664,21 +713,6 @@ fn compute_event_hash_and_signature<T: NetworkEvent, S: SecretId>(
     (hash, signature)
 }
 
-fn compute_event_hash_and_verify_signature<T: NetworkEvent, P: PublicId>(
-    content: &Content<Vote<T, P>, EventHash, P>,
-    signature: &P::Signature,
-) -> Result<EventHash, Error> {
-    let serialised_content = serialise(content);
-    if content
-        .creator
-        .verify_signature(signature, &serialised_content)
-    {
-        Ok(EventHash(Hash::from(serialised_content.as_slice())))
-    } else {
-        Err(Error::SignatureFailure)
-    }
-}
-
 #[cfg(any(test, feature = "testing"))]
 fn get_creator_initial<S: SecretId>(peer_list: &PeerList<S>, creator: PeerIndex) -> char {
     peer_list
@@ -777,12 +811,24 @@ mod tests {
     fn event_construction_initial() {
         let initial = create_event_with_single_peer("Alice").1;
         assert!(initial.is_initial());
+        assert!(!initial.is_request());
         assert!(!initial.is_response());
+        assert_eq!(initial.cause_kind(), CauseKind::Initial);
         assert!(initial.self_parent().is_none());
         assert!(initial.other_parent().is_none());
         assert_eq!(initial.index_by_creator(), 0);
     }
 
+    #[test]
+    fn qualified_name() {
+        let (alice, a_0, bob, b_0) = create_two_events("Alice", "Bob");
+        let a_0 = convert_event(&a_0, alice.as_ref(), bob.as_ref());
+        assert_eq!(a_0.qualified_name(&bob.peer_list), "Alice-0");
+        assert_eq!(b_0.qualified_name(&bob.peer_list), "Bob-0");
+        let (carol, _) = create_event_with_single_peer("Carol");
+        assert_eq!(a_0.qualified_name(&carol.peer_list), "???-0");
+    }
+
     #[test]
     fn event_construction_from_observation() {
         let (mut alice, a_0) = create_event_with_single_peer("Alice");
@@ -800,6 +846,13 @@ mod tests {
 
         let (key, observation_info) = unwrap!(observation_for_store);
         let _ = alice.observations.insert(key, observation_info);
+        assert_eq!(
+            event_from_observation.payload(&alice.observations),
+            Some(&net_event)
+        );
+        assert!(unwrap!(alice.graph.get(initial_event_index))
+            .payload(&alice.observations)
+            .is_none());
 
         let packed_event_from_observation = unwrap!(event_from_observation.pack(alice.as_ref()));
 
@@ -819,7 +872,9 @@ mod tests {
         }
         assert_eq!(event_from_observation.index_by_creator(), 1);
         assert!(!event_from_observation.is_initial());
+        assert!(!event_from_observation.is_request());
         assert!(!event_from_observation.is_response());
+        assert_eq!(event_from_observation.cause_kind(), CauseKind::Observation);
         assert_eq!(
             event_from_observation.self_parent(),
             Some(initial_event_index)
@@ -862,7 +917,9 @@ mod tests {
         );
         assert_eq!(event_from_request.index_by_creator(), 1);
         assert!(!event_from_request.is_initial());
+        assert!(event_from_request.is_request());
         assert!(!event_from_request.is_response());
+        assert_eq!(event_from_request.cause_kind(), CauseKind::Request);
         assert_eq!(event_from_request.self_parent(), Some(a_0_index));
         assert_eq!(event_from_request.other_parent(), Some(b_0_index));
     }
@@ -912,7 +969,9 @@ mod tests {
         );
         assert_eq!(event_from_response.index_by_creator(), 1);
         assert!(!event_from_response.is_initial());
+        assert!(!event_from_response.is_request());
         assert!(event_from_response.is_response());
+        assert_eq!(event_from_response.cause_kind(), CauseKind::Response);
         assert_eq!(event_from_response.self_parent(), Some(a_0_index));
         assert_eq!(event_from_response.other_parent(), Some(b_0_index));
     }
@@ -946,6 +1005,36 @@ mod tests {
         assert!(unwrap!(Event::unpack(packed_event, alice.as_ref())).is_none());
     }
 
+    #[test]
+    fn event_construction_unpack_derives_index_by_creator() {
+        let (mut alice, a_0) = create_event_with_single_peer("Alice");
+        let mut self_parent = alice.graph.insert(a_0).event_index();
+
+        // `PackedEvent` doesn't carry an index-by-creator, so there is no claimed value a sender
+        // could lie about: it is always recomputed from the self-parent on unpacking.
+        for (expected_index_by_creator, payload) in (1..).zip(&["one", "two", "three"]) {
+            let (event, observation_for_store) = unwrap!(Event::new_from_observation(
+                self_parent,
+                Observation::OpaquePayload(Transaction::new(*payload)),
+                alice.as_ref()
+            ));
+            let (key, observation_info) = unwrap!(observation_for_store);
+            let _ = alice.observations.insert(key, observation_info);
+
+            let packed_event = unwrap!(event.pack(alice.as_ref()));
+            let unpacked_event =
+                unwrap!(unwrap!(Event::unpack(packed_event, alice.as_ref()))).event;
+            assert_eq!(unpacked_event.self_parent(), Some(self_parent));
+            assert_eq!(
+                unpacked_event.index_by_creator(),
+                unwrap!(alice.graph.get(self_parent)).index_by_creator() + 1
+            );
+            assert_eq!(unpacked_event.index_by_creator(), expected_index_by_creator);
+
+            self_parent = alice.graph.insert(unpacked_event).event_index();
+        }
+    }
+
     #[test]
     fn event_construction_unpack_fail_with_wrong_signature() {
         let (mut alice, a_0) = create_event_with_single_peer("Alice");
@@ -972,4 +1061,31 @@ mod tests {
             panic!("Expected SignatureFailure, but got {:?}", error);
         }
     }
+
+    #[test]
+    fn event_construction_unpack_trusted_skips_signature_check() {
+        let (mut alice, a_0) = create_event_with_single_peer("Alice");
+        let a_0_index = alice.graph.insert(a_0).event_index();
+
+        let net_event = Observation::OpaquePayload(Transaction::new("event_observed_by_alice"));
+        let (event_from_observation, observation_for_store) = unwrap!(Event::new_from_observation(
+            a_0_index,
+            net_event,
+            alice.as_ref()
+        ));
+        let (key, observation_info) = unwrap!(observation_for_store);
+        let _ = alice.observations.insert(key, observation_info);
+
+        let mut packed_event = unwrap!(event_from_observation.pack(alice.as_ref()));
+        packed_event.signature = alice.peer_list.our_id().sign_detached(&[123]);
+
+        // The hash only covers the content, so it's unaffected by the invalid signature.
+        let unpacked_event =
+            unwrap!(unwrap!(Event::unpack_trusted(packed_event, alice.as_ref()))).event;
+        assert_eq!(unpacked_event.hash(), event_from_observation.hash());
+        assert_eq!(
+            unpacked_event.index_by_creator(),
+            event_from_observation.index_by_creator()
+        );
+    }
 }
@@ -12,7 +12,7 @@ use super::graph::Graph;
 use crate::{
     id::SecretId,
     network_event::NetworkEvent,
-    observation::{ConsensusMode, ObservationStore},
+    observation::{ConsensusModes, ObservationStore},
     peer_list::PeerList,
 };
 
@@ -20,7 +20,7 @@ pub(crate) struct EventContextRef<'a, T: NetworkEvent, S: SecretId> {
     pub(crate) graph: &'a Graph<S::PublicId>,
     pub(crate) peer_list: &'a PeerList<S>,
     pub(crate) observations: &'a ObservationStore<T, S::PublicId>,
-    pub(crate) consensus_mode: ConsensusMode,
+    pub(crate) consensus_modes: &'a ConsensusModes,
 }
 
 // `#[derive(Clone)]` doesn't work here for some reason...
@@ -30,7 +30,7 @@ impl<'a, T: NetworkEvent, S: SecretId> Clone for EventContextRef<'a, T, S> {
             graph: self.graph,
             peer_list: self.peer_list,
             observations: self.observations,
-            consensus_mode: self.consensus_mode,
+            consensus_modes: self.consensus_modes,
         }
     }
 }
@@ -41,13 +41,16 @@ impl<'a, T: NetworkEvent, S: SecretId> Copy for EventContextRef<'a, T, S> {}
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::mock::{PeerId, Transaction};
+    use crate::{
+        mock::{PeerId, Transaction},
+        observation::ConsensusMode,
+    };
 
     pub(crate) struct EventContext {
         pub graph: Graph<PeerId>,
         pub peer_list: PeerList<PeerId>,
         pub observations: ObservationStore<Transaction, PeerId>,
-        pub consensus_mode: ConsensusMode,
+        pub consensus_modes: ConsensusModes,
     }
 
     impl EventContext {
@@ -58,7 +61,7 @@ mod tests {
                 graph: Graph::new(),
                 peer_list,
                 observations: ObservationStore::new(),
-                consensus_mode: ConsensusMode::Supermajority,
+                consensus_modes: ConsensusModes::new(ConsensusMode::Supermajority),
             }
         }
 
@@ -67,7 +70,7 @@ mod tests {
                 graph: &self.graph,
                 peer_list: &self.peer_list,
                 observations: &self.observations,
-                consensus_mode: self.consensus_mode,
+                consensus_modes: &self.consensus_modes,
             }
         }
     }
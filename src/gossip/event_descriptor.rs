@@ -0,0 +1,27 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::event_hash::EventHash;
+use crate::id::PublicId;
+
+/// Identifies an event without carrying its contents.
+///
+/// Peers can exchange the descriptors of the events they hold to find out which ones the other
+/// side is missing, then only send those in full.  See
+/// [Parsec::event_descriptors](struct.Parsec.html#method.event_descriptors).
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, Debug)]
+#[serde(bound = "")]
+pub struct EventDescriptor<P: PublicId> {
+    /// Hash of the event.
+    pub hash: EventHash,
+    /// Public ID of the event's creator.
+    pub creator: P,
+    /// Index of the event among its creator's events, the initial event having index zero.
+    /// Forks share the same index.
+    pub index: usize,
+}
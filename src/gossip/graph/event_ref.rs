@@ -7,7 +7,7 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{
-    super::{abstract_event::AbstractEventRef, event::Event},
+    super::{abstract_event::AbstractEventRef, event::Event, event_hash::EventHash},
     event_index::EventIndex,
 };
 use crate::{id::PublicId, observation::ObservationKey, peer_list::PeerIndex};
@@ -40,6 +40,12 @@ impl<'a, P: PublicId> IndexedEventRef<'a, P> {
     pub fn inner(&self) -> &'a Event<P> {
         self.event
     }
+
+    /// Returns the hash of the event. Unlike `Deref::deref().hash()`, the result borrows from the
+    /// graph rather than from this reference.
+    pub fn hash(&self) -> &'a EventHash {
+        self.event.hash()
+    }
 }
 
 impl<'a, P: PublicId> Deref for IndexedEventRef<'a, P> {
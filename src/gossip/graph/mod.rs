@@ -15,7 +15,7 @@ pub(crate) use self::ancestors::Ancestors;
 pub(crate) use self::{event_index::EventIndex, event_ref::IndexedEventRef};
 
 use super::{event::Event, event_hash::EventHash};
-use crate::id::PublicId;
+use crate::{id::PublicId, peer_list::PeerIndexMap};
 #[cfg(feature = "malice-detection")]
 use fnv::FnvHashSet;
 use std::collections::btree_map::{BTreeMap, Entry};
@@ -23,10 +23,17 @@ use std::collections::btree_map::{BTreeMap, Entry};
 use std::collections::BTreeSet;
 
 /// The gossip graph.
-#[derive(Eq, PartialEq, Debug)]
+///
+/// Events are stored in a flat vector in the order they were inserted, which is always a valid
+/// topological order (an event is only inserted after both its parents). `len()` and `is_empty()`
+/// are O(1) as they only query the length of that vector, and `iter()` yields `IndexedEventRef`s
+/// in that same topological order.
+#[derive(Clone, Eq, PartialEq, Debug)]
 pub(crate) struct Graph<P: PublicId> {
     events: Vec<Event<P>>,
     indices: BTreeMap<EventHash, EventIndex>,
+    /// Number of events in `events` created by each peer.
+    event_counts: PeerIndexMap<usize>,
     /// Indices of `Requesting` events with no associated descendant `Request`, and `Request`s with
     /// no associated descendant `Response`.
     #[cfg(feature = "malice-detection")]
@@ -38,6 +45,7 @@ impl<P: PublicId> Default for Graph<P> {
         Self {
             events: Vec::new(),
             indices: BTreeMap::new(),
+            event_counts: PeerIndexMap::new(),
             #[cfg(feature = "malice-detection")]
             awaiting_associated_events: FnvHashSet::default(),
         }
@@ -49,6 +57,12 @@ impl<P: PublicId> Graph<P> {
         Self::default()
     }
 
+    /// Returns the number of events in the graph created by each peer, forks included.  Peers
+    /// with no events in the graph are absent.
+    pub fn event_count_by_creator(&self) -> &PeerIndexMap<usize> {
+        &self.event_counts
+    }
+
     /// Get index of an event with the given hash.
     pub fn get_index(&self, hash: &EventHash) -> Option<EventIndex> {
         self.indices.get(hash).cloned()
@@ -71,6 +85,13 @@ impl<P: PublicId> Graph<P> {
     /// If the event is a `Request` or `Response`, the other_parent is removed from
     /// `awaiting_associated_events`.
     pub fn insert(&mut self, event: Event<P>) -> IndexedEventRef<P> {
+        self.debug_check_index_by_creator(&event);
+        debug_assert!(
+            !self.last_ancestors_regress(&event),
+            "Last ancestors of {:?} regress from its self-parent's",
+            event
+        );
+
         let index = match self.indices.entry(*event.hash()) {
             Entry::Occupied(entry) => *entry.get(),
             Entry::Vacant(entry) => {
@@ -79,6 +100,10 @@ impl<P: PublicId> Graph<P> {
                 #[cfg(any(test, feature = "testing"))]
                 assert_ne!(index, EventIndex::PHONY);
 
+                *self
+                    .event_counts
+                    .entry(event.creator())
+                    .or_insert_with(|| 0) += 1;
                 self.events.push(event);
                 let _ = entry.insert(index);
 
@@ -95,6 +120,58 @@ impl<P: PublicId> Graph<P> {
         }
     }
 
+    // Indices by creator are never rebased: they start at zero with the initial event and increase
+    // by exactly one from self-parent to self-child.  Comparisons against `last_ancestors` rely on
+    // this, so catch any inconsistency as early as possible in debug builds.  An event whose
+    // self-parent was created by someone else is malicious and is handled by malice detection.
+    fn debug_check_index_by_creator(&self, event: &Event<P>) {
+        let expected_index_by_creator = match event.self_parent() {
+            None => 0,
+            Some(self_parent) => match self.get(self_parent) {
+                Some(self_parent) if self_parent.creator() == event.creator() => {
+                    self_parent.index_by_creator() + 1
+                }
+                _ => return,
+            },
+        };
+        debug_assert_eq!(
+            event.index_by_creator(),
+            expected_index_by_creator,
+            "Index by creator of {:?} inconsistent with its self-parent's",
+            event
+        );
+    }
+
+    // Does the `last_ancestors` of `event` fall behind its self-parent's for any peer?  It never
+    // should, as it's the entry-by-entry maximum of its parents' plus the event itself: the `sees`
+    // and `strongly_sees` relations rely on it.
+    fn last_ancestors_regress(&self, event: &Event<P>) -> bool {
+        let self_parent = match event.self_parent().and_then(|index| self.get(index)) {
+            Some(self_parent) => self_parent,
+            None => return false,
+        };
+        self_parent
+            .inner()
+            .last_ancestors()
+            .any(|(peer_index, last)| event.last_ancestor_by(peer_index) < Some(last))
+    }
+
+    /// Checks the invariants which are otherwise only debug-asserted on insertion, e.g. after
+    /// restoring a graph from a dump.  Returns the hash of the first event violating one.
+    #[cfg(any(
+        all(test, feature = "mock"),
+        all(feature = "testing", feature = "dump-graphs")
+    ))]
+    pub fn validate(&self) -> Result<(), EventHash> {
+        match self
+            .iter()
+            .find(|event| self.last_ancestors_regress(event.inner()))
+        {
+            Some(event) => Err(*event.hash()),
+            None => Ok(()),
+        }
+    }
+
     /// Gets `Event` with the given `index`, if it exists.
     pub fn get(&self, index: EventIndex) -> Option<IndexedEventRef<P>> {
         self.events
@@ -108,12 +185,42 @@ impl<P: PublicId> Graph<P> {
         self.get_index(hash).and_then(|index| self.get(index))
     }
 
-    /// Number of events in this graph.
+    /// Number of events in this graph. This is O(1).
     pub fn len(&self) -> usize {
         self.events.len()
     }
 
-    /// Iterator over all events in this graph. Yields `IndexedEventRef`s.
+    /// Returns, for each event of this graph by topological index, whether it is one of `events`
+    /// or an ancestor of one of them. This is O(n).
+    pub fn ancestry_mask<I: IntoIterator<Item = EventIndex>>(&self, events: I) -> Vec<bool> {
+        let mut mask = vec![false; self.events.len()];
+        for index in events {
+            mask[index.0] = true;
+        }
+        // Children come after their parents, so a single pass in reverse order suffices.
+        for (position, event) in self.events.iter().enumerate().rev() {
+            if mask[position] {
+                for parent in event.self_parent().into_iter().chain(event.other_parent()) {
+                    mask[parent.0] = true;
+                }
+            }
+        }
+        mask
+    }
+
+    /// Returns `true` if this graph contains no events. This is O(1).
+    #[cfg(all(test, feature = "mock"))]
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Iterator over all events in this graph in topological (insertion) order. Yields
+    /// `IndexedEventRef`s.
+    ///
+    /// Every event is guaranteed to be yielded after both its parents, since events refer to their
+    /// parents by index and so can only be inserted after them.  The order is deterministic for a
+    /// given sequence of insertions, including the sides of a fork, but may differ between peers
+    /// which received the same events in different orders.
     pub fn iter(&self) -> Iter<P> {
         self.iter_from(0)
     }
@@ -276,6 +383,10 @@ impl<P: PublicId> Graph<P> {
         }
         let event = self.events.pop()?;
         let _ = self.indices.remove(event.hash());
+        *self
+            .event_counts
+            .entry(event.creator())
+            .or_insert_with(|| 0) -= 1;
         Some((index, event))
     }
 }
@@ -369,12 +480,25 @@ impl<'a, P: PublicId> Iterator for Iter<'a, P> {
 #[cfg(any(all(test, feature = "mock"), feature = "dump-graphs"))]
 pub(crate) mod snapshot {
     use super::*;
-    use std::collections::BTreeSet;
+    #[cfg(all(test, feature = "mock"))]
+    use crate::gossip::event::ShortName;
+    #[cfg(all(test, feature = "mock"))]
+    use std::fmt::Display;
+    use std::{
+        collections::BTreeSet,
+        fmt::{self, Debug, Formatter},
+    };
 
     /// Snapshot of the graph. Two snapshots compare as equal if the graphs had the same events
     /// modulo their insertion order.
-    #[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
-    pub(crate) struct GraphSnapshot(pub BTreeSet<EventHash>);
+    #[derive(Serialize, Deserialize)]
+    pub(crate) struct GraphSnapshot {
+        events: BTreeSet<EventHash>,
+        // Only used to make `diff` readable, so neither compared nor serialised.
+        #[cfg(all(test, feature = "mock"))]
+        #[serde(skip)]
+        short_names: BTreeMap<EventHash, ShortName>,
+    }
 
     impl GraphSnapshot {
         pub fn new<P: PublicId>(graph: &Graph<P>) -> Self {
@@ -383,20 +507,140 @@ pub(crate) mod snapshot {
 
         /// Generate a snapshot without the last `ignore_last_events` events
         pub fn new_with_ignore<P: PublicId>(graph: &Graph<P>, ignore_last_events: usize) -> Self {
-            GraphSnapshot(
-                graph
-                    .iter()
-                    .map(|event| *event.hash())
-                    .take(graph.len() - ignore_last_events)
+            let events = || graph.iter().take(graph.len() - ignore_last_events);
+            GraphSnapshot {
+                events: events().map(|event| *event.hash()).collect(),
+                #[cfg(all(test, feature = "mock"))]
+                short_names: events()
+                    .map(|event| (*event.hash(), event.short_name()))
                     .collect(),
-            )
+            }
+        }
+
+        /// Describes how `self` and `other` differ.  Events are labelled by their short names
+        /// where known (i.e. not for snapshots deserialised from a dump), else by their hashes.
+        #[cfg(all(test, feature = "mock"))]
+        pub fn diff(&self, other: &Self) -> GraphDiff {
+            let only_in_self = self.labels_of(self.events.difference(&other.events));
+            let only_in_other = other.labels_of(other.events.difference(&self.events));
+
+            // Events with the same short name but different hashes are the same creator's event at
+            // the same position but with different contents, i.e. they were forked or corrupted.
+            let differing: BTreeSet<_> = only_in_self
+                .keys()
+                .filter(|label| only_in_other.contains_key(*label))
+                .cloned()
+                .collect();
+
+            let without_differing = |labels: BTreeMap<String, EventHash>| {
+                labels
+                    .into_iter()
+                    .filter(|(label, _)| !differing.contains(label))
+                    .map(|(label, _)| label)
+                    .collect()
+            };
+
+            GraphDiff {
+                only_in_self: without_differing(only_in_self),
+                only_in_other: without_differing(only_in_other),
+                differing: differing.into_iter().collect(),
+            }
+        }
+
+        #[cfg(all(test, feature = "mock"))]
+        fn labels_of<'a, I>(&self, hashes: I) -> BTreeMap<String, EventHash>
+        where
+            I: Iterator<Item = &'a EventHash>,
+        {
+            hashes
+                .map(|hash| {
+                    let label = self
+                        .short_names
+                        .get(hash)
+                        .map(ShortName::to_string)
+                        .unwrap_or_else(|| format!("{:?}", hash));
+                    (label, *hash)
+                })
+                .collect()
+        }
+    }
+
+    impl PartialEq for GraphSnapshot {
+        fn eq(&self, other: &Self) -> bool {
+            self.events == other.events
+        }
+    }
+
+    impl Eq for GraphSnapshot {}
+
+    impl Debug for GraphSnapshot {
+        fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+            formatter
+                .debug_tuple("GraphSnapshot")
+                .field(&self.events)
+                .finish()
+        }
+    }
+
+    /// Difference between two `GraphSnapshot`s, as returned by `GraphSnapshot::diff`.
+    #[cfg(all(test, feature = "mock"))]
+    #[derive(Debug, Default, PartialEq, Eq)]
+    pub(crate) struct GraphDiff {
+        /// Events only in the first snapshot.
+        pub only_in_self: Vec<String>,
+        /// Events only in the second snapshot.
+        pub only_in_other: Vec<String>,
+        /// Short names of the events in both snapshots, but with different contents.
+        pub differing: Vec<String>,
+    }
+
+    #[cfg(all(test, feature = "mock"))]
+    impl GraphDiff {
+        pub fn is_empty(&self) -> bool {
+            self.only_in_self.is_empty()
+                && self.only_in_other.is_empty()
+                && self.differing.is_empty()
+        }
+    }
+
+    #[cfg(all(test, feature = "mock"))]
+    impl Display for GraphDiff {
+        fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+            if self.is_empty() {
+                return write!(formatter, "no differences");
+            }
+            let sections = [
+                ("only in first", &self.only_in_self),
+                ("only in second", &self.only_in_other),
+                ("differing", &self.differing),
+            ];
+            for (heading, labels) in sections.iter().filter(|(_, labels)| !labels.is_empty()) {
+                writeln!(formatter, "{}: {}", heading, labels.join(", "))?;
+            }
+            Ok(())
         }
     }
 }
 
 #[cfg(all(test, feature = "testing"))]
 mod tests {
-    use crate::dev_utils::parse_test_dot_file;
+    use super::{snapshot::GraphSnapshot, Graph};
+    use crate::{
+        dev_utils::{parse_dot_file_with_test_name, parse_test_dot_file, ParsedContents},
+        gossip::{
+            event::{CauseInput, Event},
+            event_context::EventContext,
+            event_utils::AncestorInfo,
+        },
+        mock::{PeerId, Transaction},
+        observation::{ConsensusMode, Observation},
+    };
+    use std::collections::BTreeSet;
+
+    // Parses the dot file of `ancestors_iterator`, which the other tests reuse.
+    fn parse_carol_dot_file() -> ParsedContents {
+        parse_dot_file_with_test_name("carol.dot", "gossip_graph_tests_ancestors_iterator")
+    }
 
     #[test]
     fn ancestors_iterator() {
@@ -432,4 +676,128 @@ mod tests {
 
         assert_eq!(actual_indices, sorted_indices);
     }
+
+    #[test]
+    fn event_ref_accessors_borrow_from_graph() {
+        let contents = parse_carol_dot_file();
+        let graph = contents.graph;
+
+        // The hashes borrow from the graph rather than from the temporary `IndexedEventRef`s.
+        let hashes: Vec<_> = graph.iter().map(|event| event.hash()).collect();
+        for (event, hash) in graph.iter().zip(hashes) {
+            assert_eq!(hash, event.inner().hash());
+            assert_eq!(event.cause_kind(), event.inner().cause_kind());
+            assert_eq!(event.short_name(), event.inner().short_name());
+        }
+    }
+
+    #[test]
+    fn index_by_creator_follows_self_parent() {
+        let contents = parse_carol_dot_file();
+        let graph = contents.graph;
+
+        for event in &graph {
+            let expected = event
+                .self_parent()
+                .and_then(|index| graph.get(index))
+                .map_or(0, |self_parent| self_parent.index_by_creator() + 1);
+            assert_eq!(event.index_by_creator(), expected);
+        }
+    }
+
+    #[test]
+    fn last_ancestors_never_regress() {
+        let mut contents = parse_carol_dot_file();
+        assert_eq!(contents.graph.validate(), Ok(()));
+
+        // An event whose ancestry was computed from a stale copy of its self-parent's.
+        let self_parent = unwrap!(contents.graph.find_by_short_name("C_8"));
+        let stale_info = self_parent
+            .ancestor_info()
+            .iter()
+            .map(|(peer_index, info)| {
+                let info = AncestorInfo {
+                    last: 0,
+                    forks: info.forks.clone(),
+                };
+                (peer_index, info)
+            })
+            .collect();
+        let self_parent = (self_parent.event_index(), *self_parent.hash(), stale_info);
+        let index_by_creator =
+            unwrap!(contents.graph.find_by_short_name("C_8")).index_by_creator() + 1;
+        let event = Event::new_from_dot_input(
+            &PeerId::new("Carol"),
+            CauseInput::Observation(Observation::OpaquePayload(Transaction::new("stale"))),
+            Some(self_parent),
+            None,
+            index_by_creator,
+            ConsensusMode::Supermajority,
+            &mut contents.peer_list,
+            &mut contents.observations,
+        );
+        assert!(contents.graph.last_ancestors_regress(&event));
+    }
+
+    #[test]
+    fn iter_yields_parents_first() {
+        let contents = parse_carol_dot_file();
+        let graph = contents.graph;
+
+        let mut yielded = vec![false; graph.len()];
+        for event in &graph {
+            for parent in event.self_parent().into_iter().chain(event.other_parent()) {
+                assert!(yielded[parent.topological_index()]);
+            }
+            yielded[event.topological_index()] = true;
+        }
+        assert!(yielded.into_iter().all(|yielded| yielded));
+    }
+
+    #[test]
+    fn snapshot_diff() {
+        let graph = parse_carol_dot_file().graph;
+        let full = GraphSnapshot::new(&graph);
+        let partial = GraphSnapshot::new_with_ignore(&graph, 2);
+
+        assert!(full.diff(&GraphSnapshot::new(&graph)).is_empty());
+
+        let last_two = graph
+            .iter_from(graph.len() - 2)
+            .map(|event| event.short_name().to_string())
+            .collect::<BTreeSet<_>>();
+        let diff = full.diff(&partial);
+        assert_eq!(
+            diff.only_in_self.iter().cloned().collect::<BTreeSet<_>>(),
+            last_two
+        );
+        assert!(diff.only_in_other.is_empty());
+        assert!(diff.differing.is_empty());
+
+        let diff = partial.diff(&full);
+        assert!(diff.only_in_self.is_empty());
+        assert_eq!(diff.only_in_other.len(), 2);
+
+        // Two graphs where Alice's second event votes for different payloads.
+        let new_graph = |payload: &str| {
+            let mut context = EventContext::new(PeerId::new("Alice"));
+            let initial = Event::new_initial(context.as_ref());
+            let initial_index = context.graph.insert(initial).event_index();
+            let (event, _) = unwrap!(Event::new_from_observation(
+                initial_index,
+                Observation::OpaquePayload(Transaction::new(payload)),
+                context.as_ref(),
+            ));
+            let _ = context.graph.insert(event);
+            context.graph
+        };
+        let graph_0: Graph<PeerId> = new_graph("ABCD");
+        let graph_1 = new_graph("EFGH");
+
+        let diff = GraphSnapshot::new(&graph_0).diff(&GraphSnapshot::new(&graph_1));
+        assert!(diff.only_in_self.is_empty());
+        assert!(diff.only_in_other.is_empty());
+        assert_eq!(diff.differing, vec!["A_1".to_string()]);
+        assert_eq!(diff.to_string(), "differing: A_1\n");
+    }
 }
@@ -6,11 +6,26 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::{gossip::packed_event::PackedEvent, id::PublicId, network_event::NetworkEvent};
+#[cfg(feature = "compression")]
+use super::compression;
+#[cfg(feature = "compression")]
+use crate::error::Result;
+use crate::{
+    gossip::{event_hash::EventHash, packed_event::PackedEvent},
+    id::PublicId,
+    network_event::NetworkEvent,
+    peer_list::PeerIndex,
+};
+use itertools::Itertools;
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Debug, Formatter},
+    vec,
+};
 
 /// A gossip request message.
 #[serde(bound = "")]
-#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Request<T: NetworkEvent, P: PublicId> {
     pub(crate) packed_events: Vec<PackedEvent<T, P>>,
 }
@@ -19,11 +34,43 @@ impl<T: NetworkEvent, P: PublicId> Request<T, P> {
     pub(crate) fn new(packed_events: Vec<PackedEvent<T, P>>) -> Self {
         Self { packed_events }
     }
+
+    /// Returns a `Debug` representation listing every packed event, as opposed to the summary
+    /// printed by `Debug` for `Request` itself.
+    pub fn full_debug(&self) -> impl Debug + '_ {
+        &self.packed_events
+    }
+}
+
+#[cfg(feature = "compression")]
+impl<T: NetworkEvent, P: PublicId> Request<T, P> {
+    /// Serialises this request, deflating it if that makes it smaller.  This is worth it for the
+    /// large requests sent while a peer catches up, as the events carry a lot of repeated data,
+    /// e.g. the IDs of their creators.
+    ///
+    /// The result starts with a flag byte telling whether the rest is compressed, and is to be
+    /// parsed by `from_compressed_bytes`.  Compression is transport-only: the decoded request
+    /// and the hashes of its events are the same as those of the original.
+    pub fn to_compressed_bytes(&self) -> Vec<u8> {
+        compression::encode(self)
+    }
+
+    /// Parses a request serialised by `to_compressed_bytes`, whether or not it ended up
+    /// compressed.
+    pub fn from_compressed_bytes(bytes: &[u8]) -> Result<Self> {
+        compression::decode(bytes)
+    }
+}
+
+impl<T: NetworkEvent, P: PublicId> Debug for Request<T, P> {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        fmt_summary("Request", &self.packed_events, formatter)
+    }
 }
 
 /// A gossip response message.
 #[serde(bound = "")]
-#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Response<T: NetworkEvent, P: PublicId> {
     pub(crate) packed_events: Vec<PackedEvent<T, P>>,
 }
@@ -32,4 +79,205 @@ impl<T: NetworkEvent, P: PublicId> Response<T, P> {
     pub(crate) fn new(packed_events: Vec<PackedEvent<T, P>>) -> Self {
         Self { packed_events }
     }
+
+    /// Returns a `Debug` representation listing every packed event, as opposed to the summary
+    /// printed by `Debug` for `Response` itself.
+    pub fn full_debug(&self) -> impl Debug + '_ {
+        &self.packed_events
+    }
+}
+
+#[cfg(feature = "compression")]
+impl<T: NetworkEvent, P: PublicId> Response<T, P> {
+    /// Serialises this response, deflating it if that makes it smaller.  See
+    /// [Request::to_compressed_bytes](struct.Request.html#method.to_compressed_bytes).
+    pub fn to_compressed_bytes(&self) -> Vec<u8> {
+        compression::encode(self)
+    }
+
+    /// Parses a response serialised by `to_compressed_bytes`, whether or not it ended up
+    /// compressed.
+    pub fn from_compressed_bytes(bytes: &[u8]) -> Result<Self> {
+        compression::decode(bytes)
+    }
+}
+
+impl<T: NetworkEvent, P: PublicId> Debug for Response<T, P> {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        fmt_summary("Response", &self.packed_events, formatter)
+    }
+}
+
+/// The hash of the last event a peer holds of each creator.  It is sent instead of the events
+/// themselves to pull the ones the recipient has and the sender is missing.  See
+/// [Parsec::create_have_summary](struct.Parsec.html#method.create_have_summary).
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Debug)]
+#[serde(bound = "")]
+pub struct HaveSummary<P: PublicId> {
+    pub(crate) last_events: BTreeMap<P, EventHash>,
+}
+
+impl<P: PublicId> HaveSummary<P> {
+    pub(crate) fn new(last_events: BTreeMap<P, EventHash>) -> Self {
+        Self { last_events }
+    }
+}
+
+/// A `Request` which has been partially handled.  See
+/// [Parsec::start_request](struct.Parsec.html#method.start_request).
+pub struct PendingRequest<T: NetworkEvent, P: PublicId> {
+    pub(crate) src_index: PeerIndex,
+    pub(crate) packed_events: vec::IntoIter<PackedEvent<T, P>>,
+    pub(crate) hash_of_last_event: EventHash,
+}
+
+impl<T: NetworkEvent, P: PublicId> PendingRequest<T, P> {
+    /// Returns the number of events of the request which haven't been handled yet.
+    pub fn remaining_events(&self) -> usize {
+        self.packed_events.len()
+    }
+}
+
+impl<T: NetworkEvent, P: PublicId> Debug for PendingRequest<T, P> {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "PendingRequest{{ {} events left }}",
+            self.remaining_events()
+        )
+    }
+}
+
+/// The outcome of a step of handling a `Request`.  See
+/// [Parsec::continue_request](struct.Parsec.html#method.continue_request).
+#[derive(Debug)]
+pub enum RequestProgress<T: NetworkEvent, P: PublicId> {
+    /// Some events of the request haven't been handled yet.  Handling resumes by passing the
+    /// contained value to `continue_request` again.
+    Pending(PendingRequest<T, P>),
+    /// The request has been fully handled; the contained `Response` is to be sent back.
+    Done(Response<T, P>),
+}
+
+// Writes e.g. `Request{ 12 events from [Alice, Bob] }`.  Packed events don't carry their index by
+// creator, so only the count and the creators are summarised.
+fn fmt_summary<T: NetworkEvent, P: PublicId>(
+    name: &str,
+    packed_events: &[PackedEvent<T, P>],
+    formatter: &mut Formatter,
+) -> fmt::Result {
+    let creators = packed_events
+        .iter()
+        .map(|packed_event| &packed_event.content.creator)
+        .sorted()
+        .dedup()
+        .collect_vec();
+    write!(
+        formatter,
+        "{}{{ {} events from {:?} }}",
+        name,
+        packed_events.len(),
+        creators
+    )
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{gossip::EventHash, mock::PeerId};
+    #[cfg(feature = "compression")]
+    use crate::{mock::Transaction, serialise};
+    #[cfg(feature = "compression")]
+    use std::iter;
+
+    #[test]
+    fn debug_summary() {
+        let alice = PeerId::new("Alice");
+        let bob = PeerId::new("Bob");
+        let packed_events = vec![
+            PackedEvent::new_initial(bob.clone()),
+            PackedEvent::new_initial(alice.clone()),
+            PackedEvent::new_requesting(alice, bob, EventHash::ZERO),
+        ];
+        let request = Request::new(packed_events.clone());
+        let response = Response::new(packed_events);
+
+        assert_eq!(
+            format!("{:?}", request),
+            "Request{ 3 events from [Alice, Bob] }"
+        );
+        assert_eq!(
+            format!("{:?}", response),
+            "Response{ 3 events from [Alice, Bob] }"
+        );
+        assert_eq!(
+            format!("{:?}", request.full_debug()),
+            format!("{:?}", request.packed_events)
+        );
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn compressed_bytes() {
+        use crate::error::Error;
+
+        let peer_ids = ["Alice", "Bob", "Carol", "Dave"]
+            .iter()
+            .map(|name| PeerId::new(name))
+            .collect_vec();
+        let packed_events = peer_ids
+            .iter()
+            .cycle()
+            .zip(peer_ids.iter().cycle().skip(1))
+            .take(1000)
+            .map(|(creator, recipient)| {
+                PackedEvent::new_requesting(creator.clone(), recipient.clone(), EventHash::ZERO)
+            })
+            .collect_vec();
+
+        let request = Request::new(packed_events.clone());
+        let bytes = request.to_compressed_bytes();
+        assert_eq!(bytes[0], 1);
+        assert!(bytes.len() < serialise(&request).len() / 2);
+        assert_eq!(unwrap!(Request::from_compressed_bytes(&bytes)), request);
+
+        // Messages which don't compress well are sent as they are, which is flagged by a zero.
+        let response = Response::new(packed_events);
+        let bytes = iter::once(0).chain(serialise(&response)).collect_vec();
+        assert_eq!(unwrap!(Response::from_compressed_bytes(&bytes)), response);
+
+        assert_eq!(
+            Request::<Transaction, PeerId>::from_compressed_bytes(&[]),
+            Err(Error::InvalidMessage)
+        );
+        let mut bytes = request.to_compressed_bytes();
+        bytes[0] = 2;
+        assert_eq!(
+            Request::<Transaction, PeerId>::from_compressed_bytes(&bytes),
+            Err(Error::InvalidMessage)
+        );
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn oversized_compressed_bytes() {
+        use crate::error::Error;
+        use flate2::{write::DeflateEncoder, Compression};
+        use std::io::Write;
+
+        // A few kilobytes which would inflate to just over the limit.
+        let mut encoder = DeflateEncoder::new(vec![1], Compression::best());
+        let zeros = [0; 1024 * 1024];
+        for _ in 0..compression::MAX_DECOMPRESSED_LEN / zeros.len() as u64 {
+            unwrap!(encoder.write_all(&zeros));
+        }
+        unwrap!(encoder.write_all(&[0]));
+        let bytes = unwrap!(encoder.finish());
+        assert!(bytes.len() < 64 * 1024);
+
+        assert_eq!(
+            Request::<Transaction, PeerId>::from_compressed_bytes(&bytes),
+            Err(Error::InvalidMessage)
+        );
+    }
 }
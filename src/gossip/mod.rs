@@ -8,9 +8,12 @@
 
 mod abstract_event;
 mod cause;
+#[cfg(feature = "compression")]
+mod compression;
 mod content;
 mod event;
 mod event_context;
+mod event_descriptor;
 mod event_hash;
 mod event_utils;
 mod graph;
@@ -30,7 +33,9 @@ pub(super) use self::{
     graph::{EventIndex, Graph, IndexedEventRef},
 };
 pub use self::{
+    cause::CauseKind,
+    event_descriptor::EventDescriptor,
     event_hash::EventHash,
-    messages::{Request, Response},
+    messages::{HaveSummary, PendingRequest, Request, RequestProgress, Response},
     packed_event::PackedEvent,
 };
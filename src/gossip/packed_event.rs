@@ -6,18 +6,25 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{content::Content, event_hash::EventHash};
+use super::{
+    cause::{Cause, CauseKind},
+    content::Content,
+    event_hash::EventHash,
+};
+use crate::{
+    error::Error, hash::Hash, observation::Observation, serialise, NetworkEvent, PublicId, Vote,
+};
 #[cfg(all(feature = "mock", any(feature = "testing", test)))]
 use crate::{
-    gossip::Cause,
     id::SecretId,
     mock::{PeerId, Transaction},
-    observation::Observation,
 };
-use crate::{hash::Hash, serialise, NetworkEvent, PublicId, Vote};
 use std::fmt::{self, Debug, Formatter};
 
 /// Packed event contains only content and signature.
+///
+/// In particular it doesn't carry the event's index-by-creator: the receiver derives that from the
+/// self-parent, so a sender can't claim an index inconsistent with it.
 #[serde(bound = "")]
 #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct PackedEvent<T: NetworkEvent, P: PublicId> {
@@ -42,6 +49,59 @@ impl<T: NetworkEvent, P: PublicId> PackedEvent<T, P> {
     pub(crate) fn compute_hash(&self) -> EventHash {
         EventHash(Hash::from(serialise(&self.content).as_slice()))
     }
+
+    // Same as `compute_hash`, but fails with `Error::SignatureFailure` unless the event is signed
+    // by its creator.
+    pub(crate) fn compute_hash_and_verify_signature(&self) -> Result<EventHash, Error> {
+        let serialised_content = serialise(&self.content);
+        if self
+            .content
+            .creator
+            .verify_signature(&self.signature, &serialised_content)
+        {
+            Ok(EventHash(Hash::from(serialised_content.as_slice())))
+        } else {
+            Err(Error::SignatureFailure)
+        }
+    }
+
+    /// Getter for the event's creator.
+    pub fn creator(&self) -> &P {
+        &self.content.creator
+    }
+
+    /// Getter for the event's self-parent.
+    pub fn self_parent(&self) -> Option<&EventHash> {
+        self.content.self_parent()
+    }
+
+    /// Getter for the event's other-parent.
+    pub fn other_parent(&self) -> Option<&EventHash> {
+        self.content.other_parent()
+    }
+
+    /// Getter for the reason the event was created for.
+    pub fn cause_kind(&self) -> CauseKind {
+        self.content.cause.kind()
+    }
+
+    /// Getter for the observation the event votes for, if it is an `Observation` event.
+    pub fn payload(&self) -> Option<&Observation<T, P>> {
+        if let Cause::Observation { ref vote, .. } = self.content.cause {
+            Some(vote.payload())
+        } else {
+            None
+        }
+    }
+
+    // The recipient of a `Requesting` event, `None` for any other cause.
+    pub(crate) fn recipient(&self) -> Option<&P> {
+        if let Cause::Requesting { ref recipient, .. } = self.content.cause {
+            Some(recipient)
+        } else {
+            None
+        }
+    }
 }
 
 #[cfg(all(feature = "mock", any(feature = "testing", test)))]
@@ -105,24 +165,48 @@ impl PackedEvent<Transaction, PeerId> {
         Self::new(content)
     }
 
+    /// Replaces the signature with a corrupted one (see
+    /// [Signature::corrupt](mock/struct.Signature.html#method.corrupt)), so that the event gets
+    /// rejected on receipt.
+    pub fn corrupt_signature(&mut self) {
+        self.signature = self.signature.corrupt();
+    }
+
     fn new(content: Content<Vote<Transaction, PeerId>, EventHash, PeerId>) -> Self {
         let serialised_content = serialise(&content);
         let signature = content.creator.sign_detached(&serialised_content);
         PackedEvent { content, signature }
     }
+}
 
-    /// Getter for the event's creator.
-    pub fn creator(&self) -> &PeerId {
-        &self.content.creator
-    }
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
 
-    /// Getter for the event's self-parent.
-    pub fn self_parent(&self) -> Option<&EventHash> {
-        self.content.self_parent()
-    }
+    #[test]
+    fn accessors() {
+        let alice = PeerId::new("Alice");
+        let bob = PeerId::new("Bob");
 
-    /// Getter for the event's self-parent.
-    pub fn other_parent(&self) -> Option<&EventHash> {
-        self.content.other_parent()
+        let a_0 = PackedEvent::new_initial(alice.clone());
+        assert_eq!(a_0.creator(), &alice);
+        assert_eq!(a_0.cause_kind(), CauseKind::Initial);
+        assert_eq!(a_0.self_parent(), None);
+        assert_eq!(a_0.other_parent(), None);
+        assert_eq!(a_0.payload(), None);
+
+        let observation = Observation::OpaquePayload(Transaction::new("ABCD"));
+        let a_1 = PackedEvent::new_observation(alice, a_0.compute_hash(), observation.clone());
+        assert_eq!(a_1.cause_kind(), CauseKind::Observation);
+        assert_eq!(a_1.self_parent(), Some(&a_0.compute_hash()));
+        assert_eq!(a_1.payload(), Some(&observation));
+
+        let b_0 = PackedEvent::new_initial(bob.clone());
+        let b_1 = PackedEvent::new_request(bob.clone(), b_0.compute_hash(), a_1.compute_hash());
+        assert_eq!(b_1.creator(), &bob);
+        assert_eq!(b_1.cause_kind(), CauseKind::Request);
+        assert_eq!(b_1.self_parent(), Some(&b_0.compute_hash()));
+        assert_eq!(b_1.other_parent(), Some(&a_1.compute_hash()));
+        assert_eq!(b_1.payload(), None);
     }
 }
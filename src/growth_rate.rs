@@ -0,0 +1,154 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use std::{
+    collections::VecDeque,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+/// Source of the current time for the time-based instrumentation of `Parsec`.
+///
+/// Consensus itself never depends on the time; this only needs to be provided to make the
+/// instrumentation deterministic, e.g. in simulations.  Defaults to `SystemClock`.
+pub trait Clock {
+    /// Returns the current time.  Consecutive calls must never go backwards.
+    fn now(&self) -> Instant;
+}
+
+/// `Clock` reading the system's monotonic clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+// Tracks how many events were added to the gossip graph within a sliding window of time.
+#[derive(Clone)]
+pub(crate) struct GrowthRate {
+    clock: Rc<dyn Clock>,
+    window: Duration,
+    // Insertion times of the events added within the last `window`, oldest first.
+    insertions: VecDeque<Instant>,
+}
+
+impl GrowthRate {
+    pub const DEFAULT_WINDOW: Duration = Duration::from_secs(10);
+
+    pub fn new() -> Self {
+        Self {
+            clock: Rc::new(SystemClock),
+            window: Self::DEFAULT_WINDOW,
+            insertions: VecDeque::new(),
+        }
+    }
+
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.clock = Rc::from(clock);
+        // Times read from different clocks can't be compared.
+        self.insertions.clear();
+    }
+
+    // Forgets the events recorded so far, keeping the clock and window.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn reset(&mut self) {
+        self.insertions.clear();
+    }
+
+    pub fn set_window(&mut self, window: Duration) {
+        self.window = window;
+        self.prune(self.clock.now());
+    }
+
+    pub fn record_event(&mut self) {
+        let now = self.clock.now();
+        self.prune(now);
+        self.insertions.push_back(now);
+    }
+
+    // Events added per second, averaged over the window.
+    pub fn events_per_second(&self) -> f64 {
+        let window = self.window.as_secs_f64();
+        if window == 0.0 {
+            return 0.0;
+        }
+        let now = self.clock.now();
+        let count = self
+            .insertions
+            .iter()
+            .rev()
+            .take_while(|&&time| !self.is_stale(time, now))
+            .count();
+        count as f64 / window
+    }
+
+    fn prune(&mut self, now: Instant) {
+        while let Some(&oldest) = self.insertions.front() {
+            if !self.is_stale(oldest, now) {
+                break;
+            }
+            let _ = self.insertions.pop_front();
+        }
+    }
+
+    fn is_stale(&self, time: Instant, now: Instant) -> bool {
+        now.duration_since(time) >= self.window
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[derive(Clone)]
+    struct ManualClock(Rc<Cell<Instant>>);
+
+    impl ManualClock {
+        fn advance(&self, duration: Duration) {
+            self.0.set(self.0.get() + duration);
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            self.0.get()
+        }
+    }
+
+    #[test]
+    fn events_per_second_only_counts_events_within_window() {
+        let clock = ManualClock(Rc::new(Cell::new(Instant::now())));
+        let mut rate = GrowthRate::new();
+        rate.set_clock(Box::new(clock.clone()));
+        rate.set_window(Duration::from_secs(4));
+        assert_eq!(rate.events_per_second(), 0.0);
+
+        for _ in 0..4 {
+            rate.record_event();
+        }
+        clock.advance(Duration::from_secs(2));
+        for _ in 0..4 {
+            rate.record_event();
+        }
+        assert_eq!(rate.events_per_second(), 2.0);
+
+        // The first four events fall out of the window, even without any new event being added.
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(rate.events_per_second(), 1.0);
+
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(rate.events_per_second(), 0.0);
+
+        rate.record_event();
+        assert_eq!(rate.insertions.len(), 1);
+    }
+}
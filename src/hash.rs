@@ -6,7 +6,7 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use std::fmt::{self, Debug, Formatter};
+use std::fmt::{self, Debug, Display, Formatter};
 
 pub const HASH_LEN: usize = 32;
 
@@ -26,6 +26,12 @@ impl Hash {
     pub fn as_bytes(&self) -> &[u8; HASH_LEN] {
         &self.0
     }
+
+    /// Returns a value displaying only the first few bytes of this hash, which is enough to tell
+    /// hashes apart in logs.
+    pub fn short_display(&self) -> ShortDisplay<'_> {
+        ShortDisplay(self)
+    }
 }
 
 impl<'a> From<&'a [u8]> for Hash {
@@ -37,11 +43,7 @@ impl<'a> From<&'a [u8]> for Hash {
 impl Debug for Hash {
     #[cfg(any(test, feature = "testing", feature = "dump-graphs"))]
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
-        write!(
-            formatter,
-            "{:02x}{:02x}{:02x}{:02x}{:02x}..",
-            self.0[0], self.0[1], self.0[2], self.0[3], self.0[4]
-        )
+        write!(formatter, "{}", self.short_display())
     }
 
     #[cfg(not(any(test, feature = "testing", feature = "dump-graphs")))]
@@ -50,10 +52,22 @@ impl Debug for Hash {
     }
 }
 
+pub struct ShortDisplay<'a>(&'a Hash);
+
+impl<'a> Display for ShortDisplay<'a> {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        let bytes = &(self.0).0;
+        write!(
+            formatter,
+            "{:02x}{:02x}{:02x}{:02x}{:02x}..",
+            bytes[0], bytes[1], bytes[2], bytes[3], bytes[4]
+        )
+    }
+}
+
 #[cfg(any(feature = "dump-graphs", not(any(test, feature = "testing"))))]
 mod full {
     use super::*;
-    use std::fmt::Display;
 
     #[cfg(feature = "dump-graphs")]
     impl Hash {
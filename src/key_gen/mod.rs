@@ -173,7 +173,7 @@ impl Debug for Ack {
 }
 
 /// The information needed to track a single proposer's secret sharing process.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 struct ProposalState {
     /// The proposer's commitment.
     commit: BivarCommitment,
@@ -245,7 +245,7 @@ pub enum AckOutcome {
 ///
 /// It requires that all nodes handle all messages in the exact same order.
 #[cfg_attr(feature = "dump-graphs", derive(Serialize))]
-#[derive(Deserialize)]
+#[derive(Clone, Deserialize)]
 pub struct KeyGen<S: SecretId> {
     /// Our node ID.
     our_id: S::PublicId,
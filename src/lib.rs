@@ -185,16 +185,20 @@ macro_rules! log_or_panic {
 pub mod dev_utils;
 
 mod block;
+mod consensus_chain;
 mod dump_graph;
 mod error;
 mod gossip;
+mod growth_rate;
 mod hash;
 mod id;
 mod key_gen;
+mod light_client;
 mod meta_voting;
 mod network_event;
 mod observation;
 mod parsec;
+mod parsec_builder;
 mod parsec_helpers;
 mod peer_list;
 mod vote;
@@ -212,16 +216,32 @@ mod functional_tests;
 pub mod mock;
 
 #[cfg(feature = "dump-graphs")]
-pub use crate::dump_graph::{DumpGraphMode, DIR, DUMP_MODE};
+pub use crate::dump_graph::{DumpGraphMode, DIR, DUMP_CORE, DUMP_MODE};
+#[cfg(feature = "malice-detection")]
+pub use crate::parsec::{AccusationDecision, AccusationHook};
 pub use crate::{
     block::Block,
+    consensus_chain::{ChainHash, ConsensusChain, ConsensusLink},
     error::{Error, Result},
-    gossip::{EventHash, PackedEvent, Request, Response},
+    gossip::{
+        CauseKind, EventDescriptor, EventHash, HaveSummary, PackedEvent, PendingRequest, Request,
+        RequestProgress, Response,
+    },
+    growth_rate::{Clock, SystemClock},
     id::{Proof, PublicId, SecretId},
     key_gen::dkg_result::*,
+    light_client::LightClient,
     network_event::NetworkEvent,
-    observation::{ConsensusMode, Malice, Observation},
-    parsec::Parsec,
+    observation::{
+        canonical_related_info, ConsensusMode, Malice, MaliceKind, MembershipChange, Observation,
+        ObservationHash,
+    },
+    parsec::{
+        EventCreationPolicy, ForkPolicy, GossipPolicy, Parsec, Responsiveness, StateSizes,
+        UnknownPeerPolicy, MAX_IDLE_GOSSIP_BACKOFF, MAX_VOTER_WEIGHT,
+    },
+    parsec_builder::ParsecBuilder,
+    peer_list::PeerState,
     vote::Vote,
 };
 
@@ -0,0 +1,299 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{
+    block::Block,
+    error::{Error, Result},
+    id::PublicId,
+    network_event::NetworkEvent,
+    observation::{ConsensusMode, ConsensusModes, Observation},
+    parsec::ForkPolicy,
+    serialise,
+};
+use std::{collections::BTreeSet, marker::PhantomData};
+
+/// Read-only follower of the blocks consensused by a section.
+///
+/// Unlike `Parsec`, a light client neither holds a gossip graph nor takes part in the
+/// meta-elections, so it can't decide anything by itself.  Instead it verifies the blocks decided
+/// by the section, as relayed by any of its members, against the set of voters it tracks: a block
+/// is only accepted if all of its proofs are valid signatures of its payload by distinct current
+/// voters, and if there are enough of them for the payload's consensus mode.  Its only state is
+/// that voter set, which it updates from the `Genesis`, `Add`, `Remove` and `Accusation` blocks it
+/// accepts.  An accusation removes the offender, unless it's an accusation of forking and the
+/// section's [ForkPolicy](enum.ForkPolicy.html) keeps forkers.
+///
+/// Membership changes only take effect once the meta-election deciding them is over, so the other
+/// blocks decided by the same meta-election are still signed by the previous voters.  Blocks must
+/// hence be handed over in the groups returned by
+/// [Parsec::batch_poll](struct.Parsec.html#method.batch_poll), in consensus order.
+pub struct LightClient<T: NetworkEvent, P: PublicId> {
+    voters: BTreeSet<P>,
+    consensus_modes: ConsensusModes,
+    fork_policy: ForkPolicy,
+    num_blocks: usize,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: NetworkEvent, P: PublicId> LightClient<T, P> {
+    /// Creates a light client following a section from the point where its voters are `voters`,
+    /// e.g. the genesis group.
+    ///
+    /// `consensus_mode` must be the one the section members pass to their `Parsec` constructor.
+    pub fn new(voters: BTreeSet<P>, consensus_mode: ConsensusMode) -> Self {
+        Self {
+            voters,
+            consensus_modes: ConsensusModes::new(consensus_mode),
+            fork_policy: ForkPolicy::AccuseAndProposeRemoval,
+            num_blocks: 0,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Makes opaque payloads whose [tag](trait.NetworkEvent.html#method.tag) is `tag` require
+    /// `mode`, as with
+    /// [ParsecBuilder::consensus_mode_for](struct.ParsecBuilder.html#method.consensus_mode_for).
+    pub fn with_consensus_mode_for(mut self, tag: u32, mode: ConsensusMode) -> Self {
+        self.consensus_modes.set(tag, mode);
+        self
+    }
+
+    /// Sets the fork policy the section members use, as with
+    /// [ParsecBuilder::fork_policy](struct.ParsecBuilder.html#method.fork_policy).  Defaults to
+    /// `ForkPolicy::AccuseAndProposeRemoval`.
+    pub fn with_fork_policy(mut self, policy: ForkPolicy) -> Self {
+        self.fork_policy = policy;
+        self
+    }
+
+    /// Returns the peers whose signatures are currently accepted.
+    pub fn voters(&self) -> &BTreeSet<P> {
+        &self.voters
+    }
+
+    /// Returns the number of blocks accepted so far.
+    pub fn num_blocks(&self) -> usize {
+        self.num_blocks
+    }
+
+    /// Verifies the blocks decided by one meta-election, then applies the membership changes they
+    /// carry.  If any of the blocks is invalid, none of them is accepted and the voters are left
+    /// unchanged.
+    ///
+    /// `DkgResult` blocks are skipped: they carry no proofs and are specific to the peer which
+    /// polled them.
+    pub fn handle_block_group(&mut self, blocks: &[Block<T, P>]) -> Result<()> {
+        let blocks = blocks
+            .iter()
+            .filter(|block| !block.payload().is_dkg_result())
+            .collect::<Vec<_>>();
+        for block in &blocks {
+            self.verify(block)?;
+        }
+
+        for block in &blocks {
+            match *block.payload() {
+                Observation::Genesis { ref group, .. } => self.voters = group.clone(),
+                Observation::Add { ref peer_id, .. } => {
+                    let _ = self.voters.insert(peer_id.clone());
+                }
+                Observation::Remove { ref peer_id, .. } => {
+                    let _ = self.voters.remove(peer_id);
+                }
+                Observation::Accusation {
+                    ref offender,
+                    ref malice,
+                } if self.fork_policy.removes_offender(malice) => {
+                    let _ = self.voters.remove(offender);
+                }
+                _ => (),
+            }
+        }
+        self.num_blocks += blocks.len();
+        Ok(())
+    }
+
+    fn verify(&self, block: &Block<T, P>) -> Result<()> {
+        if block.signatories().len() != block.proofs().len() {
+            return Err(Error::DuplicateVote);
+        }
+
+        let payload = serialise(block.payload());
+        for proof in block.proofs() {
+            if !self.voters.contains(proof.public_id()) {
+                return Err(Error::UnknownPeer);
+            }
+            if !proof.is_valid(&payload) {
+                return Err(Error::SignatureFailure);
+            }
+        }
+
+        let mode = self.consensus_modes.of(block.payload());
+        if block.reached_quorum(self.voters.len(), mode) {
+            Ok(())
+        } else {
+            Err(Error::InsufficientVotes)
+        }
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{
+        gossip::EventHash,
+        mock::{PeerId, Transaction},
+        observation::Malice,
+        vote::Vote,
+    };
+    use std::collections::BTreeMap;
+
+    fn new_block(
+        signatories: &[&str],
+        payload: Observation<Transaction, PeerId>,
+    ) -> Block<Transaction, PeerId> {
+        let votes: BTreeMap<_, _> = signatories
+            .iter()
+            .map(|name| {
+                let peer_id = PeerId::new(name);
+                let vote = Vote::new(&peer_id, payload.clone());
+                (peer_id, vote)
+            })
+            .collect();
+        unwrap!(Block::new(&votes))
+    }
+
+    fn ids(names: &[&str]) -> BTreeSet<PeerId> {
+        names.iter().map(|name| PeerId::new(name)).collect()
+    }
+
+    fn opaque(payload: &str) -> Observation<Transaction, PeerId> {
+        Observation::OpaquePayload(Transaction::new(payload))
+    }
+
+    fn add(name: &str) -> Observation<Transaction, PeerId> {
+        Observation::Add {
+            peer_id: PeerId::new(name),
+            related_info: vec![],
+        }
+    }
+
+    fn accusation(
+        offender: &str,
+        malice: Malice<Transaction, PeerId>,
+    ) -> Observation<Transaction, PeerId> {
+        Observation::Accusation {
+            offender: PeerId::new(offender),
+            malice,
+        }
+    }
+
+    #[test]
+    fn membership_changes_apply_after_the_group() {
+        let voters = ids(&["Alice", "Bob", "Carol", "Dave"]);
+        let mut client = LightClient::new(voters, ConsensusMode::Supermajority);
+
+        // Both blocks are decided by the old voters: three out of four is enough.
+        let group = [
+            new_block(&["Alice", "Bob", "Carol"], add("Eric")),
+            new_block(&["Alice", "Bob", "Carol"], opaque("A")),
+        ];
+        unwrap!(client.handle_block_group(&group));
+        assert!(client.voters().contains(&PeerId::new("Eric")));
+        assert_eq!(client.num_blocks(), 2);
+
+        // Three out of five isn't.
+        let block = new_block(&["Alice", "Bob", "Carol"], opaque("B"));
+        assert_eq!(
+            client.handle_block_group(&[block]),
+            Err(Error::InsufficientVotes)
+        );
+        let block = new_block(&["Alice", "Bob", "Carol", "Eric"], opaque("B"));
+        unwrap!(client.handle_block_group(&[block]));
+        assert_eq!(client.num_blocks(), 3);
+    }
+
+    #[test]
+    fn invalid_groups_are_rejected_as_a_whole() {
+        let voters = ids(&["Alice", "Bob", "Carol"]);
+        let mut client = LightClient::new(voters.clone(), ConsensusMode::Single)
+            .with_consensus_mode_for(1, ConsensusMode::Supermajority);
+
+        let group = [
+            new_block(&["Alice", "Bob", "Carol"], add("Dave")),
+            new_block(&["Dave"], opaque("A")),
+        ];
+        assert_eq!(client.handle_block_group(&group), Err(Error::UnknownPeer));
+        assert_eq!(*client.voters(), voters);
+        assert_eq!(client.num_blocks(), 0);
+
+        let tagged_b = Observation::OpaquePayload(Transaction::new_tagged(1, "B"));
+        let block = new_block(&["Alice"], tagged_b.clone());
+        assert_eq!(
+            client.handle_block_group(&[block]),
+            Err(Error::InsufficientVotes)
+        );
+
+        // A relayed block whose proof is a signature over a different payload.
+        let signed = new_block(&["Alice"], opaque("C"));
+        let block = unwrap!(bincode::deserialize(&serialise(&(
+            opaque("D"),
+            signed.proofs()
+        ))));
+        assert_eq!(
+            client.handle_block_group(&[block]),
+            Err(Error::SignatureFailure)
+        );
+
+        // A relayed block carrying two proofs by the same voter, which mustn't count twice.
+        let mut proofs = new_block(&["Alice"], tagged_b.clone()).proofs().clone();
+        proofs.extend(new_block(&["Alice"], opaque("E")).proofs().iter().cloned());
+        let block = unwrap!(bincode::deserialize(&serialise(&(tagged_b, &proofs))));
+        assert_eq!(
+            client.handle_block_group(&[block]),
+            Err(Error::DuplicateVote)
+        );
+
+        let block = new_block(&["Alice"], opaque("A"));
+        unwrap!(client.handle_block_group(&[block]));
+    }
+
+    #[test]
+    fn accusations_remove_the_offender() {
+        let voters = ids(&["Alice", "Bob", "Carol", "Dave"]);
+        let mut client = LightClient::new(voters, ConsensusMode::Supermajority)
+            .with_fork_policy(ForkPolicy::AccuseOnly);
+
+        // Forkers are kept under `ForkPolicy::AccuseOnly`.
+        let fork = accusation("Dave", Malice::Fork(EventHash::ZERO));
+        let block = new_block(&["Alice", "Bob", "Carol"], fork);
+        unwrap!(client.handle_block_group(&[block]));
+        assert_eq!(*client.voters(), ids(&["Alice", "Bob", "Carol", "Dave"]));
+
+        // Other offenders aren't, so their signatures aren't accepted anymore.
+        let duplicate_vote = accusation(
+            "Dave",
+            Malice::DuplicateVote(EventHash::ZERO, EventHash::ZERO),
+        );
+        let block = new_block(&["Alice", "Bob", "Carol"], duplicate_vote);
+        unwrap!(client.handle_block_group(&[block]));
+        assert_eq!(*client.voters(), ids(&["Alice", "Bob", "Carol"]));
+        let block = new_block(&["Alice", "Bob", "Dave"], opaque("A"));
+        assert_eq!(client.handle_block_group(&[block]), Err(Error::UnknownPeer));
+
+        // Under the default policy, forkers are removed too.
+        let mut client = LightClient::new(
+            ids(&["Alice", "Bob", "Carol", "Dave"]),
+            ConsensusMode::Supermajority,
+        );
+        let fork = accusation("Dave", Malice::Fork(EventHash::ZERO));
+        let block = new_block(&["Alice", "Bob", "Carol"], fork);
+        unwrap!(client.handle_block_group(&[block]));
+        assert_eq!(*client.voters(), ids(&["Alice", "Bob", "Carol"]));
+    }
+}
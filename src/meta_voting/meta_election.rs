@@ -11,6 +11,7 @@ use super::{
     meta_vote::MetaVote,
 };
 use crate::{
+    consensus_chain::{ChainHash, ConsensusChain},
     gossip::{EventIndex, Graph},
     id::PublicId,
     observation::ObservationKey,
@@ -43,13 +44,23 @@ pub(crate) struct MetaElection {
     pub(crate) interesting_events: PeerIndexMap<(Vec<EventIndex>, FnvHashSet<ObservationKey>)>,
     // All events that carry a payload that hasn't yet been consensused.
     pub(crate) unconsensused_events: UnconsensusedEvents,
-    // Keys of the consensused blocks' payloads in the order they were consensused.
+    // Keys of the consensused blocks' payloads in the order they were consensused.  If
+    // `consensus_history_retention` is set, only the most recent ones are kept.
     pub(crate) consensus_history: Vec<ObservationKey>,
+    // Number of keys dropped from the front of `consensus_history`.
+    pub(crate) num_archived_consensus: usize,
+    // Hash of the consensus chain link of the last key dropped from `consensus_history`.
+    pub(crate) archived_chain_hash: Option<ChainHash>,
+    // Maximum number of keys to keep in `consensus_history`, or `None` to keep all of them.
+    pub(crate) consensus_history_retention: Option<usize>,
     // Topological index of the first unconsensused payload-carrying event or of the first observer
     // event, whichever is the greater.
     pub(crate) continue_consensus_start_index: usize,
     // Topological index of the first unconsensused payload-carrying event.
     pub(crate) new_consensus_start_index: usize,
+    // Coin toss results to use instead of the common coin, indexed by round.  Test-only.
+    #[cfg(any(test, feature = "testing"))]
+    pub(crate) forced_coin_tosses: Vec<bool>,
 }
 
 impl MetaElection {
@@ -60,8 +71,13 @@ impl MetaElection {
             interesting_events: PeerIndexMap::default(),
             unconsensused_events: UnconsensusedEvents::default(),
             consensus_history: Vec::new(),
+            num_archived_consensus: 0,
+            archived_chain_hash: None,
+            consensus_history_retention: None,
             continue_consensus_start_index: 0,
             new_consensus_start_index: 0,
+            #[cfg(any(test, feature = "testing"))]
+            forced_coin_tosses: Vec::new(),
         }
     }
 
@@ -103,10 +119,29 @@ impl MetaElection {
         &self.voters
     }
 
+    #[cfg(any(all(test, feature = "mock"), feature = "dump-graphs"))]
     pub fn consensus_history(&self) -> &[ObservationKey] {
         &self.consensus_history
     }
 
+    /// Number of payloads consensused so far, including those archived from `consensus_history`.
+    pub fn consensus_len(&self) -> usize {
+        self.num_archived_consensus + self.consensus_history.len()
+    }
+
+    /// Hash chain over the consensused payloads, starting after the archived ones.
+    pub fn consensus_chain(&self) -> ConsensusChain {
+        ConsensusChain::new(
+            self.archived_chain_hash,
+            self.consensus_history.iter().map(ObservationKey::hash),
+        )
+    }
+
+    pub fn set_consensus_history_retention(&mut self, retention: Option<usize>) {
+        self.consensus_history_retention = retention;
+        self.archive_consensus_history();
+    }
+
     pub fn interesting_events(&self) -> impl Iterator<Item = (PeerIndex, &[EventIndex])> {
         self.interesting_events
             .iter()
@@ -152,9 +187,15 @@ impl MetaElection {
         self.update_interesting_content(graph);
 
         self.consensus_history.extend(decided_keys);
+        self.archive_consensus_history();
+    }
+
+    // The coin toss result forced for `round` by `TestParsec::force_coin_tosses`, if any.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn forced_coin_toss(&self, round: usize) -> Option<bool> {
+        self.forced_coin_tosses.get(round).cloned()
     }
 
-    #[cfg(feature = "dump-graphs")]
     pub fn meta_events(&self) -> &FnvHashMap<EventIndex, MetaEvent> {
         &self.meta_events
     }
@@ -216,6 +257,25 @@ impl MetaElection {
         }
     }
 
+    // Drops the oldest keys from `consensus_history` beyond the retention window.  Only the number
+    // of consensused payloads and the consensus chain hash are needed from them after their
+    // election is decided; everything else belonging to decided elections is already discarded by
+    // `new_election`.
+    fn archive_consensus_history(&mut self) {
+        let retention = match self.consensus_history_retention {
+            Some(retention) => retention,
+            None => return,
+        };
+        let num_to_archive = self.consensus_history.len().saturating_sub(retention);
+        for key in self.consensus_history.drain(..num_to_archive) {
+            self.archived_chain_hash = Some(ChainHash::new(
+                self.archived_chain_hash.as_ref(),
+                key.hash(),
+            ));
+        }
+        self.num_archived_consensus += num_to_archive;
+    }
+
     fn update_voters(&mut self, peer_list_changes: Vec<PeerListChange>) {
         for peer_list_change in peer_list_changes {
             match peer_list_change {
@@ -297,7 +357,7 @@ impl MetaElection {
     }
 }
 
-#[cfg(any(all(test, feature = "mock"), feature = "dump-graphs"))]
+#[cfg(any(test, feature = "testing", feature = "dump-graphs"))]
 pub(crate) mod snapshot {
     use super::{super::meta_event::snapshot::MetaEventSnapshot, *};
     use crate::{
@@ -307,8 +367,8 @@ pub(crate) mod snapshot {
     };
     use std::collections::BTreeMap;
 
-    #[serde(bound = "")]
     #[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
+    #[serde(bound = "")]
     pub(crate) struct MetaElectionSnapshot<P: PublicId> {
         meta_events: BTreeMap<EventHash, MetaEventSnapshot<P>>,
         voters: BTreeSet<P>,
@@ -362,5 +422,46 @@ pub(crate) mod snapshot {
                 interesting_events,
             }
         }
+
+        /// Finds an event, along with a voter, for which `self` and `other` hold meta-votes
+        /// disagreeing on a round and step both of them have reached.  Both snapshots must be
+        /// taken during the same meta-election for the comparison to be meaningful.
+        #[cfg(any(test, feature = "testing"))]
+        pub fn find_divergent_meta_votes<'a>(
+            &'a self,
+            other: &'a Self,
+        ) -> Option<(&'a EventHash, &'a P, &'a [MetaVote], &'a [MetaVote])> {
+            self.meta_events
+                .iter()
+                .filter_map(|(hash, meta_event)| {
+                    other
+                        .meta_events
+                        .get(hash)
+                        .map(|other_meta_event| (hash, meta_event, other_meta_event))
+                })
+                .flat_map(|(hash, meta_event, other_meta_event)| {
+                    meta_event
+                        .meta_votes()
+                        .iter()
+                        .filter_map(move |(voter, meta_votes)| {
+                            other_meta_event
+                                .meta_votes()
+                                .get(voter)
+                                .map(|other_meta_votes| (hash, voter, meta_votes, other_meta_votes))
+                        })
+                })
+                .find(|(_, _, meta_votes, other_meta_votes)| {
+                    meta_votes.iter().any(|meta_vote| {
+                        other_meta_votes.iter().any(|other_meta_vote| {
+                            meta_vote.round == other_meta_vote.round
+                                && meta_vote.step == other_meta_vote.step
+                                && meta_vote != other_meta_vote
+                        })
+                    })
+                })
+                .map(|(hash, voter, meta_votes, other_meta_votes)| {
+                    (hash, voter, &meta_votes[..], &other_meta_votes[..])
+                })
+        }
     }
 }
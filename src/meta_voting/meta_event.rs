@@ -137,14 +137,14 @@ impl<'a, P: PublicId + 'a> MetaEventBuilder<'a, P> {
     }
 }
 
-#[cfg(any(all(test, feature = "mock"), feature = "dump-graphs"))]
+#[cfg(any(test, feature = "testing", feature = "dump-graphs"))]
 pub(crate) mod snapshot {
     use super::*;
     use crate::{id::SecretId, observation::snapshot::ObservationKeySnapshot, peer_list::PeerList};
     use std::collections::{BTreeMap, BTreeSet};
 
-    #[serde(bound = "")]
     #[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
+    #[serde(bound = "")]
     pub(crate) struct MetaEventSnapshot<P: PublicId> {
         observees: BTreeSet<P>,
         interesting_content: Vec<ObservationKeySnapshot<P>>,
@@ -183,5 +183,10 @@ pub(crate) mod snapshot {
                     .collect(),
             }
         }
+
+        #[cfg(any(test, feature = "testing"))]
+        pub fn meta_votes(&self) -> &BTreeMap<P, Vec<MetaVote>> {
+            &self.meta_votes
+        }
     }
 }
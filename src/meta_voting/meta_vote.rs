@@ -11,21 +11,68 @@ use super::meta_vote_values::{AuxValue, BinValues, Estimates};
 #[cfg(any(test, feature = "testing"))]
 use super::{bool_set::BoolSet, meta_vote_values::UndecidedMetaVoteValues};
 use super::{
-    meta_vote_counts::MetaVoteCounts,
-    meta_vote_values::{MetaVoteValues, Step},
+    meta_vote_counts::{MetaVoteCounts, VoteWeights},
+    meta_vote_values::{DecisionReason, MetaVoteValues, Step},
 };
 use std::{
     collections::BTreeMap,
     fmt::{self, Debug, Formatter},
-    num::NonZeroUsize,
 };
 
 // This holds the state of a (binary) meta vote about which we're trying to achieve consensus.
-#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
 pub(crate) struct MetaVote {
     pub round: usize,
     pub step: Step,
     pub values: MetaVoteValues,
+    // Not part of the state: ignored by comparisons and serialisation.
+    #[serde(skip)]
+    pub diagnostics: DecisionDiagnostics,
+}
+
+impl PartialEq for MetaVote {
+    fn eq(&self, other: &Self) -> bool {
+        self.round == other.round && self.step == other.step && self.values == other.values
+    }
+}
+
+impl Eq for MetaVote {}
+
+// Records how a meta-vote got decided, to be shown in the dumped graphs.  Only tracked with the
+// `dump-graphs` feature: otherwise this is empty and recording is a no-op.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct DecisionDiagnostics {
+    #[cfg(feature = "dump-graphs")]
+    reason: Option<DecisionReason>,
+    // Whether the meta-vote had to fall back to the coin toss in any `GenuineFlip` step so far.
+    #[cfg(feature = "dump-graphs")]
+    coin_tossed: bool,
+}
+
+#[cfg(feature = "dump-graphs")]
+impl DecisionDiagnostics {
+    pub fn reason(&self) -> Option<DecisionReason> {
+        self.reason
+    }
+
+    fn record_coin_toss(&mut self) {
+        self.coin_tossed = true;
+    }
+
+    fn record_decision(&mut self, reason: Option<DecisionReason>) {
+        self.reason = match reason {
+            Some(DecisionReason::ForcedValue) if self.coin_tossed => Some(DecisionReason::CoinToss),
+            Some(reason) => Some(reason),
+            None => return,
+        };
+    }
+}
+
+#[cfg(not(feature = "dump-graphs"))]
+impl DecisionDiagnostics {
+    fn record_coin_toss(&mut self) {}
+
+    fn record_decision(&mut self, _: Option<DecisionReason>) {}
 }
 
 impl Debug for MetaVote {
@@ -58,38 +105,39 @@ impl MetaVote {
             round,
             step,
             values,
+            ..Default::default()
         }
     }
 
     pub fn new_for_observer(
         initial_estimate: bool,
-        others: &[&[MetaVote]],
-        total_peers: NonZeroUsize,
+        others: &[(&[MetaVote], usize)],
+        weights: VoteWeights,
     ) -> Vec<Self> {
         let initial = Self {
             values: MetaVoteValues::from_initial_estimate(initial_estimate),
             ..Default::default()
         };
-        Self::next_votes(&[initial], others, &BTreeMap::new(), total_peers)
+        Self::next_votes(&[initial], others, &BTreeMap::new(), weights)
     }
 
     /// Create temporary next meta-votes. They must be finalized by calling `next_final` before
     /// passing them to `MetaEvent`.
     pub fn next_temp(
         parent: &[MetaVote],
-        others: &[&[MetaVote]],
-        total_peers: NonZeroUsize,
+        others: &[(&[MetaVote], usize)],
+        weights: VoteWeights,
     ) -> Vec<Self> {
-        Self::next_votes(parent, others, &BTreeMap::new(), total_peers)
+        Self::next_votes(parent, others, &BTreeMap::new(), weights)
     }
 
     /// Finalize temporary meta-votes.
     pub fn next_final(
         temp: &[MetaVote],
         coin_tosses: &BTreeMap<usize, bool>,
-        total_peers: NonZeroUsize,
+        weights: VoteWeights,
     ) -> Vec<Self> {
-        Self::next_votes(temp, &[], coin_tosses, total_peers)
+        Self::next_votes(temp, &[], coin_tosses, weights)
     }
 
     pub fn decision(&self) -> Option<bool> {
@@ -101,13 +149,13 @@ impl MetaVote {
 
     fn next_votes(
         prev: &[MetaVote],
-        others: &[&[MetaVote]],
+        others: &[(&[MetaVote], usize)],
         coin_tosses: &BTreeMap<usize, bool>,
-        total_peers: NonZeroUsize,
+        weights: VoteWeights,
     ) -> Vec<Self> {
         let mut next = Vec::new();
         for vote in prev {
-            let counts = MetaVoteCounts::new(vote, others, total_peers);
+            let counts = MetaVoteCounts::new(vote, others, weights);
             let mut updated = *vote;
             updated.update(counts, &coin_tosses);
             let decided = vote.is_decided();
@@ -117,8 +165,7 @@ impl MetaVote {
             }
         }
 
-        while let Some(next_meta_vote) =
-            Self::next_vote(next.last(), others, &coin_tosses, total_peers)
+        while let Some(next_meta_vote) = Self::next_vote(next.last(), others, &coin_tosses, weights)
         {
             next.push(next_meta_vote);
         }
@@ -140,25 +187,26 @@ impl MetaVote {
 
     fn update(&mut self, counts: MetaVoteCounts, coin_tosses: &BTreeMap<usize, bool>) {
         let coin_toss = coin_tosses.get(&self.round).cloned();
-        self.values.update(counts, coin_toss, self.step);
+        let reason = self.values.update(counts, coin_toss, self.step);
+        self.diagnostics.record_decision(reason);
     }
 
     fn next_vote(
         parent: Option<&Self>,
-        others: &[&[MetaVote]],
+        others: &[(&[MetaVote], usize)],
         coin_tosses: &BTreeMap<usize, bool>,
-        total_peers: NonZeroUsize,
+        weights: VoteWeights,
     ) -> Option<MetaVote> {
         let parent = parent?;
 
         if parent.is_decided() {
             return None;
         }
-        let counts = MetaVoteCounts::new(parent, others, total_peers);
+        let counts = MetaVoteCounts::new(parent, others, weights);
         if counts.is_supermajority(counts.aux_values_set()) {
             let coin_toss = coin_tosses.get(&parent.round);
             let mut next = parent.increase_step(&counts, coin_toss.cloned());
-            let new_counts = MetaVoteCounts::new(&next, others, total_peers);
+            let new_counts = MetaVoteCounts::new(&next, others, weights);
             next.update(new_counts, &coin_tosses);
             Some(next)
         } else {
@@ -168,6 +216,12 @@ impl MetaVote {
 
     fn increase_step(&self, counts: &MetaVoteCounts, coin_toss: Option<bool>) -> Self {
         let mut next = *self;
+        if self.step == Step::GenuineFlip
+            && !counts.is_supermajority(counts.aux_values_true)
+            && !counts.is_supermajority(counts.aux_values_false)
+        {
+            next.diagnostics.record_coin_toss();
+        }
         next.values.increase_step(counts, coin_toss, self.step);
         match next.step {
             Step::ForcedTrue => {
@@ -200,6 +254,7 @@ mod tests {
             round: 0,
             step: Step::ForcedTrue,
             values: MetaVoteValues::Decided(true),
+            ..Default::default()
         };
         let total_peers = 7;
 
@@ -209,21 +264,27 @@ mod tests {
             round: 0,
             step: Step::ForcedTrue,
             values: MetaVoteValues::Undecided(UndecidedMetaVoteValues::default()),
+            ..Default::default()
         };
         for _ in 1..total_peers - 1 {
             collected_votes.push(vec![undecided_meta_vote]);
         }
 
         for votes in collected_votes.iter() {
-            others.push(votes.as_slice());
+            others.push((votes.as_slice(), 1));
         }
         let result = MetaVote::new_for_observer(
             true,
             others.as_slice(),
-            NonZeroUsize::new(total_peers).unwrap(),
+            VoteWeights::equal(NonZeroUsize::new(total_peers).unwrap()),
         );
         assert_eq!(result.len(), 1);
         assert_eq!(result[0], decided_meta_vote);
+        #[cfg(feature = "dump-graphs")]
+        assert_eq!(
+            result[0].diagnostics.reason(),
+            Some(DecisionReason::Propagated)
+        );
     }
 
     #[test]
@@ -240,18 +301,19 @@ mod tests {
                 BinValues::new(BoolSet::Both),
                 AuxValue::new(Some(false)),
             )),
+            ..Default::default()
         };
         for _ in 0..total_peers - 1 {
             collected_votes.push(vec![undecided_meta_vote]);
         }
 
         for votes in collected_votes.iter() {
-            others.push(votes.as_slice());
+            others.push((votes.as_slice(), 1));
         }
         let result = MetaVote::new_for_observer(
             true,
             others.as_slice(),
-            NonZeroUsize::new(total_peers).unwrap(),
+            VoteWeights::equal(NonZeroUsize::new(total_peers).unwrap()),
         );
         assert_eq!(result.len(), 2);
         let expected_meta_votes = vec![
@@ -263,6 +325,7 @@ mod tests {
                     BinValues::new(BoolSet::Both),
                     AuxValue::new(Some(true)),
                 )),
+                ..Default::default()
             },
             MetaVote {
                 round: 0,
@@ -272,6 +335,7 @@ mod tests {
                     BinValues::new(BoolSet::Empty),
                     AuxValue::new(None),
                 )),
+                ..Default::default()
             },
         ];
         assert_eq!(result, expected_meta_votes);
@@ -291,6 +355,7 @@ mod tests {
                 BinValues::new(BoolSet::Both),
                 AuxValue::new(Some(false)),
             )),
+            ..Default::default()
         };
         let undecided_meta_vote_2 = MetaVote {
             round: 0,
@@ -300,18 +365,19 @@ mod tests {
                 BinValues::new(BoolSet::Single(false)),
                 AuxValue::new(Some(false)),
             )),
+            ..Default::default()
         };
         for _ in 0..total_peers - 1 {
             collected_votes.push(vec![undecided_meta_vote_1, undecided_meta_vote_2]);
         }
 
         for votes in collected_votes.iter() {
-            others.push(votes.as_slice());
+            others.push((votes.as_slice(), 1));
         }
         let result = MetaVote::new_for_observer(
             true,
             others.as_slice(),
-            NonZeroUsize::new(total_peers).unwrap(),
+            VoteWeights::equal(NonZeroUsize::new(total_peers).unwrap()),
         );
         assert_eq!(result.len(), 2);
         let expected_meta_votes = vec![
@@ -323,13 +389,129 @@ mod tests {
                     BinValues::new(BoolSet::Both),
                     AuxValue::new(Some(true)),
                 )),
+                ..Default::default()
             },
             MetaVote {
                 round: 0,
                 step: Step::ForcedFalse,
                 values: MetaVoteValues::Decided(false),
+                ..Default::default()
             },
         ];
         assert_eq!(result, expected_meta_votes);
+        #[cfg(feature = "dump-graphs")]
+        assert_eq!(
+            result[1].diagnostics.reason(),
+            Some(DecisionReason::ForcedValue)
+        );
+    }
+
+    fn undecided(
+        round: usize,
+        step: Step,
+        estimates: BoolSet,
+        bin_values: BoolSet,
+        aux_value: Option<bool>,
+    ) -> MetaVote {
+        MetaVote {
+            round,
+            step,
+            values: MetaVoteValues::Undecided(UndecidedMetaVoteValues::new(
+                Estimates::new(estimates),
+                BinValues::new(bin_values),
+                AuxValue::new(aux_value),
+            )),
+            ..Default::default()
+        }
+    }
+
+    // A genuine flip with the aux values split evenly, so that the estimates for the next round
+    // can only come from the coin toss.
+    fn split_genuine_flip() -> (MetaVote, Vec<Vec<MetaVote>>) {
+        let flip = |aux_value| {
+            undecided(
+                0,
+                Step::GenuineFlip,
+                BoolSet::Both,
+                BoolSet::Both,
+                Some(aux_value),
+            )
+        };
+        let others = vec![vec![flip(true)], vec![flip(false)], vec![flip(false)]];
+        (flip(true), others)
+    }
+
+    #[test]
+    fn genuine_flip_waits_for_coin_toss() {
+        let weights = VoteWeights::equal(NonZeroUsize::new(4).unwrap());
+        let (parent, others) = split_genuine_flip();
+        let others = others
+            .iter()
+            .map(|votes| (votes.as_slice(), 1))
+            .collect::<Vec<_>>();
+
+        // The temporary meta-votes are computed without coin tosses: the estimates are cleared.
+        let temp = MetaVote::next_temp(&[parent], &others, weights);
+        assert_eq!(temp.len(), 2);
+        let waiting = undecided(1, Step::ForcedTrue, BoolSet::Empty, BoolSet::Empty, None);
+        assert_eq!(temp[1], waiting);
+
+        // The estimates stay cleared until the coin toss for the new round is available...
+        let result = MetaVote::next_final(&temp, &BTreeMap::new(), weights);
+        assert_eq!(result[1], waiting);
+
+        // ...and then take its value.
+        let coin_tosses = vec![(0, false), (1, true)].into_iter().collect();
+        let result = MetaVote::next_final(&temp, &coin_tosses, weights);
+        assert_eq!(
+            result[1],
+            undecided(
+                1,
+                Step::ForcedTrue,
+                BoolSet::Single(true),
+                BoolSet::Empty,
+                None
+            )
+        );
+    }
+
+    #[test]
+    fn genuine_flip_without_coin_toss_follows_other_voters() {
+        let weights = VoteWeights::equal(NonZeroUsize::new(4).unwrap());
+        let waiting = undecided(1, Step::ForcedTrue, BoolSet::Empty, BoolSet::Empty, None);
+
+        // A meta-vote still waiting for the coin toss isn't stuck: the estimates of the
+        // supermajority of voters who got it give it bin values and an aux value, which can be
+        // enough to decide...
+        let estimated = undecided(
+            1,
+            Step::ForcedTrue,
+            BoolSet::Single(true),
+            BoolSet::Single(true),
+            Some(true),
+        );
+        let others = vec![vec![estimated]; 3];
+        let others = others
+            .iter()
+            .map(|votes| (votes.as_slice(), 1))
+            .collect::<Vec<_>>();
+        let result = MetaVote::next_temp(&[waiting], &others, weights);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].decision(), Some(true));
+
+        // ...and it otherwise adopts their decision.
+        let decided = MetaVote {
+            round: 1,
+            step: Step::ForcedTrue,
+            values: MetaVoteValues::Decided(true),
+            ..Default::default()
+        };
+        let others = vec![vec![decided]; 3];
+        let others = others
+            .iter()
+            .map(|votes| (votes.as_slice(), 1))
+            .collect::<Vec<_>>();
+        let result = MetaVote::next_temp(&[waiting], &others, weights);
+        assert_eq!(result, vec![decided]);
     }
 }
@@ -7,13 +7,13 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::meta_vote::MetaVote;
-use crate::observation::is_more_than_two_thirds;
+use crate::observation::supermajority;
 use std::iter;
 use std::num::NonZeroUsize;
 use std::ops::AddAssign;
 
 // This is used to collect the meta votes of other events relating to a single (binary) meta vote at
-// a given round and step.
+// a given round and step.  With weighted voters, the counts and `total_peers` are sums of weights.
 #[derive(Debug, PartialEq, Eq)]
 pub(crate) struct MetaVoteCounts {
     pub estimates_true: usize,
@@ -41,29 +41,22 @@ impl AddAssign for MetaVoteCounts {
 impl MetaVoteCounts {
     // Construct a `MetaVoteCounts` by collecting details from all meta votes which are for the
     // given `parent`'s `round` and `step`.  These results will include info from our own `parent`
-    // meta vote.
-    pub fn new(parent: &MetaVote, others: &[&[MetaVote]], total_peers: NonZeroUsize) -> Self {
-        let mut counts = MetaVoteCounts {
-            estimates_true: 0,
-            estimates_false: 0,
-            bin_values_true: 0,
-            bin_values_false: 0,
-            aux_values_true: 0,
-            aux_values_false: 0,
-            decision: None,
-            total_peers,
-        };
-        for vote in others
+    // meta vote.  Each of `others` is paired with the weight of the voter it comes from, and
+    // contributes that weight rather than 1 to the counts.
+    pub fn new(parent: &MetaVote, others: &[(&[MetaVote], usize)], weights: VoteWeights) -> Self {
+        let mut counts = MetaVoteCounts::default_counts(weights.total);
+        for (vote, weight) in others
             .iter()
-            .filter_map(|other| {
+            .filter_map(|(other, weight)| {
                 other
                     .iter()
                     .filter(|vote| vote.round_and_step() == parent.round_and_step())
                     .last()
+                    .map(|vote| (vote, *weight))
             })
-            .chain(iter::once(parent))
+            .chain(iter::once((parent, weights.own)))
         {
-            let contribution = vote.values.count(total_peers);
+            let contribution = vote.values.count(weights.total).weighted(weight);
             counts += contribution;
         }
 
@@ -74,8 +67,9 @@ impl MetaVoteCounts {
         self.aux_values_true + self.aux_values_false
     }
 
+    // Uses the same threshold as `Parsec::quorum_size`.
     pub fn is_supermajority(&self, count: usize) -> bool {
-        is_more_than_two_thirds(count, self.total_peers())
+        count >= supermajority(self.total_peers())
     }
 
     pub fn is_at_least_one_third(&self, count: usize) -> bool {
@@ -111,6 +105,38 @@ impl MetaVoteCounts {
     fn total_peers(&self) -> usize {
         self.total_peers.get()
     }
+
+    fn weighted(self, weight: usize) -> Self {
+        MetaVoteCounts {
+            estimates_true: self.estimates_true * weight,
+            estimates_false: self.estimates_false * weight,
+            bin_values_true: self.bin_values_true * weight,
+            bin_values_false: self.bin_values_false * weight,
+            aux_values_true: self.aux_values_true * weight,
+            aux_values_false: self.aux_values_false * weight,
+            ..self
+        }
+    }
+}
+
+// Weights the meta-votes are counted with.  Unless voter weights are set, every voter weighs 1, so
+// the counts are numbers of voters and `total` is the number of voters.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct VoteWeights {
+    // Weight of the voter whose meta-votes are being computed.
+    pub own: usize,
+    // Total weight of all the voters of the meta-election.
+    pub total: NonZeroUsize,
+}
+
+#[cfg(test)]
+impl VoteWeights {
+    pub fn equal(total_peers: NonZeroUsize) -> Self {
+        VoteWeights {
+            own: 1,
+            total: total_peers,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -299,6 +325,7 @@ mod tests {
                 BinValues::new(BoolSet::Empty),
                 AuxValue::new(None),
             )),
+            ..Default::default()
         };
         let vote1 = MetaVote {
             round: 0,
@@ -308,6 +335,7 @@ mod tests {
                 BinValues::new(BoolSet::Empty),
                 AuxValue::new(None),
             )),
+            ..Default::default()
         };
         let vote2 = MetaVote {
             round: 0,
@@ -317,6 +345,7 @@ mod tests {
                 BinValues::new(BoolSet::Empty),
                 AuxValue::new(None),
             )),
+            ..Default::default()
         };
         let vote3 = MetaVote {
             round: 0,
@@ -326,12 +355,13 @@ mod tests {
                 BinValues::new(BoolSet::Empty),
                 AuxValue::new(None),
             )),
+            ..Default::default()
         };
 
         let actual = MetaVoteCounts::new(
             &parent_vote,
-            &[&[vote0], &[vote1], &[vote2, vote3]],
-            total_peers,
+            &[(&[vote0], 1), (&[vote1], 1), (&[vote2, vote3], 1)],
+            VoteWeights::equal(total_peers),
         );
         let expected = MetaVoteCounts {
             estimates_true: 2,
@@ -340,6 +370,48 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn weighted_counts() {
+        let weights = VoteWeights {
+            own: 2,
+            total: NonZeroUsize::new(6).unwrap(),
+        };
+        let vote = |value| MetaVote {
+            values: MetaVoteValues::Decided(value),
+            ..Default::default()
+        };
+
+        // Our own vote weighs 2, the others 3 and 1.
+        let actual = MetaVoteCounts::new(
+            &vote(true),
+            &[(&[vote(true)], 3), (&[vote(false)], 1)],
+            weights,
+        );
+        let expected = MetaVoteCounts {
+            estimates_true: 5,
+            estimates_false: 1,
+            bin_values_true: 5,
+            bin_values_false: 1,
+            aux_values_true: 5,
+            aux_values_false: 1,
+            decision: Some(true),
+            ..MetaVoteCounts::default_counts(weights.total)
+        };
+        assert_eq!(actual, expected);
+        assert!(actual.is_supermajority(actual.estimates_true));
+        assert!(!actual.is_at_least_one_third(actual.estimates_false));
+    }
+
+    #[test]
+    fn supermajority_threshold() {
+        for total_peers in 1..20 {
+            let counts = MetaVoteCounts::default_counts(NonZeroUsize::new(total_peers).unwrap());
+            let threshold = supermajority(total_peers);
+            assert!(counts.is_supermajority(threshold));
+            assert!(!counts.is_supermajority(threshold - 1));
+        }
+    }
+
     fn counts_with_estimates(
         num_empty: usize,
         num_true: usize,
@@ -424,7 +496,14 @@ mod tests {
 
     fn counts_with_votes(votes: &[MetaVote], total_peers: NonZeroUsize) -> MetaVoteCounts {
         let parent_vote = MetaVote::default();
-        let votes: Vec<_> = votes.iter().map(slice::from_ref).collect();
-        MetaVoteCounts::new(&parent_vote, votes.as_slice(), total_peers)
+        let votes: Vec<_> = votes
+            .iter()
+            .map(|vote| (slice::from_ref(vote), 1))
+            .collect();
+        MetaVoteCounts::new(
+            &parent_vote,
+            votes.as_slice(),
+            VoteWeights::equal(total_peers),
+        )
     }
 }
@@ -7,7 +7,7 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{bool_set::BoolSet, meta_vote_counts::MetaVoteCounts};
-use std::fmt::{self, Debug, Formatter};
+use std::fmt::{self, Debug, Display, Formatter};
 use std::num::NonZeroUsize;
 
 #[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
@@ -249,17 +249,22 @@ impl MetaVoteValues {
         }
     }
 
-    fn calculate_new_decision(&mut self, counts: &MetaVoteCounts, step: Step) {
+    // Returns how the decision was reached, if these values just got decided.
+    fn calculate_new_decision(
+        &mut self,
+        counts: &MetaVoteCounts,
+        step: Step,
+    ) -> Option<DecisionReason> {
         if let MetaVoteValues::Undecided(values) = *self {
             let bin_values = values.bin_values;
-            let decision = match step {
+            let forced = match step {
                 Step::ForcedTrue => {
                     if bin_values.0.contains(true)
                         && counts.is_supermajority(counts.aux_values_true)
                     {
                         Some(true)
                     } else {
-                        counts.decision
+                        None
                     }
                 }
                 Step::ForcedFalse => {
@@ -268,15 +273,24 @@ impl MetaVoteValues {
                     {
                         Some(false)
                     } else {
-                        counts.decision
+                        None
                     }
                 }
-                Step::GenuineFlip => counts.decision,
+                Step::GenuineFlip => None,
             };
-            if let Some(value) = decision {
+            let decision = forced
+                .map(|value| (value, DecisionReason::ForcedValue))
+                .or_else(|| {
+                    counts
+                        .decision
+                        .map(|value| (value, DecisionReason::Propagated))
+                });
+            if let Some((value, reason)) = decision {
                 *self = MetaVoteValues::Decided(value);
+                return Some(reason);
             }
         }
+        None
     }
 
     pub fn increase_step(&mut self, counts: &MetaVoteCounts, coin_toss: Option<bool>, step: Step) {
@@ -306,7 +320,10 @@ impl MetaVoteValues {
                         BoolSet::Single(coin_toss)
                     } else {
                         // Clear the estimates to indicate we're waiting for further events to be
-                        // gossiped to try and get the coin toss result.
+                        // gossiped to try and get the coin toss result.  They are set from the coin
+                        // toss of the new round once it's available (see `Estimates::calculate`).
+                        // Meanwhile, the bin values and aux value are still derived from the other
+                        // voters' estimates, so the meta-vote can progress or get decided with them.
                         BoolSet::Empty
                     }
                 }
@@ -316,9 +333,15 @@ impl MetaVoteValues {
         }
     }
 
-    pub fn update(&mut self, mut counts: MetaVoteCounts, coin_toss: Option<bool>, step: Step) {
-        *self = match self {
-            MetaVoteValues::Decided(_) => *self,
+    // Returns how the decision was reached, if these values just got decided.
+    pub fn update(
+        &mut self,
+        mut counts: MetaVoteCounts,
+        coin_toss: Option<bool>,
+        step: Step,
+    ) -> Option<DecisionReason> {
+        match self {
+            MetaVoteValues::Decided(_) => None,
             MetaVoteValues::Undecided(ref values) => {
                 let mut updated = *self;
                 updated.calculate_new_estimates(&mut counts, coin_toss);
@@ -326,8 +349,9 @@ impl MetaVoteValues {
                 updated.calculate_new_bin_values(&mut counts);
                 updated.calculate_new_auxiliary_value(&mut counts, bin_values_before_update);
                 counts.check_exceeding();
-                updated.calculate_new_decision(&counts, step);
-                updated
+                let reason = updated.calculate_new_decision(&counts, step);
+                *self = updated;
+                reason
             }
         }
     }
@@ -373,19 +397,58 @@ impl Default for MetaVoteValues {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Serialize, Deserialize)]
+/// How a meta-vote reached its decision.  Only used for diagnostics: it has no bearing on the
+/// algorithm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum DecisionReason {
+    /// A supermajority of the auxiliary values matched the value forced by the step.
+    ForcedValue,
+    /// As `ForcedValue`, but the meta-vote had to fall back to the coin toss in an earlier
+    /// `GenuineFlip` step, which hints at contention.
+    #[cfg(feature = "dump-graphs")]
+    CoinToss,
+    /// The decision was adopted from the decided meta-votes of other peers.
+    Propagated,
+}
+
+#[cfg(feature = "dump-graphs")]
+impl DecisionReason {
+    pub fn as_char(self) -> char {
+        match self {
+            DecisionReason::ForcedValue => 's',
+            DecisionReason::CoinToss => 'c',
+            DecisionReason::Propagated => 'p',
+        }
+    }
+}
+
+/// The step of a meta-vote within its round.  Steps are ordered in the order they cycle through:
+/// `ForcedTrue < ForcedFalse < GenuineFlip`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub(crate) enum Step {
     ForcedTrue,
     ForcedFalse,
     GenuineFlip,
 }
 
+impl Step {
+    /// Human-readable name of this step.
+    pub fn name(self) -> &'static str {
+        match self {
+            Step::ForcedTrue => "ForcedTrue",
+            Step::ForcedFalse => "ForcedFalse",
+            Step::GenuineFlip => "GenuineFlip",
+        }
+    }
+}
+
 impl Default for Step {
     fn default() -> Step {
         Step::ForcedTrue
     }
 }
 
+// The numeric form is relied upon by the dot files dumped and parsed in tests.
 impl Debug for Step {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         let step = match self {
@@ -397,6 +460,12 @@ impl Debug for Step {
     }
 }
 
+impl Display for Step {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
 fn write_bool(f: &mut Formatter, a_bool: bool) -> fmt::Result {
     if a_bool {
         write!(f, "t")
@@ -438,6 +507,21 @@ mod tests {
     use super::*;
     use std::num::NonZeroUsize;
 
+    #[test]
+    /// Assert steps are ordered and named in the order they cycle through.
+    fn step_order_and_name() {
+        let mut steps = vec![Step::GenuineFlip, Step::ForcedTrue, Step::ForcedFalse];
+        steps.sort();
+        assert_eq!(
+            steps,
+            vec![Step::ForcedTrue, Step::ForcedFalse, Step::GenuineFlip]
+        );
+
+        let names: Vec<_> = steps.iter().map(|step| step.to_string()).collect();
+        assert_eq!(names, vec!["ForcedTrue", "ForcedFalse", "GenuineFlip"]);
+        assert_eq!(format!("{:?}", Step::GenuineFlip), "2");
+    }
+
     #[test]
     /// Assert counting on initial constructed MetaVoteValues is correct.
     fn meta_vote_value_initial_count() {
@@ -689,13 +773,16 @@ mod tests {
                 bin_values: BinValues(BoolSet::Both),
                 ..Default::default()
             });
-            mvv.calculate_new_decision(&counts, Step::ForcedTrue);
+            assert_eq!(
+                mvv.calculate_new_decision(&counts, Step::ForcedTrue),
+                Some(DecisionReason::ForcedValue)
+            );
 
             let expected_mvv = MetaVoteValues::Decided(true);
             assert_eq!(mvv, expected_mvv);
 
             // Decided value shall not be updated.
-            mvv.calculate_new_decision(&counts, Step::ForcedFalse);
+            assert_eq!(mvv.calculate_new_decision(&counts, Step::ForcedFalse), None);
             assert_eq!(mvv, expected_mvv);
 
             // Decided on ForcedFalse step.
@@ -703,7 +790,10 @@ mod tests {
                 bin_values: BinValues(BoolSet::Both),
                 ..Default::default()
             });
-            mvv.calculate_new_decision(&counts, Step::ForcedFalse);
+            assert_eq!(
+                mvv.calculate_new_decision(&counts, Step::ForcedFalse),
+                Some(DecisionReason::ForcedValue)
+            );
             assert_eq!(mvv, MetaVoteValues::Decided(false));
         }
 
@@ -711,14 +801,17 @@ mod tests {
         {
             let counts = MetaVoteCounts::default_counts(total_peers);
             let mut mvv = MetaVoteValues::default();
-            mvv.calculate_new_decision(&counts, Step::GenuineFlip);
+            assert_eq!(mvv.calculate_new_decision(&counts, Step::GenuineFlip), None);
             assert_eq!(mvv, MetaVoteValues::default());
 
             let counts = MetaVoteCounts {
                 decision: Some(false),
                 ..MetaVoteCounts::default_counts(total_peers)
             };
-            mvv.calculate_new_decision(&counts, Step::GenuineFlip);
+            assert_eq!(
+                mvv.calculate_new_decision(&counts, Step::GenuineFlip),
+                Some(DecisionReason::Propagated)
+            );
             assert_eq!(mvv, MetaVoteValues::Decided(false));
         }
     }
@@ -824,7 +917,10 @@ mod tests {
                 decision: Some(true),
                 ..MetaVoteCounts::default_counts(total_peers)
             };
-            mvv.update(counts, Some(false), Step::GenuineFlip);
+            assert_eq!(
+                mvv.update(counts, Some(false), Step::GenuineFlip),
+                Some(DecisionReason::Propagated)
+            );
             assert_eq!(mvv, MetaVoteValues::Decided(true));
         }
 
@@ -838,7 +934,10 @@ mod tests {
                 aux_values_false: 3,
                 ..MetaVoteCounts::default_counts(total_peers)
             };
-            mvv.update(counts, None, Step::ForcedFalse);
+            assert_eq!(
+                mvv.update(counts, None, Step::ForcedFalse),
+                Some(DecisionReason::ForcedValue)
+            );
             assert_eq!(mvv, MetaVoteValues::Decided(false));
 
             // From non-default meta_vote_values.
@@ -853,7 +952,10 @@ mod tests {
                 aux_values_true: 3,
                 ..MetaVoteCounts::default_counts(total_peers)
             };
-            mvv.update(counts, None, Step::ForcedTrue);
+            assert_eq!(
+                mvv.update(counts, None, Step::ForcedTrue),
+                Some(DecisionReason::ForcedValue)
+            );
             assert_eq!(mvv, MetaVoteValues::Decided(true));
         }
 
@@ -874,7 +976,7 @@ mod tests {
                 bin_values: BinValues(BoolSet::Both),
                 aux_value: AuxValue(Some(true)),
             });
-            mvv.update(counts, Some(false), Step::GenuineFlip);
+            assert_eq!(mvv.update(counts, Some(false), Step::GenuineFlip), None);
             assert_eq!(mvv, expected_mvv);
 
             // From non-default meta_vote_values.
@@ -890,7 +992,7 @@ mod tests {
                 aux_values_false: 2,
                 ..MetaVoteCounts::default_counts(total_peers)
             };
-            mvv.update(counts, None, Step::ForcedTrue);
+            assert_eq!(mvv.update(counts, None, Step::ForcedTrue), None);
 
             let expected_mvv = MetaVoteValues::Undecided(UndecidedMetaVoteValues {
                 estimates: Estimates(BoolSet::Both),
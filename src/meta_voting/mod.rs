@@ -13,7 +13,7 @@ mod meta_vote;
 mod meta_vote_counts;
 mod meta_vote_values;
 
-#[cfg(any(all(test, feature = "mock"), feature = "dump-graphs"))]
+#[cfg(any(test, feature = "testing", feature = "dump-graphs"))]
 pub(crate) use self::meta_election::snapshot::MetaElectionSnapshot;
 #[cfg(any(all(test, feature = "mock"), feature = "testing"))]
 pub(crate) use self::meta_election::UnconsensusedEvents;
@@ -23,4 +23,5 @@ pub(crate) use self::{
     meta_election::MetaElection,
     meta_event::{MetaEvent, MetaEventBuilder, Observer},
     meta_vote::MetaVote,
+    meta_vote_counts::VoteWeights,
 };
@@ -194,13 +194,46 @@ impl SecretId for PeerId {
 #[derive(Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Serialize, Deserialize)]
 pub struct Transaction(String);
 
+const INVALID_TRANSACTION_PREFIX: &str = "invalid:";
+const TAGGED_TRANSACTION_PREFIX: &str = "tag";
+
 impl Transaction {
     pub fn new<T: Into<String>>(id: T) -> Self {
         Transaction(id.into())
     }
+
+    /// Creates a transaction for which `NetworkEvent::validate` returns `false`.
+    pub fn new_invalid<T: Into<String>>(id: T) -> Self {
+        Transaction(format!("{}{}", INVALID_TRANSACTION_PREFIX, id.into()))
+    }
+
+    /// Creates a transaction for which `NetworkEvent::tag` returns `tag`.
+    pub fn new_tagged<T: Into<String>>(tag: u32, id: T) -> Self {
+        Transaction(format!(
+            "{}{}:{}",
+            TAGGED_TRANSACTION_PREFIX,
+            tag,
+            id.into()
+        ))
+    }
 }
 
-impl NetworkEvent for Transaction {}
+impl NetworkEvent for Transaction {
+    fn validate(&self) -> bool {
+        !self.0.starts_with(INVALID_TRANSACTION_PREFIX)
+    }
+
+    fn tag(&self) -> u32 {
+        if !self.0.starts_with(TAGGED_TRANSACTION_PREFIX) {
+            return 0;
+        }
+        self.0[TAGGED_TRANSACTION_PREFIX.len()..]
+            .split(':')
+            .next()
+            .and_then(|tag| tag.parse().ok())
+            .unwrap_or(0)
+    }
+}
 
 impl Display for Transaction {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
@@ -247,6 +280,20 @@ impl Debug for Signature {
     }
 }
 
+#[cfg(any(test, feature = "testing"))]
+impl Signature {
+    /// **NOT FOR PRODUCTION USE**: Returns this signature with all its bits flipped, so that it
+    /// fails verification against the data it was created for.  Useful to test the handling of
+    /// invalid signatures received over the wire.
+    pub fn corrupt(&self) -> Self {
+        let mut corrupted = *self;
+        for byte in corrupted.0.iter_mut() {
+            *byte = !*byte;
+        }
+        corrupted
+    }
+}
+
 #[cfg(feature = "mock")]
 struct SharedSecret([u8; KEY_LENGTH]);
 
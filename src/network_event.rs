@@ -14,4 +14,23 @@ use std::{fmt::Debug, hash::Hash};
 pub trait NetworkEvent:
     Clone + Eq + Ord + PartialEq + PartialOrd + Hash + Serialize + DeserializeOwned + Debug
 {
+    /// Returns whether this payload is valid, e.g. whether evidence attached to it checks out.
+    ///
+    /// It is consulted before accepting an opaque vote from another peer, and before voting for an
+    /// opaque payload ourselves: events carrying an invalid payload are rejected.  The result must
+    /// be deterministic, i.e. all peers must get the same result for the same payload, otherwise
+    /// their gossip graphs diverge.  By default, every payload is valid.
+    fn validate(&self) -> bool {
+        true
+    }
+
+    /// Returns the kind of this payload, used to look up the consensus mode it requires.
+    ///
+    /// Payloads whose tag has a mode registered via
+    /// [`ParsecBuilder::consensus_mode_for`](struct.ParsecBuilder.html#method.consensus_mode_for)
+    /// use that mode; all others use the mode the `Parsec` instance was constructed with.  Like
+    /// `validate`, the result must be deterministic.  By default, every payload has tag `0`.
+    fn tag(&self) -> u32 {
+        0
+    }
 }
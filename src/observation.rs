@@ -20,7 +20,7 @@ use std::{
     cmp::Ordering,
     collections::{BTreeMap, BTreeSet},
     error::Error,
-    fmt::{self, Debug, Formatter},
+    fmt::{self, Debug, Display, Formatter},
     hash::{Hash as StdHash, Hasher},
 };
 
@@ -41,14 +41,24 @@ pub enum Observation<T: NetworkEvent, P: PublicId> {
     Add {
         /// Public id of the peer to be added
         peer_id: P,
-        /// Extra arbitrary information for use by the client
+        /// Extra arbitrary information for use by the client.
+        /// Note: this is part of the observation's hash, so all voters must provide byte-identical
+        /// values for their votes to count towards the same observation.  Use
+        /// `canonical_related_info` to build it from unordered data.
         related_info: Vec<u8>,
     },
     /// Vote to remove the indicated peer from the network.
+    ///
+    /// Removal is final: a removed peer can't be added back under the same id, and votes for a
+    /// `Remove` which has already been consensused are ignored, so a late or replayed vote can't
+    /// be applied a second time.
     Remove {
         /// Public id of the peer to be removed
         peer_id: P,
-        /// Extra arbitrary information for use by the client
+        /// Extra arbitrary information for use by the client.
+        /// Note: this is part of the observation's hash, so all voters must provide byte-identical
+        /// values for their votes to count towards the same observation.  Use
+        /// `canonical_related_info` to build it from unordered data.
         related_info: Vec<u8>,
     },
     /// Output only: Do not vote for it.
@@ -79,6 +89,13 @@ pub enum Observation<T: NetworkEvent, P: PublicId> {
     /// Vote for the next message (Part or Ack) to be handled for the Distributed Key Generation
     /// algorithm used by our common coin.
     DkgMessage(DkgMessage),
+    /// Internal only: Do not vote for it or expect it to come in blocks.
+    /// Cast by a peer which created the [heartbeat
+    /// interval](struct.Parsec.html#method.set_heartbeat_interval) number of gossip events in a row
+    /// without voting for anything, so that the section keeps reaching consensus, and pending
+    /// meta-elections keep being decided, while the application is idle.  Carries the index of
+    /// the event among its creator's events, which makes each heartbeat of a peer unique.
+    Heartbeat(u64),
 }
 
 impl<T: NetworkEvent, P: PublicId> Observation<T, P> {
@@ -102,7 +119,9 @@ impl<T: NetworkEvent, P: PublicId> Observation<T, P> {
     /// Is this observation an internal and should not be published in a `Block`
     pub fn is_internal(&self) -> bool {
         match *self {
-            Observation::DkgMessage(_) | Observation::StartDkg(_) => true,
+            Observation::DkgMessage(_) | Observation::StartDkg(_) | Observation::Heartbeat(_) => {
+                true
+            }
             _ => false,
         }
     }
@@ -114,6 +133,18 @@ impl<T: NetworkEvent, P: PublicId> Observation<T, P> {
             _ => false,
         }
     }
+
+    /// Compares observations in an order which all peers agree on, as opposed to `Ord`, which
+    /// e.g. depends on how `P` orders peer IDs.
+    ///
+    /// This is the order PARSEC uses to break ties between payloads decided within the same
+    /// meta-election: these are emitted as blocks primarily in the order the voters observed
+    /// them, and only observations which can't be ordered that way are emitted in this order.
+    /// Observations with equal hashes (i.e. the same observation voted for by different peers
+    /// under `ConsensusMode::Single`) compare equal.
+    pub fn consistent_cmp(&self, other: &Self) -> Ordering {
+        ObservationHash::from(self).cmp(&ObservationHash::from(other))
+    }
 }
 
 impl<T: NetworkEvent, P: PublicId> Debug for Observation<T, P> {
@@ -131,6 +162,7 @@ impl<T: NetworkEvent, P: PublicId> Debug for Observation<T, P> {
                 dkg_result,
             } => write!(formatter, "({:?}, {:?})", participants, dkg_result),
             Observation::DkgMessage(msg) => write!(formatter, "{:?}", msg),
+            Observation::Heartbeat(index) => write!(formatter, "Heartbeat({})", index),
             Observation::OpaquePayload(payload) => {
                 write!(formatter, "OpaquePayload({:?})", payload)
             }
@@ -172,6 +204,72 @@ pub enum Malice<T: NetworkEvent, P: PublicId> {
     Accomplice(EventHash, Box<Malice<T, P>>),
 }
 
+/// Kind of a `Malice`, without the events it refers to.  Useful e.g. to count accusations by
+/// kind.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub enum MaliceKind {
+    /// See `Malice::UnexpectedGenesis`.
+    UnexpectedGenesis,
+    /// See `Malice::DuplicateVote`.
+    DuplicateVote,
+    /// See `Malice::MissingGenesis`.
+    MissingGenesis,
+    /// See `Malice::IncorrectGenesis`.
+    IncorrectGenesis,
+    /// See `Malice::Fork`.
+    Fork,
+    /// See `Malice::InvalidAccusation`.
+    InvalidAccusation,
+    /// See `Malice::OtherParentBySameCreator`.
+    OtherParentBySameCreator,
+    /// See `Malice::SelfParentByDifferentCreator`.
+    SelfParentByDifferentCreator,
+    /// See `Malice::InvalidRequest`.
+    InvalidRequest,
+    /// See `Malice::InvalidResponse`.
+    InvalidResponse,
+    /// See `Malice::Unprovable`.
+    Unprovable,
+    /// See `Malice::Accomplice`.
+    Accomplice,
+}
+
+impl Display for MaliceKind {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        Debug::fmt(self, formatter)
+    }
+}
+
+impl<T: NetworkEvent, P: PublicId> Malice<T, P> {
+    /// Returns the kind of this malice.
+    pub fn kind(&self) -> MaliceKind {
+        match self {
+            Malice::UnexpectedGenesis(_) => MaliceKind::UnexpectedGenesis,
+            Malice::DuplicateVote(_, _) => MaliceKind::DuplicateVote,
+            Malice::MissingGenesis(_) => MaliceKind::MissingGenesis,
+            Malice::IncorrectGenesis(_) => MaliceKind::IncorrectGenesis,
+            Malice::Fork(_) => MaliceKind::Fork,
+            Malice::InvalidAccusation(_) => MaliceKind::InvalidAccusation,
+            Malice::OtherParentBySameCreator(_) => MaliceKind::OtherParentBySameCreator,
+            Malice::SelfParentByDifferentCreator(_) => MaliceKind::SelfParentByDifferentCreator,
+            Malice::InvalidRequest(_) => MaliceKind::InvalidRequest,
+            Malice::InvalidResponse(_) => MaliceKind::InvalidResponse,
+            Malice::Unprovable(_) => MaliceKind::Unprovable,
+            Malice::Accomplice(_, _) => MaliceKind::Accomplice,
+        }
+    }
+}
+
+// Only names the kind of malice, e.g. "Fork", or "Accomplice(Fork)" for an accomplice to a fork.
+impl<T: NetworkEvent, P: PublicId> Display for Malice<T, P> {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            Malice::Accomplice(_, malice) => write!(formatter, "Accomplice({})", malice),
+            _ => write!(formatter, "{}", self.kind()),
+        }
+    }
+}
+
 #[cfg(any(all(test, feature = "mock"), feature = "testing"))]
 #[derive(Debug)]
 pub(crate) enum MaliceInput {
@@ -224,6 +322,37 @@ impl<T: NetworkEvent, P: PublicId> Malice<T, P> {
     }
 }
 
+/// A change to the section membership, as decided by a stable block.  See
+/// [Parsec::poll_membership](struct.Parsec.html#method.poll_membership).
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum MembershipChange<P: PublicId> {
+    /// The members of the genesis group.
+    Genesis(BTreeSet<P>),
+    /// The indicated peer has been added to the section.
+    Added(P),
+    /// The indicated peer has been removed from the section.
+    Removed(P),
+}
+
+impl<P: PublicId> MembershipChange<P> {
+    // Consensus on an accusation removes the offender too, unless the fork policy kept it, as
+    // indicated by `offender_removed`.
+    pub(crate) fn from_observation<T: NetworkEvent>(
+        observation: &Observation<T, P>,
+        offender_removed: bool,
+    ) -> Option<Self> {
+        match observation {
+            Observation::Genesis { group, .. } => Some(MembershipChange::Genesis(group.clone())),
+            Observation::Add { peer_id, .. } => Some(MembershipChange::Added(peer_id.clone())),
+            Observation::Remove { peer_id, .. } => Some(MembershipChange::Removed(peer_id.clone())),
+            Observation::Accusation { offender, .. } if offender_removed => {
+                Some(MembershipChange::Removed(offender.clone()))
+            }
+            _ => None,
+        }
+    }
+}
+
 // For internal diagnostics only. The value is ignored in comparison, ordering or hashing.
 #[derive(Clone, Debug)]
 pub enum UnprovableMalice {
@@ -283,8 +412,9 @@ impl<'a> Visitor<'a> for UnprovableMaliceVisitor {
     }
 }
 
+/// Hash of an `Observation`, as used internally to identify it.
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
-pub(crate) struct ObservationHash(pub(crate) Hash);
+pub struct ObservationHash(pub(crate) Hash);
 
 impl<'a, T: NetworkEvent, P: PublicId> From<&'a Observation<T, P>> for ObservationHash {
     fn from(observation: &'a Observation<T, P>) -> Self {
@@ -299,7 +429,7 @@ impl Debug for ObservationHash {
 }
 
 // Container for observation with its metadata.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) struct ObservationInfo<T: NetworkEvent, P: PublicId> {
     pub(crate) observation: Observation<T, P>,
     pub(crate) consensused: bool,
@@ -359,6 +489,7 @@ impl ObservationKey {
     }
 
     /// Compare `ObservationKey`s to achieve ordering that is consistent among different nodes.
+    /// Keys for different observations are ordered as by `Observation::consistent_cmp`.
     pub fn consistent_cmp<S: SecretId>(&self, other: &Self, peer_list: &PeerList<S>) -> Ordering {
         self.hash().cmp(other.hash()).then_with(|| {
             let lhs_peer_id = self
@@ -372,9 +503,44 @@ impl ObservationKey {
             lhs_peer_id.cmp(&rhs_peer_id)
         })
     }
+
+    /// Returns a value which displays this key as `Single(<short-hash>, <peer-id>)` or
+    /// `Supermajority(<short-hash>)`, resolving the peer index through `peer_list`.
+    pub fn display<'a, S: SecretId>(
+        &'a self,
+        peer_list: &'a PeerList<S>,
+    ) -> ObservationKeyDisplay<'a, S> {
+        ObservationKeyDisplay {
+            key: self,
+            peer_list,
+        }
+    }
+}
+
+/// Human-readable rendering of an `ObservationKey`, as returned by `ObservationKey::display`.
+pub(crate) struct ObservationKeyDisplay<'a, S: SecretId> {
+    key: &'a ObservationKey,
+    peer_list: &'a PeerList<S>,
+}
+
+impl<'a, S: SecretId> Display for ObservationKeyDisplay<'a, S> {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        let short_hash = self.key.hash().0.short_display();
+        match *self.key {
+            ObservationKey::Single(_, peer_index) => match self.peer_list.get(peer_index) {
+                Some(peer) => write!(formatter, "Single({}, {:?})", short_hash, peer.id()),
+                None => write!(formatter, "Single({}, {:?})", short_hash, peer_index),
+            },
+            ObservationKey::Supermajority(_) => write!(formatter, "Supermajority({})", short_hash),
+        }
+    }
 }
 
 /// Number of votes necessary to reach consensus on an `OpaquePayload`.
+///
+/// The mode passed to the `Parsec` constructors applies to every opaque payload, unless a
+/// different one has been registered for the payload's [tag](trait.NetworkEvent.html#method.tag)
+/// via [`ParsecBuilder::consensus_mode_for`](struct.ParsecBuilder.html#method.consensus_mode_for).
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum ConsensusMode {
     /// One vote is enough.
@@ -383,14 +549,43 @@ pub enum ConsensusMode {
     Supermajority,
 }
 
-impl ConsensusMode {
-    pub(crate) fn of<T: NetworkEvent, P: PublicId>(self, observation: &Observation<T, P>) -> Self {
-        if observation.is_opaque() {
-            self
-        } else if observation.is_dkg_message() {
-            ConsensusMode::Single
-        } else {
-            ConsensusMode::Supermajority
+// The consensus modes of opaque payloads: a global one, optionally overridden by payload tag.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct ConsensusModes {
+    global: ConsensusMode,
+    by_tag: BTreeMap<u32, ConsensusMode>,
+}
+
+impl ConsensusModes {
+    pub fn new(global: ConsensusMode) -> Self {
+        Self {
+            global,
+            by_tag: BTreeMap::new(),
+        }
+    }
+
+    pub fn global(&self) -> ConsensusMode {
+        self.global
+    }
+
+    pub fn set(&mut self, tag: u32, mode: ConsensusMode) {
+        let _ = self.by_tag.insert(tag, mode);
+    }
+
+    // Internal observations always need a supermajority, except DKG messages and heartbeats which
+    // only need to be seen once.
+    pub fn of<T: NetworkEvent, P: PublicId>(
+        &self,
+        observation: &Observation<T, P>,
+    ) -> ConsensusMode {
+        match *observation {
+            Observation::OpaquePayload(ref payload) => self
+                .by_tag
+                .get(&payload.tag())
+                .cloned()
+                .unwrap_or(self.global),
+            Observation::DkgMessage(_) | Observation::Heartbeat(_) => ConsensusMode::Single,
+            _ => ConsensusMode::Supermajority,
         }
     }
 }
@@ -400,13 +595,37 @@ pub fn is_more_than_two_thirds(small: usize, large: usize) -> bool {
     3 * small > 2 * large
 }
 
-#[cfg(any(all(test, feature = "mock"), feature = "dump-graphs"))]
+/// Returns the smallest count which is more than two thirds of `total`, i.e. the smallest `small`
+/// for which `is_more_than_two_thirds(small, total)` holds.
+pub fn supermajority(total: usize) -> usize {
+    2 * total / 3 + 1
+}
+
+/// Serialises `entries` into a `related_info` value which doesn't depend on the order in which the
+/// entries are yielded.
+///
+/// The `related_info` of `Add` and `Remove` observations is hashed and signed along with the rest
+/// of the observation, so peers which independently compute it (e.g. from a `HashMap`) must
+/// produce exactly the same bytes, otherwise their votes are treated as votes for different
+/// observations and may never reach consensus.  The entries are sorted by key before being serialised; if the
+/// same key is yielded more than once, the last value wins.
+pub fn canonical_related_info<I, K, V>(entries: I) -> Vec<u8>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: Ord + Serialize + Debug,
+    V: Serialize + Debug,
+{
+    let entries: BTreeMap<K, V> = entries.into_iter().collect();
+    serialise(&entries)
+}
+
+#[cfg(any(test, feature = "testing", feature = "dump-graphs"))]
 pub(crate) mod snapshot {
     use super::*;
     use crate::{id::SecretId, peer_list::PeerList};
 
-    #[serde(bound = "")]
     #[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
+    #[serde(bound = "")]
     pub(crate) enum ObservationKeySnapshot<P: PublicId> {
         Supermajority(ObservationHash),
         Single(ObservationHash, P),
@@ -435,6 +654,19 @@ mod tests {
     use super::*;
     use crate::mock::{PeerId, Transaction};
 
+    #[test]
+    fn supermajority_is_smallest_count_more_than_two_thirds() {
+        for total in 0..20 {
+            let threshold = supermajority(total);
+            assert!(is_more_than_two_thirds(threshold, total));
+            assert!(threshold == 0 || !is_more_than_two_thirds(threshold - 1, total));
+        }
+        assert_eq!(supermajority(1), 1);
+        assert_eq!(supermajority(3), 3);
+        assert_eq!(supermajority(4), 3);
+        assert_eq!(supermajority(7), 5);
+    }
+
     #[test]
     fn malice_comparison_and_hashing_ignores_unprovable_value() {
         let malice1 = Malice::Unprovable::<Transaction, PeerId>(UnprovableMalice::Spam);
@@ -450,6 +682,253 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "mock")]
+    #[test]
+    fn malice_kind_and_display() {
+        let hash = EventHash::ZERO;
+        let fork = Malice::<Transaction, PeerId>::Fork(hash);
+        assert_eq!(fork.kind(), MaliceKind::Fork);
+        assert_eq!(fork.to_string(), "Fork");
+
+        let duplicate_vote = Malice::<Transaction, PeerId>::DuplicateVote(hash, hash);
+        assert_eq!(duplicate_vote.kind(), MaliceKind::DuplicateVote);
+        assert_eq!(duplicate_vote.to_string(), "DuplicateVote");
+
+        let accomplice = Malice::Accomplice(hash, Box::new(fork));
+        assert_eq!(accomplice.kind(), MaliceKind::Accomplice);
+        assert_eq!(accomplice.to_string(), "Accomplice(Fork)");
+
+        let unprovable = Malice::<Transaction, PeerId>::Unprovable(UnprovableMalice::Spam);
+        assert_eq!(unprovable.kind(), MaliceKind::Unprovable);
+        assert_eq!(unprovable.to_string(), "Unprovable");
+    }
+
+    #[test]
+    fn canonical_related_info_ignores_entry_order() {
+        let entries = vec![("age", 3u32), ("section", 7), ("prefix", 1)];
+        let reversed = entries.iter().rev().cloned().collect::<Vec<_>>();
+
+        let info1 = canonical_related_info(entries);
+        let info2 = canonical_related_info(reversed);
+        assert_eq!(info1, info2);
+
+        let peer_id = PeerId::new("Alice");
+        let add1 = Observation::<Transaction, PeerId>::Add {
+            peer_id: peer_id.clone(),
+            related_info: info1,
+        };
+        let add2 = Observation::<Transaction, PeerId>::Add {
+            peer_id,
+            related_info: info2,
+        };
+        assert_eq!(ObservationHash::from(&add1), ObservationHash::from(&add2));
+    }
+
+    #[test]
+    fn observation_key_display_resolves_peer_id() {
+        let peer_list = PeerList::new(PeerId::new("Alice"));
+        let observation = Observation::<Transaction, PeerId>::OpaquePayload(Transaction::new("A"));
+        let hash = ObservationHash::from(&observation);
+        let short_hash = hash.0.short_display().to_string();
+
+        let key = ObservationKey::new(hash, PeerIndex::OUR, ConsensusMode::Single);
+        assert_eq!(
+            key.display(&peer_list).to_string(),
+            format!("Single({}, Alice)", short_hash)
+        );
+
+        let key = ObservationKey::new(hash, PeerIndex::OUR, ConsensusMode::Supermajority);
+        assert_eq!(
+            key.display(&peer_list).to_string(),
+            format!("Supermajority({})", short_hash)
+        );
+    }
+
+    #[test]
+    fn observation_consistent_cmp_matches_key_order() {
+        let peer_list = PeerList::new(PeerId::new("Alice"));
+        let observations = ["A", "B", "C", "D"]
+            .iter()
+            .map(|payload| {
+                Observation::<Transaction, PeerId>::OpaquePayload(Transaction::new(*payload))
+            })
+            .collect::<Vec<_>>();
+        let key = |observation| {
+            ObservationKey::new(
+                ObservationHash::from(observation),
+                PeerIndex::OUR,
+                ConsensusMode::Supermajority,
+            )
+        };
+
+        for lhs in &observations {
+            assert_eq!(lhs.consistent_cmp(lhs), Ordering::Equal);
+            for rhs in &observations {
+                assert_eq!(
+                    lhs.consistent_cmp(rhs),
+                    key(lhs).consistent_cmp(&key(rhs), &peer_list)
+                );
+                assert_eq!(lhs.consistent_cmp(rhs), rhs.consistent_cmp(lhs).reverse());
+            }
+        }
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn observation_serialisation_round_trip() {
+        use crate::{
+            dev_utils::{new_common_rng, RngChoice},
+            key_gen::{KeyGen, PartOutcome},
+        };
+
+        // Run a DKG on our own to get genuine `Part`, `Ack` and `DkgResult` values.
+        let mut rng = new_common_rng(RngChoice::Seeded([1, 2, 3, 4]));
+        let alice = PeerId::new("Alice");
+        let bob = PeerId::new("Bob");
+        let group: BTreeSet<_> = vec![alice.clone()].into_iter().collect();
+        let (mut key_gen, part) = unwrap!(KeyGen::new(&alice, group.clone(), 0, &mut rng));
+        let part = unwrap!(part);
+        let ack = match unwrap!(key_gen.handle_part(&alice, &alice, part.clone())) {
+            PartOutcome::Valid(Some(ack)) => ack,
+            _ => panic!("Expected our own part to be valid"),
+        };
+        let _ = unwrap!(key_gen.handle_ack(&alice, &alice, ack.clone()));
+        let (participants, dkg_result) = unwrap!(key_gen.generate());
+        assert!(dkg_result.secret_key_share.is_some());
+
+        let accusation = |malice| Observation::Accusation {
+            offender: bob.clone(),
+            malice,
+        };
+        let observations: Vec<Observation<Transaction, PeerId>> = vec![
+            Observation::Genesis {
+                group: group.clone(),
+                related_info: vec![1, 2, 3],
+            },
+            Observation::Add {
+                peer_id: bob.clone(),
+                related_info: vec![4],
+            },
+            Observation::Remove {
+                peer_id: bob.clone(),
+                related_info: vec![],
+            },
+            accusation(Malice::Fork(EventHash::ZERO)),
+            accusation(Malice::DuplicateVote(EventHash::ZERO, EventHash::ZERO)),
+            accusation(Malice::Unprovable(UnprovableMalice::Spam)),
+            accusation(Malice::Accomplice(
+                EventHash::ZERO,
+                Box::new(Malice::Unprovable(UnprovableMalice::Unspecified)),
+            )),
+            Observation::OpaquePayload(Transaction::new("A")),
+            Observation::StartDkg(group),
+            Observation::DkgResult {
+                participants,
+                dkg_result: DkgResultWrapper(dkg_result),
+            },
+            Observation::DkgMessage(DkgMessage::Part {
+                key_gen_id: 0,
+                part,
+            }),
+            Observation::DkgMessage(DkgMessage::Ack { key_gen_id: 1, ack }),
+            Observation::Heartbeat(5),
+        ];
+
+        for observation in &observations {
+            let deserialised: Observation<Transaction, PeerId> =
+                unwrap!(bincode::deserialize(&serialise(observation)));
+            assert_eq!(deserialised, *observation);
+            assert_eq!(
+                ObservationHash::from(&deserialised),
+                ObservationHash::from(observation)
+            );
+
+            // Listing every variant here makes this test fail to build until new ones are added
+            // to `observations`.
+            match deserialised {
+                // Secret key shares are never sent over the wire.
+                Observation::DkgResult { dkg_result, .. } => {
+                    assert!(dkg_result.0.secret_key_share.is_none())
+                }
+                Observation::Genesis { .. }
+                | Observation::Add { .. }
+                | Observation::Remove { .. }
+                | Observation::Accusation { .. }
+                | Observation::OpaquePayload(_)
+                | Observation::StartDkg(_)
+                | Observation::DkgMessage(_)
+                | Observation::Heartbeat(_) => (),
+            }
+        }
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn accusations_remove_offender_unless_fork_policy_keeps_it() {
+        use crate::parsec::ForkPolicy;
+
+        let bob = PeerId::new("Bob");
+        let fork = Malice::<Transaction, PeerId>::Fork(EventHash::ZERO);
+        let duplicate_vote =
+            Malice::<Transaction, PeerId>::DuplicateVote(EventHash::ZERO, EventHash::ZERO);
+        for &policy in &[ForkPolicy::AccuseOnly, ForkPolicy::AccuseAndIsolate] {
+            assert!(!policy.removes_offender(&fork));
+            assert!(policy.removes_offender(&duplicate_vote));
+        }
+        let policy = ForkPolicy::AccuseAndProposeRemoval;
+        assert!(policy.removes_offender(&fork));
+        assert!(policy.removes_offender(&duplicate_vote));
+
+        // Only the accusations which actually removed the offender change the membership.
+        let accusation = Observation::<Transaction, PeerId>::Accusation {
+            offender: bob.clone(),
+            malice: fork,
+        };
+        assert_eq!(MembershipChange::from_observation(&accusation, false), None);
+        assert_eq!(
+            MembershipChange::from_observation(&accusation, true),
+            Some(MembershipChange::Removed(bob))
+        );
+    }
+
+    // Serialised observations are hashed, and peers must agree on these hashes, so their
+    // encoding must not depend on the platform or on the order peers were inserted in.
+    #[cfg(feature = "mock")]
+    #[test]
+    fn genesis_serialisation_golden_bytes() {
+        let observation = |names: &[&str]| Observation::<Transaction, PeerId>::Genesis {
+            group: names.iter().map(|name| PeerId::new(name)).collect(),
+            related_info: vec![7, 8, 9],
+        };
+        let serialised = serialise(&observation(&["Carol", "Alice", "Bob"]));
+        assert_eq!(
+            serialised,
+            serialise(&observation(&["Bob", "Carol", "Alice"]))
+        );
+
+        // Variant index, then the group in `Ord` order, each peer starting with its name.
+        assert_eq!(serialised.len(), 252);
+        assert_eq!(
+            serialised[..25],
+            [
+                0, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 5, 0, 0, 0, 0, 0, 0, 0, b'A', b'l', b'i', b'c',
+                b'e'
+            ]
+        );
+        // Related info last.
+        assert_eq!(serialised[241..], [3, 0, 0, 0, 0, 0, 0, 0, 7, 8, 9]);
+
+        // The hash covers all the bytes, including the mock peers' keys.
+        let expected_hash = [
+            37, 38, 232, 189, 90, 114, 169, 41, 76, 95, 133, 133, 155, 234, 128, 2, 224, 135, 164,
+            229, 109, 119, 8, 231, 46, 116, 215, 6, 137, 7, 59, 165,
+        ];
+        assert_eq!(
+            ObservationHash::from(&observation(&["Alice", "Bob", "Carol"])),
+            ObservationHash(Hash::from_bytes(expected_hash))
+        );
+    }
+
     #[test]
     fn unprovable_malice_is_deserialisable() {
         let before = Malice::Unprovable::<Transaction, PeerId>(UnprovableMalice::Spam);
@@ -6,32 +6,44 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-#[cfg(all(test, feature = "mock"))]
+#[cfg(any(
+    all(test, feature = "mock"),
+    all(feature = "testing", feature = "dump-graphs")
+))]
 use crate::dev_utils::ParsedContents;
-#[cfg(all(test, feature = "malice-detection", feature = "mock"))]
-use crate::gossip::EventHash;
-#[cfg(all(test, any(feature = "testing", feature = "mock")))]
+#[cfg(all(feature = "testing", feature = "dump-graphs"))]
+use crate::dev_utils::{parse_dot_file, Snapshot};
+#[cfg(any(
+    all(test, any(feature = "testing", feature = "mock")),
+    all(feature = "testing", feature = "dump-graphs")
+))]
 use crate::gossip::GraphSnapshot;
+#[cfg(any(test, feature = "testing"))]
+use crate::meta_voting::MetaElectionSnapshot;
 #[cfg(feature = "malice-detection")]
-use crate::observation::Malice;
+use crate::observation::UnprovableMalice;
 use crate::{
     block::{Block, BlockGroup},
+    consensus_chain::ConsensusChain,
     dump_graph,
     error::{Error, Result},
     gossip::{
-        Event, EventContextRef, EventIndex, Graph, IndexedEventRef, PackedEvent, Request, Response,
+        Event, EventContextRef, EventDescriptor, EventHash, EventIndex, Graph, HaveSummary,
+        IndexedEventRef, PackedEvent, PendingRequest, Request, RequestProgress, Response,
     },
+    growth_rate::{Clock, GrowthRate},
     id::{PublicId, SecretId},
     key_gen::{
         dkg_threshold, message::DkgMessage, parsec_rng::ParsecRng, Ack, AckOutcome, KeyGen, Part,
         PartOutcome,
     },
-    meta_voting::{MetaElection, MetaEvent, MetaEventBuilder, MetaVote, Observer},
+    meta_voting::{MetaElection, MetaEvent, MetaEventBuilder, MetaVote, Observer, VoteWeights},
     network_event::NetworkEvent,
     observation::{
-        is_more_than_two_thirds, ConsensusMode, Observation, ObservationHash, ObservationKey,
-        ObservationStore,
+        supermajority, ConsensusMode, ConsensusModes, Malice, MembershipChange, Observation,
+        ObservationHash, ObservationInfo, ObservationKey, ObservationStore,
     },
+    parsec_builder::ParsecBuilder,
     parsec_helpers::find_interesting_content_for_event,
     peer_list::{Peer, PeerIndex, PeerIndexMap, PeerIndexSet, PeerList, PeerListChange, PeerState},
 };
@@ -41,20 +53,186 @@ use crate::{
     mock::{PeerId, Transaction},
 };
 use itertools::Itertools;
+#[cfg(any(test, feature = "testing"))]
+use rand::rngs::mock::StepRng;
 use rand::RngCore;
 #[cfg(any(test, feature = "testing"))]
 use std::ops::{Deref, DerefMut};
 use std::{
+    cell::RefCell,
+    cmp,
     collections::{BTreeMap, BTreeSet, VecDeque},
     iter,
     marker::PhantomData,
-    mem,
+    mem::{self, size_of},
     num::NonZeroUsize,
+    rc::Rc,
+    time::Duration,
     usize,
 };
+#[cfg(all(feature = "testing", feature = "dump-graphs"))]
+use std::{fs, io, path::Path};
 
 pub(crate) type KeyGenId = usize;
 
+// Callback receiving the retained blocks evicted to respect the cap set by
+// `Parsec::set_block_archive`.
+pub(crate) type OnArchive<T, P> = Box<dyn FnMut(Block<T, P>)>;
+// Shared, like the clock, by the copies made by `clone_with_rng`.
+type BlockArchive<T, P> = Rc<RefCell<OnArchive<T, P>>>;
+
+#[cfg(feature = "malice-detection")]
+type SharedAccusationHook<T, P> = Rc<RefCell<AccusationHook<T, P>>>;
+
+pub(crate) const DEFAULT_UNKNOWN_PEER_POLICY: UnknownPeerPolicy = UnknownPeerPolicy::Buffer {
+    capacity: 1000,
+    max_blocks: 10,
+};
+
+/// Determines when the owning peer creates a new gossip event while handling a gossip message.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EventCreationPolicy {
+    /// A sync event is created for every `Request` and `Response` handled.
+    Always,
+    /// A sync event is created for every `Request`, but a `Response` only results in a new event
+    /// if it taught us something, i.e. if the sender's latest event isn't already an ancestor of
+    /// our latest event.  Such a redundant event would carry no new ancestry, so skipping it
+    /// doesn't change which events are seen or strongly seen.
+    ///
+    /// `Request` events are always created, since the sender's `Response` event must follow our
+    /// `Request` event to be a valid sync event.
+    SkipRedundantResponses,
+}
+
+/// Determines whether the owning peer initiates gossip when it has nothing new to tell.  See
+/// [Parsec::should_gossip_to](struct.Parsec.html#method.should_gossip_to).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GossipPolicy {
+    /// Every gossip opportunity is taken.
+    Always,
+    /// Gossip is only initiated towards a peer which hasn't seen all our events other than sync
+    /// events, e.g. votes, or while any meta-election is undecided.  Sync events are ignored as
+    /// every gossip exchange creates some, so counting them would never let the section go idle.
+    OnChange,
+    /// As `OnChange`, but an idle peer still gossips every so often, skipping twice as many
+    /// opportunities each time up to `MAX_IDLE_GOSSIP_BACKOFF`.  The backoff is reset as soon as
+    /// there is something new to tell.
+    Adaptive,
+}
+
+/// Maximum number of consecutive gossip opportunities skipped under `GossipPolicy::Adaptive`.
+pub const MAX_IDLE_GOSSIP_BACKOFF: usize = 64;
+
+/// Largest weight accepted by `Parsec::set_voter_weights`.  This keeps the weighted vote counts,
+/// which get tripled in the supermajority checks, from overflowing a 32-bit `usize` in sections of
+/// up to twenty thousand voters.
+pub const MAX_VOTER_WEIGHT: usize = 1 << 16;
+
+/// Determines what happens to a peer once an accusation of it having forked has been consensused.
+/// Set at construction, see
+/// [ParsecBuilder::fork_policy](struct.ParsecBuilder.html#method.fork_policy).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ForkPolicy {
+    /// The accusation is only reported via the accusation block: the forker remains a member of
+    /// the section.
+    AccuseOnly,
+    /// As `AccuseOnly`, but we additionally stop gossiping to the forker.  We still accept gossip
+    /// from it, as well-behaved peers may keep relaying its events.
+    AccuseAndIsolate,
+    /// The forker is removed from the section, as it would be by consensus on an
+    /// `Observation::Remove` for it.  This is the default.
+    AccuseAndProposeRemoval,
+}
+
+impl ForkPolicy {
+    /// Returns whether consensus on an accusation of `malice` removes the offender from the
+    /// section under this policy.
+    pub(crate) fn removes_offender<T: NetworkEvent, P: PublicId>(
+        self,
+        malice: &Malice<T, P>,
+    ) -> bool {
+        match *malice {
+            Malice::Fork(_) => self == ForkPolicy::AccuseAndProposeRemoval,
+            _ => true,
+        }
+    }
+}
+
+/// Verdict of the [accusation hook](struct.Parsec.html#method.set_accusation_hook) on an
+/// accusation we're about to raise.
+#[cfg(feature = "malice-detection")]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AccusationDecision {
+    /// The accusation is raised as usual, i.e. voted for along with our next sync event.
+    Approve,
+    /// The accusation is dropped: we won't vote for it.
+    Veto,
+}
+
+/// Callback called with the offender and the malice of each accusation we're about to raise.  See
+/// [Parsec::set_accusation_hook](struct.Parsec.html#method.set_accusation_hook).
+#[cfg(feature = "malice-detection")]
+pub type AccusationHook<T, P> = Box<dyn FnMut(&P, &Malice<T, P>) -> AccusationDecision>;
+
+/// Determines what happens to received events which refer to peers we don't know yet, e.g.
+/// because the sender already reached consensus on adding them but we haven't.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UnknownPeerPolicy {
+    /// The message is rejected with `Error::UnknownPeer` at the first such event, so its remaining
+    /// events have to be gossiped to us again.
+    Reject,
+    /// Such events, and the events of the same messages descending from them, are put aside and
+    /// added once we know the peers they refer to.  The rest of the message is handled as usual,
+    /// but if its last event was put aside, it still fails with `Error::UnknownPeer`.  Their
+    /// signatures are verified before they are put aside, as they would be if they were added.
+    ///
+    /// At most `capacity` events are kept.  Once full, the oldest event relayed by the peer which
+    /// relayed the most of them is evicted, so that a single peer can't crowd out the others.
+    /// Events still put aside once `max_blocks` more blocks have been consensused are dropped.
+    Buffer {
+        /// The maximum number of events put aside.
+        capacity: usize,
+        /// The number of blocks after which an event put aside is dropped.
+        max_blocks: usize,
+    },
+}
+
+/// How far a peer lags behind the gossip graph of the owning peer.  See
+/// [Parsec::peer_responsiveness](struct.Parsec.html#method.peer_responsiveness).
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct Responsiveness {
+    /// The number of events in our gossip graph which the latest event created by the peer doesn't
+    /// descend from, i.e. which the peer hadn't seen when it last gossiped as far as we know.  If
+    /// we have no event by the peer yet, this is the size of our whole graph.
+    pub events_behind: usize,
+    /// Whether our gossip graph holds any event created by the peer.
+    pub heard_from: bool,
+}
+
+/// Sizes of the state held by a `Parsec` instance.  See
+/// [Parsec::state_sizes](struct.Parsec.html#method.state_sizes).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct StateSizes {
+    /// The number of events in the gossip graph.
+    pub graph_events: usize,
+    /// The number of observations voted for or consensused which are still tracked.
+    pub observations: usize,
+    /// The number of meta-events of the current meta-election.
+    pub meta_events: usize,
+    /// The number of events carrying a payload which hasn't been consensused yet.
+    pub unconsensused_events: usize,
+    /// The number of payload keys kept in the consensus history.  See
+    /// [Parsec::set_consensus_history_retention](struct.Parsec.html#method.set_consensus_history_retention).
+    pub consensus_history: usize,
+    /// The number of stable blocks kept for
+    /// [Parsec::blocks](struct.Parsec.html#method.blocks).
+    pub retained_blocks: usize,
+    /// A coarse estimate of the memory taken by all of the above, in bytes.  Only the fixed size
+    /// of each entry is counted, so data on the heap, such as the contents of the payloads or the
+    /// meta-votes, is not.
+    pub estimated_bytes: usize,
+}
+
 /// The main object which manages creating and receiving gossip about network events from peers, and
 /// which provides a sequence of consensused [Block](struct.Block.html)s by applying the PARSEC
 /// algorithm. A `Block`'s payload, described by the [Observation](enum.Observation.html) type, is
@@ -106,16 +284,66 @@ pub struct Parsec<T: NetworkEvent, S: SecretId> {
     observations: ObservationStore<T, S::PublicId>,
     // Consensused network events that have not been returned via `poll()` yet.
     consensused_blocks: VecDeque<BlockGroup<T, S::PublicId>>,
+    // Number of blocks consensused so far, whether already returned via `poll()` or not.
+    num_stable_blocks: usize,
+    // Index, in the consensus order, of the block at the front of `consensused_blocks`.
+    next_block_index: usize,
+    // Indices of blocks after `next_block_index` which have already been taken out of
+    // `consensused_blocks` by `poll_membership()`.
+    membership_polled: BTreeSet<usize>,
+    // Indices of the accusation blocks not taken by `poll_membership()` yet whose consensus
+    // removed the offender.
+    removals_by_accusation: BTreeSet<usize>,
+    retain_blocks: bool,
+    // Copies of the stable blocks, kept for `blocks()` while `retain_blocks` is set.  The first
+    // one is at index `first_retained_block_index` in the consensus order.
+    retained_blocks: VecDeque<Block<T, S::PublicId>>,
+    first_retained_block_index: usize,
+    // Maximum number of `retained_blocks` once they have been returned by `poll()`, and the
+    // callback the evicted ones are passed to.
+    block_archive: Option<(usize, BlockArchive<T, S::PublicId>)>,
     // The map of meta votes of the events on each consensus block.
     meta_election: MetaElection,
-    consensus_mode: ConsensusMode,
+    consensus_modes: ConsensusModes,
+    event_creation_policy: EventCreationPolicy,
+    fork_policy: ForkPolicy,
+    gossip_policy: GossipPolicy,
+    // Gossip opportunities skipped towards each peer under `GossipPolicy::Adaptive`, and the
+    // number to skip before the next idle gossip.
+    idle_gossip_backoff: BTreeMap<PeerIndex, (usize, usize)>,
+    // Number of events received from each peer which were already in our graph.
+    redundant_events: BTreeMap<PeerIndex, usize>,
+    // Peers we sent a `HaveSummary` to, and whose response we haven't handled yet.
+    have_summaries_sent: BTreeSet<PeerIndex>,
+    // Peers whose addition we consensused but who haven't gossiped to us since, mapped to the
+    // value of `num_stable_blocks` at that point.
+    pending_joins: BTreeMap<PeerIndex, usize>,
+    join_stall_threshold: Option<usize>,
+    // Number of our events in a row without a payload after which we vote for a heartbeat.
+    heartbeat_interval: Option<usize>,
+    unknown_peer_policy: UnknownPeerPolicy,
+    // Weights of the voters in the supermajority checks.  Voters not in the map weigh 1.
+    voter_weights: BTreeMap<S::PublicId, usize>,
+    // Whether `handle_request_trusted` and `handle_response_trusted` may be used.
+    trusted_ingest: bool,
+    // Received events referring to peers we don't know yet, in the order we received them.
+    deferred_events: DeferredEvents<T, S::PublicId>,
+    // Value of `num_stable_blocks` when we last tried adding `deferred_events`.
+    deferred_events_retried_at: usize,
     // Dkg messages to raise at the end of processing of current gossip message.
     pending_dkg_msgs: Vec<DkgMessage>,
     // Accusations to raise at the end of the processing of current gossip message.
     #[cfg(feature = "malice-detection")]
     pending_accusations: Accusations<T, S::PublicId>,
+    // False if malice detection has been disabled at runtime.
+    #[cfg(feature = "malice-detection")]
+    malice_detection_enabled: bool,
+    #[cfg(feature = "malice-detection")]
+    accusation_hook: Option<SharedAccusationHook<T, S::PublicId>>,
     // Events to be inserted into the gossip graph when this node becomes voter.
     pending_events: Vec<PendingEvent<T, S::PublicId>>,
+    // Rate at which events are being added to `graph`.
+    growth_rate: GrowthRate,
     // True to disable processing consensus on this instance to speed up processing for irrelevant
     // parsec instances.
     #[cfg(any(test, feature = "testing"))]
@@ -135,6 +363,13 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
     /// * `consensus_mode` determines how many votes are needed for an observation to become a
     /// candidate for consensus. For more details, see [ConsensusMode](enum.ConsensusMode.html)
     /// * `secure_rng` cryptographically secure RNG to use for DKG key generation.
+    ///
+    /// The genesis group must not be empty.  A group made of only us is supported: as the sole
+    /// voter, we reach consensus on each of our votes on our own, right when casting it, and can
+    /// grow the section from there by voting to add peers.  Such a section can't tolerate any
+    /// faulty member until it grows to at least four.
+    ///
+    /// This is a shorthand for the corresponding [ParsecBuilder](struct.ParsecBuilder.html).
     pub fn from_genesis(
         our_id: S,
         genesis_group: &BTreeSet<S::PublicId>,
@@ -142,23 +377,35 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         consensus_mode: ConsensusMode,
         secure_rng: Box<dyn RngCore>,
     ) -> Self {
-        if !genesis_group.contains(our_id.public_id()) {
-            log_or_panic!("Genesis group must contain us");
-        }
+        ParsecBuilder::from_genesis(our_id, genesis_group, secure_rng)
+            .genesis_related_info(genesis_related_info)
+            .consensus_mode(consensus_mode)
+            .build_or_log()
+    }
 
-        let mut peer_list = PeerList::new(our_id);
-        let genesis_indices: PeerIndexSet = genesis_group
+    // Construct `Parsec` for a member of the genesis group, without validating the arguments.
+    pub(crate) fn genesis_unchecked(
+        our_id: S,
+        genesis_group: &BTreeSet<S::PublicId>,
+        genesis_related_info: Vec<u8>,
+        consensus_mode: ConsensusMode,
+        secure_rng: Box<dyn RngCore>,
+    ) -> Self {
+        let mut peer_list = PeerList::with_capacity(our_id, genesis_group.len());
+        let mut genesis_indices = PeerIndexSet::new();
+        if genesis_group.contains(peer_list.our_pub_id()) {
+            peer_list.change_peer_state(PeerIndex::OUR, PeerState::active());
+            let _ = genesis_indices.insert(PeerIndex::OUR);
+        }
+        let others = genesis_group
             .iter()
-            .map(|peer_id| {
-                if peer_id == peer_list.our_pub_id() {
-                    let peer_index = PeerIndex::OUR;
-                    peer_list.change_peer_state(peer_index, PeerState::active());
-                    peer_index
-                } else {
-                    peer_list.add_peer(peer_id.clone(), PeerState::active())
-                }
-            })
-            .collect();
+            .filter(|peer_id| *peer_id != peer_list.our_pub_id())
+            .map(|peer_id| (peer_id.clone(), PeerState::active()))
+            .collect_vec();
+        match peer_list.add_peers(others) {
+            Ok(indices) => genesis_indices.extend(indices),
+            Err(error) => log_or_panic!("Failed to add the genesis group: {:?}", error),
+        }
 
         let mut parsec = Self::empty(peer_list, genesis_indices, consensus_mode, secure_rng);
 
@@ -194,6 +441,8 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
     /// * `consensus_mode` determines how many votes are needed for an observation to become a
     /// candidate for consensus. For more details, see [ConsensusMode](enum.ConsensusMode.html)
     /// * `secure_rng` cryptographically secure RNG to use for DKG key generation.
+    ///
+    /// This is a shorthand for the corresponding [ParsecBuilder](struct.ParsecBuilder.html).
     pub fn from_existing(
         our_id: S,
         genesis_group: &BTreeSet<S::PublicId>,
@@ -201,23 +450,21 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         consensus_mode: ConsensusMode,
         secure_rng: Box<dyn RngCore>,
     ) -> Self {
-        if genesis_group.is_empty() {
-            log_or_panic!("Genesis group can't be empty");
-        }
-
-        if genesis_group.contains(our_id.public_id()) {
-            log_or_panic!("Genesis group can't already contain us");
-        }
-
-        if section.is_empty() {
-            log_or_panic!("Section can't be empty");
-        }
-
-        if section.contains(our_id.public_id()) {
-            log_or_panic!("Section can't already contain us");
-        }
+        ParsecBuilder::from_existing(our_id, genesis_group, section, secure_rng)
+            .consensus_mode(consensus_mode)
+            .build_or_log()
+    }
 
-        let mut peer_list = PeerList::new(our_id);
+    // Construct `Parsec` for a peer joining an existing section, without validating the
+    // arguments.
+    pub(crate) fn existing_unchecked(
+        our_id: S,
+        genesis_group: &BTreeSet<S::PublicId>,
+        section: &BTreeSet<S::PublicId>,
+        consensus_mode: ConsensusMode,
+        secure_rng: Box<dyn RngCore>,
+    ) -> Self {
+        let mut peer_list = PeerList::with_capacity(our_id, section.len());
 
         // Add ourselves
         peer_list.change_peer_state(PeerIndex::OUR, PeerState::RECV);
@@ -239,6 +486,12 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         Self::empty(peer_list, genesis_indices, consensus_mode, secure_rng)
     }
 
+    // Only called at construction, see `ParsecBuilder::consensus_mode_for`.
+    pub(crate) fn with_consensus_mode_for(mut self, tag: u32, mode: ConsensusMode) -> Self {
+        self.consensus_modes.set(tag, mode);
+        self
+    }
+
     // Construct empty `Parsec` with no peers (except us) and no gossip events.
     fn empty(
         peer_list: PeerList<S>,
@@ -254,13 +507,40 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
             key_gen_next_id: KeyGenId::default(),
             graph: Graph::new(),
             consensused_blocks: VecDeque::new(),
+            num_stable_blocks: 0,
+            next_block_index: 0,
+            membership_polled: BTreeSet::new(),
+            removals_by_accusation: BTreeSet::new(),
+            retain_blocks: false,
+            retained_blocks: VecDeque::new(),
+            first_retained_block_index: 0,
+            block_archive: None,
             observations: BTreeMap::new(),
             meta_election: MetaElection::new(genesis_group),
-            consensus_mode,
+            consensus_modes: ConsensusModes::new(consensus_mode),
+            event_creation_policy: EventCreationPolicy::Always,
+            fork_policy: ForkPolicy::AccuseAndProposeRemoval,
+            gossip_policy: GossipPolicy::Always,
+            idle_gossip_backoff: BTreeMap::new(),
+            redundant_events: BTreeMap::new(),
+            have_summaries_sent: BTreeSet::new(),
+            pending_joins: BTreeMap::new(),
+            join_stall_threshold: None,
+            heartbeat_interval: None,
+            unknown_peer_policy: DEFAULT_UNKNOWN_PEER_POLICY,
+            voter_weights: BTreeMap::new(),
+            trusted_ingest: false,
+            deferred_events: DeferredEvents::new(),
+            deferred_events_retried_at: 0,
             pending_dkg_msgs: vec![],
             #[cfg(feature = "malice-detection")]
             pending_accusations: vec![],
+            #[cfg(feature = "malice-detection")]
+            malice_detection_enabled: true,
+            #[cfg(feature = "malice-detection")]
+            accusation_hook: None,
             pending_events: vec![],
+            growth_rate: GrowthRate::new(),
 
             #[cfg(any(test, feature = "testing"))]
             ignore_process_events: false,
@@ -274,22 +554,25 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         self.peer_list.our_pub_id()
     }
 
+    /// Returns an iterator with the IDs of all peers known to the owning peer, including its own
+    /// ID, which haven't been removed from the section.
+    pub fn peer_ids(&self) -> impl Iterator<Item = &S::PublicId> {
+        self.peer_list
+            .iter()
+            .filter(|(_, peer)| peer.removal_event().is_none())
+            .map(|(_, peer)| peer.id())
+    }
+
     /// Inserts the owning peer's vote for `observation` into the gossip graph. The subsequent
     /// gossip messages will spread the vote to other peers, eventually making it a candidate for
     /// the next consensused block.
     ///
-    /// Returns an error if the owning peer is not a full member of the section yet, if it has
-    /// already voted for this `observation`, or if adding a gossip event containing the vote to
-    /// the gossip graph failed.
+    /// Returns an error if `can_vote_for(&observation)` fails, or if adding a gossip event
+    /// containing the vote to the gossip graph failed.
     pub fn vote_for(&mut self, observation: Observation<T, S::PublicId>) -> Result<()> {
         debug!("{:?} voting for {:?}", self.our_pub_id(), observation);
 
-        self.confirm_self_state(PeerState::VOTE)?;
-
-        if self.have_voted_for(&observation) {
-            return Err(Error::DuplicateVote);
-        }
-
+        self.can_vote_for(&observation)?;
         self.flush_pending_events()?;
 
         let self_parent = self.our_last_event_index()?;
@@ -299,6 +582,68 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         self.create_sole_voter_gossip_event()
     }
 
+    /// Checks whether the owning peer can currently vote for `observation`.
+    ///
+    /// Returns `Error::InvalidObservation` if `observation` is output only (`Accusation` and
+    /// `DkgResult`) or internal only (`DkgMessage` and `Heartbeat`), as these are only ever
+    /// created by Parsec itself, or if it is an `OpaquePayload` failing `NetworkEvent::validate`,
+    /// as other peers would reject it.  Returns an error if the owning peer is not a full member
+    /// of the section yet, or if it has already voted for this `observation`.
+    pub fn can_vote_for(&self, observation: &Observation<T, S::PublicId>) -> Result<()> {
+        match *observation {
+            Observation::Accusation { .. }
+            | Observation::DkgResult { .. }
+            | Observation::DkgMessage(_)
+            | Observation::Heartbeat(_) => return Err(Error::InvalidObservation),
+            Observation::OpaquePayload(ref payload) => {
+                if !payload.validate() {
+                    return Err(Error::InvalidObservation);
+                }
+            }
+            Observation::Genesis { .. }
+            | Observation::Add { .. }
+            | Observation::Remove { .. }
+            | Observation::StartDkg(_) => (),
+        }
+
+        self.confirm_self_state(PeerState::VOTE)?;
+
+        if self.have_voted_for(observation) {
+            return Err(Error::DuplicateVote);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the IDs of the peers, including the owning peer, which haven't been removed from the
+    /// section and whose state includes all the flags of `state`.
+    ///
+    /// E.g. `peers_in_state(PeerState::VOTE)` returns the current voters, including those we
+    /// haven't received any gossip from yet.
+    pub fn peers_in_state(&self, state: PeerState) -> Vec<S::PublicId> {
+        self.peer_list
+            .iter()
+            .filter(|(_, peer)| peer.removal_event().is_none() && peer.state().contains(state))
+            .map(|(_, peer)| peer.id().clone())
+            .collect()
+    }
+
+    /// Returns the IDs of the peers whose addition was consensused more than the
+    /// [join stall threshold](#method.set_join_stall_threshold) blocks ago, but who haven't
+    /// gossiped to us since.  Such peers are voters which can't vote, so they count against the
+    /// supermajority until they either manage to sync or get removed.
+    pub fn stalled_joins(&self) -> Vec<&S::PublicId> {
+        let threshold = match self.join_stall_threshold {
+            Some(threshold) => threshold,
+            None => return vec![],
+        };
+        self.pending_joins
+            .iter()
+            .filter(|(_, &decided_at)| self.num_stable_blocks - decided_at > threshold)
+            .filter_map(|(&peer_index, _)| self.peer_list.get(peer_index).map(Peer::id))
+            .collect()
+    }
+
     /// Returns an iterator with the IDs of peers who the owning peer can send gossip messages to.
     /// Calling `create_gossip` with a peer ID returned by this method is guaranteed to succeed
     /// (assuming no section mutation happened in between).
@@ -308,13 +653,113 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
             .map(|(_, peer)| peer.id())
     }
 
+    /// Returns the same peers as `gossip_recipients`, each paired with the number of events in our
+    /// gossip graph which, as far as we know, it hasn't seen yet.
+    ///
+    /// This is an estimate based on the last event by that peer we know of, so it only decreases
+    /// once we receive gossip from it.  It is roughly the number of events a `create_gossip` for
+    /// that peer would send, and lets the application favour the recipients lagging furthest
+    /// behind.
+    pub fn gossip_recipients_with_staleness(&self) -> impl Iterator<Item = (&S::PublicId, usize)> {
+        self.peer_list
+            .gossip_recipients()
+            .map(move |(peer_index, peer)| (peer.id(), self.num_events_unseen_by(peer_index)))
+    }
+
+    /// Returns the responsiveness of every peer of the section other than the owning one, as seen
+    /// from our gossip graph.
+    ///
+    /// A peer which keeps gossiping has its latest event regularly replaced by one descending from
+    /// most of our graph, so its `events_behind` stays low, whereas for a straggler it grows with
+    /// every event we add.  The score only depends on which events our graph holds, not on the
+    /// order we received them in nor on any clock, so peers with the same graph compute the same
+    /// scores.  This can be used to decide which peers to propose removing, or to favour the
+    /// lagging ones as gossip recipients.
+    pub fn peer_responsiveness(&self) -> BTreeMap<S::PublicId, Responsiveness> {
+        self.peer_list
+            .iter()
+            .filter(|(peer_index, peer)| {
+                *peer_index != PeerIndex::OUR && peer.removal_event().is_none()
+            })
+            .map(|(peer_index, peer)| {
+                let responsiveness = Responsiveness {
+                    events_behind: self.num_events_unseen_by(peer_index),
+                    heard_from: self.peer_list.last_event(peer_index).is_some(),
+                };
+                (peer.id().clone(), responsiveness)
+            })
+            .collect()
+    }
+
+    /// Returns the number of events in our gossip graph.
+    pub fn events_len(&self) -> usize {
+        self.graph.len()
+    }
+
+    /// Returns the sizes of the main parts of our state, e.g. to see how much memory it takes or
+    /// the effect of [set_consensus_history_retention](#method.set_consensus_history_retention).
+    /// This only reads the lengths of the collections making up the state, so it doesn't depend
+    /// on the number of events.
+    pub fn state_sizes(&self) -> StateSizes {
+        let num_peers = self.peer_list.iter().count();
+        let meta_election = &self.meta_election;
+        let unconsensused_events = meta_election.unconsensused_events.ordered_indices.len();
+
+        let mut sizes = StateSizes {
+            graph_events: self.graph.len(),
+            observations: self.observations.len(),
+            meta_events: meta_election.meta_events.len(),
+            unconsensused_events,
+            consensus_history: meta_election.consensus_history.len(),
+            retained_blocks: self.retained_blocks.len(),
+            estimated_bytes: 0,
+        };
+        sizes.estimated_bytes = sizes.graph_events
+            * (Event::<S::PublicId>::estimated_size(num_peers) + size_of::<EventHash>())
+            + sizes.observations
+                * size_of::<(ObservationKey, ObservationInfo<T, S::PublicId>)>()
+            + sizes.meta_events * size_of::<(EventIndex, MetaEvent)>()
+            // Unconsensused events are indexed both in order and by payload.
+            + 2 * sizes.unconsensused_events * size_of::<EventIndex>()
+            + sizes.consensus_history * size_of::<ObservationKey>()
+            + sizes.retained_blocks * size_of::<Block<T, S::PublicId>>();
+        sizes
+    }
+
+    fn num_events_unseen_by(&self, peer_index: PeerIndex) -> usize {
+        let last_event = match self
+            .peer_list
+            .last_event(peer_index)
+            .and_then(|event_index| self.graph.get(event_index))
+        {
+            Some(last_event) => last_event,
+            None => return self.graph.len(),
+        };
+
+        let last_ancestors: PeerIndexMap<_> = last_event.last_ancestors().collect();
+        self.peer_list
+            .iter()
+            .map(|(creator, peer)| {
+                let first = last_ancestors
+                    .get(creator)
+                    .map(|index_by_creator| index_by_creator + 1)
+                    .unwrap_or(0);
+                peer.events_from(first).count()
+            })
+            .sum()
+    }
+
     /// Creates a new message to be gossiped to a peer, containing all gossip events this peer
     /// thinks that peer needs.  If the given peer is not an active node, an error is returned.
+    /// Returns `Error::SelfGossip` if `peer_id` is our own ID.
     ///
     /// * `peer_id`: the intended recipient of the gossip message
     /// * returns a `Request` to be sent to the intended recipient
     pub fn create_gossip(&mut self, peer_id: &S::PublicId) -> Result<Request<T, S::PublicId>> {
         let peer_index = self.get_peer_index(peer_id)?;
+        if peer_index == PeerIndex::OUR {
+            return Err(Error::SelfGossip);
+        }
         self.confirm_allowed_to_gossip_to(peer_index)?;
 
         self.add_requesting_event(peer_id)?;
@@ -327,6 +772,99 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         self.pack_events(events).map(Request::new)
     }
 
+    /// Returns whether the gossip policy lets us initiate gossip to `peer_id` now.  Intended to be
+    /// called every time the caller would otherwise call `create_gossip`; under
+    /// `GossipPolicy::Adaptive`, each call returning `false` counts as a skipped opportunity.
+    ///
+    /// Returns `true` for unknown peers and for our own ID, so that the caller's `create_gossip`
+    /// reports the error.
+    pub fn should_gossip_to(&mut self, peer_id: &S::PublicId) -> bool {
+        if self.gossip_policy == GossipPolicy::Always {
+            return true;
+        }
+        let peer_index = match self.get_peer_index(peer_id) {
+            Ok(PeerIndex::OUR) | Err(_) => return true,
+            Ok(peer_index) => peer_index,
+        };
+
+        if self.has_undecided_work() || self.has_news_for(peer_index) {
+            let _ = self.idle_gossip_backoff.remove(&peer_index);
+            return true;
+        }
+        if self.gossip_policy == GossipPolicy::OnChange {
+            return false;
+        }
+
+        let (skipped, backoff) = self.idle_gossip_backoff.entry(peer_index).or_insert((0, 1));
+        if *skipped < *backoff {
+            *skipped += 1;
+            false
+        } else {
+            *skipped = 0;
+            *backoff = cmp::min(*backoff * 2, MAX_IDLE_GOSSIP_BACKOFF);
+            true
+        }
+    }
+
+    // Liveness guard for `GossipPolicy::OnChange`: meta-votes only get decided if the section
+    // keeps gossiping, so we gossip regardless while any payload or DKG is pending.
+    fn has_undecided_work(&self) -> bool {
+        !self
+            .meta_election
+            .unconsensused_events
+            .ordered_indices
+            .is_empty()
+            || !self.key_gen.is_empty()
+    }
+
+    // Do we hold any event other than a sync event which isn't an ancestor of the latest event we
+    // know from `peer_index`?
+    fn has_news_for(&self, peer_index: PeerIndex) -> bool {
+        let last_ancestors: PeerIndexMap<_> = match self
+            .peer_list
+            .last_event(peer_index)
+            .and_then(|event_index| self.graph.get(event_index))
+        {
+            Some(last_event) => last_event.last_ancestors().collect(),
+            None => return true,
+        };
+
+        self.peer_list.iter().any(|(creator, peer)| {
+            let first = last_ancestors
+                .get(creator)
+                .map(|index_by_creator| index_by_creator + 1)
+                .unwrap_or(0);
+            peer.events_from(first)
+                .filter_map(|event_index| self.graph.get(event_index))
+                .any(|event| !event.is_sync_event())
+        })
+    }
+
+    /// Creates the first gossip message the owning peer should send to join in the gossip
+    /// protocol, along with the ID of the peer to send it to.
+    ///
+    /// A peer created via `from_genesis` can call this straight away.  A peer created via
+    /// `from_existing` can't initiate contact with the section: it has to wait until it receives a
+    /// `Request` from one of the section members, which only happens once the section has reached
+    /// consensus on adding it.  Until then, this returns `Error::InvalidSelfState`.
+    ///
+    /// Returns `None` if there is no other peer to gossip to, e.g. if we're the only member of the
+    /// section.
+    #[allow(clippy::type_complexity)]
+    pub fn create_bootstrap_gossip(
+        &mut self,
+    ) -> Result<Option<(S::PublicId, Request<T, S::PublicId>)>> {
+        self.confirm_self_state(PeerState::SEND)?;
+
+        let peer_id = if let Some((_, peer)) = self.peer_list.gossip_recipients().next() {
+            peer.id().clone()
+        } else {
+            return Ok(None);
+        };
+        let request = self.create_gossip(&peer_id)?;
+        Ok(Some((peer_id, request)))
+    }
+
     fn create_sole_voter_gossip_event(&mut self) -> Result<()> {
         if !iter::once(PeerIndex::OUR).eq(self.voters()) {
             return Ok(());
@@ -368,7 +906,109 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         );
 
         let src_index = self.get_peer_index(src)?;
-        let other_parent = self.unpack_and_add_events(src_index, req.packed_events)?;
+        let other_parent =
+            self.unpack_and_add_events(src_index, req.packed_events, SignatureCheck::Verify)?;
+        self.respond_to_request(src_index, other_parent)
+    }
+
+    /// **Dangerous:** same as `handle_request`, except that the signatures of the received events
+    /// are not verified.
+    ///
+    /// This removes PARSEC's protection against Byzantine peers: whoever can deliver a request
+    /// this way can forge events on behalf of any member of the section, e.g. to vote in its
+    /// name.  It must only be used for requests received over links which already authenticate
+    /// `src` (e.g. mutually authenticated TLS), in a network whose members are all fully trusted,
+    /// such as a permissioned cluster run by a single operator.  It is never required: it only
+    /// saves the cost of verifying the signatures.
+    ///
+    /// The structure of the events is still validated, and their hashes are still computed from
+    /// their contents, so a well-behaved peer handling a request either way ends up with the same
+    /// gossip graph.
+    ///
+    /// Returns `Error::InvalidConfiguration` unless enabled by
+    /// [ParsecBuilder::trusted_ingest](struct.ParsecBuilder.html#method.trusted_ingest).
+    pub fn handle_request_trusted(
+        &mut self,
+        src: &S::PublicId,
+        req: Request<T, S::PublicId>,
+    ) -> Result<Response<T, S::PublicId>> {
+        self.confirm_trusted_ingest()?;
+        debug!(
+            "{:?} received trusted gossip request from {:?}",
+            self.our_pub_id(),
+            src
+        );
+
+        let src_index = self.get_peer_index(src)?;
+        let other_parent =
+            self.unpack_and_add_events(src_index, req.packed_events, SignatureCheck::Skip)?;
+        self.respond_to_request(src_index, other_parent)
+    }
+
+    /// Starts handling a `Request` the owning peer received from the `src` peer, without handling
+    /// any of its events yet.  This is a cooperative alternative to `handle_request`, meant for
+    /// e.g. async runtimes, where handling a large request in one go would block other tasks for
+    /// too long.  The returned `PendingRequest` is to be passed to `continue_request` until that
+    /// returns `RequestProgress::Done`.
+    ///
+    /// Returns `Err` if the request is empty, or if `handle_request` would reject it before
+    /// handling any of its events (e.g. if `src` has been removed from the section already).
+    pub fn start_request(
+        &self,
+        src: &S::PublicId,
+        req: Request<T, S::PublicId>,
+    ) -> Result<PendingRequest<T, S::PublicId>> {
+        debug!(
+            "{:?} started handling gossip request from {:?}",
+            self.our_pub_id(),
+            src
+        );
+
+        let src_index = self.get_peer_index(src)?;
+        let hash_of_last_event = self.confirm_can_handle_message(src_index, &req.packed_events)?;
+        Ok(PendingRequest {
+            src_index,
+            packed_events: req.packed_events.into_iter(),
+            hash_of_last_event,
+        })
+    }
+
+    /// Handles at most `max_events` further events of a request started by `start_request`.  Once
+    /// all of them are handled, this finishes handling the request exactly as `handle_request`
+    /// does, and returns the `Response` to be sent back.  Otherwise, the `PendingRequest` is
+    /// returned for handling to be resumed by another call.
+    ///
+    /// Each event is added to the gossip graph, and consensus is updated accordingly, as soon as
+    /// it is handled, so this `Parsec` can be used normally in between calls, e.g. to poll blocks
+    /// or to handle other messages.  The `PendingRequest` must only ever be passed back to the
+    /// `Parsec` which created it.  If `Err` is returned, handling of the request is abandoned,
+    /// but the events handled so far remain in the graph, as with `handle_request`.
+    pub fn continue_request(
+        &mut self,
+        mut pending: PendingRequest<T, S::PublicId>,
+        max_events: usize,
+    ) -> Result<RequestProgress<T, S::PublicId>> {
+        for packed_event in pending.packed_events.by_ref().take(max_events) {
+            self.unpack_and_add_message_event(
+                pending.src_index,
+                packed_event,
+                SignatureCheck::Verify,
+            )?;
+        }
+        if pending.remaining_events() > 0 {
+            return Ok(RequestProgress::Pending(pending));
+        }
+
+        let other_parent = self.finish_adding_message_events(&pending.hash_of_last_event)?;
+        self.respond_to_request(pending.src_index, other_parent)
+            .map(RequestProgress::Done)
+    }
+
+    fn respond_to_request(
+        &mut self,
+        src_index: PeerIndex,
+        other_parent: EventIndex,
+    ) -> Result<Response<T, S::PublicId>> {
         self.create_dkg_events()?;
         #[cfg(feature = "malice-detection")]
         self.create_accusation_events(other_parent)?;
@@ -393,43 +1033,656 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         );
 
         let src_index = self.get_peer_index(src)?;
-        let other_parent = self.unpack_and_add_events(src_index, resp.packed_events)?;
-        self.create_dkg_events()?;
-        #[cfg(feature = "malice-detection")]
-        self.create_accusation_events(other_parent)?;
-        self.create_sync_event(false, other_parent)?;
-        self.flush_pending_events()
+        let other_parent =
+            self.unpack_and_add_events(src_index, resp.packed_events, SignatureCheck::Verify)?;
+        self.finish_handling_response(other_parent)
     }
 
-    /// Returns the next stable block, if any. The method might need to be called more than once
-    /// for the caller to get all the blocks that have been consensused. A `None` value means that
-    /// all the blocks consensused so far have already been returned.
+    /// **Dangerous:** same as `handle_response`, except that the signatures of the received
+    /// events are not verified.  See [handle_request_trusted](#method.handle_request_trusted) for
+    /// why this must only ever be used with fully trusted peers.
     ///
-    /// Once the owning peer has been removed from the section (i.e. a block with payload
-    /// `Observation::Remove(our_id)` has been made stable), then no further blocks will be
-    /// enqueued. So, once `poll()` returns such a block, it will continue to return `None` forever.
-    pub fn poll(&mut self) -> Option<Block<T, S::PublicId>> {
-        let mut block_group = self.batch_poll()?;
-        let block = block_group.pop_front()?;
-        if !block_group.is_empty() {
-            self.consensused_blocks.push_front(block_group);
-        }
-        Some(block)
+    /// Returns `Error::InvalidConfiguration` unless enabled by
+    /// [ParsecBuilder::trusted_ingest](struct.ParsecBuilder.html#method.trusted_ingest).
+    pub fn handle_response_trusted(
+        &mut self,
+        src: &S::PublicId,
+        resp: Response<T, S::PublicId>,
+    ) -> Result<()> {
+        self.confirm_trusted_ingest()?;
+        debug!(
+            "{:?} received trusted gossip response from {:?}",
+            self.our_pub_id(),
+            src
+        );
+
+        let src_index = self.get_peer_index(src)?;
+        let other_parent =
+            self.unpack_and_add_events(src_index, resp.packed_events, SignatureCheck::Skip)?;
+        self.finish_handling_response(other_parent)
     }
 
-    /// Returns the next group of stable blocks, if any. The method might need to be called more
-    /// than once for the caller to get all the blocks that have been consensused. A `None` value
-    /// means that all the blocks consensused so far have already been returned.
+    /// Summarises the events in our gossip graph, to be sent to `peer_id` instead of a `Request`
+    /// to pull only the events we are missing.  The peer answers with the `Response` returned by
+    /// its [handle_have_summary](#method.handle_have_summary), to be passed to our
+    /// [handle_have_response](#method.handle_have_response).
     ///
-    /// Once the owning peer has been removed from the section (i.e. a block with payload
-    /// `Observation::Remove(our_id)` has been made stable), then no further blocks will be
-    /// enqueued. So, once `poll()` or `batch_poll()` returns such a block, it will continue to
-    /// return `None` forever.
-    pub(crate) fn batch_poll(&mut self) -> Option<BlockGroup<T, S::PublicId>> {
-        self.consensused_blocks.pop_front()
+    /// This is much cheaper than push gossip for catching up with a section after having fallen
+    /// behind: a `Request` holds whatever its sender thinks we are missing, based on the last of
+    /// our events it knows, which may be long outdated, while the summary only holds one hash per
+    /// creator.  To also push the events the peer is missing, it can in turn send us its own
+    /// summary.  The exchange creates no sync event, so it complements gossip rather than
+    /// replacing it.
+    ///
+    /// Returns an error if we aren't allowed to gossip to `peer_id`.
+    pub fn create_have_summary(
+        &mut self,
+        peer_id: &S::PublicId,
+    ) -> Result<HaveSummary<S::PublicId>> {
+        let peer_index = self.get_peer_index(peer_id)?;
+        if peer_index == PeerIndex::OUR {
+            return Err(Error::SelfGossip);
+        }
+        self.confirm_allowed_to_gossip_to(peer_index)?;
+        let _ = self.have_summaries_sent.insert(peer_index);
+
+        Ok(HaveSummary::new(
+            self.peer_list
+                .iter()
+                .filter_map(|(peer_index, peer)| {
+                    let event = self.graph.get(self.peer_list.last_event(peer_index)?)?;
+                    Some((peer.id().clone(), *event.hash()))
+                })
+                .collect(),
+        ))
     }
 
-    /// Check if the owning peer can vote (that is, it has reached a consensus on itself being a
+    /// Handles a `HaveSummary` received from the `src` peer, and returns a `Response` containing
+    /// the events we have and `src` is missing according to `summary`.  The response is empty if
+    /// `src` isn't missing anything.  Returns an error if we aren't allowed to gossip to `src`.
+    ///
+    /// `src` holds all the ancestors of the events in `summary`, so all our other events are
+    /// included, including the sides of forks `src` is missing.  Events in `summary` we don't hold
+    /// tell us nothing, so some of the included events may already be known to `src`.
+    pub fn handle_have_summary(
+        &self,
+        src: &S::PublicId,
+        summary: &HaveSummary<S::PublicId>,
+    ) -> Result<Response<T, S::PublicId>> {
+        debug!(
+            "{:?} received have summary from {:?}",
+            self.our_pub_id(),
+            src
+        );
+
+        let src_index = self.get_peer_index(src)?;
+        if src_index == PeerIndex::OUR {
+            return Err(Error::SelfGossip);
+        }
+        self.confirm_allowed_to_gossip_to(src_index)?;
+
+        let known_to_src = self.graph.ancestry_mask(
+            summary
+                .last_events
+                .values()
+                .filter_map(|hash| self.graph.get_index(hash)),
+        );
+        let events = self
+            .graph
+            .iter()
+            .filter(|event| !known_to_src[event.topological_index()])
+            .map(|event| event.inner());
+        self.pack_events(events).map(Response::new)
+    }
+
+    /// Handles the `Response` to a `HaveSummary` we sent to the `src` peer, i.e. adds the events we
+    /// were missing to our gossip graph.  Returns `Err` if the response was not valid, if `src` has
+    /// been removed from the section already, or if we haven't sent `src` a summary since the
+    /// last response we handled from it.
+    ///
+    /// Unlike `handle_response`, this doesn't create a sync event.  Hence accusations of malice
+    /// found in the added events are only raised along with our next sync event descending from
+    /// them.
+    pub fn handle_have_response(
+        &mut self,
+        src: &S::PublicId,
+        resp: Response<T, S::PublicId>,
+    ) -> Result<()> {
+        debug!(
+            "{:?} received have response from {:?}",
+            self.our_pub_id(),
+            src
+        );
+
+        let src_index = self.get_peer_index(src)?;
+        self.confirm_self_state(PeerState::RECV)?;
+        self.confirm_peer_state(src_index, PeerState::SEND)?;
+        if !self.have_summaries_sent.remove(&src_index) {
+            debug!(
+                "{:?} received unsolicited have response from {:?}",
+                self.our_pub_id(),
+                src
+            );
+            return Err(Error::InvalidMessage);
+        }
+        if resp.packed_events.is_empty() {
+            return Ok(());
+        }
+
+        let _ =
+            self.unpack_and_add_events(src_index, resp.packed_events, SignatureCheck::Verify)?;
+        self.create_dkg_events()?;
+        // Only the accusations of events our last event descends from can be raised without a
+        // sync event: the others are kept pending.
+        #[cfg(feature = "malice-detection")]
+        {
+            let our_last_event = self.our_last_event_index()?;
+            self.create_accusation_events(our_last_event)?;
+        }
+        self.flush_pending_events()
+    }
+
+    fn finish_handling_response(&mut self, other_parent: EventIndex) -> Result<()> {
+        self.create_dkg_events()?;
+        #[cfg(feature = "malice-detection")]
+        self.create_accusation_events(other_parent)?;
+        if self.should_create_response_event(other_parent) {
+            self.create_sync_event(false, other_parent)?;
+        }
+        self.flush_pending_events()
+    }
+
+    /// Sets the policy determining when gossip events are created while handling gossip messages.
+    /// Defaults to `EventCreationPolicy::Always`.
+    pub fn set_event_creation_policy(&mut self, policy: EventCreationPolicy) {
+        self.event_creation_policy = policy;
+    }
+
+    /// Sets the policy determining whether we initiate gossip when we have nothing new to tell.
+    /// Defaults to `GossipPolicy::Always`.
+    pub fn set_gossip_policy(&mut self, policy: GossipPolicy) {
+        self.gossip_policy = policy;
+    }
+
+    // Only set at construction, by `ParsecBuilder::fork_policy` or the test networks.
+    pub(crate) fn set_fork_policy(&mut self, policy: ForkPolicy) {
+        self.fork_policy = policy;
+    }
+
+    /// Sets the number of blocks after which a peer whose addition was consensused, but who still
+    /// hasn't gossiped to us, is considered stuck joining.  A warning is logged once for each such
+    /// peer, and it is reported by [stalled_joins](#method.stalled_joins) until it syncs or is
+    /// removed.  Defaults to `None`, which disables the detection.
+    pub fn set_join_stall_threshold(&mut self, threshold: Option<usize>) {
+        self.join_stall_threshold = threshold;
+    }
+
+    /// Sets the number of gossip events we create in a row without voting for anything, after
+    /// which we vote for an internal `Observation::Heartbeat`.  Defaults to `None`, which disables
+    /// heartbeats.
+    ///
+    /// Meta-elections only get decided once there is something to reach consensus on, so while
+    /// the application doesn't vote for anything, observations which are about to be consensused
+    /// can stall.  Heartbeats are consensused like any other observation, but never returned by
+    /// `poll`, so they keep consensus progressing without the application having to vote for dummy
+    /// payloads.  Only voters cast heartbeats, and they do so independently of each other, so
+    /// peers don't need to agree on the interval.  Heartbeats are only cast while some other
+    /// observation is still awaiting consensus, so an idle section stays quiet.
+    ///
+    /// Returns `Error::InvalidConfiguration` if the interval is zero.
+    pub fn set_heartbeat_interval(&mut self, interval: Option<usize>) -> Result<()> {
+        if interval == Some(0) {
+            return Err(Error::InvalidConfiguration(
+                "Heartbeat interval can't be zero",
+            ));
+        }
+        self.heartbeat_interval = interval;
+        Ok(())
+    }
+
+    /// Sets what happens to received events which refer to peers we don't know yet.  Defaults to
+    /// `UnknownPeerPolicy::Buffer { capacity: 1000, max_blocks: 10 }`.
+    ///
+    /// Switching to `UnknownPeerPolicy::Reject` drops any event currently put aside.
+    pub fn set_unknown_peer_policy(&mut self, policy: UnknownPeerPolicy) {
+        self.unknown_peer_policy = policy;
+        match policy {
+            UnknownPeerPolicy::Reject => self.deferred_events.clear(),
+            UnknownPeerPolicy::Buffer { capacity, .. } => {
+                while self.deferred_events.len() > capacity {
+                    self.deferred_events.evict();
+                }
+            }
+        }
+    }
+
+    /// Returns the number of received events put aside until we know the peers they refer to.
+    /// See [UnknownPeerPolicy](enum.UnknownPeerPolicy.html).
+    pub fn deferred_event_count(&self) -> usize {
+        self.deferred_events.len()
+    }
+
+    /// **Experimental:** weights the voters in the supermajority checks of the meta-elections by
+    /// `weights`, e.g. by their stake, rather than counting each of them once.  Something is then
+    /// decided once it is backed by voters with more than two thirds of the total weight of the
+    /// voters.  Voters not in `weights` weigh 1, so by default all voters weigh the same.
+    ///
+    /// The weights must be the same for all peers, and must be set before any gossip is handled,
+    /// otherwise peers will decide differently.  Only the meta-elections are weighted: the
+    /// signatures of a `Block` still count one per voter, e.g. for
+    /// [Block::reached_quorum](struct.Block.html#method.reached_quorum).
+    ///
+    /// This mode hasn't been audited: the Byzantine fault tolerance of PARSEC has only been
+    /// established for equal weights.
+    ///
+    /// Returns `Error::InvalidConfiguration` if any weight is zero or exceeds `MAX_VOTER_WEIGHT`,
+    /// in which case the current weights are kept.
+    pub fn set_voter_weights(&mut self, weights: BTreeMap<S::PublicId, usize>) -> Result<()> {
+        validate_voter_weights(&weights).map_err(Error::InvalidConfiguration)?;
+        self.voter_weights = weights;
+        Ok(())
+    }
+
+    // Only set at construction, by `ParsecBuilder::trusted_ingest` or the test records.
+    pub(crate) fn set_trusted_ingest(&mut self, enabled: bool) {
+        self.trusted_ingest = enabled;
+    }
+
+    /// Enables or disables malice detection.  Defaults to enabled.
+    ///
+    /// While disabled, received events are still rejected if their signature or structure is
+    /// invalid (e.g. parents by the wrong creators, or malformed sync events), but we neither look
+    /// for provable malice such as forks or duplicate votes, nor raise accusations.  This saves
+    /// work when all peers are trusted, e.g. in permissioned deployments.
+    #[cfg(feature = "malice-detection")]
+    pub fn set_malice_detection(&mut self, enabled: bool) {
+        self.malice_detection_enabled = enabled;
+    }
+
+    /// Sets a hook called with the offender and the malice of every accusation we're about to
+    /// raise, e.g. to have it authorised by a monitoring service first, or to suppress the false
+    /// positives of a faulty detector during an incident.  An accusation is only raised if the
+    /// hook returns `AccusationDecision::Approve`.  Defaults to approving all of them.
+    ///
+    /// **Vetoing is not free**: peers check that everyone accuses the malice they can see, so any
+    /// correct peer which detects the vetoed malice will in turn accuse us of being an accomplice
+    /// of the offender, which gets us removed from the section once consensused.  And if the
+    /// malice is genuine, the offender stays in the section unless others accuse it.  Only veto
+    /// accusations known to be false positives.
+    #[cfg(feature = "malice-detection")]
+    pub fn set_accusation_hook(&mut self, hook: AccusationHook<T, S::PublicId>) {
+        self.accusation_hook = Some(Rc::new(RefCell::new(hook)));
+    }
+
+    /// Returns the hash chain over the payloads consensused so far, in consensus order, which
+    /// peers can compare to check they reached the same decisions, and which auditors can verify
+    /// against the polled blocks.
+    ///
+    /// The chain also links internal payloads (DKG messages and heartbeats), which are consensused
+    /// like any other but never returned by `poll`, so the payloads of the polled blocks only form
+    /// a subsequence of it.
+    ///
+    /// Only the retained part of the history is included (see
+    /// [set_consensus_history_retention](#method.set_consensus_history_retention)): the chain is
+    /// then based on the hash of the last link dropped.
+    pub fn consensus_chain(&self) -> ConsensusChain {
+        self.meta_election.consensus_chain()
+    }
+
+    /// Limits how many of the most recently consensused payload keys are retained by the
+    /// meta-election, or keeps all of them if `None` (the default).
+    ///
+    /// Meta-votes and other per-event data of a decided meta-election are always dropped once the
+    /// next one starts, leaving the history of consensused keys as the only part of the
+    /// meta-election that grows with the number of blocks.  The history isn't needed for
+    /// consensus, but graph dumps only include the retained part of it.
+    pub fn set_consensus_history_retention(&mut self, retention: Option<usize>) {
+        self.meta_election
+            .set_consensus_history_retention(retention);
+    }
+
+    /// Sets the clock used to measure the [event ingestion rate](#method.event_ingestion_rate).
+    /// Defaults to `SystemClock`.
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.growth_rate.set_clock(clock);
+    }
+
+    /// Sets the length of the sliding window over which the
+    /// [event ingestion rate](#method.event_ingestion_rate) is averaged.  Defaults to 10 seconds.
+    pub fn set_event_ingestion_window(&mut self, window: Duration) {
+        self.growth_rate.set_window(window);
+    }
+
+    /// Returns the number of events added to our gossip graph per second, averaged over the last
+    /// [window](#method.set_event_ingestion_window).  This includes both the events we created and
+    /// the ones received through gossip.
+    ///
+    /// This is purely informational: e.g. an application can call `create_gossip` more often
+    /// while the graph grows quickly, to converge faster, and less often during quiet periods to
+    /// save bandwidth.
+    pub fn event_ingestion_rate(&self) -> f64 {
+        self.growth_rate.events_per_second()
+    }
+
+    /// Returns the number of events in our gossip graph created by each peer, forks included.
+    ///
+    /// A peer with many more events than the others may be spamming the section.  Peers which
+    /// have no events in the graph are omitted.
+    pub fn event_count_by_creator(&self) -> BTreeMap<S::PublicId, usize> {
+        self.graph
+            .event_count_by_creator()
+            .iter()
+            .filter_map(|(peer_index, &count)| {
+                self.peer_list
+                    .get(peer_index)
+                    .map(|peer| (peer.id().clone(), count))
+            })
+            .collect()
+    }
+
+    /// Returns the descriptors of all the events in our gossip graph, in topological order.
+    ///
+    /// A descriptor only holds an event's hash, creator and index, so it is much cheaper to send
+    /// than the event itself: two peers can exchange descriptors to find out which events the
+    /// other is missing before sending any in full.
+    pub fn event_descriptors(&self) -> impl Iterator<Item = EventDescriptor<S::PublicId>> + '_ {
+        self.graph
+            .iter()
+            .filter_map(move |event| event.descriptor(&self.peer_list))
+    }
+
+    /// Returns, for each peer which sent us at least one, the number of events received through
+    /// gossip from that peer which were already in our gossip graph.
+    ///
+    /// Such events are a pure waste of bandwidth: in a dense section, a peer sends us events we
+    /// already got from someone else whenever its view of what we know lags behind.  A count
+    /// growing quickly relative to [events_len](#method.events_len) shows the application gossips
+    /// more often, or to more peers, than needed.
+    pub fn redundant_events_received(&self) -> BTreeMap<S::PublicId, usize> {
+        self.redundant_events
+            .iter()
+            .filter_map(|(&peer_index, &count)| {
+                self.peer_list
+                    .get(peer_index)
+                    .map(|peer| (peer.id().clone(), count))
+            })
+            .collect()
+    }
+
+    /// Returns the total number of events received through gossip which were already in our
+    /// gossip graph.  See [redundant_events_received](#method.redundant_events_received).
+    pub fn total_redundant_events_received(&self) -> usize {
+        self.redundant_events.values().sum()
+    }
+
+    /// Returns, for each observation interesting to the creator of at least one observer event of
+    /// the current meta-election, the number of events added to our graph between the first vote
+    /// for it and the first such observer, in no particular order.
+    ///
+    /// An observer is an event which strongly sees a supermajority of voters, so this measures how
+    /// quickly the section notices a payload, independently of how long it then takes to decide
+    /// it.  Only the current meta-election is covered: this data is discarded once it's decided.
+    pub fn observer_latencies(&self) -> Vec<(&Observation<T, S::PublicId>, usize)> {
+        let interesting_events: PeerIndexMap<_> = self.meta_election.interesting_events().collect();
+        let mut first_observers = BTreeMap::new();
+        for (&observer_index, meta_event) in self.meta_election.meta_events() {
+            if !meta_event.is_observer() {
+                continue;
+            }
+            let creator = match self.graph.get(observer_index) {
+                Some(observer) => observer.creator(),
+                None => continue,
+            };
+
+            // The payloads interesting to the creator as of this observer.
+            let payload_keys = interesting_events
+                .get(creator)
+                .into_iter()
+                .flat_map(|event_indices| event_indices.iter())
+                .filter(|&&event_index| event_index <= observer_index)
+                .filter_map(|&event_index| self.meta_election.meta_event(event_index))
+                .flat_map(|meta_event| &meta_event.interesting_content);
+            for payload_key in payload_keys {
+                let first_observer = first_observers
+                    .entry(*payload_key)
+                    .or_insert(observer_index);
+                *first_observer = cmp::min(*first_observer, observer_index);
+            }
+        }
+
+        first_observers
+            .into_iter()
+            .filter_map(|(payload_key, observer_index)| {
+                let first_vote = self
+                    .meta_election
+                    .unconsensused_events(Some(&payload_key))
+                    .next()?;
+                let observation = &self.observations.get(&payload_key)?.observation;
+                let latency = observer_index
+                    .topological_index()
+                    .saturating_sub(first_vote.topological_index());
+                Some((observation, latency))
+            })
+            .collect()
+    }
+
+    /// Returns the next stable block, if any. The method might need to be called more than once
+    /// for the caller to get all the blocks that have been consensused. A `None` value means that
+    /// all the blocks consensused so far have already been returned.
+    ///
+    /// Once the owning peer has been removed from the section (i.e. a block with payload
+    /// `Observation::Remove(our_id)` has been made stable), then no further blocks will be
+    /// enqueued. So, once `poll()` returns such a block, it will continue to return `None` forever.
+    ///
+    /// Blocks already returned by `poll_membership()` are not returned again.
+    pub fn poll(&mut self) -> Option<Block<T, S::PublicId>> {
+        let block_group = self.consensused_blocks.front_mut()?;
+        let block = block_group.pop_front()?;
+        if block_group.is_empty() {
+            let _ = self.consensused_blocks.pop_front();
+        }
+        self.advance_block_index(1);
+        Some(block)
+    }
+
+    /// Returns the next stable block changing the section membership (i.e. with a `Genesis`,
+    /// `Add` or `Remove` payload, or an `Accusation` whose consensus removed the offender as per
+    /// our [fork policy](struct.ParsecBuilder.html#method.fork_policy)) which hasn't been returned
+    /// yet, along with the index of that block in the consensus order.  A `None` value means that
+    /// all such blocks consensused so far have already been returned.
+    ///
+    /// This allows membership changes to be handled separately from the other payloads: a block
+    /// returned by this method is not returned by `poll()` anymore, while all other blocks are
+    /// still returned by `poll()` in consensus order.  To interleave the two streams correctly,
+    /// compare the index returned here with `next_block_index()`.
+    pub fn poll_membership(&mut self) -> Option<(usize, MembershipChange<S::PublicId>)> {
+        let mut block_index = self.next_block_index;
+        let mut found = None;
+        'groups: for (group_pos, block_group) in self.consensused_blocks.iter().enumerate() {
+            for (block_pos, block) in block_group.iter().enumerate() {
+                let offender_removed = self.removals_by_accusation.contains(&block_index);
+                if let Some(change) =
+                    MembershipChange::from_observation(block.payload(), offender_removed)
+                {
+                    found = Some((group_pos, block_pos, change));
+                    break 'groups;
+                }
+                block_index += 1;
+                while self.membership_polled.contains(&block_index) {
+                    block_index += 1;
+                }
+            }
+        }
+
+        let (group_pos, block_pos, change) = found?;
+        let _ = self.removals_by_accusation.remove(&block_index);
+        let block_group = &mut self.consensused_blocks[group_pos];
+        let _ = block_group.remove(block_pos);
+        if block_group.is_empty() {
+            let _ = self.consensused_blocks.remove(group_pos);
+        }
+        if block_index == self.next_block_index {
+            self.advance_block_index(1);
+        } else {
+            let _ = self.membership_polled.insert(block_index);
+        }
+
+        Some((block_index, change))
+    }
+
+    /// Returns the index, in the consensus order, of the block which the next call to `poll()`
+    /// will return.  The genesis block has index 0.
+    pub fn next_block_index(&self) -> usize {
+        self.next_block_index
+    }
+
+    /// Returns an iterator over the stable blocks not returned yet, in consensus order. This is
+    /// equivalent to calling `poll()` repeatedly: the iterator is lazy, so blocks which aren't
+    /// consumed remain available to later calls of `poll()` or `poll_iter()`.
+    pub fn poll_iter(&mut self) -> impl Iterator<Item = Block<T, S::PublicId>> + '_ {
+        iter::from_fn(move || self.poll())
+    }
+
+    /// Returns the next stable block, if any, along with the blocks following it if they all carry
+    /// opaque payloads and were decided by the same meta-election.  A block with any other
+    /// payload is always returned on its own.  This lets applications with high-throughput opaque
+    /// workloads process the payloads consensused together in one go.  The blocks are in
+    /// consensus order, which is the same on every peer, and which `poll()` would return them in.
+    ///
+    /// Blocks already returned by `poll_membership()` are not returned again.
+    pub fn poll_batch(&mut self) -> Option<Vec<Block<T, S::PublicId>>> {
+        let block_group = self.consensused_blocks.front_mut()?;
+        let num_blocks = if block_group.front()?.opaque_payload().is_some() {
+            block_group
+                .iter()
+                .take_while(|block| block.opaque_payload().is_some())
+                .count()
+        } else {
+            1
+        };
+        let batch = block_group.drain(..num_blocks).collect_vec();
+        if block_group.is_empty() {
+            let _ = self.consensused_blocks.pop_front();
+        }
+        self.advance_block_index(num_blocks);
+        Some(batch)
+    }
+
+    /// Returns the next group of stable blocks, if any, i.e. the blocks decided by the same
+    /// meta-election, in consensus order. The method might need to be called more than once for
+    /// the caller to get all the blocks that have been consensused. A `None` value means that all
+    /// the blocks consensused so far have already been returned.
+    ///
+    /// Blocks of the group already returned by `poll()` or `poll_membership()` are not returned
+    /// again.  Membership changes only take effect once the meta-election deciding them is over,
+    /// so this is how blocks must be relayed to a [LightClient](struct.LightClient.html).
+    ///
+    /// Once the owning peer has been removed from the section (i.e. a block with payload
+    /// `Observation::Remove(our_id)` has been made stable), then no further blocks will be
+    /// enqueued. So, once `poll()` or `batch_poll()` returns such a block, it will continue to
+    /// return `None` forever.
+    pub fn batch_poll(&mut self) -> Option<Vec<Block<T, S::PublicId>>> {
+        let block_group = self.consensused_blocks.pop_front()?;
+        self.advance_block_index(block_group.len());
+        Some(block_group.into_iter().collect())
+    }
+
+    // Moves `next_block_index` past the given number of blocks taken from the front of
+    // `consensused_blocks`, skipping the ones already taken by `poll_membership()`.
+    fn advance_block_index(&mut self, num_blocks: usize) {
+        for _ in 0..num_blocks {
+            self.next_block_index += 1;
+            while self.membership_polled.remove(&self.next_block_index) {
+                self.next_block_index += 1;
+            }
+        }
+        self.removals_by_accusation = self
+            .removals_by_accusation
+            .split_off(&self.next_block_index);
+        self.archive_blocks();
+    }
+
+    /// Returns the number of blocks which are final, i.e. which have been consensused so far,
+    /// including those already returned by `poll()`.
+    ///
+    /// Only final blocks are ever returned by `poll()`: a block becomes available once the
+    /// meta-election deciding it is complete, and its position in the sequence never changes
+    /// afterwards, regardless of any meta-election still in progress.  So after `poll()` returns
+    /// `None`, exactly `stable_up_to()` blocks have been returned in total.
+    pub fn stable_up_to(&self) -> usize {
+        self.num_stable_blocks
+    }
+
+    /// Enables or disables keeping a copy of the stable blocks, to be read through
+    /// [blocks](#method.blocks), [blocks_from](#method.blocks_from) and
+    /// [blocks_rev](#method.blocks_rev) independently of `poll()`.  Defaults to disabled, as the
+    /// retained blocks grow with the whole consensus history.
+    ///
+    /// Only the blocks made stable while enabled are retained, from index
+    /// [first_retained_block_index](#method.first_retained_block_index) onwards.  Disabling drops
+    /// all the retained blocks.
+    pub fn set_block_retention(&mut self, enabled: bool) {
+        if enabled != self.retain_blocks {
+            self.retain_blocks = enabled;
+            self.retained_blocks.clear();
+            self.first_retained_block_index = self.num_stable_blocks;
+        }
+    }
+
+    /// Caps the number of retained blocks to `cap`, passing each block evicted to respect it to
+    /// `on_archive`, oldest first, so that the application can persist the full history
+    /// elsewhere.  Evicted blocks are no longer returned by [blocks](#method.blocks) nor
+    /// [blocks_from](#method.blocks_from).  Has no effect unless
+    /// [block retention](#method.set_block_retention) is enabled.
+    ///
+    /// A block is never evicted before it has been returned by `poll()` (or one of its variants),
+    /// so while some stable blocks haven't been polled yet, more than `cap` blocks may be
+    /// retained.
+    pub fn set_block_archive(
+        &mut self,
+        cap: usize,
+        on_archive: Box<dyn FnMut(Block<T, S::PublicId>)>,
+    ) {
+        self.block_archive = Some((cap, Rc::new(RefCell::new(on_archive))));
+        self.archive_blocks();
+    }
+
+    /// Returns the index, in the consensus order, of the first block returned by
+    /// [blocks](#method.blocks).  Blocks before it were made stable while block retention was
+    /// disabled, can only have been returned by `poll()`, or were
+    /// [archived](#method.set_block_archive).
+    pub fn first_retained_block_index(&self) -> usize {
+        self.first_retained_block_index
+    }
+
+    /// Returns the retained stable blocks in consensus order, whether they have been returned by
+    /// `poll()` yet or not.  Empty unless [block retention](#method.set_block_retention) is
+    /// enabled.
+    pub fn blocks(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = &Block<T, S::PublicId>> + ExactSizeIterator {
+        self.retained_blocks.iter()
+    }
+
+    /// Returns the retained stable blocks whose index in the consensus order is `index` or more,
+    /// in consensus order.  E.g. a component which has processed the first `n` blocks can resume
+    /// with `blocks_from(n)`.
+    ///
+    /// Blocks which weren't retained are skipped: if `index` is lower than
+    /// [first_retained_block_index](#method.first_retained_block_index), this starts with the
+    /// block at `first_retained_block_index()` instead.
+    pub fn blocks_from(&self, index: usize) -> impl Iterator<Item = &Block<T, S::PublicId>> {
+        self.retained_blocks
+            .iter()
+            .skip(index.saturating_sub(self.first_retained_block_index))
+    }
+
+    /// Returns the retained stable blocks, most recent first.
+    pub fn blocks_rev(&self) -> impl Iterator<Item = &Block<T, S::PublicId>> {
+        self.retained_blocks.iter().rev()
+    }
+
+    /// Check if the owning peer can vote (that is, it has reached a consensus on itself being a
     /// full member of the section).
     pub fn can_vote(&self) -> bool {
         self.peer_list.our_state().can_vote()
@@ -438,13 +1691,87 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
     /// Checks if the given `observation` has already been voted for by the owning peer.
     pub fn have_voted_for(&self, observation: &Observation<T, S::PublicId>) -> bool {
         let hash = ObservationHash::from(observation);
-        let key = ObservationKey::new(hash, PeerIndex::OUR, self.consensus_mode.of(observation));
+        let key = ObservationKey::new(hash, PeerIndex::OUR, self.consensus_modes.of(observation));
         self.observations
             .get(&key)
             .map(|info| info.created_by_us)
             .unwrap_or(false)
     }
 
+    /// Checks if more than two thirds of the current voters have voted for the given `observation`,
+    /// as seen from the owning peer's latest event.  With
+    /// [voter weights](#method.set_voter_weights), that's two thirds of their total weight.  This
+    /// is the threshold which makes a payload interesting to the meta-election, so it is an early
+    /// indication that the observation is likely to be consensused.
+    ///
+    /// **This is speculative**: the observation is not decided until it is returned by `poll`.
+    /// The meta-election may still end without it, e.g. if another observation gets consensused
+    /// first and the voters change, so applications must not treat a `true` result as final.
+    ///
+    /// Returns `false` once the observation has been consensused, as its votes are then no longer
+    /// tracked; use `poll` to get the block.
+    pub fn has_supermajority_votes(&self, observation: &Observation<T, S::PublicId>) -> bool {
+        let our_last_event = match self
+            .peer_list
+            .last_event(PeerIndex::OUR)
+            .and_then(|event_index| self.get_known_event(event_index).ok())
+        {
+            Some(event) => event,
+            None => return false,
+        };
+
+        let hash = ObservationHash::from(observation);
+        let voting_events = self
+            .unconsensused_events(None)
+            .filter(|event| event.payload_key().map(|key| *key.hash() == hash) == Some(true))
+            .filter(|event| our_last_event.is_descendant_of(*event))
+            .collect_vec();
+        let weight_for = self.weight_of(self.voters().iter().filter(|peer_index| {
+            voting_events
+                .iter()
+                .any(|event| event.creator() == *peer_index)
+        }));
+        self.is_supermajority(weight_for)
+    }
+
+    /// Returns the total voter weight which has to back a payload for the meta-election in
+    /// progress to decide it, i.e. the smallest weight which is more than two thirds of the total
+    /// weight of its voters.  This is the threshold all the supermajority checks use, e.g.
+    /// `has_supermajority_votes`.
+    ///
+    /// Unless weights are set by `set_voter_weights`, each voter weighs 1, so this is the number of
+    /// votes needed, which is also the number of signatures a block with a
+    /// `ConsensusMode::Supermajority` payload carries at least.  See
+    /// [Block::reached_quorum](struct.Block.html#method.reached_quorum).
+    ///
+    /// The meta-election keeps the voters it started with until it's decided, so a membership
+    /// change only affects this once the meta-election which decided it is over.
+    pub fn quorum_size(&self) -> usize {
+        self.quorum_weight()
+    }
+
+    /// Returns the hashes of the events each peer created which are interesting to the current
+    /// meta-election, i.e. which were the first by their creator to see a supermajority of votes
+    /// for some payload not consensused yet, in the order they were created.
+    ///
+    /// This reflects the meta-election in progress, not its outcome: payloads carried by
+    /// interesting events are gaining traction, but only the blocks returned by `poll` are
+    /// decided.  The map is emptied whenever a meta-election ends.
+    pub fn interesting_events(&self) -> BTreeMap<S::PublicId, Vec<EventHash>> {
+        self.meta_election
+            .interesting_events()
+            .filter_map(|(peer_index, event_indices)| {
+                let peer_id = self.peer_list.get(peer_index)?.id().clone();
+                let hashes = event_indices
+                    .iter()
+                    .filter_map(|event_index| self.graph.get(*event_index))
+                    .map(|event| *event.hash())
+                    .collect();
+                Some((peer_id, hashes))
+            })
+            .collect()
+    }
+
     /// Check if there are any observations that have been voted for but not yet polled - that is,
     /// either they haven't been consensused yet or a block containing that observation hasn't yet
     /// been retrieved by calling `poll`, or a DKG is running.
@@ -465,6 +1792,44 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
             .chain(self.our_unconsensused_observations())
     }
 
+    /// Returns the observations which have been consensused so far, together with their hashes,
+    /// whether or not the blocks containing them have already been returned by `poll()`.
+    ///
+    /// The items borrow from `self`, so this is a cheap way to inspect consensused payloads
+    /// without cloning them or the proofs of their blocks.  The iteration order is by hash, not by
+    /// consensus order; use `poll()` to get the observations in consensus order.  In
+    /// `ConsensusMode::Single` the same observation may be yielded once per peer who voted for it.
+    pub fn consensused_observations(
+        &self,
+    ) -> impl Iterator<Item = (&ObservationHash, &Observation<T, S::PublicId>)> {
+        self.observations.iter().filter_map(|(key, info)| {
+            if info.consensused {
+                Some((key.hash(), &info.observation))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Returns the observations voted for by any peer, as far as the owning peer has seen, which
+    /// haven't been consensused yet.  Unlike `our_unpolled_observations`, this is the whole
+    /// section's in-flight set, e.g. for applying backpressure before voting for more.
+    ///
+    /// Each observation is yielded once, however many peers voted for it, and in an order which
+    /// all peers agree on (see `Observation::consistent_cmp`).
+    pub fn pending_observations(&self) -> impl Iterator<Item = &Observation<T, S::PublicId>> {
+        self.unconsensused_events(None)
+            .filter_map(|event| event.payload_key().cloned())
+            .unique()
+            .sorted_by(|lhs_key, rhs_key| lhs_key.consistent_cmp(rhs_key, &self.peer_list))
+            .filter_map(move |key| {
+                self.observations
+                    .get(&key)
+                    .filter(|info| !info.consensused)
+                    .map(|info| &info.observation)
+            })
+    }
+
     fn our_consensused_observations(&self) -> impl Iterator<Item = &Observation<T, S::PublicId>> {
         self.observations.values().filter_map(move |info| {
             if info.created_by_us
@@ -499,7 +1864,7 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         // signed by us, yet with payloads voted for by us.
         // In `Single` mode, on the other hand, check also that we signed it, to avoid false
         // positives when there are blocks with the same payloads but signed by someone else.
-        match self.consensus_mode.of(payload) {
+        match self.consensus_modes.of(payload) {
             ConsensusMode::Supermajority => matching_blocks.next().is_some(),
             ConsensusMode::Single => {
                 matching_blocks.any(|block| block.is_signed_by(self.our_pub_id()))
@@ -518,7 +1883,22 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         // peer does not have `PeerState::DKG`, it means we haven't yet reached consensus on
         // adding them to the section so we shouldn't contact them yet.
         // `PeerState::VOTE` automatically includes `PeerState::DKG`.
-        self.confirm_peer_state(peer_index, PeerState::DKG | PeerState::RECV)
+        self.confirm_peer_state(peer_index, PeerState::DKG | PeerState::RECV)?;
+        if self.peer_list.is_isolated(peer_index) {
+            trace!(
+                "{:?} not gossiping to isolated {:?}",
+                self.our_pub_id(),
+                peer_index
+            );
+            return Err(Error::InvalidPeerState {
+                required: PeerState::RECV,
+                actual: self
+                    .peer_list
+                    .peer_state(peer_index)
+                    .without(PeerState::RECV),
+            });
+        }
+        Ok(())
     }
 
     fn confirm_peer_state(&self, peer_index: PeerIndex, required: PeerState) -> Result<()> {
@@ -552,6 +1932,14 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         }
     }
 
+    fn confirm_trusted_ingest(&self) -> Result<()> {
+        if self.trusted_ingest {
+            Ok(())
+        } else {
+            Err(Error::InvalidConfiguration("Trusted ingest isn't enabled"))
+        }
+    }
+
     fn confirm_can_add_event(&self, event: &Event<S::PublicId>) -> Result<()> {
         let peer = self
             .peer_list
@@ -595,63 +1983,223 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         })
     }
 
-    fn pack_events<'a, I>(&self, events: I) -> Result<Vec<PackedEvent<T, S::PublicId>>>
-    where
-        I: IntoIterator<Item = &'a Event<S::PublicId>>,
-        S::PublicId: 'a,
-    {
-        events
-            .into_iter()
-            .map(|event| event.pack(self.event_context()))
-            .collect()
+    fn pack_events<'a, I>(&self, events: I) -> Result<Vec<PackedEvent<T, S::PublicId>>>
+    where
+        I: IntoIterator<Item = &'a Event<S::PublicId>>,
+        S::PublicId: 'a,
+    {
+        events
+            .into_iter()
+            .map(|event| event.pack(self.event_context()))
+            .collect()
+    }
+
+    // Returns the list peers which have created forked events, and the event to use as the
+    // other-parent when creating our sync event as a result of handling this message.
+    fn unpack_and_add_events(
+        &mut self,
+        src_index: PeerIndex,
+        packed_events: Vec<PackedEvent<T, S::PublicId>>,
+        signature_check: SignatureCheck,
+    ) -> Result<EventIndex> {
+        let hash_of_last_event = self.confirm_can_handle_message(src_index, &packed_events)?;
+        for packed_event in packed_events {
+            self.unpack_and_add_message_event(src_index, packed_event, signature_check)?;
+        }
+        self.finish_adding_message_events(&hash_of_last_event)
+    }
+
+    // Checks we can handle a message containing `packed_events` from `src_index`, and returns the
+    // hash of its last event.
+    fn confirm_can_handle_message(
+        &self,
+        src_index: PeerIndex,
+        packed_events: &[PackedEvent<T, S::PublicId>],
+    ) -> Result<EventHash> {
+        self.confirm_self_state(PeerState::RECV)?;
+        self.confirm_peer_state(src_index, PeerState::SEND)?;
+
+        packed_events
+            .last()
+            .map(PackedEvent::compute_hash)
+            .ok_or(Error::InvalidMessage)
+    }
+
+    fn unpack_and_add_message_event(
+        &mut self,
+        src_index: PeerIndex,
+        packed_event: PackedEvent<T, S::PublicId>,
+        signature_check: SignatureCheck,
+    ) -> Result<()> {
+        if self.should_defer(&packed_event) {
+            return self.defer(src_index, packed_event, signature_check);
+        }
+
+        if let Some(event) = self.unpack(packed_event, signature_check)? {
+            let event_creator = event.creator();
+            let event_index = self.add_event(event)?;
+
+            // We have received an event of a peer in the message. The peer can now receive
+            // gossips from us as well.
+            self.peer_list
+                .change_peer_state(event_creator, PeerState::RECV);
+            self.peer_list
+                .record_gossiped_event_by(src_index, event_index);
+
+            #[cfg(feature = "malice-detection")]
+            {
+                if self.malice_detection_enabled {
+                    self.detect_accomplice(event_index)?;
+                }
+            }
+        } else {
+            *self.redundant_events.entry(src_index).or_insert(0) += 1;
+        }
+        Ok(())
+    }
+
+    // Whether `packed_event` has to be put aside as per `UnknownPeerPolicy::Buffer`, i.e. whether
+    // it refers to a peer we don't know, or is a child of an event already put aside.
+    fn should_defer(&self, packed_event: &PackedEvent<T, S::PublicId>) -> bool {
+        if self.unknown_peer_policy == UnknownPeerPolicy::Reject {
+            return false;
+        }
+
+        iter::once(packed_event.creator())
+            .chain(packed_event.recipient())
+            .any(|peer_id| self.peer_list.get_index(peer_id).is_none())
+            || packed_event
+                .self_parent()
+                .into_iter()
+                .chain(packed_event.other_parent())
+                .any(|hash| self.is_deferred(hash))
+    }
+
+    fn is_deferred(&self, hash: &EventHash) -> bool {
+        self.deferred_events.contains(hash)
+    }
+
+    // Puts `packed_event` aside.  Its signature is verified first, as it would be if it was added
+    // right away.  That doesn't stop a peer from flooding us with events by peers we don't know, as
+    // anyone can sign as such a peer, hence evicting from whoever relayed the most events.
+    fn defer(
+        &mut self,
+        src_index: PeerIndex,
+        packed_event: PackedEvent<T, S::PublicId>,
+        signature_check: SignatureCheck,
+    ) -> Result<()> {
+        let capacity = match self.unknown_peer_policy {
+            UnknownPeerPolicy::Buffer { capacity, .. } => capacity,
+            UnknownPeerPolicy::Reject => return Ok(()),
+        };
+        if capacity == 0 {
+            return Ok(());
+        }
+        let hash = match signature_check {
+            SignatureCheck::Verify => packed_event.compute_hash_and_verify_signature()?,
+            SignatureCheck::Skip => packed_event.compute_hash(),
+        };
+        if self.is_deferred(&hash) {
+            return Ok(());
+        }
+        if self.deferred_events.len() == capacity {
+            // Any descendant of the evicted event will fail to unpack once retried, and be
+            // dropped then.
+            self.deferred_events.evict();
+        }
+        trace!(
+            "{:?} putting aside {:?} until we know the peers it refers to",
+            self.our_pub_id(),
+            packed_event
+        );
+        self.deferred_events.push_back(DeferredEvent {
+            hash,
+            packed_event,
+            src_index,
+            num_stable_blocks: self.num_stable_blocks,
+        });
+        Ok(())
+    }
+
+    // Drops the events put aside for too long, then retries adding the others if blocks have been
+    // consensused since our last attempt, as only those can change the peers we know.
+    fn add_deferred_events(&mut self) {
+        let max_blocks = match self.unknown_peer_policy {
+            UnknownPeerPolicy::Buffer { max_blocks, .. } => max_blocks,
+            UnknownPeerPolicy::Reject => return,
+        };
+        let num_stable_blocks = self.num_stable_blocks;
+        self.deferred_events
+            .retain(|event| num_stable_blocks - event.num_stable_blocks <= max_blocks);
+        if self.deferred_events.is_empty() || self.deferred_events_retried_at == num_stable_blocks {
+            return;
+        }
+        self.deferred_events_retried_at = num_stable_blocks;
+
+        // Parents come before their children, so a single pass in the original order suffices.
+        for event in self.deferred_events.take() {
+            if self.should_defer(&event.packed_event) {
+                self.deferred_events.push_back(event);
+                continue;
+            }
+            // The signature was verified when the event was put aside.
+            if let Err(error) = self.unpack_and_add_message_event(
+                event.src_index,
+                event.packed_event,
+                SignatureCheck::Skip,
+            ) {
+                debug!(
+                    "{:?} dropping event {:?} put aside: {:?}",
+                    self.our_pub_id(),
+                    event.hash,
+                    error
+                );
+            }
+        }
     }
 
-    // Returns the list peers which have created forked events, and the event to use as the
-    // other-parent when creating our sync event as a result of handling this message.
-    fn unpack_and_add_events(
+    // Adds the events put aside which now can be, then returns the index of the last event of the
+    // message, to be used as the other-parent of our sync event.  Fails with `Error::UnknownPeer`
+    // if that event is still put aside.
+    fn finish_adding_message_events(
         &mut self,
-        src_index: PeerIndex,
-        packed_events: Vec<PackedEvent<T, S::PublicId>>,
+        hash_of_last_event: &EventHash,
     ) -> Result<EventIndex> {
-        self.confirm_self_state(PeerState::RECV)?;
-        self.confirm_peer_state(src_index, PeerState::SEND)?;
-
-        let hash_of_last_event = packed_events
-            .last()
-            .map(PackedEvent::compute_hash)
-            .ok_or_else(|| Error::InvalidMessage)?;
-        for packed_event in packed_events {
-            if let Some(event) = self.unpack(packed_event)? {
-                let event_creator = event.creator();
-                let event_index = self.add_event(event)?;
-
-                // We have received an event of a peer in the message. The peer can now receive
-                // gossips from us as well.
-                self.peer_list
-                    .change_peer_state(event_creator, PeerState::RECV);
-                self.peer_list
-                    .record_gossiped_event_by(src_index, event_index);
-
-                #[cfg(feature = "malice-detection")]
-                self.detect_accomplice(event_index)?;
-            }
+        self.add_deferred_events();
+        if self.is_deferred(hash_of_last_event) {
+            return Err(Error::UnknownPeer);
         }
 
         #[cfg(feature = "malice-detection")]
         self.detect_premature_gossip()?;
 
-        let last_event_index = self
-            .graph
-            .get_index(&hash_of_last_event)
-            .ok_or_else(|| Error::InvalidMessage)?;
-        Ok(last_event_index)
+        self.graph
+            .get_index(hash_of_last_event)
+            .ok_or(Error::InvalidMessage)
     }
 
     fn unpack(
         &mut self,
         packed_event: PackedEvent<T, S::PublicId>,
+        signature_check: SignatureCheck,
     ) -> Result<Option<Event<S::PublicId>>> {
-        if let Some(unpacked_event) = Event::unpack(packed_event, self.event_context())? {
+        let unpacked_event = match signature_check {
+            SignatureCheck::Verify => Event::unpack(packed_event, self.event_context())?,
+            SignatureCheck::Skip => Event::unpack_trusted(packed_event, self.event_context())?,
+        };
+        if let Some(unpacked_event) = unpacked_event {
+            if let Some((_, ref observation_info)) = unpacked_event.observation_for_store {
+                if let Observation::OpaquePayload(ref payload) = observation_info.observation {
+                    if !payload.validate() {
+                        #[cfg(feature = "malice-detection")]
+                        self.accuse(
+                            unpacked_event.event.creator(),
+                            Malice::Unprovable(UnprovableMalice::Spam),
+                        );
+                        return Err(Error::InvalidEvent);
+                    }
+                }
+            }
             if let Some((payload_key, observation_info)) = unpacked_event.observation_for_store {
                 let _ = self
                     .observations
@@ -742,6 +2290,7 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
     fn insert_event(&mut self, event: Event<S::PublicId>) -> EventIndex {
         let event = self.graph.insert(event);
         self.peer_list.add_event(event);
+        self.growth_rate.record_event();
         event.event_index()
     }
 
@@ -779,16 +2328,28 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
 
         self.output_consensus_info(&payload_keys);
 
+        let first_block_index = self.num_stable_blocks;
         let blocks = self.create_blocks(&payload_keys)?;
-        if !blocks.is_empty() {
-            self.consensused_blocks.push_back(blocks);
-        }
+        let block_hashes = blocks
+            .iter()
+            .map(|block| ObservationHash::from(block.payload()))
+            .collect_vec();
+        self.push_consensused_blocks(blocks);
 
         self.mark_observations_as_consensused(&payload_keys);
 
         let peer_list_changes = payload_keys
             .iter()
-            .filter_map(|payload_key| self.handle_consensus(event_index, payload_key))
+            .filter_map(|payload_key| {
+                let change = self.handle_consensus(event_index, payload_key)?;
+                if let Some(block_pos) = block_hashes
+                    .iter()
+                    .position(|hash| hash == payload_key.hash())
+                {
+                    self.record_removal_by_accusation(payload_key, first_block_index + block_pos);
+                }
+                Some(change)
+            })
             .collect();
 
         self.meta_election
@@ -799,10 +2360,22 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         Ok(PostProcessAction::Restart(start_index))
     }
 
+    // Records that the block at `block_index` changes the membership if it's the accusation which
+    // just removed its offender, so that `poll_membership()` doesn't have to guess.
+    fn record_removal_by_accusation(&mut self, payload_key: &ObservationKey, block_index: usize) {
+        if let Some(Observation::Accusation { .. }) = self
+            .observations
+            .get(payload_key)
+            .map(|info| &info.observation)
+        {
+            let _ = self.removals_by_accusation.insert(block_index);
+        }
+    }
+
     fn output_consensus_info(&self, payload_keys: &[ObservationKey]) {
         dump_graph::to_file(dump_graph::ToFileInfo {
             owner_id: self.our_pub_id(),
-            consensus_mode: self.consensus_mode,
+            consensus_mode: self.consensus_modes.global(),
             gossip_graph: &self.graph,
             meta_election: &self.meta_election,
             peer_list: &self.peer_list,
@@ -818,15 +2391,77 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
                 .get(payload_key)
                 .map(|info| &info.observation);
             info!(
-                "{:?} got consensus on block {} with payload {:?} and payload hash {:?}",
+                "{:?} got consensus on block {} with payload {:?} and payload key {}",
                 self.our_pub_id(),
-                self.meta_election.consensus_history().len() + index,
+                self.meta_election.consensus_len() + index,
                 payload,
-                payload_key.hash()
+                payload_key.display(&self.peer_list)
             )
         }
     }
 
+    fn push_consensused_blocks(&mut self, blocks: BlockGroup<T, S::PublicId>) {
+        if blocks.is_empty() {
+            return;
+        }
+
+        if self.retain_blocks {
+            self.retained_blocks.extend(blocks.iter().cloned());
+        }
+        let prev_num_stable_blocks = self.num_stable_blocks;
+        self.num_stable_blocks += blocks.len();
+        self.consensused_blocks.push_back(blocks);
+        self.archive_blocks();
+        self.detect_stalled_joins(prev_num_stable_blocks);
+    }
+
+    // Evicts the oldest retained blocks beyond the cap set by `set_block_archive()`, passing them
+    // to the archive.  Blocks not returned by `poll()` yet are kept regardless of the cap, so
+    // every block is delivered at least once before it can only be found in the archive.
+    fn archive_blocks(&mut self) {
+        let (cap, on_archive) = match self.block_archive {
+            Some((cap, ref on_archive)) => (cap, on_archive),
+            None => return,
+        };
+        while self.retained_blocks.len() > cap
+            && self.first_retained_block_index < self.next_block_index
+        {
+            if let Some(block) = self.retained_blocks.pop_front() {
+                self.first_retained_block_index += 1;
+                (on_archive.borrow_mut())(block);
+            }
+        }
+    }
+
+    // Stops tracking the joins which completed or were cancelled, and warns about those which
+    // crossed the stall threshold since `num_stable_blocks` was `prev_num_stable_blocks`.
+    fn detect_stalled_joins(&mut self, prev_num_stable_blocks: usize) {
+        let peer_list = &self.peer_list;
+        self.pending_joins
+            .retain(|&peer_index, _| match peer_list.get(peer_index) {
+                Some(peer) => peer.removal_event().is_none() && peer.events().next().is_none(),
+                None => false,
+            });
+
+        let threshold = match self.join_stall_threshold {
+            Some(threshold) => threshold,
+            None => return,
+        };
+        for (&peer_index, &decided_at) in &self.pending_joins {
+            if prev_num_stable_blocks - decided_at <= threshold
+                && self.num_stable_blocks - decided_at > threshold
+            {
+                warn!(
+                    "{:?} hasn't received any gossip from {:?} in the {} blocks since consensus \
+                     on adding it",
+                    self.our_pub_id(),
+                    self.peer_list.get(peer_index).map(Peer::id),
+                    self.num_stable_blocks - decided_at
+                );
+            }
+        }
+    }
+
     fn mark_observations_as_consensused(&mut self, payload_keys: &[ObservationKey]) {
         for payload_key in payload_keys {
             if let Some(info) = self.observations.get_mut(payload_key) {
@@ -856,12 +2491,29 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
             Some(Observation::Remove { ref peer_id, .. }) => {
                 self.handle_remove_peer(event_index, peer_id)
             }
+            Some(Observation::Accusation {
+                ref offender,
+                ref malice,
+            }) if !self.fork_policy.removes_offender(malice) => {
+                info!(
+                    "{:?} keeping {:?} due to fork policy {:?}",
+                    self.our_pub_id(),
+                    offender,
+                    self.fork_policy
+                );
+                if self.fork_policy == ForkPolicy::AccuseAndIsolate {
+                    if let Some(peer_index) = self.peer_list.get_index(offender) {
+                        self.peer_list.isolate_peer(peer_index);
+                    }
+                }
+                None
+            }
             Some(Observation::Accusation {
                 ref offender,
                 ref malice,
             }) => {
                 info!(
-                    "{:?} removing {:?} due to consensus on accusation of malice {:?}",
+                    "{:?} removing {:?} due to consensus on accusation of malice {}",
                     self.our_pub_id(),
                     offender,
                     malice
@@ -888,7 +2540,9 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
                 }
                 None
             }
-            Some(Observation::Genesis { .. }) | Some(Observation::OpaquePayload(_)) => None,
+            Some(Observation::Genesis { .. })
+            | Some(Observation::OpaquePayload(_))
+            | Some(Observation::Heartbeat(_)) => None,
             None => {
                 log_or_panic!("Failed to get observation from hash.");
                 None
@@ -961,7 +2615,7 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
                         );
                         let dkg_result = key_gen.generate().ok()?;
 
-                        self.consensused_blocks.push_back(BlockGroup(
+                        self.push_consensused_blocks(BlockGroup(
                             iter::once(Block::new_dkg_block(dkg_result)).collect(),
                         ));
                         let _ = self.key_gen.remove(&key_gen_id);
@@ -1040,6 +2694,11 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         };
 
         let peer_index = self.add_gossip_peer(peer_id, state);
+        if peer_index != PeerIndex::OUR && self.peer_list.last_event(peer_index).is_none() {
+            let _ = self
+                .pending_joins
+                .insert(peer_index, self.num_stable_blocks);
+        }
         PeerListChange::Add(peer_index)
     }
 
@@ -1051,20 +2710,16 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         //
         // To distinguish between the two, we check whether everyone we reached consensus on
         // adding also reached consensus on adding us.
+        //
+        // Voters are the peers we got consensus on adding.
         self.peer_list
-            .iter()
-            .filter(|(peer_index, peer)| {
-                // Peers that can vote, which means we got consensus on adding them.
-                peer.state().can_vote() &&
-                        // Excluding us.
-                        *peer_index != PeerIndex::OUR &&
-                        // Excluding the peer being added.
-                        peer.id() != peer_id
-            })
-            .all(|(_, peer)| {
+            .other_voters()
+            // Excluding the peer being added.
+            .filter(|(_, other_id)| *other_id != peer_id)
+            .all(|(peer_index, _)| {
                 // Peers that can receive, which implies they've already sent us at least
                 // one message which implies they've already reached consensus on adding us.
-                peer.state().can_recv()
+                self.peer_list.peer_state(peer_index).can_recv()
             })
     }
 
@@ -1104,9 +2759,9 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         }
 
         trace!(
-            "{:?} creating a meta-event for event {:?}",
+            "{:?} creating a meta-event for event {}",
             self.our_pub_id(),
-            event
+            event.qualified_name(&self.peer_list)
         );
 
         let mut builder =
@@ -1171,37 +2826,39 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
     ) -> bool {
         match payload_key.consensus_mode() {
             ConsensusMode::Single => {
-                let num_ancestor_peers =
-                    self.num_creators_of_ancestors(peers_that_can_vote, &*builder.event());
-                is_more_than_two_thirds(num_ancestor_peers, peers_that_can_vote.len())
+                let ancestor_peers_weight =
+                    self.weight_of_creators_of_ancestors(peers_that_can_vote, &*builder.event());
+                self.is_supermajority(ancestor_peers_weight)
                     && self.has_ancestor_carrying_payload(builder.event(), payload_key)
             }
             ConsensusMode::Supermajority => {
-                let num_peers_that_did_vote = self.num_creators_of_ancestors_carrying_payload(
+                let weight_that_did_vote = self.weight_of_creators_of_ancestors_carrying_payload(
                     peers_that_can_vote,
                     builder.event(),
                     payload_key,
                 );
-                is_more_than_two_thirds(num_peers_that_did_vote, peers_that_can_vote.len())
+                self.is_supermajority(weight_that_did_vote)
             }
         }
     }
 
-    // Number of unique peers that created at least one ancestor of the given event.
-    fn num_creators_of_ancestors(
+    // Total weight of the unique peers that created at least one ancestor of the given event.
+    fn weight_of_creators_of_ancestors(
         &self,
         peers_that_can_vote: &PeerIndexSet,
         event: &Event<S::PublicId>,
     ) -> usize {
-        event
-            .last_ancestors()
-            .filter(|(peer_index, _)| peers_that_can_vote.contains(*peer_index))
-            .count()
+        self.weight_of(
+            event
+                .last_ancestors()
+                .map(|(peer_index, _)| peer_index)
+                .filter(|peer_index| peers_that_can_vote.contains(*peer_index)),
+        )
     }
 
-    // Number of unique peers that created at least one ancestor of the given event that carries the
-    // given payload.
-    fn num_creators_of_ancestors_carrying_payload(
+    // Total weight of the unique peers that created at least one ancestor of the given event that
+    // carries the given payload.
+    fn weight_of_creators_of_ancestors_carrying_payload(
         &self,
         peers_that_can_vote: &PeerIndexSet,
         event: IndexedEventRef<S::PublicId>,
@@ -1209,14 +2866,11 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
     ) -> usize {
         let unconsensused_events = self.unconsensused_events(Some(payload_key)).collect_vec();
 
-        peers_that_can_vote
-            .iter()
-            .filter(|peer_index| {
-                unconsensused_events.iter().any(|that_event| {
-                    that_event.creator() == *peer_index && event.is_descendant_of(*that_event)
-                })
+        self.weight_of(peers_that_can_vote.iter().filter(|peer_index| {
+            unconsensused_events.iter().any(|that_event| {
+                that_event.creator() == *peer_index && event.is_descendant_of(*that_event)
             })
-            .count()
+        }))
     }
 
     // At least one ancestor of the given event carries the given payload.
@@ -1238,7 +2892,6 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
             return;
         }
 
-        let voter_count = self.voter_count();
         let observees: PeerIndexSet = self
             .meta_election
             .interesting_events()
@@ -1253,7 +2906,7 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
             })
             .collect();
 
-        if is_more_than_two_thirds(observees.len(), voter_count) {
+        if self.is_supermajority(self.weight_of(&observees)) {
             builder.set_observer(Observer::This(observees));
         } else {
             builder.set_observer(Observer::None);
@@ -1283,8 +2936,8 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         }
 
         let voters = self.voters();
-        let voters_len = match NonZeroUsize::new(voters.len()) {
-            Some(num) => num,
+        let voters_weight = match NonZeroUsize::new(self.voters_weight()) {
+            Some(weight) => weight,
             None => {
                 log_or_panic!("{:?} has no voters", self.our_pub_id());
                 return Ok(());
@@ -1296,6 +2949,10 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
             // This event wasn't created by a valid voter. It has no meta_votes.
             return Ok(());
         }
+        let weights = VoteWeights {
+            own: self.voter_weight(builder.event().creator()),
+            total: voters_weight,
+        };
 
         let ancestors_meta_votes =
             self.other_voting_ancestors_meta_votes(&voters, &builder.event());
@@ -1314,7 +2971,7 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
                 .into_iter()
                 .map(|(peer_index, parent_votes)| {
                     let other_votes = Self::peer_meta_votes(&ancestors_meta_votes, peer_index);
-                    let temp_votes = MetaVote::next_temp(parent_votes, &other_votes, voters_len);
+                    let temp_votes = MetaVote::next_temp(parent_votes, &other_votes, weights);
 
                     (peer_index, temp_votes)
                 })
@@ -1322,7 +2979,7 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
 
             for (peer_index, temp_votes) in &temp_votes {
                 let coin_tosses = self.toss_coins(&voters, peer_index, temp_votes)?;
-                let final_meta_votes = MetaVote::next_final(temp_votes, &coin_tosses, voters_len);
+                let final_meta_votes = MetaVote::next_final(temp_votes, &coin_tosses, weights);
 
                 builder.add_meta_votes(peer_index, final_meta_votes);
             }
@@ -1333,7 +2990,7 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
                     let other_votes = Self::peer_meta_votes(&ancestors_meta_votes, peer_index);
                     let initial_estimate = builder.has_observee(peer_index);
 
-                    MetaVote::new_for_observer(initial_estimate, &other_votes, voters_len)
+                    MetaVote::new_for_observer(initial_estimate, &other_votes, weights)
                 };
 
                 builder.add_meta_votes(peer_index, new_meta_votes);
@@ -1341,9 +2998,9 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         }
 
         trace!(
-            "{:?} has set the meta votes for {:?}",
+            "{:?} has set the meta votes for {}",
             self.our_pub_id(),
-            *builder.event(),
+            builder.event().qualified_name(&self.peer_list),
         );
 
         Ok(())
@@ -1370,18 +3027,26 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         _peer_index: PeerIndex,
         temp_vote: &MetaVote,
     ) -> Result<Option<bool>> {
+        #[cfg(any(test, feature = "testing"))]
+        {
+            if let Some(coin) = self.meta_election.forced_coin_toss(temp_vote.round) {
+                return Ok(Some(coin));
+            }
+        }
+
         // FIXME: This coin is common, but also predictable. It means that we can't guarentee
         // Liveness in the presence of a sophisticated adversary which controls the scheduler.
         // It is only a temporary measure until we implement an actual common coin.
         Ok(Some(temp_vote.round % 2 != 0))
     }
 
-    // Returns all the meta votes from the event's voting ancestors except the event's creator.
+    // Returns all the meta votes from the event's voting ancestors except the event's creator,
+    // along with the weights of their creators.
     fn other_voting_ancestors_meta_votes(
         &self,
         voters: &PeerIndexSet,
         event: &Event<S::PublicId>,
-    ) -> Vec<&PeerIndexMap<Vec<MetaVote>>> {
+    ) -> Vec<(&PeerIndexMap<Vec<MetaVote>>, usize)> {
         voters
             .iter()
             .filter(|voter_index| *voter_index != event.creator())
@@ -1389,25 +3054,27 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
                 event
                     .non_fork_last_ancestor_by(creator)
                     .and_then(|index_by_creator| {
-                        let event_index = self
-                            .peer_list
-                            .events_by_index(creator, index_by_creator)
-                            .next()?;
+                        let event_index =
+                            self.peer_list.event_by_index(creator, index_by_creator)?;
                         self.meta_election.populated_meta_votes(event_index)
                     })
+                    .map(|meta_votes| (meta_votes, self.voter_weight(creator)))
             })
             .collect()
     }
 
-    // Collect the vectors of meta votes for the peer
+    // Collect the vectors of meta votes for the peer, along with the weights of their creators.
     fn peer_meta_votes<'a>(
-        meta_votes_maps: &'a [&PeerIndexMap<Vec<MetaVote>>],
+        meta_votes_maps: &'a [(&PeerIndexMap<Vec<MetaVote>>, usize)],
         peer_index: PeerIndex,
-    ) -> Vec<&'a [MetaVote]> {
+    ) -> Vec<(&'a [MetaVote], usize)> {
         meta_votes_maps
             .iter()
-            .filter_map(|meta_votes| meta_votes.get(peer_index))
-            .map(Vec::as_slice)
+            .filter_map(|(meta_votes, weight)| {
+                meta_votes
+                    .get(peer_index)
+                    .map(|meta_votes| (meta_votes.as_slice(), *weight))
+            })
             .collect()
     }
 
@@ -1421,6 +3088,42 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         self.meta_election.voters().len()
     }
 
+    // Weight of the given peer in the supermajority checks: 1 unless set by `set_voter_weights`.
+    fn voter_weight(&self, peer_index: PeerIndex) -> usize {
+        if self.voter_weights.is_empty() {
+            return 1;
+        }
+        self.peer_list
+            .get(peer_index)
+            .and_then(|peer| self.voter_weights.get(peer.id()))
+            .cloned()
+            .unwrap_or(1)
+    }
+
+    // Total weight of the given peers.
+    fn weight_of<I: IntoIterator<Item = PeerIndex>>(&self, peers: I) -> usize {
+        peers
+            .into_iter()
+            .map(|peer_index| self.voter_weight(peer_index))
+            .sum()
+    }
+
+    // Total weight of the voters for the given meta-election.
+    fn voters_weight(&self) -> usize {
+        self.weight_of(self.voters())
+    }
+
+    // Smallest weight which is more than two thirds of the total weight of the voters for the
+    // given meta-election.  This is the single threshold all the supermajority checks use.
+    fn quorum_weight(&self) -> usize {
+        supermajority(self.voters_weight())
+    }
+
+    // Whether `weight` reaches `quorum_weight`.
+    fn is_supermajority(&self, weight: usize) -> bool {
+        weight >= self.quorum_weight()
+    }
+
     fn unconsensused_events(
         &self,
         filter_key: Option<&ObservationKey>,
@@ -1485,7 +3188,7 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
             .fold(BTreeMap::new(), |mut map, (idx, payload_key)| {
                 let (count, min_index) = map.entry(*payload_key).or_insert((0, idx));
                 *count += 1;
-                *min_index = std::cmp::min(*min_index, idx);
+                *min_index = cmp::min(*min_index, idx);
                 map
             });
 
@@ -1535,26 +3238,28 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         Ok(BlockGroup(blocks?))
     }
 
-    // Returns the number of peers that created events which are seen by event X (descendant) and
-    // see event Y (ancestor). This means number of peers through which there is a directed path
-    // between x and y, excluding peers contains fork.
-    fn num_peers_created_events_seen_by_x_that_can_see_y(
+    // Returns the total weight of the peers that created events which are seen by event X
+    // (descendant) and see event Y (ancestor). This means the peers through which there is a
+    // directed path between x and y, excluding peers contains fork.
+    fn weight_of_peers_created_events_seen_by_x_that_can_see_y(
         &self,
         x: &Event<S::PublicId>,
         y: &Event<S::PublicId>,
     ) -> usize {
-        x.last_ancestors()
-            .filter(|(peer_index, event_index)| {
-                for event_idx in self.peer_list.events_by_index(*peer_index, *event_index) {
-                    if let Ok(event) = self.get_known_event(event_idx) {
-                        if x.sees(event) && event.sees(y) {
-                            return true;
+        self.weight_of(
+            x.last_ancestors()
+                .filter(|(peer_index, event_index)| {
+                    for event_idx in self.peer_list.events_by_index(*peer_index, *event_index) {
+                        if let Ok(event) = self.get_known_event(event_idx) {
+                            if x.sees(event) && event.sees(y) {
+                                return true;
+                            }
                         }
                     }
-                }
-                false
-            })
-            .count()
+                    false
+                })
+                .map(|(peer_index, _)| peer_index),
+        )
     }
 
     // Returns whether event X can strongly see the event Y during the evaluation of the given
@@ -1564,9 +3269,8 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         A: AsRef<Event<S::PublicId>>,
         B: AsRef<Event<S::PublicId>>,
     {
-        is_more_than_two_thirds(
-            self.num_peers_created_events_seen_by_x_that_can_see_y(x.as_ref(), y.as_ref()),
-            self.voter_count(),
+        self.is_supermajority(
+            self.weight_of_peers_created_events_seen_by_x_that_can_see_y(x.as_ref(), y.as_ref()),
         )
     }
 
@@ -1580,6 +3284,25 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         })
     }
 
+    fn should_create_response_event(&self, other_parent: EventIndex) -> bool {
+        match self.event_creation_policy {
+            EventCreationPolicy::Always => true,
+            EventCreationPolicy::SkipRedundantResponses => {
+                let our_last_event = self
+                    .peer_list
+                    .last_event(PeerIndex::OUR)
+                    .and_then(|index| self.graph.get(index));
+                let other_parent = self.graph.get(other_parent);
+                match (our_last_event, other_parent) {
+                    (Some(our_last_event), Some(other_parent)) => {
+                        !our_last_event.is_descendant_of(other_parent)
+                    }
+                    _ => true,
+                }
+            }
+        }
+    }
+
     fn add_sync_event(&mut self, is_request: bool, other_parent: EventIndex) -> Result<()> {
         let self_parent = self.our_last_event_index()?;
         let event = if is_request {
@@ -1597,10 +3320,62 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
             }
         }
 
+        let _ = self.add_event(event)?;
+        self.add_heartbeat_event_if_idle()
+    }
+
+    // Votes for a heartbeat if we are a voter, some observation other than a heartbeat is still
+    // awaiting consensus, and our last `heartbeat_interval` events carry no payload.
+    fn add_heartbeat_event_if_idle(&mut self) -> Result<()> {
+        let interval = match self.heartbeat_interval {
+            Some(interval) => interval,
+            None => return Ok(()),
+        };
+        if !self.peer_list.our_state().contains(PeerState::VOTE)
+            || !self.has_stallable_observations()
+        {
+            return Ok(());
+        }
+
+        let mut index = self.peer_list.last_event(PeerIndex::OUR);
+        for _ in 0..interval {
+            match index.and_then(|index| self.graph.get(index)) {
+                Some(event) if event.payload_key().is_none() => index = event.self_parent(),
+                _ => return Ok(()),
+            }
+        }
+
+        let self_parent = self.our_last_event_index()?;
+        let index_by_creator = self
+            .graph
+            .get(self_parent)
+            .map_or(0, |event| event.index_by_creator() + 1);
+        let event = self.new_event_from_observation(
+            self_parent,
+            Observation::Heartbeat(index_by_creator as u64),
+        )?;
         let _ = self.add_event(event)?;
         Ok(())
     }
 
+    // Whether any observation other than a heartbeat has been voted for but not yet consensused.
+    // Heartbeats only exist to get such observations decided, so they aren't worth casting
+    // otherwise.
+    fn has_stallable_observations(&self) -> bool {
+        for key in self
+            .meta_election
+            .unconsensused_events
+            .indices_by_key
+            .keys()
+        {
+            match self.observations.get(key).map(|info| &info.observation) {
+                Some(Observation::Heartbeat(_)) | None => (),
+                Some(_) => return true,
+            }
+        }
+        false
+    }
+
     fn create_dkg_events(&mut self) -> Result<()> {
         for msg in mem::replace(&mut self.pending_dkg_msgs, vec![]) {
             self.create_dkg_event(msg)?;
@@ -1732,7 +3507,7 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
             graph: &self.graph,
             peer_list: &self.peer_list,
             observations: &self.observations,
-            consensus_mode: self.consensus_mode,
+            consensus_modes: &self.consensus_modes,
         }
     }
 
@@ -1741,10 +3516,7 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         &'a self,
         event: &Event<S::PublicId>,
     ) -> Option<&'a Observation<T, S::PublicId>> {
-        event
-            .payload_key()
-            .and_then(|key| self.observations.get(key))
-            .map(|info| &info.observation)
+        event.payload(&self.observations)
     }
 
     #[cfg(any(all(test, feature = "mock"), feature = "malice-detection"))]
@@ -1837,6 +3609,10 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         self.detect_self_parent_by_different_creator(event)?;
         self.detect_invalid_sync_event(event)?;
 
+        if !self.malice_detection_enabled {
+            return Ok(());
+        }
+
         self.detect_unexpected_genesis(event);
         self.detect_missing_genesis(event);
         self.detect_duplicate_vote(event);
@@ -2041,7 +3817,7 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
         // self_parent means already cast forking accusation.
         same_index_events
             .filter_map(|other_event| self.graph.get(other_event))
-            .filter(|other_event| other_event.inner().self_parent() == event.self_parent())
+            .filter(|other_event| other_event.self_parent() == event.self_parent())
             .count()
             == 1
     }
@@ -2124,6 +3900,20 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
     }
 
     fn accuse(&mut self, offender: PeerIndex, malice: Malice<T, S::PublicId>) {
+        if !self.malice_detection_enabled {
+            return;
+        }
+        if let (Some(hook), Some(peer)) = (&self.accusation_hook, self.peer_list.get(offender)) {
+            if (hook.borrow_mut())(peer.id(), &malice) == AccusationDecision::Veto {
+                info!(
+                    "{:?} not accusing {:?} of malice {}: vetoed by the accusation hook",
+                    self.our_pub_id(),
+                    peer.id(),
+                    malice
+                );
+                return;
+            }
+        }
         self.pending_accusations.push((offender, malice));
     }
 
@@ -2300,7 +4090,7 @@ impl<T: NetworkEvent, S: SecretId> Drop for Parsec<T, S> {
     fn drop(&mut self) {
         dump_graph::to_file(dump_graph::ToFileInfo {
             owner_id: self.our_pub_id(),
-            consensus_mode: self.consensus_mode,
+            consensus_mode: self.consensus_modes.global(),
             gossip_graph: &self.graph,
             meta_election: &self.meta_election,
             peer_list: &self.peer_list,
@@ -2323,6 +4113,112 @@ fn get_known_event<'a, P: PublicId>(
     })
 }
 
+// Checks the weights passed to `set_voter_weights`, returning the reason they're invalid if so.
+pub(crate) fn validate_voter_weights<P: PublicId>(
+    weights: &BTreeMap<P, usize>,
+) -> std::result::Result<(), &'static str> {
+    if weights.values().any(|&weight| weight == 0) {
+        return Err("Voter weights can't be zero");
+    }
+    if weights.values().any(|&weight| weight > MAX_VOTER_WEIGHT) {
+        return Err("Voter weights can't exceed MAX_VOTER_WEIGHT");
+    }
+    Ok(())
+}
+
+// Whether to verify the signatures of the events received in a gossip message.
+#[derive(Clone, Copy)]
+enum SignatureCheck {
+    Verify,
+    // Only for messages from fully trusted peers, see `handle_request_trusted`.
+    Skip,
+}
+
+// A received event put aside as per `UnknownPeerPolicy::Buffer`.
+#[derive(Clone)]
+struct DeferredEvent<T: NetworkEvent, P: PublicId> {
+    hash: EventHash,
+    packed_event: PackedEvent<T, P>,
+    // The peer which gossiped it to us.
+    src_index: PeerIndex,
+    // Value of `num_stable_blocks` when it was put aside.
+    num_stable_blocks: usize,
+}
+
+// The events put aside as per `UnknownPeerPolicy::Buffer`, in the order we received them and
+// indexed by hash.
+#[derive(Clone)]
+struct DeferredEvents<T: NetworkEvent, P: PublicId> {
+    events: VecDeque<DeferredEvent<T, P>>,
+    hashes: BTreeSet<EventHash>,
+}
+
+impl<T: NetworkEvent, P: PublicId> DeferredEvents<T, P> {
+    fn new() -> Self {
+        Self {
+            events: VecDeque::new(),
+            hashes: BTreeSet::new(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    fn contains(&self, hash: &EventHash) -> bool {
+        self.hashes.contains(hash)
+    }
+
+    fn push_back(&mut self, event: DeferredEvent<T, P>) {
+        let _ = self.hashes.insert(event.hash);
+        self.events.push_back(event);
+    }
+
+    // Removes the oldest event relayed by the peer which relayed the most of them.
+    fn evict(&mut self) {
+        let mut counts = BTreeMap::new();
+        for event in &self.events {
+            *counts.entry(event.src_index).or_insert(0) += 1;
+        }
+        let max_count = match counts.values().max() {
+            Some(max_count) => *max_count,
+            None => return,
+        };
+        let position = self
+            .events
+            .iter()
+            .position(|event| counts[&event.src_index] == max_count);
+        if let Some(event) = position.and_then(|position| self.events.remove(position)) {
+            let _ = self.hashes.remove(&event.hash);
+        }
+    }
+
+    fn retain<F: FnMut(&DeferredEvent<T, P>) -> bool>(&mut self, mut keep: F) {
+        let hashes = &mut self.hashes;
+        self.events.retain(|event| {
+            keep(event) || {
+                let _ = hashes.remove(&event.hash);
+                false
+            }
+        });
+    }
+
+    fn clear(&mut self) {
+        self.events.clear();
+        self.hashes.clear();
+    }
+
+    // Removes and returns all the events.
+    fn take(&mut self) -> VecDeque<DeferredEvent<T, P>> {
+        self.hashes.clear();
+        self.events.drain(..).collect()
+    }
+}
+
 // What to do after processing the current event.
 enum PostProcessAction {
     // Continue with the next event (if any)
@@ -2334,6 +4230,7 @@ enum PostProcessAction {
 #[cfg(feature = "malice-detection")]
 type Accusations<T, P> = Vec<(PeerIndex, Malice<T, P>)>;
 
+#[derive(Clone)]
 enum PendingEvent<T: NetworkEvent, P: PublicId> {
     Sync {
         is_request: bool,
@@ -2363,11 +4260,174 @@ impl<T: NetworkEvent, S: SecretId> Parsec<T, S> {
     pub(crate) fn ignore_process_events(&self) -> bool {
         self.ignore_process_events
     }
+
+    /// **NOT FOR PRODUCTION USE**: makes the coin toss of each meta-vote round `r` yield
+    /// `coin_tosses[r]` instead of the common coin, for rounds covered by `coin_tosses`.  The
+    /// sequence applies to every meta-election from now on.
+    ///
+    /// The coin must stay common, so all peers of a test network need the same sequence.  This
+    /// lets tests deterministically steer the `GenuineFlip` steps of undecided meta-votes.
+    pub fn force_coin_tosses(&mut self, coin_tosses: Vec<bool>) {
+        self.meta_election.forced_coin_tosses = coin_tosses;
+    }
+
+    // Returns the number of meta-elections decided so far, along with a snapshot of the current
+    // one.
+    pub(crate) fn meta_election_snapshot(&self) -> (usize, MetaElectionSnapshot<S::PublicId>) {
+        (
+            self.meta_election.consensus_len(),
+            MetaElectionSnapshot::new(&self.meta_election, &self.graph, &self.peer_list),
+        )
+    }
+
+    /// **NOT FOR PRODUCTION USE**: Returns a deep copy of this instance which will use
+    /// `secure_rng` as its RNG.
+    ///
+    /// The copy includes our secret id and any secret shares of DKGs in progress, so it must only
+    /// be used for testing, e.g. to feed two copies of the same peer diverging inputs and compare
+    /// the results.  `Clone` isn't implemented since the RNG can't be duplicated.
+    pub fn clone_with_rng(&self, secure_rng: Box<dyn RngCore>) -> Self
+    where
+        S: Clone,
+    {
+        Self {
+            peer_list: self.peer_list.clone(),
+            key_gen: self.key_gen.clone(),
+            key_gen_next_id: self.key_gen_next_id,
+            graph: self.graph.clone(),
+            observations: self.observations.clone(),
+            consensused_blocks: self.consensused_blocks.clone(),
+            num_stable_blocks: self.num_stable_blocks,
+            retain_blocks: self.retain_blocks,
+            retained_blocks: self.retained_blocks.clone(),
+            first_retained_block_index: self.first_retained_block_index,
+            block_archive: self.block_archive.clone(),
+            next_block_index: self.next_block_index,
+            membership_polled: self.membership_polled.clone(),
+            removals_by_accusation: self.removals_by_accusation.clone(),
+            meta_election: self.meta_election.clone(),
+            consensus_modes: self.consensus_modes.clone(),
+            event_creation_policy: self.event_creation_policy,
+            fork_policy: self.fork_policy,
+            gossip_policy: self.gossip_policy,
+            idle_gossip_backoff: self.idle_gossip_backoff.clone(),
+            redundant_events: self.redundant_events.clone(),
+            have_summaries_sent: self.have_summaries_sent.clone(),
+            pending_joins: self.pending_joins.clone(),
+            join_stall_threshold: self.join_stall_threshold,
+            heartbeat_interval: self.heartbeat_interval,
+            unknown_peer_policy: self.unknown_peer_policy,
+            voter_weights: self.voter_weights.clone(),
+            trusted_ingest: self.trusted_ingest,
+            deferred_events: self.deferred_events.clone(),
+            deferred_events_retried_at: self.deferred_events_retried_at,
+            pending_dkg_msgs: self.pending_dkg_msgs.clone(),
+            #[cfg(feature = "malice-detection")]
+            pending_accusations: self.pending_accusations.clone(),
+            #[cfg(feature = "malice-detection")]
+            malice_detection_enabled: self.malice_detection_enabled,
+            #[cfg(feature = "malice-detection")]
+            accusation_hook: self.accusation_hook.clone(),
+            pending_events: self.pending_events.clone(),
+            growth_rate: self.growth_rate.clone(),
+            ignore_process_events: self.ignore_process_events,
+            secure_rng: ParsecRng::new(secure_rng),
+        }
+    }
+
+    /// **NOT FOR PRODUCTION USE**: Resets this instance to the state it would have right after
+    /// `from_genesis` with the given `genesis_group` and `genesis_related_info`.
+    ///
+    /// The graph, meta-election, observations, blocks, peer list and any events put aside or
+    /// pulls in flight are all discarded, while our secret id, RNG and configuration (consensus
+    /// modes, policies, voter weights, retention settings, block archive, hooks and so on) are
+    /// kept.  This lets test suites reuse an instance across cases without paying for key
+    /// generation each time.
+    pub fn reset_to_genesis(
+        &mut self,
+        genesis_group: &BTreeSet<S::PublicId>,
+        genesis_related_info: Vec<u8>,
+    ) where
+        S: Clone,
+    {
+        let mut parsec = Self::from_genesis(
+            self.peer_list.our_id().clone(),
+            genesis_group,
+            genesis_related_info,
+            self.consensus_modes.global(),
+            Box::new(StepRng::new(0, 0)),
+        );
+        parsec.consensus_modes = self.consensus_modes.clone();
+        parsec.retain_blocks = self.retain_blocks;
+        parsec.block_archive = self.block_archive.take();
+        parsec.event_creation_policy = self.event_creation_policy;
+        parsec.fork_policy = self.fork_policy;
+        parsec.gossip_policy = self.gossip_policy;
+        parsec.join_stall_threshold = self.join_stall_threshold;
+        parsec.heartbeat_interval = self.heartbeat_interval;
+        parsec.unknown_peer_policy = self.unknown_peer_policy;
+        parsec.voter_weights = self.voter_weights.clone();
+        parsec.trusted_ingest = self.trusted_ingest;
+        parsec
+            .meta_election
+            .set_consensus_history_retention(self.meta_election.consensus_history_retention);
+        parsec.meta_election.forced_coin_tosses = self.meta_election.forced_coin_tosses.clone();
+        #[cfg(feature = "malice-detection")]
+        {
+            parsec.malice_detection_enabled = self.malice_detection_enabled;
+            parsec.accusation_hook = self.accusation_hook.take();
+        }
+        parsec.ignore_process_events = self.ignore_process_events;
+        mem::swap(&mut parsec.growth_rate, &mut self.growth_rate);
+        parsec.growth_rate.reset();
+        mem::swap(&mut parsec.secure_rng, &mut self.secure_rng);
+        *self = parsec;
+    }
 }
 
 #[cfg(any(feature = "testing", all(test, feature = "mock")))]
 impl Parsec<Transaction, PeerId> {
-    #[cfg(all(test, feature = "mock"))]
+    /// Rebuilds the state of a peer dumped with `DUMP_CORE` set, from the `.core` file at
+    /// `core_path`, so that it can be stepped through and debugged.
+    ///
+    /// A `.core` file only holds the hashes of the dumped events, so the state itself is read from
+    /// the `.dot` file next to it.  The `.core` file is then used to validate the result: an error
+    /// of kind `InvalidData` is returned if the rebuilt graph or meta-election differ from the
+    /// dumped ones.  Our ID is the dumped peer's, which is recorded in the `.dot` file.
+    #[cfg(feature = "dump-graphs")]
+    pub fn from_core_snapshot<P: AsRef<Path>>(
+        core_path: P,
+        secure_rng: Box<dyn RngCore>,
+    ) -> io::Result<Self> {
+        let core_path = core_path.as_ref();
+        let expected: Snapshot = bincode::deserialize(&fs::read(core_path)?)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+
+        let parsed_contents = parse_dot_file(core_path.with_extension("dot"))?;
+        if let Err(hash) = parsed_contents.graph.validate() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{:?} contains inconsistent event {:?}", core_path, hash),
+            ));
+        }
+        let parsec = Self::from_parsed_contents(parsed_contents, secure_rng);
+        let actual = (
+            GraphSnapshot::new(&parsec.graph),
+            MetaElectionSnapshot::new(&parsec.meta_election, &parsec.graph, &parsec.peer_list),
+        );
+        if actual != expected {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{:?} doesn't match its dot file", core_path),
+            ));
+        }
+        Ok(parsec)
+    }
+
+    #[cfg(any(
+        all(test, feature = "mock"),
+        all(feature = "testing", feature = "dump-graphs")
+    ))]
     pub(crate) fn from_parsed_contents(
         mut parsed_contents: ParsedContents,
         secure_rng: Box<dyn RngCore>,
@@ -2376,7 +4436,7 @@ impl Parsec<Transaction, PeerId> {
         let mut parsec = Parsec::empty(
             peer_list,
             PeerIndexSet::default(),
-            parsed_contents.consensus_mode,
+            parsed_contents.consensus_modes.global(),
             secure_rng,
         );
 
@@ -2492,6 +4552,25 @@ impl<T: NetworkEvent, S: SecretId> TestParsec<T, S> {
         self.0.confirm_allowed_to_gossip_to(peer_index)
     }
 
+    // Votes for `observation` again, bypassing the check `vote_for` makes against voting for the
+    // same observation twice.
+    pub fn vote_for_again(&mut self, observation: Observation<T, S::PublicId>) -> Result<()> {
+        let self_parent = self.our_last_event_index();
+        let event = self
+            .0
+            .new_event_from_observation(self_parent, observation)?;
+        let _ = self.0.add_event(event)?;
+        Ok(())
+    }
+
+    // The coin toss results our meta-votes would use, for the rounds of `temp_votes`.
+    #[cfg(all(test, feature = "mock"))]
+    pub fn toss_coins(&self, temp_votes: &[MetaVote]) -> BTreeMap<usize, bool> {
+        unwrap!(self
+            .0
+            .toss_coins(self.0.voters(), PeerIndex::OUR, temp_votes))
+    }
+
     #[cfg(all(test, feature = "mock"))]
     pub fn event_payload(
         &self,
@@ -2523,6 +4602,22 @@ impl TestParsec<Transaction, PeerId> {
         self.0.peer_list.change_peer_state(peer_index, state)
     }
 
+    pub fn isolate_peer(&mut self, peer_id: &PeerId) {
+        let peer_index = unwrap!(self.0.peer_list.get_index(peer_id));
+        self.0.peer_list.isolate_peer(peer_index)
+    }
+
+    // Applies consensus on adding `peer_id` to the section.
+    pub fn handle_add_peer(&mut self, peer_id: &PeerId) {
+        let _ = self.0.handle_add_peer(peer_id);
+    }
+
+    // Applies consensus on removing `peer_id` from the section.
+    pub fn handle_remove_peer(&mut self, peer_id: &PeerId) {
+        let event_index = self.our_last_event_index();
+        let _ = self.0.handle_remove_peer(event_index, peer_id);
+    }
+
     pub fn pack_event(&self, event: &Event<PeerId>) -> PackedEvent<Transaction, PeerId> {
         unwrap!(event.pack(self.0.event_context()))
     }
@@ -2531,7 +4626,7 @@ impl TestParsec<Transaction, PeerId> {
         &mut self,
         packed_event: PackedEvent<Transaction, PeerId>,
     ) -> Result<EventIndex> {
-        match self.0.unpack(packed_event)? {
+        match self.0.unpack(packed_event, SignatureCheck::Verify)? {
             Some(event) => self.0.add_event(event),
             None => Err(Error::Logic),
         }
@@ -2595,14 +4690,23 @@ impl TestParsec<Transaction, PeerId> {
     ) -> EventHash {
         let src_index = unwrap!(self.0.get_peer_index(src));
         let last_hash = unwrap!(req.packed_events.last()).compute_hash();
-        let other_parent = unwrap!(self.0.unpack_and_add_events(src_index, req.packed_events));
+        let other_parent = unwrap!(self.0.unpack_and_add_events(
+            src_index,
+            req.packed_events,
+            SignatureCheck::Verify
+        ));
         unwrap!(self.0.create_accusation_events(other_parent));
 
         let invalid_observation = Observation::<Transaction, _>::Accusation {
             offender: src.clone(),
             malice: Malice::Fork(last_hash),
         };
-        unwrap!(self.0.vote_for(invalid_observation.clone()));
+        // `vote_for` rejects accusations, so create the event directly.
+        let self_parent = self.our_last_event_index();
+        let event = unwrap!(self
+            .0
+            .new_event_from_observation(self_parent, invalid_observation.clone()));
+        let _ = unwrap!(self.0.add_event(event));
         let invalid_accusation_hash = {
             let invalid_accusation = unwrap!(self.0.graph.get(self.our_last_event_index()));
             assert_eq!(
@@ -2624,7 +4728,11 @@ impl TestParsec<Transaction, PeerId> {
         req: Request<Transaction, PeerId>,
     ) {
         let src_index = unwrap!(self.0.get_peer_index(src));
-        let other_parent = unwrap!(self.0.unpack_and_add_events(src_index, req.packed_events));
+        let other_parent = unwrap!(self.0.unpack_and_add_events(
+            src_index,
+            req.packed_events,
+            SignatureCheck::Verify
+        ));
         self.0.pending_accusations.clear();
         unwrap!(self.0.create_sync_event(true, other_parent));
     }
@@ -0,0 +1,502 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+#[cfg(feature = "malice-detection")]
+use crate::parsec::AccusationHook;
+use crate::{
+    block::Block,
+    error::{Error, Result},
+    growth_rate::Clock,
+    id::SecretId,
+    network_event::NetworkEvent,
+    observation::ConsensusMode,
+    parsec::{
+        validate_voter_weights, EventCreationPolicy, ForkPolicy, GossipPolicy, OnArchive, Parsec,
+        UnknownPeerPolicy, DEFAULT_UNKNOWN_PEER_POLICY,
+    },
+};
+use rand::RngCore;
+use std::{
+    collections::{btree_map::Entry, BTreeMap, BTreeSet},
+    marker::PhantomData,
+    time::Duration,
+};
+
+enum Membership<P> {
+    Genesis {
+        genesis_group: BTreeSet<P>,
+    },
+    Existing {
+        genesis_group: BTreeSet<P>,
+        section: BTreeSet<P>,
+    },
+}
+
+/// Builder gathering the configuration of a new `Parsec` instance.
+///
+/// The arguments every instance needs are passed to [from_genesis](#method.from_genesis) or
+/// [from_existing](#method.from_existing), and everything else has a default which can be
+/// overridden by chaining the other methods, e.g. `consensus_mode` then `fork_policy`.  `build`
+/// then checks the configuration as a whole before creating the instance, so that e.g. conflicting
+/// consensus modes registered for the same tag are reported as `Error::InvalidConfiguration`
+/// rather than discovered once peers disagree.
+pub struct ParsecBuilder<T: NetworkEvent, S: SecretId> {
+    our_id: S,
+    membership: Membership<S::PublicId>,
+    secure_rng: Box<dyn RngCore>,
+    genesis_related_info: Option<Vec<u8>>,
+    consensus_mode: ConsensusMode,
+    consensus_modes_by_tag: Vec<(u32, ConsensusMode)>,
+    event_creation_policy: EventCreationPolicy,
+    fork_policy: ForkPolicy,
+    gossip_policy: GossipPolicy,
+    join_stall_threshold: Option<usize>,
+    heartbeat_interval: Option<usize>,
+    unknown_peer_policy: UnknownPeerPolicy,
+    voter_weights: BTreeMap<S::PublicId, usize>,
+    trusted_ingest: bool,
+    consensus_history_retention: Option<usize>,
+    block_retention: bool,
+    block_archive: Option<(usize, OnArchive<T, S::PublicId>)>,
+    clock: Option<Box<dyn Clock>>,
+    event_ingestion_window: Option<Duration>,
+    #[cfg(feature = "malice-detection")]
+    malice_detection: bool,
+    #[cfg(feature = "malice-detection")]
+    accusation_hook: Option<AccusationHook<T, S::PublicId>>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: NetworkEvent, S: SecretId> ParsecBuilder<T, S> {
+    /// Starts configuring a `Parsec` for a peer with the given ID and genesis peer IDs (ours
+    /// included).  See [Parsec::from_genesis](struct.Parsec.html#method.from_genesis).
+    pub fn from_genesis(
+        our_id: S,
+        genesis_group: &BTreeSet<S::PublicId>,
+        secure_rng: Box<dyn RngCore>,
+    ) -> Self {
+        let membership = Membership::Genesis {
+            genesis_group: genesis_group.clone(),
+        };
+        Self::new(our_id, membership, secure_rng)
+    }
+
+    /// Starts configuring a `Parsec` for a peer joining an existing section.  See
+    /// [Parsec::from_existing](struct.Parsec.html#method.from_existing).
+    pub fn from_existing(
+        our_id: S,
+        genesis_group: &BTreeSet<S::PublicId>,
+        section: &BTreeSet<S::PublicId>,
+        secure_rng: Box<dyn RngCore>,
+    ) -> Self {
+        let membership = Membership::Existing {
+            genesis_group: genesis_group.clone(),
+            section: section.clone(),
+        };
+        Self::new(our_id, membership, secure_rng)
+    }
+
+    fn new(our_id: S, membership: Membership<S::PublicId>, secure_rng: Box<dyn RngCore>) -> Self {
+        Self {
+            our_id,
+            membership,
+            secure_rng,
+            genesis_related_info: None,
+            consensus_mode: ConsensusMode::Supermajority,
+            consensus_modes_by_tag: vec![],
+            event_creation_policy: EventCreationPolicy::Always,
+            fork_policy: ForkPolicy::AccuseAndProposeRemoval,
+            gossip_policy: GossipPolicy::Always,
+            join_stall_threshold: None,
+            heartbeat_interval: None,
+            unknown_peer_policy: DEFAULT_UNKNOWN_PEER_POLICY,
+            voter_weights: BTreeMap::new(),
+            trusted_ingest: false,
+            consensus_history_retention: None,
+            block_retention: false,
+            block_archive: None,
+            clock: None,
+            event_ingestion_window: None,
+            #[cfg(feature = "malice-detection")]
+            malice_detection: true,
+            #[cfg(feature = "malice-detection")]
+            accusation_hook: None,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Sets extra arbitrary information attached to the genesis event for use by the client.
+    /// Only valid when starting from genesis.  Defaults to empty.
+    pub fn genesis_related_info(mut self, info: Vec<u8>) -> Self {
+        self.genesis_related_info = Some(info);
+        self
+    }
+
+    /// Sets how many votes are needed for an observation to become a candidate for consensus.
+    /// Defaults to `ConsensusMode::Supermajority`.
+    pub fn consensus_mode(mut self, mode: ConsensusMode) -> Self {
+        self.consensus_mode = mode;
+        self
+    }
+
+    /// Makes opaque payloads whose [tag](trait.NetworkEvent.html#method.tag) is `tag` require
+    /// `mode` instead of the one set by [consensus_mode](#method.consensus_mode).
+    ///
+    /// All peers must register exactly the same modes, otherwise they will disagree on which
+    /// votes count towards the same observation.  Internal observations (membership changes, DKG,
+    /// accusations) aren't affected.
+    pub fn consensus_mode_for(mut self, tag: u32, mode: ConsensusMode) -> Self {
+        self.consensus_modes_by_tag.push((tag, mode));
+        self
+    }
+
+    /// See [Parsec::set_event_creation_policy](struct.Parsec.html#method.set_event_creation_policy).
+    pub fn event_creation_policy(mut self, policy: EventCreationPolicy) -> Self {
+        self.event_creation_policy = policy;
+        self
+    }
+
+    /// Sets what happens to a peer once an accusation of it having forked has been consensused.
+    /// Defaults to `ForkPolicy::AccuseAndProposeRemoval`.  Accusations of other kinds of malice
+    /// always lead to the removal of the offender.
+    ///
+    /// Whether forkers are removed affects the section membership, so all peers must use the
+    /// same policy, up to isolating forkers or not, which only affects our own gossip.  Hence it
+    /// can't be changed once the instance is built.
+    pub fn fork_policy(mut self, policy: ForkPolicy) -> Self {
+        self.fork_policy = policy;
+        self
+    }
+
+    /// See [Parsec::set_gossip_policy](struct.Parsec.html#method.set_gossip_policy).
+    pub fn gossip_policy(mut self, policy: GossipPolicy) -> Self {
+        self.gossip_policy = policy;
+        self
+    }
+
+    /// See [Parsec::set_join_stall_threshold](struct.Parsec.html#method.set_join_stall_threshold).
+    pub fn join_stall_threshold(mut self, threshold: Option<usize>) -> Self {
+        self.join_stall_threshold = threshold;
+        self
+    }
+
+    /// See [Parsec::set_heartbeat_interval](struct.Parsec.html#method.set_heartbeat_interval).
+    pub fn heartbeat_interval(mut self, interval: Option<usize>) -> Self {
+        self.heartbeat_interval = interval;
+        self
+    }
+
+    /// See [Parsec::set_unknown_peer_policy](struct.Parsec.html#method.set_unknown_peer_policy).
+    pub fn unknown_peer_policy(mut self, policy: UnknownPeerPolicy) -> Self {
+        self.unknown_peer_policy = policy;
+        self
+    }
+
+    /// **Experimental:** see
+    /// [Parsec::set_voter_weights](struct.Parsec.html#method.set_voter_weights).
+    pub fn voter_weights(mut self, weights: BTreeMap<S::PublicId, usize>) -> Self {
+        self.voter_weights = weights;
+        self
+    }
+
+    /// **Dangerous:** allows received gossip to be handled by
+    /// [Parsec::handle_request_trusted](struct.Parsec.html#method.handle_request_trusted) and
+    /// [Parsec::handle_response_trusted](struct.Parsec.html#method.handle_response_trusted),
+    /// which don't verify the signatures of the received events.  Only ever enable it in a network
+    /// whose members are all fully trusted.  Defaults to `false`, in which case these methods
+    /// return `Error::InvalidConfiguration`.
+    pub fn trusted_ingest(mut self, enabled: bool) -> Self {
+        self.trusted_ingest = enabled;
+        self
+    }
+
+    /// See
+    /// [Parsec::set_consensus_history_retention](struct.Parsec.html#method.set_consensus_history_retention).
+    pub fn consensus_history_retention(mut self, retention: Option<usize>) -> Self {
+        self.consensus_history_retention = retention;
+        self
+    }
+
+    /// See [Parsec::set_block_retention](struct.Parsec.html#method.set_block_retention).
+    pub fn block_retention(mut self, enabled: bool) -> Self {
+        self.block_retention = enabled;
+        self
+    }
+
+    /// See [Parsec::set_block_archive](struct.Parsec.html#method.set_block_archive).
+    pub fn block_archive(
+        mut self,
+        cap: usize,
+        on_archive: Box<dyn FnMut(Block<T, S::PublicId>)>,
+    ) -> Self {
+        self.block_archive = Some((cap, on_archive));
+        self
+    }
+
+    /// See [Parsec::set_clock](struct.Parsec.html#method.set_clock).
+    pub fn clock(mut self, clock: Box<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// See
+    /// [Parsec::set_event_ingestion_window](struct.Parsec.html#method.set_event_ingestion_window).
+    pub fn event_ingestion_window(mut self, window: Duration) -> Self {
+        self.event_ingestion_window = Some(window);
+        self
+    }
+
+    /// See [Parsec::set_malice_detection](struct.Parsec.html#method.set_malice_detection).
+    #[cfg(feature = "malice-detection")]
+    pub fn malice_detection(mut self, enabled: bool) -> Self {
+        self.malice_detection = enabled;
+        self
+    }
+
+    /// See [Parsec::set_accusation_hook](struct.Parsec.html#method.set_accusation_hook).
+    #[cfg(feature = "malice-detection")]
+    pub fn accusation_hook(mut self, hook: AccusationHook<T, S::PublicId>) -> Self {
+        self.accusation_hook = Some(hook);
+        self
+    }
+
+    /// Creates the `Parsec` instance, or returns `Error::InvalidConfiguration` if the
+    /// configuration is inconsistent, e.g. if we are missing from the genesis group, or if
+    /// different consensus modes were registered for the same tag.
+    pub fn build(self) -> Result<Parsec<T, S>> {
+        self.validate().map_err(Error::InvalidConfiguration)?;
+        Ok(self.build_unchecked())
+    }
+
+    // Logs (or panics in tests) if the configuration is invalid, but creates the instance anyway,
+    // as the plain constructors always did.
+    pub(crate) fn build_or_log(self) -> Parsec<T, S> {
+        if let Err(reason) = self.validate() {
+            log_or_panic!("{}", reason);
+        }
+        self.build_unchecked()
+    }
+
+    fn validate(&self) -> ::std::result::Result<(), &'static str> {
+        let our_id = self.our_id.public_id();
+        match self.membership {
+            Membership::Genesis { ref genesis_group } => {
+                if genesis_group.is_empty() {
+                    return Err("Genesis group can't be empty");
+                }
+                if !genesis_group.contains(our_id) {
+                    return Err("Genesis group must contain us");
+                }
+            }
+            Membership::Existing {
+                ref genesis_group,
+                ref section,
+            } => {
+                if genesis_group.is_empty() {
+                    return Err("Genesis group can't be empty");
+                }
+                if genesis_group.contains(our_id) {
+                    return Err("Genesis group can't already contain us");
+                }
+                if section.is_empty() {
+                    return Err("Section can't be empty");
+                }
+                if section.contains(our_id) {
+                    return Err("Section can't already contain us");
+                }
+                if self.genesis_related_info.is_some() {
+                    return Err("Genesis related info can only be set when starting from genesis");
+                }
+            }
+        }
+
+        if self.heartbeat_interval == Some(0) {
+            return Err("Heartbeat interval can't be zero");
+        }
+        validate_voter_weights(&self.voter_weights)?;
+
+        let mut modes_by_tag = BTreeMap::new();
+        for &(tag, mode) in &self.consensus_modes_by_tag {
+            match modes_by_tag.entry(tag) {
+                Entry::Vacant(entry) => {
+                    let _ = entry.insert(mode);
+                }
+                Entry::Occupied(entry) => {
+                    if *entry.get() != mode {
+                        return Err("Conflicting consensus modes registered for the same tag");
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn build_unchecked(self) -> Parsec<T, S> {
+        let mut parsec = match self.membership {
+            Membership::Genesis { genesis_group } => Parsec::genesis_unchecked(
+                self.our_id,
+                &genesis_group,
+                self.genesis_related_info.unwrap_or_default(),
+                self.consensus_mode,
+                self.secure_rng,
+            ),
+            Membership::Existing {
+                genesis_group,
+                section,
+            } => Parsec::existing_unchecked(
+                self.our_id,
+                &genesis_group,
+                &section,
+                self.consensus_mode,
+                self.secure_rng,
+            ),
+        };
+
+        for (tag, mode) in self.consensus_modes_by_tag {
+            parsec = parsec.with_consensus_mode_for(tag, mode);
+        }
+        parsec.set_event_creation_policy(self.event_creation_policy);
+        parsec.set_fork_policy(self.fork_policy);
+        parsec.set_gossip_policy(self.gossip_policy);
+        parsec.set_join_stall_threshold(self.join_stall_threshold);
+        // Checked by `validate`, as are the voter weights.
+        let _ = parsec.set_heartbeat_interval(self.heartbeat_interval);
+        parsec.set_unknown_peer_policy(self.unknown_peer_policy);
+        let _ = parsec.set_voter_weights(self.voter_weights);
+        parsec.set_trusted_ingest(self.trusted_ingest);
+        parsec.set_consensus_history_retention(self.consensus_history_retention);
+        parsec.set_block_retention(self.block_retention);
+        if let Some((cap, on_archive)) = self.block_archive {
+            parsec.set_block_archive(cap, on_archive);
+        }
+        if let Some(clock) = self.clock {
+            parsec.set_clock(clock);
+        }
+        if let Some(window) = self.event_ingestion_window {
+            parsec.set_event_ingestion_window(window);
+        }
+        #[cfg(feature = "malice-detection")]
+        parsec.set_malice_detection(self.malice_detection);
+        #[cfg(feature = "malice-detection")]
+        {
+            if let Some(hook) = self.accusation_hook {
+                parsec.set_accusation_hook(hook);
+            }
+        }
+        parsec
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{
+        mock::{self, PeerId, Transaction},
+        parsec::{TestParsec, MAX_VOTER_WEIGHT},
+    };
+    use rand::rngs::mock::StepRng;
+    use std::iter;
+
+    type Builder = ParsecBuilder<Transaction, PeerId>;
+
+    fn rng() -> Box<dyn RngCore> {
+        Box::new(StepRng::new(0, 1))
+    }
+
+    #[test]
+    fn build_rejects_inconsistent_configuration() {
+        let ids = mock::create_ids(3);
+        let our_id = ids[0].clone();
+        let others: BTreeSet<_> = ids[1..].iter().cloned().collect();
+        let error = |reason| Err(Error::InvalidConfiguration(reason));
+
+        let result = Builder::from_genesis(our_id.clone(), &BTreeSet::new(), rng()).build();
+        assert_eq!(result.map(|_| ()), error("Genesis group can't be empty"));
+
+        let result = Builder::from_genesis(our_id.clone(), &others, rng()).build();
+        assert_eq!(result.map(|_| ()), error("Genesis group must contain us"));
+
+        let result = Builder::from_existing(our_id.clone(), &others, &others, rng())
+            .genesis_related_info(vec![1])
+            .build();
+        assert_eq!(
+            result.map(|_| ()),
+            error("Genesis related info can only be set when starting from genesis")
+        );
+
+        let result = Builder::from_existing(our_id.clone(), &others, &others, rng())
+            .consensus_mode(ConsensusMode::Single)
+            .consensus_mode_for(1, ConsensusMode::Supermajority)
+            .consensus_mode_for(2, ConsensusMode::Single)
+            .consensus_mode_for(1, ConsensusMode::Single)
+            .build();
+        assert_eq!(
+            result.map(|_| ()),
+            error("Conflicting consensus modes registered for the same tag")
+        );
+
+        let result = Builder::from_existing(our_id.clone(), &others, &others, rng())
+            .heartbeat_interval(Some(0))
+            .build();
+        assert_eq!(
+            result.map(|_| ()),
+            error("Heartbeat interval can't be zero")
+        );
+
+        let weights = |weight| iter::once((our_id.clone(), weight)).collect();
+        let result = Builder::from_existing(our_id.clone(), &others, &others, rng())
+            .voter_weights(weights(0))
+            .build();
+        assert_eq!(result.map(|_| ()), error("Voter weights can't be zero"));
+        let result = Builder::from_existing(our_id.clone(), &others, &others, rng())
+            .voter_weights(weights(MAX_VOTER_WEIGHT + 1))
+            .build();
+        assert_eq!(
+            result.map(|_| ()),
+            error("Voter weights can't exceed MAX_VOTER_WEIGHT")
+        );
+
+        // Registering the same mode twice is harmless.
+        let result = Builder::from_existing(our_id, &others, &others, rng())
+            .consensus_mode_for(1, ConsensusMode::Single)
+            .consensus_mode_for(1, ConsensusMode::Single)
+            .build();
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn build_matches_constructors() {
+        let ids = mock::create_ids(3);
+        let group: BTreeSet<_> = ids.iter().cloned().collect();
+
+        let built = Builder::from_genesis(ids[0].clone(), &group, rng())
+            .genesis_related_info(vec![1, 2])
+            .consensus_mode(ConsensusMode::Single)
+            .fork_policy(ForkPolicy::AccuseOnly)
+            .build();
+        let built = TestParsec::from(unwrap!(built));
+        let constructed = TestParsec::from(Parsec::from_genesis(
+            ids[0].clone(),
+            &group,
+            vec![1, 2],
+            ConsensusMode::Single,
+            rng(),
+        ));
+        assert_eq!(built.graph(), constructed.graph());
+
+        let states = |parsec: &TestParsec<Transaction, PeerId>| {
+            parsec
+                .peer_list()
+                .iter()
+                .map(|(_, peer)| peer.state())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(states(&built), states(&constructed));
+    }
+}
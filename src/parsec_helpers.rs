@@ -13,6 +13,9 @@ use std::usize;
 
 /// Find interesting payloads for the builder_event.
 /// For payload observed from builder_event, order them by creation index.
+/// Each payload is only listed once, however many times it was voted for: votes are weighed
+/// per creator by `is_interesting_payload`, so a creator voting for the same payload more than
+/// once (e.g. on both branches of a fork) doesn't count more than once.
 pub(crate) fn find_interesting_content_for_event<'a, E>(
     builder_event: E,
     unconsensused_events: impl Iterator<Item = E>,
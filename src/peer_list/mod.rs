@@ -28,11 +28,15 @@ use crate::{
     id::SecretId,
 };
 use std::{
-    collections::btree_map::{BTreeMap, Entry},
+    collections::{
+        btree_map::{BTreeMap, Entry},
+        BTreeSet,
+    },
     fmt::{self, Debug, Formatter},
     iter,
 };
 
+#[derive(Clone)]
 pub(crate) struct PeerList<S: SecretId> {
     our_id: S,
     our_peer: Peer<S::PublicId>,
@@ -41,13 +45,20 @@ pub(crate) struct PeerList<S: SecretId> {
 }
 
 impl<S: SecretId> PeerList<S> {
+    #[cfg(any(test, feature = "testing"))]
     pub fn new(our_id: S) -> Self {
+        Self::with_capacity(our_id, 0)
+    }
+
+    /// Creates a peer list with room for `capacity` peers other than us, e.g. when the size of the
+    /// genesis group is known upfront.
+    pub fn with_capacity(our_id: S, capacity: usize) -> Self {
         let our_peer = Peer::new(our_id.public_id().clone(), PeerState::inactive());
 
         PeerList {
             our_id,
             our_peer,
-            peers: Vec::new(),
+            peers: Vec::with_capacity(capacity),
             indices: BTreeMap::new(),
         }
     }
@@ -116,6 +127,17 @@ impl<S: SecretId> PeerList<S> {
         )
     }
 
+    /// Returns an iterator of the indices and ids of all the peers except us.
+    pub fn others(&self) -> impl Iterator<Item = (PeerIndex, &S::PublicId)> {
+        self.iter().skip(1).map(|(index, peer)| (index, peer.id()))
+    }
+
+    /// Returns an iterator of the indices and ids of the peers other than us that can vote.
+    pub fn other_voters(&self) -> impl Iterator<Item = (PeerIndex, &S::PublicId)> {
+        self.others()
+            .filter(move |(index, _)| self.peer_state(*index).can_vote())
+    }
+
     /// Returns an iterator of peers that can vote.
     #[cfg(feature = "malice-detection")]
     pub fn voters(&self) -> impl Iterator<Item = (PeerIndex, &Peer<S::PublicId>)> {
@@ -127,10 +149,9 @@ impl<S: SecretId> PeerList<S> {
         &'a self,
     ) -> impl Iterator<Item = (PeerIndex, &Peer<S::PublicId>)> + 'a {
         let iter = if self.our_peer.state().can_send() {
-            let iter = self
-                .iter()
-                .skip(1)
-                .filter(|(_, peer)| peer.state().can_dkg() && peer.state().can_recv());
+            let iter = self.iter().skip(1).filter(|(_, peer)| {
+                peer.state().can_dkg() && peer.state().can_recv() && !peer.is_isolated()
+            });
             Some(iter)
         } else {
             None
@@ -187,6 +208,32 @@ impl<S: SecretId> PeerList<S> {
         }
     }
 
+    /// Adds a batch of peers in the given states, returning their indices in the same order.
+    ///
+    /// The whole batch is checked first: if any of the peers is us, is already in the map, or
+    /// appears twice in the batch, none of them is added and `Error::DuplicatePeer` is returned.
+    pub fn add_peers<I>(&mut self, peers: I) -> Result<Vec<PeerIndex>, Error>
+    where
+        I: IntoIterator<Item = (S::PublicId, PeerState)>,
+    {
+        let peers = peers.into_iter().collect::<Vec<_>>();
+        let mut batch_ids = BTreeSet::new();
+        for (peer_id, _) in &peers {
+            if peer_id == self.our_id.public_id()
+                || self.indices.contains_key(peer_id)
+                || !batch_ids.insert(peer_id)
+            {
+                return Err(Error::DuplicatePeer);
+            }
+        }
+
+        self.peers.reserve(peers.len());
+        Ok(peers
+            .into_iter()
+            .map(|(peer_id, state)| self.add_peer(peer_id, state))
+            .collect())
+    }
+
     /// Remove peer at `peer_index` after reaching consensus on the removal at the event
     /// at `deciding_event_index`.
     pub fn remove_peer(&mut self, peer_index: PeerIndex, deciding_event_index: EventIndex) {
@@ -201,6 +248,19 @@ impl<S: SecretId> PeerList<S> {
         }
     }
 
+    /// Stops us from gossiping to the peer for good, even if it keeps gossiping to us.  This
+    /// doesn't affect the peer's state, which other peers' states are derived from.
+    pub fn isolate_peer(&mut self, index: PeerIndex) {
+        if let Some(peer) = self.get_known_mut(index) {
+            peer.isolate();
+        }
+    }
+
+    /// Returns whether we've stopped gossiping to the peer via `isolate_peer`.
+    pub fn is_isolated(&self, index: PeerIndex) -> bool {
+        self.get(index).map(Peer::is_isolated).unwrap_or(false)
+    }
+
     /// Returns the index of the last event created by this peer. Returns `None` if cannot find.
     pub fn last_event(&self, peer_index: PeerIndex) -> Option<EventIndex> {
         self.get(peer_index)
@@ -218,6 +278,18 @@ impl<S: SecretId> PeerList<S> {
             .flat_map(move |peer| peer.events_by_index(index_by_creator))
     }
 
+    /// Returns the index of the event at the given index-by-creator. This is O(1). If the peer has
+    /// forked at that index-by-creator, the event we received first is returned; use
+    /// `events_by_index` to get all of them.
+    pub fn event_by_index(
+        &self,
+        peer_index: PeerIndex,
+        index_by_creator: usize,
+    ) -> Option<EventIndex> {
+        self.get(peer_index)
+            .and_then(|peer| peer.event_by_index(index_by_creator))
+    }
+
     /// Record that the given peer gossiped to us the given event.
     pub fn record_gossiped_event_by(&mut self, index: PeerIndex, event_index: EventIndex) {
         if let Some(peer) = self.get_known_mut(index) {
@@ -341,7 +413,6 @@ pub(crate) enum PeerListChange {
 pub(crate) mod snapshot {
     use super::*;
     use crate::{gossip::EventHash, id::PublicId};
-    use std::collections::BTreeSet;
 
     #[derive(Eq, PartialEq, Debug)]
     pub(crate) struct PeerListSnapshot<P: PublicId>(
@@ -371,3 +442,60 @@ pub(crate) mod snapshot {
         }
     }
 }
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::PeerId;
+
+    #[test]
+    fn others_exclude_us() {
+        let mut peer_list = PeerList::new(PeerId::new("Alice"));
+        peer_list.change_peer_state(PeerIndex::OUR, PeerState::active());
+        let bob = peer_list.add_peer(PeerId::new("Bob"), PeerState::active());
+        let carol = peer_list.add_peer(PeerId::new("Carol"), PeerState::SEND | PeerState::RECV);
+
+        let others: Vec<_> = peer_list.others().map(|(index, _)| index).collect();
+        assert_eq!(others, vec![bob, carol]);
+
+        let other_voters: Vec<_> = peer_list.other_voters().map(|(_, id)| id.clone()).collect();
+        assert_eq!(other_voters, vec![PeerId::new("Bob")]);
+    }
+
+    #[test]
+    fn add_peers_is_atomic() {
+        let mut peer_list = PeerList::with_capacity(PeerId::new("Alice"), 3);
+        let bob = peer_list.add_peer(PeerId::new("Bob"), PeerState::active());
+        let batch = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| (PeerId::new(name), PeerState::active()))
+                .collect::<Vec<_>>()
+        };
+        let ids = |peer_list: &PeerList<PeerId>| {
+            peer_list
+                .all_ids()
+                .map(|(_, id)| id.clone())
+                .collect::<Vec<_>>()
+        };
+
+        // Duplicates within the batch, of an existing peer, and of us.
+        for names in &[
+            ["Carol", "Dave", "Carol"],
+            ["Carol", "Dave", "Bob"],
+            ["Carol", "Dave", "Alice"],
+        ] {
+            assert_eq!(peer_list.add_peers(batch(names)), Err(Error::DuplicatePeer));
+            assert_eq!(
+                ids(&peer_list),
+                vec![PeerId::new("Alice"), PeerId::new("Bob")]
+            );
+        }
+
+        let indices = unwrap!(peer_list.add_peers(batch(&["Dave", "Carol"])));
+        assert_eq!(indices.len(), 2);
+        assert!(!indices.contains(&bob));
+        assert_eq!(peer_list.get_index(&PeerId::new("Dave")), Some(indices[0]));
+        assert_eq!(peer_list.get_index(&PeerId::new("Carol")), Some(indices[1]));
+    }
+}
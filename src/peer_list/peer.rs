@@ -23,12 +23,15 @@ impl<P: PublicId> Debug for Peer<P> {
     }
 }
 
+#[derive(Clone)]
 pub(crate) struct Peer<P: PublicId> {
     id: P,
     presence: Presence,
     pub(super) events: Events,
     pub(super) last_gossiped_event: Option<EventIndex>,
     has_fork: bool,
+    // If true, we never gossip to this peer, regardless of its state.
+    isolated: bool,
     // As a performance optimisation we keep track of which events we've cleared for Accomplice
     // accusations.
     #[cfg(feature = "malice-detection")]
@@ -43,6 +46,7 @@ impl<P: PublicId> Peer<P> {
             events: Events::new(),
             last_gossiped_event: None,
             has_fork: false,
+            isolated: false,
             #[cfg(feature = "malice-detection")]
             accomplice_event_checkpoint: None,
         }
@@ -65,6 +69,14 @@ impl<P: PublicId> Peer<P> {
         }
     }
 
+    pub fn is_isolated(&self) -> bool {
+        self.isolated
+    }
+
+    pub(super) fn isolate(&mut self) {
+        self.isolated = true;
+    }
+
     pub(super) fn set_removed(&mut self, deciding_event_index: EventIndex) {
         self.presence = Presence::Removed(deciding_event_index)
     }
@@ -91,6 +103,10 @@ impl<P: PublicId> Peer<P> {
         self.events.by_index(index)
     }
 
+    pub fn event_by_index(&self, index: usize) -> Option<EventIndex> {
+        self.events.first_at(index)
+    }
+
     pub fn removal_event(&self) -> Option<EventIndex> {
         match self.presence {
             Presence::Present(_) => None,
@@ -120,14 +136,14 @@ impl<P: PublicId> Peer<P> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 enum Presence {
     Present(PeerState),
     // Contains the index of the event at which we reached the consensus on the removal.
     Removed(EventIndex),
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(super) struct Events(Vec<Slot>);
 
 impl Events {
@@ -190,6 +206,11 @@ impl Events {
             .into_iter()
             .flat_map(Slot::iter)
     }
+
+    // The first event we received at the given index-by-creator, if any.
+    fn first_at(&self, index_by_creator: usize) -> Option<EventIndex> {
+        self.0.get(index_by_creator).map(|slot| slot.first)
+    }
 }
 
 impl<'a, P> FromIterator<IndexedEventRef<'a, P>> for Events
@@ -209,6 +230,7 @@ where
     }
 }
 
+#[derive(Clone)]
 struct Slot {
     first: EventIndex,
     rest: Vec<EventIndex>,
@@ -34,30 +34,42 @@ impl PeerState {
     /// The peer can participate in DKG.
     pub const DKG: Self = PeerState(0b0000_1000);
 
+    /// Returns the state with none of the flags enabled.
     pub fn inactive() -> Self {
         PeerState(0)
     }
 
+    /// Returns the state with `VOTE`, `SEND` and `RECV` enabled.
     pub fn active() -> Self {
         Self::VOTE | Self::SEND | Self::RECV
     }
 
+    /// Returns whether all the flags enabled in `other` are enabled in `self`.
     pub fn contains(self, other: Self) -> bool {
         self.0 & other.0 == other.0
     }
 
+    /// Returns `self` with the flags enabled in `other` disabled.
+    pub fn without(self, other: Self) -> Self {
+        PeerState(self.0 & !other.0)
+    }
+
+    /// Returns whether `VOTE` is enabled.
     pub fn can_vote(self) -> bool {
         self.contains(Self::VOTE)
     }
 
+    /// Returns whether `DKG` is enabled.
     pub fn can_dkg(self) -> bool {
         self.contains(Self::DKG)
     }
 
+    /// Returns whether `SEND` is enabled.
     pub fn can_send(self) -> bool {
         self.contains(Self::SEND)
     }
 
+    /// Returns whether `RECV` is enabled.
     pub fn can_recv(self) -> bool {
         self.contains(Self::RECV)
     }
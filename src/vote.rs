@@ -10,7 +10,7 @@ use crate::{
     error::Error,
     id::{Proof, PublicId, SecretId},
     network_event::NetworkEvent,
-    observation::{ConsensusMode, Observation, ObservationHash, ObservationKey, ObservationStore},
+    observation::{ConsensusModes, Observation, ObservationHash, ObservationKey, ObservationStore},
     peer_list::PeerIndex,
     serialise,
 };
@@ -77,9 +77,9 @@ impl<P: PublicId> VoteKey<P> {
     pub fn new<T: NetworkEvent>(
         vote: Vote<T, P>,
         creator: PeerIndex,
-        consensus_mode: ConsensusMode,
+        consensus_modes: &ConsensusModes,
     ) -> (Self, Observation<T, P>) {
-        let consensus_mode = consensus_mode.of(&vote.payload);
+        let consensus_mode = consensus_modes.of(&vote.payload);
         let hash = ObservationHash::from(&vote.payload);
         let payload_key = ObservationKey::new(hash, creator, consensus_mode);
 
@@ -57,20 +57,24 @@ extern crate proptest;
 #[macro_use]
 extern crate unwrap;
 
+#[cfg(feature = "malice-detection")]
+use parsec::{dev_utils::malice_scenarios, Malice};
 use parsec::{
     dev_utils::{
         proptest::{arbitrary_delay, ScheduleOptionsStrategy, ScheduleStrategy},
-        DelayDistribution, Environment, Genesis, ObservationEvent, ObservationSchedule, RngChoice,
-        Sampling, Schedule, ScheduleOptions,
+        DelayDistribution, Environment, Genesis, GossipScheduler, Message, ObservationEvent,
+        ObservationSchedule, PeerStatus, RngChoice, Sampling, Schedule, ScheduleEvent,
+        ScheduleOptions,
     },
     mock::{PeerId, Transaction, NAMES},
-    ConsensusMode, Observation,
+    ConsensusMode, EventCreationPolicy, GossipPolicy, Observation,
 };
 use proptest::{prelude::ProptestConfig, test_runner::FileFailurePersistence};
 use rand::Rng;
 use std::{
     collections::{BTreeMap, BTreeSet},
     sync::Once,
+    time::Duration,
 };
 
 // Alter the seed here to reproduce failures
@@ -94,6 +98,50 @@ fn minimal_network() {
     assert!(result.is_ok(), "{:?}", result);
 }
 
+#[test]
+fn run_summary() {
+    let mut env = Environment::new(SEED);
+    let options = ScheduleOptions {
+        genesis_size: 4,
+        opaque_to_add: 2,
+        ..Default::default()
+    };
+    let schedule = Schedule::new(&mut env, &options);
+    unwrap!(env.execute_schedule(schedule));
+
+    let summary = env.network.summary();
+    assert!(summary.num_steps > 0);
+    assert!(summary.num_requests_sent >= summary.num_responses_sent);
+    assert!(summary.num_responses_sent > 0);
+    // The genesis block and the two opaque payloads.
+    assert_eq!(summary.num_blocks, 3);
+    assert_eq!(summary.num_accusations, 0);
+    assert_eq!(summary.peers.len(), 4);
+    assert!(summary
+        .peers
+        .values()
+        .all(|status| *status == PeerStatus::Active));
+
+    let table = summary.to_string();
+    assert!(table.contains(&format!("blocks decided{:>10}", 3)));
+    assert_eq!(table.lines().count(), 10);
+}
+
+#[test]
+fn fair_gossip_scheduler() {
+    let mut env = Environment::new(SEED);
+    let options = ScheduleOptions {
+        genesis_size: 5,
+        opaque_to_add: 5,
+        gossip_scheduler: GossipScheduler::Fair,
+        ..Default::default()
+    };
+    let schedule = Schedule::new(&mut env, &options);
+
+    let result = env.execute_schedule(schedule);
+    assert!(result.is_ok(), "{:?}", result);
+}
+
 #[test]
 fn multiple_votes_before_gossip() {
     let num_observations = 10;
@@ -125,6 +173,98 @@ fn multiple_votes_during_gossip() {
     assert!(result.is_ok(), "{:?}", result);
 }
 
+#[test]
+fn skipping_redundant_responses_preserves_consensus() {
+    let mut env = Environment::new(SEED);
+    let options = ScheduleOptions {
+        genesis_size: 7,
+        opaque_to_add: 10,
+        prob_gossip: 0.1,
+        event_creation_policy: EventCreationPolicy::SkipRedundantResponses,
+        ..Default::default()
+    };
+
+    let schedule = Schedule::new(&mut env, &options);
+
+    let result = env.execute_schedule(schedule);
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn gossiping_only_on_change_preserves_consensus() {
+    for &gossip_policy in &[GossipPolicy::OnChange, GossipPolicy::Adaptive] {
+        let mut env = Environment::new(SEED);
+        let options = ScheduleOptions {
+            genesis_size: 7,
+            opaque_to_add: 10,
+            peers_to_add: 2,
+            gossip_policy,
+            ..Default::default()
+        };
+
+        let schedule = Schedule::new(&mut env, &options);
+
+        let result = env.execute_schedule(schedule);
+        assert!(result.is_ok(), "{:?} with {:?}", result, gossip_policy);
+    }
+}
+
+#[test]
+fn clock_skew_does_not_affect_consensus() {
+    // Time is local metadata only: the same schedule must give the same blocks however far apart
+    // the peers' clocks are.
+    let seed = match SEED {
+        RngChoice::Random => RngChoice::Seeded(rand::thread_rng().gen()),
+        seed => seed,
+    };
+    let run = |max_clock_offset, max_clock_drift| {
+        let mut env = Environment::new(seed);
+        let options = ScheduleOptions {
+            genesis_size: 5,
+            opaque_to_add: 5,
+            peers_to_add: 1,
+            max_clock_offset,
+            max_clock_drift,
+            ..Default::default()
+        };
+        let schedule = Schedule::new(&mut env, &options);
+        unwrap!(env.execute_schedule(schedule));
+        env.network
+            .peers
+            .values()
+            .map(|peer| {
+                (
+                    peer.id().clone(),
+                    peer.blocks().cloned().collect::<Vec<_>>(),
+                )
+            })
+            .collect::<BTreeMap<_, _>>()
+    };
+
+    let synchronised = run(Duration::from_secs(0), 0.0);
+    let skewed = run(Duration::from_secs(3600), 0.5);
+    assert!(synchronised.values().all(|blocks| !blocks.is_empty()));
+    assert_eq!(synchronised, skewed);
+}
+
+#[test]
+fn meta_votes_consistent_across_peers() {
+    let mut env = Environment::new(SEED);
+    let options = ScheduleOptions {
+        genesis_size: 5,
+        opaque_to_add: 5,
+        peers_to_add: 1,
+        peers_to_remove: 1,
+        meta_vote_consistency_checks: true,
+        ..Default::default()
+    };
+
+    let schedule = Schedule::new(&mut env, &options);
+
+    let result = env.execute_schedule(schedule);
+    assert!(result.is_ok(), "{:?}", result);
+}
+
 #[test]
 fn duplicate_vote_is_reduced_to_single() {
     let mut env = Environment::new(SEED);
@@ -255,6 +395,37 @@ fn add_few_peers_and_vote() {
     unwrap!(env.execute_schedule(schedule));
 }
 
+#[test]
+fn inject_opaque_batch() {
+    let seed = match SEED {
+        RngChoice::Random => RngChoice::Seeded(rand::thread_rng().gen()),
+        seed => seed,
+    };
+    let genesis = || Genesis::new(NAMES.iter().take(4).cloned().map(PeerId::new).collect());
+    let gen_batch = |env: &mut Environment| {
+        ObservationSchedule::new(genesis()).with_opaque_batch(&mut env.rng, 30, 10..50)
+    };
+
+    let mut env = Environment::new(seed);
+    let obs_schedule = gen_batch(&mut env);
+    assert_eq!(obs_schedule.schedule.len(), 30);
+    assert!(obs_schedule
+        .schedule
+        .iter()
+        .all(|(step, event)| (10..50).contains(step) && event.is_opaque()));
+    // The same seed gives the same batch.
+    assert_eq!(
+        format!("{:?}", obs_schedule.schedule),
+        format!("{:?}", gen_batch(&mut Environment::new(seed)).schedule)
+    );
+
+    let options = ScheduleOptions::default();
+    let schedule = Schedule::from_observation_schedule(&mut env, &options, obs_schedule);
+    unwrap!(env.execute_schedule(schedule));
+    // The genesis block and the 30 transactions.
+    assert_eq!(env.network.summary().num_blocks, 31);
+}
+
 // Run DKG with the 4 voters in genesis
 #[test]
 fn run_dkg() {
@@ -609,6 +780,175 @@ fn consensus_with_fork() {
     unwrap!(env.execute_schedule(schedule));
 }
 
+// One out of seven peers forks.  The honest peers must accuse it, and still agree on a single order
+// for all of the observations.
+#[cfg(feature = "malice-detection")]
+#[test]
+fn consensus_survives_forks() {
+    use parsec::Malice;
+
+    let mut env = Environment::new(SEED);
+    let options = ScheduleOptions {
+        genesis_size: 7,
+        malicious_genesis_count: 1,
+        opaque_to_add: 5,
+        ..Default::default()
+    };
+    let schedule = Schedule::new(&mut env, &options);
+    let mut offenders = BTreeSet::new();
+    let mut opaque_payloads = BTreeSet::new();
+    for event in &schedule.events {
+        match event {
+            ScheduleEvent::Genesis(genesis) => {
+                offenders.extend(genesis.ids_of_malicious_peers().cloned())
+            }
+            ScheduleEvent::VoteFor(_, observation) if observation.is_opaque() => {
+                let _ = opaque_payloads.insert(observation.clone());
+            }
+            _ => (),
+        }
+    }
+    assert_eq!(offenders.len(), 1);
+    unwrap!(env.execute_schedule(schedule));
+
+    let honest_peers: Vec<_> = env.network.running_non_malicious_peers().collect();
+    assert_eq!(honest_peers.len(), 6);
+    let expected_order = honest_peers[0].blocks_payloads();
+    for peer in &honest_peers {
+        let payloads = peer.blocks_payloads();
+        assert_eq!(payloads, expected_order);
+
+        let accused: BTreeSet<_> = payloads
+            .iter()
+            .filter_map(|payload| match payload {
+                Observation::Accusation {
+                    offender,
+                    malice: Malice::Fork(_),
+                } => Some(offender.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(accused, offenders);
+
+        let consensused: BTreeSet<_> = payloads
+            .into_iter()
+            .filter(|payload| payload.is_opaque())
+            .cloned()
+            .collect();
+        assert_eq!(consensused, opaque_payloads);
+    }
+}
+
+// Runs the malice scenario, then checks that every good peer consensused accusations of exactly the
+// malicious peers, for the malice matched by `is_expected`.
+#[cfg(feature = "malice-detection")]
+fn assert_offenders_accused<F>(scenario: (Environment, Schedule), is_expected: F)
+where
+    F: Fn(&Malice<Transaction, PeerId>) -> bool,
+{
+    let (mut env, schedule) = scenario;
+    let offenders: BTreeSet<_> = schedule
+        .events
+        .iter()
+        .filter_map(|event| match event {
+            ScheduleEvent::Genesis(genesis) => Some(genesis.ids_of_malicious_peers()),
+            _ => None,
+        })
+        .flatten()
+        .cloned()
+        .collect();
+    assert!(!offenders.is_empty());
+    unwrap!(env.execute_schedule(schedule));
+
+    for peer in env.network.running_non_malicious_peers() {
+        let accused: BTreeSet<_> = peer
+            .blocks_payloads()
+            .into_iter()
+            .filter_map(|payload| match payload {
+                Observation::Accusation { offender, malice } if is_expected(malice) => {
+                    Some(offender.clone())
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(accused, offenders);
+    }
+}
+
+#[cfg(feature = "malice-detection")]
+#[test]
+fn malice_scenario_fork() {
+    assert_offenders_accused(malice_scenarios::fork(SEED), |malice| {
+        matches!(malice, Malice::Fork(_))
+    })
+}
+
+#[cfg(feature = "malice-detection")]
+#[test]
+fn malice_scenario_duplicate_vote() {
+    assert_offenders_accused(malice_scenarios::duplicate_vote(SEED), |malice| {
+        matches!(malice, Malice::DuplicateVote(..))
+    })
+}
+
+#[cfg(feature = "malice-detection")]
+#[test]
+fn malice_scenario_invalid_accusation() {
+    assert_offenders_accused(malice_scenarios::invalid_accusation(SEED), |malice| {
+        matches!(malice, Malice::InvalidAccusation(_))
+    })
+}
+
+// Runs the fork scenario with all peers using `fork_policy`, then checks whether the good peers
+// still consider the forker a section member and a gossip recipient.
+#[cfg(feature = "malice-detection")]
+fn check_fork_policy(fork_policy: parsec::ForkPolicy, expect_member: bool, expect_recipient: bool) {
+    let (mut env, schedule) = malice_scenarios::fork_with_policy(SEED, fork_policy);
+    unwrap!(env.execute_schedule(schedule));
+
+    let offender = unwrap!(env.network.peers.values().find(|peer| peer.is_malicious()))
+        .id()
+        .clone();
+    for peer in env.network.running_non_malicious_peers() {
+        assert!(peer
+            .blocks_payloads()
+            .into_iter()
+            .any(|payload| match payload {
+                Observation::Accusation {
+                    offender: accused,
+                    malice: Malice::Fork(_),
+                } => *accused == offender,
+                _ => false,
+            }));
+        assert_eq!(
+            peer.section_members().any(|id| *id == offender),
+            expect_member
+        );
+        assert_eq!(
+            peer.gossip_recipients().any(|id| *id == offender),
+            expect_recipient
+        );
+    }
+}
+
+#[cfg(feature = "malice-detection")]
+#[test]
+fn fork_policy_accuse_only() {
+    check_fork_policy(parsec::ForkPolicy::AccuseOnly, true, true)
+}
+
+#[cfg(feature = "malice-detection")]
+#[test]
+fn fork_policy_accuse_and_isolate() {
+    check_fork_policy(parsec::ForkPolicy::AccuseAndIsolate, true, false)
+}
+
+#[cfg(feature = "malice-detection")]
+#[test]
+fn fork_policy_accuse_and_propose_removal() {
+    check_fork_policy(parsec::ForkPolicy::AccuseAndProposeRemoval, false, false)
+}
+
 #[test]
 fn grow_network_from_two_nodes() {
     let mut env = Environment::new(SEED);
@@ -633,6 +973,82 @@ fn grow_network_from_one_node() {
     unwrap!(env.execute_schedule(schedule));
 }
 
+#[test]
+fn injected_gossip_is_handled() {
+    let mut env = Environment::new(SEED);
+    let options = ScheduleOptions {
+        genesis_size: 4,
+        opaque_to_add: 2,
+        ..Default::default()
+    };
+    let schedule = Schedule::new(&mut env, &options);
+    let peers = schedule.peers.clone();
+    unwrap!(env.execute_schedule(schedule));
+
+    // Have the second peer gossip to the first one outside of the random scheduler.
+    let mut peer_ids = peers.keys();
+    let dst = unwrap!(peer_ids.next()).clone();
+    let src = unwrap!(peer_ids.next()).clone();
+    let src_events = unwrap!(env.network.peers.get(&src)).event_count_by_creator()[&src];
+    let dst_events = unwrap!(env.network.peers.get(&dst)).event_count_by_creator()[&dst];
+    let request = unwrap!(unwrap!(env.network.peers.get_mut(&src)).create_gossip(&dst));
+    env.network
+        .inject_message(src.clone(), &dst, Message::Request(request, 0), 0);
+
+    // Run a single step with no further gossip, which delivers the injected request.
+    let num_blocks = unwrap!(env.network.peers.get(&dst)).blocks().count();
+    let schedule = Schedule {
+        peers,
+        min_observations: num_blocks,
+        max_observations: num_blocks,
+        events: vec![],
+        additional_steps: 0..1,
+        options: ScheduleOptions {
+            prob_gossip: 0.0,
+            ..options
+        },
+    };
+    unwrap!(env.execute_schedule(schedule));
+
+    // The first peer now holds all the events of the second one, and answered with a new sync
+    // event of its own.
+    let event_counts = unwrap!(env.network.peers.get(&dst)).event_count_by_creator();
+    assert!(event_counts[&src] >= src_events);
+    assert!(event_counts[&dst] > dst_events);
+}
+
+#[test]
+fn network_delay_change() {
+    let mut env = Environment::new(SEED);
+    let options = ScheduleOptions {
+        genesis_size: 4,
+        opaque_to_add: 5,
+        delay_distr: DelayDistribution::Constant(0),
+        ..Default::default()
+    };
+    let mut schedule = Schedule::new(&mut env, &options);
+
+    // The network degrades after the first step and recovers a few steps later.
+    let position_of_step = |events: &[ScheduleEvent], step| {
+        unwrap!(events.iter().position(|event| match event {
+            ScheduleEvent::LocalStep(s) => *s == step,
+            _ => false,
+        }))
+    };
+    let degrade_at = position_of_step(&schedule.events, 1);
+    schedule.events.insert(
+        degrade_at,
+        ScheduleEvent::NetworkDelayChange(DelayDistribution::Constant(10)),
+    );
+    let recover_at = position_of_step(&schedule.events, 4);
+    schedule.events.insert(
+        recover_at,
+        ScheduleEvent::NetworkDelayChange(DelayDistribution::Constant(0)),
+    );
+
+    unwrap!(env.execute_schedule(schedule));
+}
+
 proptest! {
     #![proptest_config(ProptestConfig {
         failure_persistence: Some(Box::new(FileFailurePersistence::WithSource("regressions"))),